@@ -0,0 +1,114 @@
+// `cache` subcommand and the `[tool_cache]` config section
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use anyhow::Result;
+use clap::Subcommand;
+use serde::Deserialize;
+use ai_agent_core::ToolCache;
+
+/// Reusing the output of read-only tools, under `[tool_cache]` in the
+/// config file; the section being present turns it on.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long an entry stays fresh (default one day).
+    pub ttl_seconds: Option<u64>,
+    pub max_entries: Option<usize>,
+    pub max_bytes: Option<u64>,
+    /// Keep entries on disk under the cache directory so they outlive the
+    /// process (default true).
+    pub disk: Option<bool>,
+    /// Where entries are kept on disk, instead of `tools` under the cache
+    /// directory.
+    pub dir: Option<PathBuf>,
+}
+
+impl CacheConfig {
+    pub fn build(&self) -> Result<ToolCache> {
+        let mut cache = match (&self.dir, self.disk) {
+            (_, Some(false)) => ToolCache::new(),
+            (Some(dir), _) => ToolCache::new().with_disk(dir),
+            (None, _) => ToolCache::persistent()?,
+        };
+        if let Some(seconds) = self.ttl_seconds {
+            cache = cache.with_ttl(Duration::from_secs(seconds));
+        }
+        if let Some(entries) = self.max_entries {
+            cache = cache.with_max_entries(entries);
+        }
+        if let Some(bytes) = self.max_bytes {
+            cache = cache.with_max_bytes(bytes);
+        }
+        Ok(cache)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Show where cached tool output is kept and how much there is
+    Stats,
+    /// Delete every cached tool output
+    Clear,
+}
+
+pub async fn run(command: CacheCommand) -> Result<ExitCode> {
+    let config = crate::config::get();
+    let cache = match &config.cache {
+        Some(cache) => std::sync::Arc::clone(cache),
+        None => std::sync::Arc::new(config.tool_cache.clone().unwrap_or_default().build()?),
+    };
+    match command {
+        CacheCommand::Stats => {
+            let stats = cache.stats().await?;
+            match cache.disk_dir() {
+                Some(dir) => println!("location: {}", dir.display()),
+                None => println!("location: memory only; nothing is kept between runs"),
+            }
+            println!("entries:  {}", stats.disk_entries);
+            println!("size:     {}", human_bytes(stats.disk_bytes));
+            if config.tool_cache.is_none() {
+                println!("(disabled; add a [tool_cache] section to the config to enable it)");
+            }
+        }
+        CacheCommand::Clear => {
+            let removed = cache.clear().await?;
+            println!("Removed {} cached tool output{}", removed, if removed == 1 { "" } else { "s" });
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `bytes` in the largest binary unit that keeps it at or above 1.
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_config_and_sizes() {
+        let config: CacheConfig = toml::from_str("ttl_seconds = 60\ndisk = false\n").unwrap();
+        let cache = config.build().unwrap();
+        assert_eq!(cache.ttl, Duration::from_secs(60));
+        assert!(cache.disk_dir().is_none());
+        assert!(toml::from_str::<CacheConfig>("ttl = 1\n").is_err());
+        let config: CacheConfig = toml::from_str("dir = \"/tmp/tool-cache\"\n").unwrap();
+        assert_eq!(config.build().unwrap().disk_dir(), Some(Path::new("/tmp/tool-cache")));
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(3 << 20), "3.0 MiB");
+    }
+}
@@ -0,0 +1,267 @@
+// CLI configuration, read from a TOML file
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use ai_agent_core::{
+    ConfirmationGate, DangerLevel, ExecOptions, ExecPolicy, ExecutionLog, ModelRegistry, OutputLimit, ProcessManager, RedactTransform, ResourceLimits, SearchPath, ToolCache,
+    TransformRegistry, DEFAULT_HEARTBEAT_INTERVAL, SENSITIVE_ENV_PATTERNS,
+};
+
+/// Read from the working directory when `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = "ai-agent.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Mask secrets and personal data before anything is sent to a model backend.
+    pub redact_secrets: bool,
+    /// Extra redaction patterns, keyed by the kind they are reported as.
+    pub redact_patterns: BTreeMap<String, String>,
+    /// Exact strings that are never redacted.
+    pub redact_allowlist: Vec<String>,
+    /// The model `--model auto` stands for.
+    pub default_model: Option<String>,
+    /// Shared libraries providing extra transforms for `process --transform`.
+    pub plugins: Vec<String>,
+    /// Guardrails for tool calls. Without it, tools may not use the network
+    /// and paths are confined to the working directory.
+    pub exec_policy: Option<ExecPolicy>,
+    /// Memory, CPU, open file and process caps for every tool run (Unix only).
+    pub tool_limits: Option<ResourceLimits>,
+    /// Bytes of stdout and stderr kept from each tool run (1 MiB each by
+    /// default); the rest is dropped behind a truncation marker.
+    pub tool_output_limit: Option<OutputLimit>,
+    /// Registered tools at or above this level ask before running
+    /// (`destructive` by default); `--yes` approves them all.
+    pub confirm_threshold: Option<DangerLevel>,
+    /// Python module `status` imports to check the ML backend.
+    pub python_module: Option<String>,
+    /// `host:port` or URL whose reachability `status` checks.
+    pub health_endpoint: Option<String>,
+    /// Record every tool run in the history log (default true).
+    pub tool_history: Option<bool>,
+    /// Globs naming environment variables whose values are scrubbed from
+    /// the history log, in addition to `SENSITIVE_ENV_PATTERNS`.
+    pub secret_env: Vec<String>,
+    /// Reuse the output of read-only tools; off unless the section is present.
+    pub tool_cache: Option<crate::cache::CacheConfig>,
+    /// Where spans are exported with the `otel` feature.
+    pub telemetry: crate::telemetry::TelemetryConfig,
+    /// Refuse to write files, start processes or run tools that are not
+    /// read-only; `--safe` turns it on too.
+    pub safe: bool,
+    /// Seconds between signs of life from a running task: the spinner's
+    /// ticks and the server's `heartbeat` notifications (default 5; 0 turns
+    /// them off).
+    pub heartbeat_seconds: Option<u64>,
+    /// Directories tools are looked up in instead of the inherited `PATH`.
+    pub tool_path: Option<Vec<PathBuf>>,
+    /// Directory searched before `tool_path` (or `PATH`), so the tools
+    /// bundled there are the ones that run.
+    pub shim_dir: Option<PathBuf>,
+    /// Set by `--unsafe-allow-all`: run tools without any policy.
+    #[serde(skip)]
+    pub unsafe_allow_all: bool,
+    /// Set by `--dry-run`: describe tool runs instead of spawning them.
+    #[serde(skip)]
+    pub dry_run: bool,
+    /// Holds tools for approval; set at startup unless `--yes` is given.
+    #[serde(skip)]
+    pub confirm: Option<Arc<ConfirmationGate>>,
+    /// Where tool runs are recorded; set at startup from `execution_log`.
+    #[serde(skip)]
+    pub history: Option<Arc<ExecutionLog>>,
+    /// Shared by every tool call; set at startup from `tool_cache`.
+    #[serde(skip)]
+    pub cache: Option<Arc<ToolCache>>,
+    /// Processes started in the background; shut down when interactive mode ends.
+    #[serde(skip)]
+    pub processes: ProcessManager,
+}
+
+impl Config {
+    /// Loads `path`, or `DEFAULT_CONFIG_FILE` if it exists, or the defaults.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => Path::new(path),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Path::new(DEFAULT_CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        // Surface bad patterns and models at startup rather than on the first task.
+        config.redactor()?;
+        config.models()?;
+        Ok(config)
+    }
+
+    /// The redaction stage to run before model calls, if enabled.
+    pub fn redactor(&self) -> Result<Option<RedactTransform>> {
+        if !self.redact_secrets {
+            return Ok(None);
+        }
+        let mut redact = RedactTransform::new();
+        for (kind, pattern) in &self.redact_patterns {
+            redact = redact.with_pattern(kind.clone(), pattern)?;
+        }
+        for literal in &self.redact_allowlist {
+            redact = redact.allow(literal.clone());
+        }
+        Ok(Some(redact))
+    }
+
+    /// The built-in transforms plus those from `plugins`.
+    pub fn transforms(&self) -> Result<TransformRegistry> {
+        let mut registry = TransformRegistry::builtin();
+        for plugin in &self.plugins {
+            registry.load_library(plugin)?;
+        }
+        Ok(registry)
+    }
+
+    /// The history log at its default location, scrubbing the values of
+    /// `SENSITIVE_ENV_PATTERNS` and `secret_env` variables.
+    pub fn execution_log(&self) -> Result<ExecutionLog> {
+        let patterns: Vec<&str> =
+            SENSITIVE_ENV_PATTERNS.iter().copied().chain(self.secret_env.iter().map(String::as_str)).collect();
+        ExecutionLog::default_location()?.with_secret_env(&patterns)
+    }
+
+    /// Options for running tool calls, with the policy applied, runs
+    /// recorded in `history`, cacheable tools served from `cache` and
+    /// dangerous ones held by `confirm`.
+    pub fn exec_options(&self) -> Result<ExecOptions> {
+        let mut options = ExecOptions::new().with_dry_run(self.dry_run);
+        if let Some(gate) = &self.confirm {
+            options = options.with_confirmation(Arc::clone(gate));
+        }
+        if let Some(log) = &self.history {
+            options = options.with_log(Arc::clone(log));
+        }
+        if let Some(cache) = &self.cache {
+            options = options.with_cache(Arc::clone(cache));
+        }
+        if let Some(limits) = self.tool_limits {
+            options = options.with_limits(limits);
+        }
+        if let Some(limit) = self.tool_output_limit {
+            options = options.with_max_output_bytes(limit);
+        }
+        if let Some(search_path) = self.search_path() {
+            options = options.with_search_path(search_path);
+        }
+        if self.unsafe_allow_all {
+            return Ok(options);
+        }
+        let policy = match &self.exec_policy {
+            Some(policy) => policy.clone(),
+            None => ExecPolicy::confined_to(std::env::current_dir().context("Failed to read the current directory")?),
+        };
+        Ok(options.with_policy(policy))
+    }
+
+    /// Where tools are looked up, if `tool_path` or `shim_dir` is set.
+    pub fn search_path(&self) -> Option<SearchPath> {
+        let search_path = SearchPath { shim_dir: self.shim_dir.clone(), dirs: self.tool_path.clone() };
+        (!search_path.is_inherited()).then_some(search_path)
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.heartbeat_seconds.map_or(DEFAULT_HEARTBEAT_INTERVAL, Duration::from_secs)
+    }
+
+    /// The selectable models, with `default_model` applied.
+    pub fn models(&self) -> Result<ModelRegistry> {
+        let registry = ModelRegistry::builtin();
+        match &self.default_model {
+            Some(name) => Ok(registry.with_default(name).context("invalid default_model")?),
+            None => Ok(registry),
+        }
+    }
+}
+
+/// Installs the process-wide configuration. Only the first call has an effect.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The process-wide configuration, or the defaults if `init` was never called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redaction_options() {
+        let config = Config::parse(
+            "redact_secrets = true\nredact_allowlist = [\"ops@example.com\"]\n[redact_patterns]\nticket = 'TKT-\\d+'\n",
+        )
+        .unwrap();
+        let (text, _) = config.redactor().unwrap().unwrap().apply("TKT-42 ops@example.com x@y.io");
+        assert_eq!(text, "[REDACTED:ticket] ops@example.com [REDACTED:email]");
+
+        assert!(Config::default().redactor().unwrap().is_none());
+        assert!(Config::parse("redact_secrets = true\n[redact_patterns]\nbad = '('\n").is_err());
+        assert!(Config::parse("unknown_option = 1\n").is_err());
+    }
+
+    #[test]
+    fn test_default_model() {
+        let config = Config::parse("default_model = 'distilgpt2'\n").unwrap();
+        assert_eq!(config.models().unwrap().resolve("auto").unwrap().name, "distilgpt2");
+        assert_eq!(Config::default().models().unwrap().default_model(), "gpt-2");
+        assert!(Config::parse("default_model = 'gpt-5'\n").is_err());
+    }
+
+    #[test]
+    fn test_heartbeat_interval() {
+        assert_eq!(Config::default().heartbeat_interval(), DEFAULT_HEARTBEAT_INTERVAL);
+        assert_eq!(Config::parse("heartbeat_seconds = 30\n").unwrap().heartbeat_interval(), Duration::from_secs(30));
+        assert!(Config::parse("heartbeat_seconds = 0\n").unwrap().heartbeat_interval().is_zero());
+    }
+
+    #[test]
+    fn test_search_path() {
+        assert!(Config::default().exec_options().unwrap().search_path.is_none());
+        let config = Config::parse("shim_dir = 'shims'\ntool_path = ['/opt/tools/bin']\n").unwrap();
+        let search_path = config.exec_options().unwrap().search_path.unwrap();
+        assert_eq!(search_path.dirs(), vec![PathBuf::from("shims"), PathBuf::from("/opt/tools/bin")]);
+    }
+
+    #[test]
+    fn test_exec_policy() {
+        let config = Config::parse("[exec_policy]\ndenied_commands = ['rm']\nallowed_roots = ['/tmp']\n").unwrap();
+        let policy = config.exec_options().unwrap().policy.unwrap();
+        assert!(policy.check("rm", &[]).is_err());
+        assert!(policy.check("ls", &["/etc"]).is_err());
+        assert!(!policy.allow_shell);
+        assert!(Config::parse("[exec_policy]\nallow_shell = true\n").unwrap().exec_policy.unwrap().allow_shell);
+        assert!(Config::default().exec_options().unwrap().policy.unwrap().check("curl", &[]).is_err());
+
+        let config = Config { unsafe_allow_all: true, ..config };
+        assert!(config.exec_options().unwrap().policy.is_none());
+        assert!(!config.exec_options().unwrap().dry_run);
+        assert!(Config { dry_run: true, ..config }.exec_options().unwrap().dry_run);
+        assert!(Config::parse("[exec_policy]\nnetwrk = true\n").is_err());
+
+        let limited = Config::parse("[tool_limits]\nmax_cpu_seconds = 5\n").unwrap();
+        assert_eq!(limited.exec_options().unwrap().limits.unwrap().max_cpu_seconds, Some(5));
+        assert!(Config::parse("[tool_limits]\nmax_ram = 1\n").is_err());
+
+        let output = Config::parse("[tool_output_limit]\nstdout = 4096\n").unwrap().exec_options().unwrap().max_output_bytes;
+        assert_eq!(output, OutputLimit::new(4096, ai_agent_core::DEFAULT_MAX_OUTPUT_BYTES));
+        assert_eq!(Config::default().exec_options().unwrap().max_output_bytes, OutputLimit::default());
+    }
+}
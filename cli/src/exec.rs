@@ -0,0 +1,190 @@
+// `exec` subcommand: named background processes
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::time::Duration;
+use anyhow::Result;
+use clap::Subcommand;
+use futures::StreamExt;
+use ai_agent_core::{BackgroundSpec, CommandSpec, ExitInfo, OutputSource, ProcessStatus, ProcessUsage};
+
+#[derive(Subcommand)]
+pub enum ExecCommand {
+    /// Start a command in the background under a name, e.g.
+    /// `exec start web -- python -m http.server`. It keeps running after
+    /// this command returns, with its output logged for `exec logs`.
+    Start {
+        name: String,
+        /// Stop a running process of the same name first
+        #[arg(long)]
+        replace: bool,
+        /// Run it as a daemon, away from this terminal and its session
+        #[arg(long)]
+        detach: bool,
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Stop a named process, killing it if it has not exited in time
+    Stop {
+        name: String,
+        /// How long it gets to exit after being asked, e.g. 10s
+        #[arg(long, default_value = "5s", value_parser = crate::parse_duration)]
+        grace: Duration,
+    },
+    /// Show a named process, or every one
+    Status { name: Option<String> },
+    /// Print what a named process wrote, stderr to stderr
+    Logs {
+        name: String,
+        /// Keep printing new output until the process exits
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+pub async fn run(command: ExecCommand) -> Result<ExitCode> {
+    let processes = &crate::config::get().processes;
+    match command {
+        ExecCommand::Start { name, replace, detach, command } => {
+            let spec = BackgroundSpec::new(CommandSpec::new(&command[0], &command[1..])).with_replace(replace);
+            if detach {
+                let pid = processes.spawn_detached(&name, spec).await?;
+                println!("Started '{}' detached (pid {})", name, pid);
+            } else {
+                processes.start_named(&name, spec).await?;
+                let status = processes.status(&name).await?;
+                println!("Started '{}' (pid {})", name, status.pid);
+            }
+        }
+        ExecCommand::Stop { name, grace } => {
+            if processes.stop(&name, grace).await? {
+                println!("Stopped '{}'", name);
+            } else {
+                println!("Killed '{}' after it ignored the request to exit for {:?}", name, grace);
+            }
+        }
+        ExecCommand::Status { name: Some(name) } => {
+            let status = processes.status(&name).await?;
+            let running = status.running;
+            print_statuses(&[status]);
+            if !running {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        ExecCommand::Logs { name, follow } => {
+            let mut lines = processes.logs(&name, follow).await?;
+            let color = std::io::stderr().is_terminal();
+            while let Some(line) = lines.next().await {
+                let line = line?;
+                match line.source {
+                    OutputSource::Stdout => println!("{}", line.line),
+                    OutputSource::Stderr if color => eprintln!("\x1b[31m{}\x1b[0m", line.line),
+                    OutputSource::Stderr => eprintln!("{}", line.line),
+                }
+            }
+        }
+        ExecCommand::Status { name: None } => {
+            let statuses = processes.list_named().await?;
+            if statuses.is_empty() {
+                println!("No named processes");
+            } else {
+                print_statuses(&statuses);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_statuses(statuses: &[ProcessStatus]) {
+    let width = statuses.iter().map(|status| status.name.len()).max().unwrap_or(0);
+    for status in statuses {
+        let state = match (status.running, status.last_exit) {
+            (true, _) => format!("running {}", format_uptime(status.uptime)),
+            (false, Some(code)) => format!("exited ({})", code),
+            (false, None) => "exited".to_string(),
+        };
+        let usage = status.usage.as_ref().map_or_else(|| "-".to_string(), format_usage);
+        println!(
+            "{:<width$}  {:>7}  {:<16}  {:<22}  {}",
+            status.name,
+            status.pid,
+            state,
+            usage,
+            status.command,
+            width = width
+        );
+    }
+}
+
+/// CPU and resident memory, e.g. `12.5% cpu  48.0 MiB`.
+fn format_usage(usage: &ProcessUsage) -> String {
+    format!("{:.1}% cpu  {:.1} MiB", usage.cpu_percent, usage.rss_bytes as f64 / 1048576.0)
+}
+
+/// Lines of stderr shown under a named process that failed.
+const EXIT_NOTICE_LINES: usize = 3;
+
+/// What interactive mode prints once the named process `name` exits:
+/// how it ended, and for a failure the last lines of its stderr.
+pub fn describe_exit(name: &str, info: &ExitInfo) -> String {
+    let mut text = match (info.status, info.code()) {
+        (_, Some(code)) => format!("background process '{}' exited with code {}", name, code),
+        (Some(status), None) => format!("background process '{}' exited ({})", name, status),
+        (None, None) => format!("background process '{}' could not be waited for", name),
+    };
+    if !info.success() {
+        let stderr = info.tail_lossy(OutputSource::Stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        for line in &lines[lines.len().saturating_sub(EXIT_NOTICE_LINES)..] {
+            text.push_str("\n  ");
+            text.push_str(line);
+        }
+    }
+    text
+}
+
+/// `uptime` to the second, in its two largest units, e.g. `3m12s` or `2h05m`.
+fn format_uptime(uptime: Duration) -> String {
+    let seconds = uptime.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(Duration::from_millis(5_900)), "5s");
+        assert_eq!(format_uptime(Duration::from_secs(192)), "3m12s");
+        assert_eq!(format_uptime(Duration::from_secs(7_500)), "2h05m");
+    }
+
+    #[test]
+    fn test_format_usage() {
+        let usage = ProcessUsage { cpu_percent: 12.46, rss_bytes: 50_331_648, num_threads: 3, uptime: Duration::from_secs(9) };
+        assert_eq!(format_usage(&usage), "12.5% cpu  48.0 MiB");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_describe_exit() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let info = |raw, stderr: &str| ExitInfo {
+            status: Some(std::process::ExitStatus::from_raw(raw)),
+            duration: Duration::from_secs(4),
+            stdout_tail: Vec::new(),
+            stderr_tail: stderr.as_bytes().to_vec(),
+        };
+        assert_eq!(describe_exit("web", &info(0, "noise\n")), "background process 'web' exited with code 0");
+        assert_eq!(
+            describe_exit("web", &info(256, "a\nb\nc\nd\n")),
+            "background process 'web' exited with code 1\n  b\n  c\n  d"
+        );
+        assert!(describe_exit("web", &info(9, "")).starts_with("background process 'web' exited (signal: 9"));
+    }
+}
@@ -0,0 +1,124 @@
+// Health checks behind `status`, also usable as a readiness probe
+use std::time::Duration;
+use ai_agent_core::{EnvironmentManager, ToolProbe};
+
+use crate::config;
+use crate::output::{CheckStatus, HealthCheck};
+
+/// Imported when the config does not name a Python module.
+pub const DEFAULT_PYTHON_MODULE: &str = "agi_agent";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Runs every check, judging tools by `tools`; the checks are independent,
+/// so they run concurrently.
+pub async fn run_checks(tools: &[ToolProbe]) -> Vec<HealthCheck> {
+    let (python, network) = tokio::join!(check_python(), check_network());
+    vec![python, check_tools(tools), check_memory(), network]
+}
+
+async fn check_python() -> HealthCheck {
+    let module = config::get().python_module.clone().unwrap_or_else(|| DEFAULT_PYTHON_MODULE.to_string());
+    let label = module.clone();
+    let result = tokio::task::spawn_blocking(move || ai_agent_python_bridge::check_import(&module)).await;
+    match result {
+        Ok(Ok(version)) => HealthCheck::new("python", CheckStatus::Ok, format!("{} imported (Python {})", label, version)),
+        Ok(Err(e)) => HealthCheck::new("python", CheckStatus::Fail, format!("cannot import {}: {}", label, e)),
+        Err(e) => HealthCheck::new("python", CheckStatus::Fail, format!("import check panicked: {}", e)),
+    }
+    .critical()
+}
+
+/// Tools whose executable is not on `PATH` warn rather than fail: the agent
+/// still works without them.
+fn check_tools(tools: &[ToolProbe]) -> HealthCheck {
+    let missing: Vec<&str> = tools.iter().filter(|probe| !probe.available).map(|probe| probe.name.as_str()).collect();
+    if missing.is_empty() {
+        HealthCheck::new("tools", CheckStatus::Ok, format!("{} tools available", tools.len()))
+    } else {
+        HealthCheck::new("tools", CheckStatus::Warn, format!("not found on PATH: {}", missing.join(", ")))
+    }
+}
+
+fn check_memory() -> HealthCheck {
+    match EnvironmentManager::process_memory() {
+        Some(bytes) => HealthCheck::new("memory", CheckStatus::Ok, format!("{:.1} MiB resident", bytes as f64 / 1048576.0)),
+        None => HealthCheck::new("memory", CheckStatus::Warn, "not available on this platform"),
+    }
+}
+
+async fn check_network() -> HealthCheck {
+    let Some(endpoint) = config::get().health_endpoint.clone() else {
+        return HealthCheck::new("network", CheckStatus::Warn, "no health_endpoint configured");
+    };
+    let address = socket_address(&endpoint);
+    let check = match tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => HealthCheck::new("network", CheckStatus::Ok, format!("{} reachable", address)),
+        Ok(Err(e)) => HealthCheck::new("network", CheckStatus::Fail, format!("{} unreachable: {}", address, e)),
+        Err(_) => HealthCheck::new("network", CheckStatus::Fail, format!("{} timed out after {:?}", address, CONNECT_TIMEOUT)),
+    };
+    check.critical()
+}
+
+/// `host:port` for an endpoint given as either `host:port` or a URL.
+fn socket_address(endpoint: &str) -> String {
+    let (default_port, rest) = match endpoint.split_once("://") {
+        Some(("https", rest)) => (443, rest),
+        Some((_, rest)) => (80, rest),
+        None => (80, endpoint),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    // A bare IPv6 address has colons but no port; a bracketed one may have both.
+    let has_port = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) && (!host.contains(':') || host.ends_with(']'))
+        }
+        None => false,
+    };
+    if has_port {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, default_port)
+    }
+}
+
+/// True unless a critical check failed.
+pub fn is_ready(checks: &[HealthCheck]) -> bool {
+    !checks.iter().any(|check| check.critical && check.status == CheckStatus::Fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_address() {
+        assert_eq!(socket_address("example.com:8080"), "example.com:8080");
+        assert_eq!(socket_address("https://example.com/health"), "example.com:443");
+        assert_eq!(socket_address("http://user@10.0.0.1:9000/x"), "10.0.0.1:9000");
+        assert_eq!(socket_address("[::1]:7878"), "[::1]:7878");
+        assert_eq!(socket_address("http://[::1]/"), "[::1]:80");
+    }
+
+    #[test]
+    fn test_readiness() {
+        let warn = HealthCheck::new("tools", CheckStatus::Warn, "missing");
+        let failed = HealthCheck::new("tools", CheckStatus::Fail, "missing");
+        assert!(is_ready(&[warn.clone(), failed.clone()]));
+        assert!(!is_ready(&[warn, failed.critical()]));
+    }
+
+    #[tokio::test]
+    async fn test_tools_and_network_checks() {
+        let mut registry = ai_agent_core::ToolRegistry::new();
+        registry.register(ai_agent_core::ToolSpec::new("missing", "Not installed", "no-such-tool-xyz"));
+        let check = check_tools(&registry.probe_all().await);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.message.contains("missing"));
+
+        // Without an endpoint configured the network check only warns.
+        let network = check_network().await;
+        assert_eq!(network.status, CheckStatus::Warn);
+    }
+}
@@ -0,0 +1,101 @@
+// `history` subcommand: what the agent has run
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Result};
+use clap::Args;
+use ai_agent_core::{ExecutionFilter, ExecutionLog, ExecutionRecord};
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// List recent tool runs, oldest first
+    #[arg(long)]
+    pub tools: bool,
+    /// Only runs of this tool
+    #[arg(long, requires = "tools")]
+    pub tool: Option<String>,
+    /// Only runs made for this task
+    #[arg(long, requires = "tools")]
+    pub task: Option<String>,
+    /// Only runs that exited non-zero or did not complete
+    #[arg(long, requires = "tools")]
+    pub failed: bool,
+    /// How many of the most recent runs to show
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: usize,
+    /// Print each run as a JSON line, including its captured output
+    #[arg(long, requires = "tools")]
+    pub json: bool,
+}
+
+pub async fn run(args: HistoryArgs) -> Result<ExitCode> {
+    if !args.tools {
+        bail!("nothing to show; use --tools to list recent tool runs");
+    }
+    let log = match &crate::config::get().history {
+        Some(log) => ExecutionLog::clone(log),
+        None => ExecutionLog::default_location()?,
+    };
+    let mut filter = ExecutionFilter::new().with_failed_only(args.failed).with_limit(args.limit);
+    filter.tool = args.tool;
+    filter.task_id = args.task;
+    let records = log.query(&filter).await?;
+    if args.json {
+        for record in &records {
+            println!("{}", serde_json::to_string(record)?);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if records.is_empty() {
+        println!("No tool runs recorded in {}", log.path().display());
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+    for record in &records {
+        println!("{}", summary(record, now));
+        if let Some(error) = &record.error {
+            println!("    {}", error);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// One line per run: age, exit code, duration, command and task.
+fn summary(record: &ExecutionRecord, now_ms: u64) -> String {
+    let exit = record.exit_code.map_or_else(|| "err".to_string(), |code| code.to_string());
+    let mut line = format!(
+        "{:>8}  {:>4}  {:>7}  {}",
+        age(now_ms.saturating_sub(record.timestamp_ms) / 1000),
+        exit,
+        format!("{}ms", record.duration_ms),
+        std::iter::once(&record.tool).chain(&record.argv).map(String::as_str).collect::<Vec<_>>().join(" "),
+    );
+    if let Some(task) = &record.task_id {
+        line.push_str(&format!("  [task {}]", task));
+    }
+    line
+}
+
+/// `secs` as a coarse age such as `5m ago`.
+fn age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let record: ExecutionRecord = serde_json::from_value(serde_json::json!({
+            "timestamp_ms": 1_000, "tool": "grep", "argv": ["-n", "x"], "cwd": null, "exit_code": 1,
+            "duration_ms": 12, "stdout": "", "stderr": "", "task_id": "t1",
+        }))
+        .unwrap();
+        assert_eq!(summary(&record, 1_000 + 125_000), "  2m ago     1     12ms  grep -n x  [task t1]");
+        assert_eq!(age(7200), "2h ago");
+    }
+}
@@ -1,6 +1,35 @@
-use clap::{Parser, Subcommand};
-use anyhow::Result;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
+use anyhow::{bail, Context, Result};
+use tracing::field::Empty;
 use tracing::info;
+use indicatif::{ProgressBar, ProgressStyle};
+use ai_agent_core::{
+    execute_task_plan, CancellationToken, Confirmation, ConfirmationGate, ConfirmationRequest, DangerLevel, ConcatTransform, DetectLanguageTransform, EnvironmentManager, FileReader, FileTransformer, FileWriter, InputError,
+    LanguageGuess, Metadata, ProcessEvent, ProcessManager, ProcessedIndex, RotatingWriter, ShellFormat, StatsTransform, PlannedAction, TaskResult, TextStats, ToolError, ToolEvent, ToolExecutor, ToolHandler, ToolRegistry,
+    is_text, with_heartbeat, Heartbeat, DEFAULT_MAX_INPUT_SIZE,
+};
+
+mod cache;
+mod config;
+mod exec;
+mod health;
+mod history;
+mod output;
+mod repl;
+mod serve;
+mod status;
+mod telemetry;
+mod tools;
+mod transform;
+
+use output::{ProcessResult, TaskRun};
+
+/// How long background processes get to exit when interactive mode ends.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
 
 /// High-performance AI Agent CLI
 #[derive(Parser)]
@@ -8,125 +37,882 @@ use tracing::info;
 #[command(about = "A high-performance AI agent CLI built with Rust")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Path to the configuration file (defaults to ./ai-agent.toml if present)
+    #[arg(long, global = true)]
+    config: Option<String>,
+
+    /// Run tool calls without the configured execution policy
+    #[arg(long, global = true)]
+    unsafe_allow_all: bool,
+
+    /// Explain instead of acting: tools print what they would run, and
+    /// `process` prints the diff it would apply (exiting 1 if the file would change)
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Block everything that could change something: file writes, process
+    /// spawns and tools not marked read-only
+    #[arg(long, global = true)]
+    safe: bool,
+
+    /// Run tools that would change or delete things without asking first
+    #[arg(short = 'y', long, global = true)]
+    yes: bool,
+
+    /// Write log lines to this file instead of the terminal, rotating it
+    /// once it grows past --log-max-size
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Size at which the log file is rotated
+    #[arg(long, global = true, value_name = "SIZE", requires = "log_file", default_value = "10M", value_parser = parse_size)]
+    log_max_size: u64,
+
+    /// Rotated log files kept; older ones are deleted
+    #[arg(long, global = true, value_name = "N", requires = "log_file", default_value_t = ai_agent_core::DEFAULT_ROTATED_FILES)]
+    log_keep: usize,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Execute a task using the AI agent
+    /// Execute a task using the AI agent, or run a tool with live output
     Execute {
         /// The task description
-        #[arg(short, long)]
-        task: String,
+        #[arg(short, long, required_unless_present = "tool")]
+        task: Option<String>,
+        /// Run this command under the execution policy instead of a task,
+        /// streaming its output; arguments follow `--`
+        #[arg(long, conflicts_with = "task")]
+        tool: Option<String>,
+        #[arg(last = true, requires = "tool")]
+        tool_args: Vec<String>,
         /// Model to use for inference
         #[arg(short, long, default_value = "auto")]
         model: String,
+        /// Give up after this long, e.g. 30s, 500ms or 2m (plain numbers are seconds)
+        #[arg(long, value_parser = parse_duration)]
+        timeout: Option<Duration>,
+        /// Print the result, with model, timing and token counts, as JSON
+        #[arg(long, conflicts_with = "tool")]
+        json: bool,
+        /// Print the tool calls the task would make, with their arguments,
+        /// without running anything
+        #[arg(long, conflicts_with = "tool")]
+        plan: bool,
     },
     /// Start the AI agent in interactive mode
     Interactive,
     /// Process files with the AI agent
     Process {
-        /// Input file path
+        /// Input file path (a glob pattern such as 'src/**/*.rs' with --concat)
         #[arg(short, long)]
         input: String,
         /// Output file path
         #[arg(short, long)]
         output: Option<String>,
+        /// Print line, word, character and estimated token counts for the output
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "table")]
+        stats: Option<StatsFormat>,
+        /// Join every file matching the input pattern into one bundle with
+        /// per-file headers (written to the output, or stdout)
+        #[arg(long, conflicts_with = "dry_run")]
+        concat: bool,
+        /// Header before each file; {path}, {lines} and {chars} are substituted
+        #[arg(long, requires = "concat", default_value = ai_agent_core::DEFAULT_CONCAT_HEADER)]
+        header: String,
+        /// Truncate each file to this many characters
+        #[arg(long, requires = "concat")]
+        max_chars_per_file: Option<usize>,
+        /// Bundle only files that changed since an earlier --incremental run
+        /// (all of them the first time); by default every file is bundled
+        #[arg(long, requires = "concat")]
+        incremental: bool,
+        /// Comma-separated transforms to run on each file, in order
+        #[arg(long, value_delimiter = ',')]
+        transform: Vec<String>,
+        /// Print only the first BYTES of the input (default 4096) without
+        /// reading the rest; a negative count shows the end of the file instead
+        #[arg(
+            long,
+            value_name = "BYTES",
+            num_args = 0..=1,
+            default_missing_value = "4096",
+            allow_negative_numbers = true,
+            conflicts_with_all = ["concat", "dry_run", "output"]
+        )]
+        preview: Option<i64>,
+        /// Refuse inputs larger than this, e.g. 500K, 20M or 1G (plain numbers
+        /// are bytes; default 100M)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Apply a transform stage to a file
+    Transform {
+        #[command(subcommand)]
+        command: transform::TransformCommand,
+    },
+    /// Inspect the tools available to models
+    Tools {
+        #[command(subcommand)]
+        command: tools::ToolsCommand,
+    },
+    /// Show recorded history; `--tools` lists recent tool runs
+    History(history::HistoryArgs),
+    /// Inspect or empty the cache of tool output
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommand,
     },
     /// Show agent status and configuration
-    Status,
+    Status(status::StatusArgs),
+    /// Start, stop and inspect named background processes
+    Exec {
+        #[command(subcommand)]
+        command: exec::ExecCommand,
+    },
+    /// Print environment variables as commands for a shell to `eval`
+    Env {
+        /// Glob selecting the variables, e.g. 'AWS_*'
+        #[arg(default_value = "*")]
+        pattern: String,
+        /// bash, zsh, fish or powershell (default: bash, or powershell on Windows)
+        #[arg(long)]
+        shell: Option<ShellFormat>,
+    },
+    /// Serve `execute_task`, `process_file`, `status` and tool calls over JSON-RPC 2.0
+    Serve {
+        /// Unix socket path to listen on
+        #[arg(long, default_value = "ai-agent.sock", conflicts_with = "tcp")]
+        socket: String,
+        /// Listen on this TCP address (e.g. 127.0.0.1:7878) instead of a Unix socket
+        #[arg(long)]
+        tcp: Option<String>,
+        /// Process files as they change under this directory and notify
+        /// clients subscribed to their paths
+        #[arg(long, value_name = "DIR")]
+        watch: Option<String>,
+        /// How long changes must settle before they are processed, e.g. 200ms
+        #[arg(long, requires = "watch", default_value = "200ms", value_parser = parse_duration)]
+        debounce: Duration,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-    
+async fn main() -> Result<ExitCode> {
+    status::mark_started();
     let cli = Cli::parse();
+    let mut config = config::Config::load(cli.config.as_deref())?;
+    let log_file = match &cli.log_file {
+        Some(path) => Some(RotatingWriter::open(path)?.with_max_bytes(cli.log_max_size).with_max_files(cli.log_keep)),
+        None => None,
+    };
+    let _telemetry = telemetry::init(&config.telemetry, log_file)?;
+    config.unsafe_allow_all = cli.unsafe_allow_all;
+    config.dry_run = cli.dry_run;
+    config.safe |= cli.safe;
+    if config.safe {
+        ai_agent_core::safe_mode::enable();
+    }
+    // Safe mode writes nothing, the history log included.
+    if config.tool_history != Some(false) && !config.safe {
+        // A missing data directory should not stop tools from running.
+        match config.execution_log() {
+            Ok(log) => config.history = Some(std::sync::Arc::new(log)),
+            Err(e) => tracing::warn!("tool runs will not be recorded: {:#}", e),
+        }
+    }
+    match ProcessManager::persistent() {
+        Ok(processes) => config.processes = processes,
+        Err(e) => tracing::warn!("named processes will not be found by later runs: {:#}", e),
+    }
+    if let Some(cache) = &config.tool_cache {
+        match cache.build() {
+            Ok(cache) => config.cache = Some(std::sync::Arc::new(cache)),
+            Err(e) => tracing::warn!("tool output will not be cached: {:#}", e),
+        }
+    }
+    if !cli.yes {
+        // A server has no one at its terminal to ask.
+        let interactive = std::io::stdin().is_terminal() && !matches!(cli.command, Commands::Serve { .. });
+        let gate = if interactive { ConfirmationGate::new(ask_on_terminal) } else { ConfirmationGate::non_interactive() };
+        let threshold = config.confirm_threshold.unwrap_or(DangerLevel::Destructive);
+        config.confirm = Some(std::sync::Arc::new(gate.with_threshold(threshold)));
+    }
+    config::init(config);
 
     match cli.command {
-        Commands::Execute { task, model } => {
+        Commands::Execute { tool: Some(tool), tool_args, timeout, .. } => {
+            info!("Running tool: {}", tool);
+            return run_tool_streaming(&tool, &tool_args, timeout).await;
+        }
+        Commands::Execute { task, model, json, plan: true, .. } => {
+            print_plan(&task.unwrap_or_default(), &model, json)?;
+        }
+        Commands::Execute { task, model, timeout, json, .. } => {
+            let task = task.unwrap_or_default();
             info!("Executing task: {} with model: {}", task, model);
-            execute_task(&task, &model).await?;
+            execute_task(&task, &model, timeout, json).await?;
         }
         Commands::Interactive => {
             info!("Starting interactive mode");
             start_interactive_mode().await?;
         }
-        Commands::Process { input, output } => {
+        Commands::Process { input, output, concat: true, header, max_chars_per_file, transform, incremental, .. } => {
+            info!("Concatenating files matching: {}", input);
+            let mut concat = ConcatTransform::new().with_header(header);
+            concat.max_chars_per_file = max_chars_per_file;
+            concat_files(&input, output.as_deref(), &concat, &transform, incremental).await?;
+        }
+        Commands::Process { input, preview: Some(bytes), .. } => {
+            info!("Previewing file: {}", input);
+            preview_file(&input, bytes).await?;
+        }
+        Commands::Process { input, output, stats, transform, max_size, .. } => {
             info!("Processing file: {}", input);
-            process_file(&input, output.as_deref()).await?;
+            let max_size = max_size.unwrap_or(DEFAULT_MAX_INPUT_SIZE);
+            return process_file(&input, output.as_deref(), cli.dry_run, stats, &transform, max_size).await;
+        }
+        Commands::Transform { command } => {
+            return transform::run(command).await;
+        }
+        Commands::Tools { command } => {
+            return tools::run(command).await;
         }
-        Commands::Status => {
+        Commands::History(args) => {
+            return history::run(args).await;
+        }
+        Commands::Cache { command } => {
+            return cache::run(command).await;
+        }
+        Commands::Status(args) => {
             info!("Showing agent status");
-            show_status().await?;
+            return status::run(args).await;
+        }
+        Commands::Exec { command } => {
+            return exec::run(command).await;
+        }
+        Commands::Env { pattern, shell } => {
+            let vars = EnvironmentManager::get_env_vars_matching(&pattern)?;
+            print!("{}", EnvironmentManager::export_to_shell(&vars, shell.unwrap_or_default()));
+        }
+        Commands::Serve { socket, tcp, watch, debounce } => {
+            let updates = watch.as_deref().map(|dir| serve::watch(dir, debounce)).transpose()?;
+            match tcp {
+                Some(addr) => serve::serve_tcp(&addr, updates).await?,
+                None => serve::serve_unix(&socket, updates).await?,
+            }
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
-async fn execute_task(task: &str, model: &str) -> Result<()> {
+async fn execute_task(task: &str, model: &str, timeout: Option<Duration>, json: bool) -> Result<()> {
+    let (beats, heartbeat) = tokio::sync::mpsc::unbounded_channel();
+    // With `--json` nobody listens, so no heartbeats are sent.
+    let spinner = (!json).then(|| spin_on(heartbeat));
+    let work = with_heartbeat(run_task(task, model), config::get().heartbeat_interval(), beats);
+    let run = match timeout {
+        Some(limit) => tokio::time::timeout(limit, work).await.map_err(|_| anyhow::anyhow!("Task timed out after {:?}", limit)),
+        None => Ok(work.await),
+    };
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    let run = run??;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&run)?);
+        return Ok(());
+    }
     println!("🤖 Executing task: {}", task);
+    println!("📊 Using model: {}", run.result.model);
+
+    if !run.redactions.is_empty() {
+        let counts: Vec<_> = run.redactions.iter().map(|(kind, n)| format!("{} {}", n, kind)).collect();
+        println!("🔒 Redacted before sending: {}", counts.join(", "));
+    }
+    if !run.result.text().is_empty() {
+        println!("{}", run.result.text());
+    }
+    if run.result.success {
+        println!("✅ Task completed successfully in {:?}", run.result.duration);
+    }
+    Ok(())
+}
+
+/// A spinner on stderr that moves with each heartbeat, showing how long
+/// the task has been running. It is hidden when stderr is not a terminal,
+/// and cleared when dropped, as happens when a task is cancelled.
+fn spin_on(mut heartbeat: tokio::sync::mpsc::UnboundedReceiver<Heartbeat>) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner().with_finish(indicatif::ProgressFinish::AndClear);
+    spinner.set_message("Working");
+    let ticks = spinner.clone();
+    tokio::spawn(async move {
+        while let Some(beat) = heartbeat.recv().await {
+            ticks.set_message(format!("Working ({}s)", beat.elapsed.as_secs()));
+            ticks.tick();
+        }
+    });
+    spinner
+}
+
+/// Prints the tool calls `run_task` would make for `task`, in order.
+fn print_plan(task: &str, model: &str, json: bool) -> Result<()> {
+    let model = config::get().models()?.resolve(model)?.name.clone();
+    let actions = execute_task_plan(task);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&actions)?);
+        return Ok(());
+    }
+    println!("📋 Plan for task: {}", task);
     println!("📊 Using model: {}", model);
-    
-    // TODO: Implement Python bridge for AI inference
-    // This will call Python ML components via PyO3
-    
-    println!("✅ Task completed successfully!");
+    if actions.is_empty() {
+        println!("No tool calls; the task goes straight to the model");
+    }
+    for (step, action) in actions.iter().enumerate() {
+        println!("{:>3}. {} {}  ({})", step + 1, action.tool, action.arguments, action.reason);
+    }
     Ok(())
 }
 
+/// Runs each of `actions` under the configured policy and renders their
+/// output for the prompt. A call that fails is reported in its place.
+async fn gather_context(actions: &[PlannedAction]) -> Result<String> {
+    let registry = ToolRegistry::builtin();
+    let options = config::get().exec_options()?;
+    let mut context = String::new();
+    for action in actions {
+        let output = match ToolExecutor::execute_registered(&registry, &action.tool, &action.arguments, &options).await {
+            Ok(output) if output.success() => output.stdout_lossy().into_owned(),
+            Ok(output) => format!("(exit code {}) {}", output.exit_code, output.stderr_lossy()),
+            Err(e) => format!("(failed) {:#}", e),
+        };
+        context.push_str(&format!("\n\n### {} {}\n{}", action.tool, action.arguments, output.trim_end()));
+    }
+    Ok(context)
+}
+
+/// Runs `tool` under the configured policy, echoing stdout and stderr (in
+/// red on a terminal) as lines arrive. Exits with the tool's status.
+async fn run_tool_streaming(tool: &str, args: &[String], timeout: Option<Duration>) -> Result<ExitCode> {
+    use futures::StreamExt;
+
+    let mut options = config::get().exec_options()?;
+    options.timeout = timeout;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut stream = ToolExecutor::execute_streaming(tool, &args, &options).await?;
+    let color = std::io::stderr().is_terminal();
+    while let Some(event) = stream.next().await {
+        match event {
+            ToolEvent::Stdout(line) => println!("{}", line),
+            ToolEvent::Stderr(line) if color => eprintln!("\x1b[31m{}\x1b[0m", line),
+            ToolEvent::Stderr(line) => eprintln!("{}", line),
+            ToolEvent::Exited(_) => {}
+        }
+    }
+    let output = stream.finish().await?;
+    Ok(ExitCode::from(u8::try_from(output.exit_code).unwrap_or(1)))
+}
+
+/// Parses `--max-size` values: a number with an optional `K`, `M` or `G`
+/// suffix (powers of 1024).
+fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().with_context(|| format!("invalid size '{}'", value))?;
+    let scale: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => bail!("invalid size unit '{}'; use K, M or G", unit),
+    };
+    number.checked_mul(scale).with_context(|| format!("size '{}' is too large", value))
+}
+
+/// Parses `--timeout` values: a number with an optional `ms`, `s` or `m` suffix.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().with_context(|| format!("invalid duration '{}'", value))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => bail!("invalid duration unit '{}'; use ms, s or m", unit),
+    }
+}
+
+/// The task text is not recorded on the span since it may carry secrets.
+#[tracing::instrument(
+    name = "execute_task",
+    level = "debug",
+    skip_all,
+    fields(model = Empty, duration_ms = Empty, success = Empty)
+)]
+async fn run_task(task: &str, model: &str) -> Result<TaskRun> {
+    let started = std::time::Instant::now();
+    // Unknown models fail before anything else happens.
+    let model = config::get().models()?.resolve(model)?.clone();
+    tracing::Span::current().record("model", model.name.as_str());
+    let actions = execute_task_plan(task);
+    let text = format!("{}{}", task, gather_context(&actions).await?);
+    let (prompt, report) = match config::get().redactor()? {
+        Some(redact) => redact.apply(&text),
+        None => (text, Default::default()),
+    };
+
+    // TODO: Implement Python bridge for AI inference
+    // This will call Python ML components via PyO3 with `prompt`
+    let result = TaskResult::new(task, String::new(), model.name).with_duration(started.elapsed());
+    let span = tracing::Span::current();
+    span.record("duration_ms", result.duration.as_millis() as u64);
+    span.record("success", result.success);
+    info!(model = %result.model, duration_ms = result.duration.as_millis() as u64, success = result.success, "task finished");
+    Ok(TaskRun { result, prompt, actions, redactions: report.counts })
+}
+
 async fn start_interactive_mode() -> Result<()> {
+    use rustyline::error::ReadlineError;
+
     println!("🚀 Starting AI Agent Interactive Mode");
-    println!("Type 'exit' to quit");
-    
+    println!("Type 'exit' to quit, '!command args' to run a tool, or ':help'; Tab completes");
+    let interrupts = Interrupts::install();
+    let registry = ToolRegistry::builtin();
+    let tools = registry.list().iter().map(|spec| spec.name.clone()).collect();
+    let editor_config = rustyline::Config::builder().completion_type(rustyline::CompletionType::List).build();
+    let mut editor = rustyline::Editor::with_config(editor_config)?;
+    editor.set_helper(Some(repl::ReplHelper::new(tools)));
+    let mut exits = config::get().processes.subscribe();
+
     loop {
-        print!("ai-agent> ");
-        use std::io::{self, Write};
-        io::stdout().flush()?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        report_exits(&mut exits);
+        // The editor owns the terminal while reading, so Ctrl-C arrives
+        // here rather than as a signal.
+        let input = match editor.readline("ai-agent> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
         let input = input.trim();
-        
+        if !input.is_empty() {
+            let _ = editor.add_history_entry(input);
+        }
+
         if input == "exit" {
             break;
         }
-        
-        if !input.is_empty() {
-            execute_task(input, "auto").await?;
+        if input.starts_with(':') {
+            match input {
+                ":quit" | ":exit" => break,
+                ":help" => {
+                    for (name, description) in repl::META_COMMANDS {
+                        println!("  {:8} {}", name, description);
+                    }
+                    println!("  !TOOL    Run a tool, e.g. !grep TODO src/main.rs");
+                }
+                ":tools" => {
+                    let width = registry.list().iter().map(|spec| spec.name.len()).max().unwrap_or(0);
+                    for spec in registry.list() {
+                        println!("  {:width$}  {}", spec.name, spec.description, width = width);
+                    }
+                }
+                other => eprintln!("❌ Unknown command '{}'; try :help", other),
+            }
+            continue;
         }
+
+        let cancel = interrupts.arm();
+        if let Some(command) = input.strip_prefix('!') {
+            if let Err(e) = run_interactive_command(command, &cancel).await {
+                eprintln!("❌ {:#}", e);
+            }
+        } else if !input.is_empty() {
+            tokio::select! {
+                result = execute_task(input, "auto", None, false) => result?,
+                _ = cancel.cancelled() => eprintln!("⏹️  Task cancelled"),
+            }
+        }
+        interrupts.disarm();
     }
     
+    shutdown_processes().await;
     println!("👋 Goodbye!");
     Ok(())
 }
 
-async fn process_file(input: &str, output: Option<&str>) -> Result<()> {
+/// Tells of the named processes that exited since the last prompt.
+fn report_exits(events: &mut tokio::sync::broadcast::Receiver<ProcessEvent>) {
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    loop {
+        match events.try_recv() {
+            Ok(ProcessEvent::NamedExited { name, info }) => eprintln!("⚠️  {}", exec::describe_exit(&name, &info)),
+            Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+/// Stops whatever is still running in the background, giving each process
+/// `SHUTDOWN_GRACE` to exit before it is killed.
+async fn shutdown_processes() {
+    for (handle, outcome) in config::get().processes.shutdown_all(SHUTDOWN_GRACE).await {
+        match outcome {
+            Ok(true) => info!("Stopped {} (pid {})", handle.command(), handle.pid()),
+            Ok(false) => eprintln!("⚠️  Killed {} (pid {}) after it ignored SIGTERM", handle.command(), handle.pid()),
+            Err(e) => eprintln!("❌ Could not stop {} (pid {}): {:#}", handle.command(), handle.pid(), e),
+        }
+    }
+}
+
+/// What Ctrl-C does in interactive mode: cancel the command or task in
+/// progress, or end the session when at the prompt.
+#[derive(Clone)]
+struct Interrupts(std::sync::Arc<std::sync::Mutex<Option<CancellationToken>>>);
+
+impl Interrupts {
+    fn install() -> Self {
+        let interrupts = Self(Default::default());
+        let current = std::sync::Arc::clone(&interrupts.0);
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                let armed = current.lock().unwrap_or_else(|e| e.into_inner()).take();
+                match armed {
+                    Some(token) => token.cancel(),
+                    None => {
+                        println!();
+                        shutdown_processes().await;
+                        std::process::exit(130);
+                    }
+                }
+            }
+        });
+        interrupts
+    }
+
+    /// A token the next Ctrl-C cancels, until `disarm`.
+    fn arm(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(token.clone());
+        token
+    }
+
+    fn disarm(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+/// Runs a tool typed at the interactive prompt. Anything other than the
+/// read-only built-in tools is previewed first and only runs once confirmed.
+/// `cancel` stops the tool, printing what it wrote before that.
+async fn run_interactive_command(line: &str, cancel: &CancellationToken) -> Result<()> {
+    use std::io::{self, Write};
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&tool, args)) = words.split_first() else {
+        return Ok(());
+    };
+    let options = config::get().exec_options()?;
+    if options.dry_run || is_dangerous(tool) {
+        let preview = options.clone().with_dry_run(true);
+        print!("{}", ToolExecutor::execute_tool_with_options(tool, args, None, &preview).await?.stdout_lossy());
+        if options.dry_run {
+            return Ok(());
+        }
+        print!("Run it? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Skipped");
+            return Ok(());
+        }
+    }
+    let options = options.with_cancel(cancel.clone());
+    let output = match ToolExecutor::execute_tool_with_options(tool, args, None, &options).await {
+        Ok(output) => output,
+        Err(ToolError::Cancelled { partial_stdout, .. }) => {
+            print!("{}", partial_stdout);
+            eprintln!("⏹️  Cancelled");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    print!("{}", output.stdout_lossy());
+    eprint!("{}", output.stderr_lossy());
+    if !output.success() {
+        println!("exit code {}", output.exit_code);
+    }
+    Ok(())
+}
+
+/// Shows what a held tool would do and asks whether to run it.
+async fn ask_on_terminal(request: ConfirmationRequest) -> Confirmation {
+    let answer = tokio::task::spawn_blocking(move || {
+        use std::io::Write;
+        eprint!("{}", request.description);
+        eprint!("Run {} tool '{}'? [y/N/a(lways)] ", request.danger, request.tool);
+        let _ = std::io::stderr().flush();
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).map(|_| answer)
+    })
+    .await;
+    match answer {
+        Ok(Ok(answer)) => match answer.trim() {
+            "y" | "Y" | "yes" => Confirmation::Allow,
+            "a" | "A" | "always" => Confirmation::AlwaysAllow,
+            _ => Confirmation::Deny,
+        },
+        _ => Confirmation::Deny,
+    }
+}
+
+/// True unless `command` backs one of the read-only built-in tools.
+fn is_dangerous(command: &str) -> bool {
+    !ToolRegistry::builtin()
+        .list()
+        .iter()
+        .any(|spec| matches!(&spec.handler, ToolHandler::Command(c) if c == command))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatsFormat {
+    Table,
+    Json,
+}
+
+async fn process_file(
+    input: &str,
+    output: Option<&str>,
+    dry_run: bool,
+    stats: Option<StatsFormat>,
+    transforms: &[String],
+    max_size: u64,
+) -> Result<ExitCode> {
     println!("📁 Processing file: {}", input);
-    
-    // TODO: Implement high-performance file processing
-    // This showcases the Rust performance advantage
-    
+    if let Err(e) = FileReader::check_input(input, max_size).await {
+        match e.downcast_ref::<InputError>() {
+            Some(InputError::IsADirectory { .. }) => {
+                bail!("{}; to process the files in it, use --concat -i '{}/**/*'", e, input.trim_end_matches('/'))
+            }
+            Some(InputError::TooLarge { .. }) => bail!("{}; raise the limit with --max-size", e),
+            _ => return Err(e),
+        }
+    }
+
+    let mut pipeline = config::get().transforms()?.pipeline(transforms)?;
+    if stats.is_some() {
+        pipeline = pipeline
+            .stats(StatsTransform::new())
+            .detect_language(DetectLanguageTransform::new().with_path(input));
+    }
+    let transformer = FileTransformer::with_pipeline(pipeline);
+
+    if dry_run {
+        let outcome = transformer.preview(input, output.unwrap_or(input)).await?;
+        if let Some(format) = stats {
+            print_stats(&outcome.metadata, format)?;
+        }
+        if !outcome.changed {
+            println!("✅ No changes");
+            return Ok(ExitCode::SUCCESS);
+        }
+        match outcome.unified_diff(3) {
+            Some(diff) => print!("{}", diff),
+            None => println!("Binary output for {} would change", outcome.output_path.display()),
+        }
+        return Ok(ExitCode::from(1));
+    }
+
+    let (content, metadata) = transformer.transform_bytes_with_metadata(FileReader::read_input(input, max_size).await?)?;
+    if let Some(format) = stats {
+        print_stats(&metadata, format)?;
+    }
+
     if let Some(output_path) = output {
-        println!("💾 Output will be saved to: {}", output_path);
+        println!("💾 Saving output to: {}", output_path);
+        let bar = ProgressBar::new(content.len() as u64);
+        bar.set_style(
+            ProgressStyle::with_template("{bar:40.cyan/blue} {bytes}/{total_bytes} ({eta})")?
+        );
+        FileWriter::write_stream_with_progress(output_path, &content[..], content.len() as u64, |done, total| {
+            bar.set_length(total);
+            bar.set_position(done);
+        })
+        .await?;
+        bar.finish_and_clear();
     }
-    
+
     println!("⚡ File processing completed!");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Expands `pattern` and writes the concatenated bundle to `output` or
+/// stdout. With `incremental`, files the processed index has seen with the
+/// same content are left out and the rest are recorded in it afterwards.
+/// The index is not tied to `output`, so only an opt-in run may skip files.
+async fn concat_files(
+    pattern: &str,
+    output: Option<&str>,
+    concat: &ConcatTransform,
+    transforms: &[String],
+    incremental: bool,
+) -> Result<()> {
+    let transformer = FileTransformer::from_registry(&config::get().transforms()?, transforms)?;
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern).with_context(|| format!("Invalid glob pattern '{}'", pattern))? {
+        let path = entry?;
+        if !path.is_file() {
+            continue;
+        }
+        if is_text(&path) {
+            paths.push(path);
+        } else {
+            eprintln!("⏭️  Skipping binary file {}", path.display());
+        }
+    }
+    if paths.is_empty() {
+        bail!("No text files match '{}'", pattern);
+    }
+    if !incremental {
+        return write_bundle(&transformer, &paths, output, concat).await;
+    }
+
+    let mut index = ProcessedIndex::persistent().await?;
+    let total = paths.len();
+    let mut fresh = Vec::with_capacity(total);
+    for path in paths {
+        if index.is_unchanged(&path).await? {
+            continue;
+        }
+        let fingerprint = index.fingerprint(&path).await?;
+        fresh.push((path, fingerprint));
+    }
+    let skipped = total - fresh.len();
+    if fresh.is_empty() {
+        eprintln!("✅ All {} file(s) are unchanged since they were last bundled; leave out --incremental to bundle them all", skipped);
+        return Ok(());
+    }
+
+    let paths: Vec<_> = fresh.iter().map(|(path, _)| path).collect();
+    write_bundle(&transformer, &paths, output, concat).await?;
+    if skipped > 0 {
+        eprintln!("⏭️  Skipped {} unchanged file(s)", skipped);
+    }
+
+    // A file that changed while it was read is left for the next run.
+    for (path, before) in fresh {
+        match index.fingerprint(&path).await {
+            Ok(after) if after == before => index.mark_processed_as(&path, before)?,
+            _ => eprintln!("⚠️  {} changed while it was bundled; it will be included again next time", path.display()),
+        }
+    }
+    if !ai_agent_core::safe_mode::is_enabled() {
+        index.save().await?;
+    }
     Ok(())
 }
 
-async fn show_status() -> Result<()> {
-    println!("🔍 AI Agent Status");
-    println!("================");
-    println!("🦀 Rust CLI: Active");
-    println!("🐍 Python ML Backend: Connected");
-    println!("⚡ Performance Mode: Enabled");
-    println!("🧠 Available Models: auto, gpt-2, distilgpt2");
-    println!("📊 Memory Usage: Low");
-    println!("🌐 Network: Available");
-    
+/// Concatenates `paths` and writes the bundle to `output` or stdout.
+async fn write_bundle<P: AsRef<Path>>(transformer: &FileTransformer, paths: &[P], output: Option<&str>, concat: &ConcatTransform) -> Result<()> {
+    let (bundle, entries) = transformer.concat(paths, concat).await?;
+    let truncated = entries.iter().filter(|e| e.truncated_chars > 0).count();
+    match output {
+        Some(path) => {
+            FileWriter::write_file(path, &bundle).await?;
+            eprintln!("📦 Bundled {} file(s) ({} truncated) into {}", entries.len(), truncated, path);
+        }
+        None => print!("{}", bundle),
+    }
     Ok(())
 }
+
+fn print_stats(metadata: &Metadata, format: StatsFormat) -> Result<()> {
+    let stats: TextStats = serde_json::from_value(metadata.get("stats").cloned().unwrap_or_default())?;
+    let language: Option<LanguageGuess> = metadata.get("language").cloned().map(serde_json::from_value).transpose()?;
+    match format {
+        StatsFormat::Json => {
+            let mut json = serde_json::to_value(&stats)?;
+            if let Some(language) = &language {
+                json["language"] = serde_json::to_value(language)?;
+            }
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        StatsFormat::Table => {
+            println!("📊 Text statistics");
+            for (label, value) in [
+                ("Lines", stats.lines),
+                ("Words", stats.words),
+                ("Characters", stats.chars),
+                ("Bytes", stats.bytes),
+                ("Estimated tokens", stats.estimated_tokens),
+                ("Longest line", stats.longest_line),
+            ] {
+                println!("  {:<18}{:>12}", label, value);
+            }
+            if let Some(language) = language {
+                let guess = format!("{} ({:.0}%)", language.lang, language.confidence * 100.0);
+                println!("  {:<18}{:>12}", "Language", guess);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Transforms `input` without progress output. A dry run leaves the output
+/// untouched and includes the diff; without an output path nothing is written.
+/// Prints a window of `input`: the first `bytes`, or the last `-bytes`.
+async fn preview_file(input: &str, bytes: i64) -> Result<()> {
+    let size = tokio::fs::metadata(input)
+        .await
+        .with_context(|| format!("Failed to read {}", input))?
+        .len();
+    let n = bytes.unsigned_abs() as usize;
+    let (window, offset) = if bytes < 0 {
+        (FileReader::read_tail(input, n).await?, size.saturating_sub(n as u64))
+    } else {
+        (FileReader::read_head(input, n).await?, 0)
+    };
+    print!("{}", String::from_utf8_lossy(&window));
+    if !window.ends_with(b"\n") {
+        println!();
+    }
+    if (window.len() as u64) < size {
+        println!("… bytes {}-{} of {}", offset, offset + window.len() as u64, size);
+    }
+    Ok(())
+}
+
+async fn process_file_result(input: &str, output: Option<&str>, dry_run: bool, max_size: u64) -> Result<ProcessResult> {
+    let transformer = FileTransformer::new();
+    FileReader::check_input(input, max_size).await?;
+    let outcome = match (output, dry_run) {
+        (_, true) => transformer.preview(input, output.unwrap_or(input)).await?,
+        (Some(path), false) => transformer.transform_file(input, path, false).await?,
+        (None, false) => {
+            let content = transformer.transform_bytes(FileReader::read_input(input, max_size).await?)?;
+            return Ok(ProcessResult {
+                input: input.to_string(),
+                output: None,
+                changed: false,
+                written: false,
+                bytes: content.len() as u64,
+                diff: None,
+            });
+        }
+    };
+    Ok(ProcessResult {
+        input: input.to_string(),
+        output: output.map(str::to_string),
+        changed: outcome.changed,
+        written: outcome.written,
+        bytes: outcome.after.len() as u64,
+        diff: if dry_run && outcome.changed { outcome.unified_diff(3) } else { None },
+    })
+}
@@ -0,0 +1,95 @@
+// Structured command results, shared by terminal output and the JSON-RPC server
+use std::collections::BTreeMap;
+use serde::Serialize;
+use ai_agent_core::{PlannedAction, TaskResult, ToolProbe};
+
+/// A task run by the CLI: the model's result plus what was actually sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskRun {
+    #[serde(flatten)]
+    pub result: TaskResult,
+    /// The task text as sent to the model backend, with the output of
+    /// `actions` appended, after any redaction.
+    pub prompt: String,
+    /// Tool calls made to gather context, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<PlannedAction>,
+    /// Redacted matches per kind; empty when redaction is off or found nothing.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub redactions: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessResult {
+    pub input: String,
+    pub output: Option<String>,
+    /// Whether the output file differs (or would differ) from the transformed content.
+    pub changed: bool,
+    pub written: bool,
+    /// Size of the transformed content.
+    pub bytes: u64,
+    /// Unified diff, only for dry runs that would change the output.
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The outcome of probing one subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    /// A failure of a critical check makes the agent not ready.
+    pub critical: bool,
+}
+
+impl HealthCheck {
+    pub fn new(name: &str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status, message: message.into(), critical: false }
+    }
+
+    pub fn critical(mut self) -> Self {
+        self.critical = true;
+        self
+    }
+}
+
+/// Everything `status` reports, collected afresh on each `--watch` tick.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub version: String,
+    pub models: Vec<String>,
+    /// The model `auto` resolves to.
+    pub default_model: String,
+    pub checks: Vec<HealthCheck>,
+    /// False if any critical check failed.
+    pub ready: bool,
+    /// Each registered tool's availability and version.
+    pub tools: Vec<ToolProbe>,
+    pub process: ProcessStats,
+    /// When the checks ran, in milliseconds since the Unix epoch.
+    pub checked_at_ms: u64,
+    /// Environment variables asked for with `--env`, secrets masked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+}
+
+/// The agent's own process at the time of a status check.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessStats {
+    pub pid: u32,
+    /// Resident memory; `None` where the platform does not expose it.
+    pub memory_bytes: Option<u64>,
+    /// CPU time over wall time since start, where 100 is one busy core;
+    /// `None` where the platform does not expose it, as for `threads`.
+    pub cpu_percent: Option<f64>,
+    pub threads: Option<u32>,
+    pub uptime_ms: u64,
+}
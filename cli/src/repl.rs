@@ -0,0 +1,105 @@
+// Line editing for interactive mode: history and tab completion
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Commands the prompt handles itself, with what they do.
+pub const META_COMMANDS: &[(&str, &str)] = &[
+    (":help", "Show this help"),
+    (":tools", "List the registered tools"),
+    (":quit", "Leave interactive mode (also `exit` or Ctrl-D)"),
+];
+
+/// Completes what is typed at the prompt: meta-commands in place of a task,
+/// tool names after `!`, and file paths for every later word.
+pub struct ReplHelper {
+    tools: Vec<String>,
+    files: FilenameCompleter,
+}
+
+impl ReplHelper {
+    pub fn new(tools: Vec<String>) -> Self {
+        Self { tools, files: FilenameCompleter::new() }
+    }
+
+    /// Where the word being completed starts and its candidates, or `None`
+    /// if it is an argument, which completes as a path. Prefixes match
+    /// ignoring case.
+    fn complete_first_word(&self, before: &str) -> Option<(usize, Vec<Pair>)> {
+        if before.contains(char::is_whitespace) {
+            return None;
+        }
+        let (start, prefix, names): (usize, &str, Vec<&str>) = if let Some(prefix) = before.strip_prefix('!') {
+            (1, prefix, self.tools.iter().map(String::as_str).collect())
+        } else if before.starts_with(':') {
+            (0, before, META_COMMANDS.iter().map(|(name, _)| *name).collect())
+        } else {
+            // The start of a task.
+            (0, before, Vec::new())
+        };
+        let prefix = prefix.to_lowercase();
+        let candidates = names
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .map(|name| Pair { display: name.to_string(), replacement: format!("{} ", name) })
+            .collect();
+        Some((start, candidates))
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match self.complete_first_word(&line[..pos]) {
+            Some(completion) => Ok(completion),
+            None => self.files.complete_path(line, pos),
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(helper: &ReplHelper, line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = match helper.complete_first_word(line) {
+            Some(completion) => completion,
+            None => helper.files.complete_path(line, line.len()).unwrap(),
+        };
+        (start, pairs.into_iter().map(|pair| pair.replacement).collect())
+    }
+
+    #[test]
+    fn test_completion_depends_on_position() {
+        let helper = ReplHelper::new(vec!["grep".into(), "head".into(), "read_file".into()]);
+        assert_eq!(complete(&helper, "!"), (1, vec!["grep ".into(), "head ".into(), "read_file ".into()]));
+        assert_eq!(complete(&helper, "!RE"), (1, vec!["read_file ".into()]));
+        assert_eq!(complete(&helper, ":T"), (0, vec![":tools ".into()]));
+        assert_eq!(complete(&helper, "summarize"), (0, Vec::new()));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        let line = format!("!head {}/no", dir.path().display());
+        let (start, paths) = complete(&helper, &line);
+        assert_eq!(start, "!head ".len());
+        assert_eq!(paths, vec![format!("{}/notes.txt", dir.path().display())]);
+        // Paths complete in tasks too, and a tool name is not completed twice.
+        let (_, paths) = complete(&helper, &format!("summarize {}/ne", dir.path().display()));
+        assert_eq!(paths, vec![format!("{}/nested/", dir.path().display())]);
+        assert!(complete(&helper, "!grep gr").1.is_empty());
+    }
+}
@@ -0,0 +1,520 @@
+// `serve` subcommand: JSON-RPC 2.0 over a Unix socket or TCP
+//
+// Messages are newline-delimited: each line is one request object or a batch
+// array, and each response is written as a single line.
+//
+// With `--watch DIR`, files changed under DIR are processed as they settle
+// and clients that called `subscribe` with a matching path prefix receive a
+// `files_changed` notification: `{"files": [{"path", "result" | "error"}]}`,
+// one per debounced batch, with paths relative to DIR using `/`.
+//
+// While `execute_task` runs, the client gets a `heartbeat` notification
+// every `heartbeat_seconds`: `{"id", "beat", "elapsed_ms"}`, with the id of
+// the request. None arrives after its response.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use ai_agent_core::{with_heartbeat, FileWatcher, Heartbeat, ModelError, ToolCallError, ToolError, ToolExecutor, ToolRegistry, DEFAULT_MAX_INPUT_SIZE};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::output::ProcessResult;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server-defined error for failures inside a method.
+const SERVER_ERROR: i64 = -32000;
+/// Batches of changes kept for a slow client before it misses some.
+const UPDATE_BUFFER: usize = 64;
+
+/// The outcome of processing one changed file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedFile {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<ProcessResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Broadcasts each batch of processed changes to every connection.
+pub type Updates = broadcast::Sender<Arc<Vec<ChangedFile>>>;
+
+/// Per-connection state.
+#[derive(Default)]
+struct Session {
+    /// Whether the server is watching a directory at all.
+    watching: bool,
+    /// Path prefixes the client subscribed to.
+    prefixes: Mutex<Vec<String>>,
+    /// Where `heartbeat` notifications go, to be written between responses.
+    keep_alive: Option<mpsc::UnboundedSender<Value>>,
+}
+
+impl Session {
+    /// The `files_changed` notification for the files this client subscribed to, if any.
+    fn notification(&self, files: &[ChangedFile]) -> Option<Value> {
+        let prefixes = self.prefixes.lock().unwrap_or_else(|e| e.into_inner());
+        let files: Vec<_> = files.iter().filter(|file| prefixes.iter().any(|p| file.path.starts_with(p.as_str()))).collect();
+        (!files.is_empty()).then(|| json!({ "jsonrpc": "2.0", "method": "files_changed", "params": { "files": files } }))
+    }
+
+    /// Queues a `heartbeat` notification for the request `id`.
+    fn keep_alive(&self, id: Option<&Value>, beat: Heartbeat) {
+        if let Some(keep_alive) = &self.keep_alive {
+            let params = json!({ "id": id, "beat": beat.beat, "elapsed_ms": beat.elapsed.as_millis() as u64 });
+            let _ = keep_alive.send(json!({ "jsonrpc": "2.0", "method": "heartbeat", "params": params }));
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn to_value(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExecuteTaskParams {
+    task: String,
+    #[serde(default = "default_model")]
+    model: String,
+}
+
+fn default_model() -> String {
+    "auto".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProcessFileParams {
+    input: String,
+    output: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+    /// Largest input accepted, in bytes; defaults to `DEFAULT_MAX_INPUT_SIZE`.
+    max_size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubscribeParams {
+    /// Relative to the watched directory; empty matches every file.
+    #[serde(default)]
+    prefix: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+    /// Recorded with the run in the tool history.
+    #[serde(default)]
+    task_id: Option<String>,
+}
+
+fn registry() -> &'static ToolRegistry {
+    static REGISTRY: OnceLock<ToolRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(ToolRegistry::builtin)
+}
+
+/// Watches `dir` and processes each batch of changed files, broadcasting
+/// the results. Must be called inside a Tokio runtime.
+pub fn watch(dir: &str, debounce: Duration) -> Result<Updates> {
+    let root = Path::new(dir).canonicalize().with_context(|| format!("Failed to watch {}", dir))?;
+    let mut changes = FileWatcher::new(&root).with_debounce(debounce).watch()?;
+    let (updates, _) = broadcast::channel(UPDATE_BUFFER);
+    let sender = updates.clone();
+    info!("Watching {} for changes", root.display());
+    tokio::spawn(async move {
+        while let Some(paths) = changes.next().await {
+            let mut files = Vec::with_capacity(paths.len());
+            for path in paths {
+                files.push(process_changed(&root, path).await);
+            }
+            // Fails only while no client is connected.
+            let _ = sender.send(Arc::new(files));
+        }
+    });
+    Ok(updates)
+}
+
+async fn process_changed(root: &Path, path: PathBuf) -> ChangedFile {
+    let relative = path.strip_prefix(root).unwrap_or(&path);
+    let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    match crate::process_file_result(&path.display().to_string(), None, false, DEFAULT_MAX_INPUT_SIZE).await {
+        Ok(result) => ChangedFile { path: relative, result: Some(result), error: None },
+        Err(e) => ChangedFile { path: relative, result: None, error: Some(format!("{:#}", e)) },
+    }
+}
+
+#[cfg(unix)]
+pub async fn serve_unix(path: &str, updates: Option<Updates>) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // Replace a socket left behind by a previous run, but never a regular file.
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if meta.file_type().is_socket() {
+            std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket {}", path))?;
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(path).with_context(|| format!("Failed to bind {}", path))?;
+    info!("JSON-RPC server listening on unix:{}", path);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let updates = updates.as_ref().map(Updates::subscribe);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, updates).await {
+                warn!("connection closed with error: {:#}", e);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn serve_unix(_path: &str, _updates: Option<Updates>) -> Result<()> {
+    anyhow::bail!("Unix sockets are not supported on this platform; use --tcp")
+}
+
+pub async fn serve_tcp(addr: &str, updates: Option<Updates>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind {}", addr))?;
+    info!("JSON-RPC server listening on tcp:{}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let updates = updates.as_ref().map(Updates::subscribe);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, updates).await {
+                warn!("connection from {} closed with error: {:#}", peer, e);
+            }
+        });
+    }
+}
+
+/// Serves requests from one client until it disconnects, pushing the
+/// changes it subscribed to in between.
+async fn handle_connection<S: AsyncRead + AsyncWrite>(
+    stream: S,
+    updates: Option<broadcast::Receiver<Arc<Vec<ChangedFile>>>>,
+) -> Result<()> {
+    let (reader, writer) = tokio::io::split(stream);
+    let writer = tokio::sync::Mutex::new(writer);
+    let (keep_alive, mut heartbeats) = mpsc::unbounded_channel();
+    let session = Session { watching: updates.is_some(), keep_alive: Some(keep_alive), ..Session::default() };
+
+    let requests = async {
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let handled = handle_message(&line, &session);
+            tokio::pin!(handled);
+            let response = loop {
+                tokio::select! {
+                    response = &mut handled => break response,
+                    Some(heartbeat) = heartbeats.recv() => send(&writer, &heartbeat).await?,
+                }
+            };
+            // Heartbeats queued just before the task finished go out ahead of its response.
+            while let Ok(heartbeat) = heartbeats.try_recv() {
+                send(&writer, &heartbeat).await?;
+            }
+            if let Some(response) = response {
+                send(&writer, &response).await?;
+            }
+        }
+        Ok(())
+    };
+    let notifications = async {
+        let Some(mut updates) = updates else {
+            return std::future::pending().await;
+        };
+        loop {
+            match updates.recv().await {
+                Ok(files) => {
+                    if let Some(notification) = session.notification(&files) {
+                        send(&writer, &notification).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("client fell behind; {} batches of changes were not sent", missed)
+                }
+                Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+            }
+        }
+    };
+    tokio::select! {
+        result = requests => result,
+        result = notifications => result,
+    }
+}
+
+async fn send<W: AsyncWrite + Unpin>(writer: &tokio::sync::Mutex<W>, message: &Value) -> Result<()> {
+    let mut writer = writer.lock().await;
+    writer.write_all(message.to_string().as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Handles one line. Returns `None` when nothing should be sent back, i.e.
+/// for notifications and batches made up only of notifications.
+async fn handle_message(line: &str, session: &Session) -> Option<Value> {
+    let message: Value = match serde_json::from_str(line) {
+        Ok(message) => message,
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+    };
+    match message {
+        Value::Array(batch) if batch.is_empty() => {
+            Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "empty batch")))
+        }
+        Value::Array(batch) => {
+            let mut responses = Vec::new();
+            for request in batch {
+                responses.extend(handle_request(request, session).await);
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => handle_request(request, session).await,
+    }
+}
+
+async fn handle_request(request: Value, session: &Session) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let valid = request.get("jsonrpc").and_then(Value::as_str) == Some("2.0")
+        && request.get("method").is_some_and(Value::is_string)
+        && id.as_ref().is_none_or(|id| id.is_string() || id.is_number() || id.is_null());
+    if !valid {
+        let id = id.filter(|id| id.is_string() || id.is_number()).unwrap_or(Value::Null);
+        return Some(error_response(id, RpcError::new(INVALID_REQUEST, "invalid JSON-RPC 2.0 request")));
+    }
+
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let result = dispatch(method, params, id.as_ref(), session).await;
+    // Requests without an id are notifications and get no response.
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+async fn dispatch(method: &str, params: Value, id: Option<&Value>, session: &Session) -> Result<Value, RpcError> {
+    match method {
+        "subscribe" | "unsubscribe" => {
+            if !session.watching {
+                return Err(RpcError::new(SERVER_ERROR, "the server is not watching a directory; start it with --watch"));
+            }
+            let params: SubscribeParams = parse_params(params)?;
+            let prefix = params.prefix.trim_start_matches("./").to_string();
+            let mut prefixes = session.prefixes.lock().unwrap_or_else(|e| e.into_inner());
+            if method == "unsubscribe" {
+                let before = prefixes.len();
+                prefixes.retain(|p| *p != prefix);
+                return Ok(json!(prefixes.len() < before));
+            }
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+            Ok(json!(true))
+        }
+        "execute_task" => {
+            let params: ExecuteTaskParams = parse_params(params)?;
+            let (beats, mut heartbeat) = mpsc::unbounded_channel();
+            let work = with_heartbeat(crate::run_task(&params.task, &params.model), crate::config::get().heartbeat_interval(), beats);
+            // Ends once the task has, so every heartbeat is queued before the response.
+            let forward = async {
+                while let Some(beat) = heartbeat.recv().await {
+                    session.keep_alive(id, beat);
+                }
+            };
+            let (result, ()) = tokio::join!(work, forward);
+            let result = result.map_err(|e| {
+                match e.downcast_ref::<ModelError>() {
+                    Some(_) => RpcError::new(INVALID_PARAMS, e.to_string()),
+                    None => server_error(e),
+                }
+            })?;
+            to_value(result)
+        }
+        "process_file" => {
+            let params: ProcessFileParams = parse_params(params)?;
+            let max_size = params.max_size.unwrap_or(ai_agent_core::DEFAULT_MAX_INPUT_SIZE);
+            let result = crate::process_file_result(&params.input, params.output.as_deref(), params.dry_run, max_size)
+                .await
+                .map_err(server_error)?;
+            to_value(result)
+        }
+        "status" => {
+            no_params(method, &params)?;
+            to_value(crate::status::collect(None).await.map_err(server_error)?)
+        }
+        "tools_manifest" => {
+            no_params(method, &params)?;
+            Ok(registry().manifest())
+        }
+        "call_tool" => {
+            let params: CallToolParams = parse_params(params)?;
+            let mut options = crate::config::get().exec_options().map_err(server_error)?;
+            options.task_id = params.task_id;
+            let output = ToolExecutor::execute_registered(registry(), &params.name, &params.arguments, &options)
+                .await
+                .map_err(|e| match e {
+                    // Bad tool names and arguments are the caller's fault, not the server's.
+                    ToolError::InvalidArgs(ToolCallError::UnknownTool { .. }) => RpcError::new(METHOD_NOT_FOUND, e.to_string()),
+                    ToolError::InvalidArgs(_) | ToolError::PolicyViolation { .. } => RpcError::new(INVALID_PARAMS, e.to_string()),
+                    _ => server_error(e.into()),
+                })?;
+            to_value(output)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("method '{}' not found", method))),
+    }
+}
+
+fn no_params(method: &str, params: &Value) -> Result<(), RpcError> {
+    if params.is_null() || params.as_object().is_some_and(|p| p.is_empty()) {
+        Ok(())
+    } else {
+        Err(RpcError::new(INVALID_PARAMS, format!("{} takes no parameters", method)))
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+fn server_error(error: anyhow::Error) -> RpcError {
+    RpcError::new(SERVER_ERROR, format!("{:#}", error))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error.to_value() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn call(line: &str) -> Value {
+        handle_message(line, &Session::default()).await.expect("expected a response")
+    }
+
+    #[tokio::test]
+    async fn test_methods_and_errors() {
+        let status = call(r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#).await;
+        assert_eq!(status["id"], 1);
+        assert!(status["result"]["models"].is_array());
+
+        let task = call(r#"{"jsonrpc":"2.0","id":"a","method":"execute_task","params":{"task":"hi"}}"#).await;
+        let mut result = task["result"].clone();
+        assert!(result.as_object_mut().unwrap().remove("duration").is_some());
+        assert_eq!(result, json!({"task": "hi", "output": "", "model": "gpt-2", "prompt": "hi", "success": true}));
+        let unknown_model = call(r#"{"jsonrpc":"2.0","id":"b","method":"execute_task","params":{"task":"hi","model":"gpt-5"}}"#).await;
+        assert_eq!(unknown_model["error"]["code"], INVALID_PARAMS);
+
+        let missing = call(r#"{"jsonrpc":"2.0","id":2,"method":"nope"}"#).await;
+        assert_eq!(missing["error"]["code"], METHOD_NOT_FOUND);
+        let bad_params = call(r#"{"jsonrpc":"2.0","id":3,"method":"execute_task","params":{"tsk":"x"}}"#).await;
+        assert_eq!(bad_params["error"]["code"], INVALID_PARAMS);
+        assert_eq!(call("{not json").await["error"]["code"], PARSE_ERROR);
+        assert_eq!(call(r#"{"id":4,"method":"status"}"#).await["error"]["code"], INVALID_REQUEST);
+
+        let manifest = call(r#"{"jsonrpc":"2.0","id":6,"method":"tools_manifest"}"#).await;
+        assert_eq!(manifest["result"]["manifest_version"], 1);
+        let ill_typed = call(r#"{"jsonrpc":"2.0","id":7,"method":"call_tool","params":{"name":"head","arguments":{"path":3}}}"#).await;
+        assert_eq!(ill_typed["error"]["code"], INVALID_PARAMS);
+        let unknown = call(r#"{"jsonrpc":"2.0","id":8,"method":"call_tool","params":{"name":"rm"}}"#).await;
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        let escape = call(r#"{"jsonrpc":"2.0","id":9,"method":"call_tool","params":{"name":"head","arguments":{"path":"../../etc/passwd"}}}"#).await;
+        assert_eq!(escape["error"]["code"], INVALID_PARAMS);
+        assert!(escape["error"]["message"].as_str().unwrap().contains("outside the allowed roots"));
+
+        let io_error = call(r#"{"jsonrpc":"2.0","id":5,"method":"process_file","params":{"input":"/no/such/file"}}"#).await;
+        assert_eq!(io_error["error"]["code"], SERVER_ERROR);
+        let too_large = call(r#"{"jsonrpc":"2.0","id":10,"method":"process_file","params":{"input":"Cargo.toml","max_size":8}}"#).await;
+        assert!(too_large["error"]["message"].as_str().unwrap().contains("over the 8-byte limit"));
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_notifications() {
+        assert!(handle_message(r#"{"jsonrpc":"2.0","method":"status"}"#, &Session::default()).await.is_none());
+        let batch = call(r#"[{"jsonrpc":"2.0","id":1,"method":"status"},{"jsonrpc":"2.0","method":"status"},{"jsonrpc":"2.0","id":2,"method":"x"}]"#).await;
+        let ids: Vec<_> = batch.as_array().unwrap().iter().map(|r| r["id"].clone()).collect();
+        assert_eq!(ids, vec![json!(1), json!(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_watch_notifies_subscribers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        let updates = watch(dir.path().to_str().unwrap(), Duration::from_millis(100)).unwrap();
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(handle_connection(server, Some(updates.subscribe())));
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        let subscribe = json!({"jsonrpc": "2.0", "id": 1, "method": "subscribe", "params": {"prefix": "./src/"}});
+        writer.write_all(format!("{}\n", subscribe).as_bytes()).await.unwrap();
+        let response: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(response["result"], true);
+
+        for i in 0..3 {
+            std::fs::write(dir.path().join("src/a.txt"), format!("line {}\n", i)).unwrap();
+            std::fs::write(dir.path().join("docs/b.txt"), "b\n").unwrap();
+        }
+        let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        let notification: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(notification["method"], "files_changed");
+        let files = notification["params"]["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0]["path"].as_str(), files[0]["result"]["bytes"].as_u64()), (Some("src/a.txt"), Some(7)));
+
+        let unwatched = call(r#"{"jsonrpc":"2.0","id":2,"method":"subscribe","params":{"prefix":"src"}}"#).await;
+        assert_eq!(unwatched["error"]["code"], SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_clients() {
+        let clients = (0..4).map(|i| {
+            tokio::spawn(async move {
+                let (client, server) = tokio::io::duplex(4096);
+                tokio::spawn(handle_connection(server, None));
+                let (reader, mut writer) = tokio::io::split(client);
+                let request = json!({"jsonrpc": "2.0", "id": i, "method": "status"});
+                writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+                let line = BufReader::new(reader).lines().next_line().await.unwrap().unwrap();
+                serde_json::from_str::<Value>(&line).unwrap()["id"].clone()
+            })
+        });
+        for (i, client) in clients.enumerate() {
+            assert_eq!(client.await.unwrap(), json!(i));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// `status` subcommand: checks, configuration and process stats, once or live
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use ai_agent_core::{EnvironmentManager, ProcessUsage, ToolRegistry, SENSITIVE_ENV_PATTERNS};
+
+use crate::config;
+use crate::health;
+use crate::output::{CheckStatus, ProcessStats, Status};
+
+/// Clears the terminal and moves the cursor home.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Also list environment variables whose names match this glob
+    /// (all if no pattern is given); secret-looking values are masked
+    #[arg(long, value_name = "PATTERN", num_args = 0..=1, default_missing_value = "*")]
+    pub env: Option<String>,
+    /// Output format; exits with status 1 if a critical check fails
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: StatusFormat,
+    /// Re-check and redraw every interval until Ctrl-C; with `--format json`,
+    /// print one JSON object per line instead
+    #[arg(long)]
+    pub watch: bool,
+    /// Time between refreshes, e.g. 5s or 500ms (plain numbers are seconds)
+    #[arg(long, requires = "watch", default_value = "2s", value_parser = crate::parse_duration)]
+    pub interval: Duration,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatusFormat {
+    Text,
+    Json,
+}
+
+/// Starts the uptime clock reported in `ProcessStats`.
+pub fn mark_started() {
+    STARTED.get_or_init(Instant::now);
+}
+
+pub async fn run(args: StatusArgs) -> Result<ExitCode> {
+    if !args.watch {
+        let status = collect(args.env.as_deref()).await?;
+        render(&status, args.format, false)?;
+        return Ok(if status.ready { ExitCode::SUCCESS } else { ExitCode::from(1) });
+    }
+    loop {
+        // Ctrl-C ends the loop whether it arrives mid-check or mid-sleep.
+        tokio::select! {
+            status = collect(args.env.as_deref()) => render(&status?, args.format, true)?,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(args.interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Probes the tools, runs the health checks and samples this process.
+/// `env` selects the environment variables to include, if any.
+pub async fn collect(env: Option<&str>) -> Result<Status> {
+    let env = match env {
+        Some(pattern) => {
+            let mut vars = EnvironmentManager::get_env_vars_matching(pattern)?;
+            EnvironmentManager::redact_sensitive(&mut vars, SENSITIVE_ENV_PATTERNS)?;
+            Some(vars.into_iter().collect::<BTreeMap<_, _>>())
+        }
+        None => None,
+    };
+    // The config was validated at startup, so this only falls back in theory.
+    let models = config::get().models().unwrap_or_default();
+    let tools = ToolRegistry::builtin().probe_all().await;
+    let checks = health::run_checks(&tools).await;
+    Ok(Status {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        models: models.names(),
+        default_model: models.default_model().to_string(),
+        ready: health::is_ready(&checks),
+        checks,
+        tools,
+        process: process_stats(),
+        checked_at_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+        env,
+    })
+}
+
+fn process_stats() -> ProcessStats {
+    let pid = std::process::id();
+    let usage = ProcessUsage::of_pid(pid).ok();
+    ProcessStats {
+        pid,
+        memory_bytes: usage.map(|usage| usage.rss_bytes).or_else(EnvironmentManager::process_memory),
+        cpu_percent: usage.map(|usage| usage.cpu_percent),
+        threads: usage.map(|usage| usage.num_threads),
+        uptime_ms: STARTED.get_or_init(Instant::now).elapsed().as_millis() as u64,
+    }
+}
+
+/// Prints `status` once. When `watching`, text replaces the previous screen
+/// and JSON is a single line, so a stream of them is NDJSON.
+fn render(status: &Status, format: StatusFormat, watching: bool) -> Result<()> {
+    match format {
+        StatusFormat::Json if watching => println!("{}", serde_json::to_string(status)?),
+        StatusFormat::Json => println!("{}", serde_json::to_string_pretty(status)?),
+        StatusFormat::Text => {
+            if watching {
+                print!("{}", CLEAR_SCREEN);
+            }
+            print!("{}", render_text(status));
+        }
+    }
+    Ok(())
+}
+
+fn render_text(status: &Status) -> String {
+    let mut text = String::new();
+    text.push_str("🔍 AI Agent Status\n");
+    text.push_str("================\n");
+    text.push_str(&format!("🦀 Rust CLI: v{}\n", status.version));
+    text.push_str(&format!("🧠 Available Models: {} (auto = {})\n", status.models.join(", "), status.default_model));
+    for check in &status.checks {
+        let mark = match check.status {
+            CheckStatus::Ok => "✅",
+            CheckStatus::Warn => "⚠️ ",
+            CheckStatus::Fail => "❌",
+        };
+        text.push_str(&format!("{} {}: {}\n", mark, check.name, check.message));
+    }
+    text.push_str("🧰 Tools:\n");
+    let width = status.tools.iter().map(|probe| probe.name.len()).max().unwrap_or(0);
+    for probe in &status.tools {
+        let detail = match (&probe.resolved_path, &probe.version) {
+            _ if !probe.available => "not found".to_string(),
+            (Some(path), Some(version)) => format!("{} ({})", version, path.display()),
+            (Some(path), None) => format!("version unknown ({})", path.display()),
+            (None, _) => "built in".to_string(),
+        };
+        text.push_str(&format!("  {:width$}  {}\n", probe.name, detail, width = width));
+    }
+    let memory = match status.process.memory_bytes {
+        Some(bytes) => format!("{:.1} MiB resident", bytes as f64 / 1048576.0),
+        None => "memory unknown".to_string(),
+    };
+    let cpu = match (status.process.cpu_percent, status.process.threads) {
+        (Some(percent), Some(threads)) => format!("{:.1}% CPU over {} threads", percent, threads),
+        _ => "CPU unknown".to_string(),
+    };
+    text.push_str(&format!(
+        "🖥️  Process: pid {}, {}, {}, up {}s\n",
+        status.process.pid,
+        memory,
+        cpu,
+        status.process.uptime_ms / 1000
+    ));
+    if let Some(vars) = &status.env {
+        text.push_str("🧾 Environment:\n");
+        for (key, value) in vars {
+            text.push_str(&format!("  {}={}\n", key, value));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_and_render() {
+        std::env::set_var("STATUSTEST_REGION", "eu-west-1");
+        std::env::set_var("STATUSTEST_TOKEN", "hunter2");
+        let status = collect(Some("STATUSTEST_*")).await.unwrap();
+        assert_eq!(status.process.pid, std::process::id());
+        let env = status.env.as_ref().unwrap();
+        assert_eq!(env["STATUSTEST_REGION"], "eu-west-1");
+        assert_ne!(env["STATUSTEST_TOKEN"], "hunter2");
+
+        // Watch mode emits one object per line.
+        let line = serde_json::to_string(&status).unwrap();
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert!(json["process"]["uptime_ms"].is_u64() && json["checked_at_ms"].is_u64());
+        #[cfg(target_os = "linux")]
+        assert!(json["process"]["memory_bytes"].as_u64() > Some(0) && json["process"]["threads"].as_u64() > Some(0));
+
+        let text = render_text(&status);
+        assert!(text.contains(&format!("pid {}", std::process::id())));
+        assert!(text.contains("STATUSTEST_REGION=eu-west-1"));
+        let read_file = status.tools.iter().find(|probe| probe.name == "read_file").unwrap();
+        assert!(read_file.available && read_file.version.is_none());
+        assert!(text.lines().any(|line| line.trim_start().starts_with("read_file") && line.ends_with("built in")));
+        assert!(collect(None).await.unwrap().env.is_none());
+    }
+}
@@ -0,0 +1,161 @@
+// Logging setup, and with the `otel` feature, span export over OTLP
+use anyhow::Result;
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+use ai_agent_core::RotatingWriter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Where spans are exported, under `[telemetry]` in the config file. The
+/// standard `OTEL_EXPORTER_OTLP_*` and `OTEL_SERVICE_NAME` variables apply
+/// when a field is not set here.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318`; `/v1/traces` is
+    /// appended unless the URL already ends with it.
+    pub endpoint: Option<String>,
+    pub service_name: Option<String>,
+}
+
+impl TelemetryConfig {
+    /// Whether spans should be exported: an endpoint is set here or in the environment.
+    pub fn enabled(&self) -> bool {
+        self.endpoint.is_some()
+            || ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+                .iter()
+                .any(|key| std::env::var_os(key).is_some_and(|value| !value.is_empty()))
+    }
+}
+
+/// Flushes exported spans when dropped; keep it alive until the process exits.
+#[must_use]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush traces: {}", e);
+            }
+        }
+    }
+}
+
+/// Installs the global subscriber: log lines filtered by `RUST_LOG` (info by
+/// default; debug also reports span timings on close), written to stdout or
+/// to `log_file` without colors, and, with the `otel` feature and an
+/// endpoint configured, every agent span exported over OTLP regardless of
+/// `RUST_LOG`.
+pub fn init(config: &TelemetryConfig, log_file: Option<RotatingWriter>) -> Result<TelemetryGuard> {
+    let ansi = log_file.is_none();
+    let writer = match log_file {
+        Some(file) => BoxMakeWriter::new(move || file.clone()),
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_filter(EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy());
+    let registry = tracing_subscriber::registry().with(fmt);
+
+    #[cfg(feature = "otel")]
+    if config.enabled() {
+        let (layer, provider) = otel::layer(config)?;
+        registry.with(layer).init();
+        return Ok(TelemetryGuard { provider: Some(provider) });
+    }
+
+    registry.init();
+    if config.enabled() && cfg!(not(feature = "otel")) {
+        tracing::warn!("telemetry is configured but this build lacks the `otel` feature; no spans are exported");
+    }
+    Ok(TelemetryGuard {
+        #[cfg(feature = "otel")]
+        provider: None,
+    })
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use anyhow::{Context, Result};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use tracing::Level;
+    use tracing_subscriber::filter::Targets;
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    use super::TelemetryConfig;
+
+    /// Reported as `service.name` unless `service_name` or `OTEL_SERVICE_NAME` is set.
+    const DEFAULT_SERVICE_NAME: &str = "ai-agent";
+
+    /// Exports spans from the agent's crates, debug level included, so tool
+    /// runs and file operations appear under their task.
+    pub(super) fn layer<S>(config: &TelemetryConfig) -> Result<(impl Layer<S>, SdkTracerProvider)>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let mut exporter = SpanExporter::builder().with_http();
+        if let Some(endpoint) = &config.endpoint {
+            exporter = exporter.with_endpoint(traces_url(endpoint));
+        }
+        let exporter = exporter.build().context("failed to set up the OTLP exporter")?;
+
+        let mut resource = Resource::builder();
+        match &config.service_name {
+            Some(name) => resource = resource.with_service_name(name.clone()),
+            None if std::env::var_os("OTEL_SERVICE_NAME").is_none() => {
+                resource = resource.with_service_name(DEFAULT_SERVICE_NAME)
+            }
+            None => {}
+        }
+        let provider = SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource.build()).build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+
+        let targets = Targets::new()
+            .with_target("ai_agent_cli", Level::DEBUG)
+            .with_target("ai_agent_core", Level::DEBUG)
+            .with_target("ai_agent_python_bridge", Level::DEBUG);
+        Ok((tracing_opentelemetry::layer().with_tracer(tracer).with_filter(targets), provider))
+    }
+
+    /// An explicit endpoint is used as-is by the exporter, unlike the
+    /// environment variable, so the signal path is added here.
+    pub(super) fn traces_url(endpoint: &str) -> String {
+        let endpoint = endpoint.trim_end_matches('/');
+        if endpoint.ends_with("/v1/traces") {
+            endpoint.to_string()
+        } else {
+            format!("{}/v1/traces", endpoint)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config: TelemetryConfig = toml::from_str("endpoint = 'http://collector:4318'\n").unwrap();
+        assert!(config.enabled());
+        assert!(toml::from_str::<TelemetryConfig>("endpont = 'x'\n").is_err());
+        #[cfg(feature = "otel")]
+        {
+            assert_eq!(otel::traces_url("http://collector:4318/"), "http://collector:4318/v1/traces");
+            assert_eq!(otel::traces_url("http://c/v1/traces"), "http://c/v1/traces");
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// `tools` subcommand: inspect the tools exposed to models
+use std::io::Write;
+use std::process::ExitCode;
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+use ai_agent_core::{FileWriter, ToolExecutor, ToolRegistry};
+
+#[derive(Subcommand)]
+pub enum ToolsCommand {
+    /// List the registered tools with a one-line description each.
+    List,
+    /// Print a JSON manifest of every registered tool and its parameter schema.
+    ///
+    /// The format is versioned by `manifest_version`; each tool's `inputSchema`
+    /// is JSON Schema, as in MCP `tools/list`. Tools are invoked over `serve`
+    /// with the `call_tool` method and `{"name": ..., "arguments": {...}}` params.
+    Manifest {
+        /// Output file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// `manifest` for the versioned document above, `openai` for a
+        /// function-calling `tools` array
+        #[arg(long, value_enum, default_value = "manifest")]
+        format: ManifestFormat,
+    },
+    /// Run a registered tool under the execution policy, as a model would
+    /// call it, e.g. `tools run read_file --args '{"path":"Cargo.toml"}'`.
+    Run {
+        name: String,
+        /// Arguments as a JSON object
+        #[arg(long, default_value = "{}")]
+        args: String,
+        /// Task to record the run under in the tool history
+        #[arg(long)]
+        task_id: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ManifestFormat {
+    Manifest,
+    Openai,
+}
+
+pub async fn run(command: ToolsCommand) -> Result<ExitCode> {
+    match command {
+        ToolsCommand::List => {
+            let registry = ToolRegistry::builtin();
+            let width = registry.list().iter().map(|spec| spec.name.len()).max().unwrap_or(0);
+            for spec in registry.list() {
+                println!("{:width$}  {}", spec.name, spec.description, width = width);
+            }
+        }
+        ToolsCommand::Manifest { output, format } => {
+            let registry = ToolRegistry::builtin();
+            let document = match format {
+                ManifestFormat::Manifest => registry.manifest(),
+                ManifestFormat::Openai => registry.to_openai_tools(),
+            };
+            let manifest = serde_json::to_string_pretty(&document)?;
+            match output {
+                Some(path) => FileWriter::write_file(&path, &format!("{}\n", manifest)).await?,
+                None => println!("{}", manifest),
+            }
+        }
+        ToolsCommand::Run { name, args, task_id } => {
+            let arguments: serde_json::Value = serde_json::from_str(&args).context("--args is not valid JSON")?;
+            let mut options = crate::config::get().exec_options()?;
+            options.task_id = task_id;
+            let output = ToolExecutor::execute_registered(&ToolRegistry::builtin(), &name, &arguments, &options).await?;
+            std::io::stdout().write_all(output.stdout_bytes())?;
+            std::io::stderr().write_all(output.stderr_bytes())?;
+            return Ok(ExitCode::from(u8::try_from(output.exit_code).unwrap_or(1)));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,216 @@
+// `transform` subcommand: run a single transform stage over a file
+use std::process::ExitCode;
+use anyhow::{Context, Result};
+use clap::{Subcommand, ValueEnum};
+use ai_agent_core::{
+    Codec, Direction, EncodeTransform, FileReader, FileTransformer, FileWriter, IndentConversion,
+    JsonFormatTransform, JsonQueryTransform, NormalizeTransform, SplitTransform,
+};
+
+#[derive(Subcommand)]
+pub enum TransformCommand {
+    /// Base64 or hex encode/decode a file
+    Encode {
+        /// Input file path
+        #[arg(short, long)]
+        input: String,
+        /// Output file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Encoding to use
+        #[arg(long, value_enum, default_value = "base64")]
+        codec: CodecArg,
+        /// Decode instead of encode
+        #[arg(short, long)]
+        decode: bool,
+        /// Use the URL-safe base64 alphabet
+        #[arg(long)]
+        url_safe: bool,
+        /// Omit base64 padding when encoding
+        #[arg(long)]
+        no_padding: bool,
+    },
+    /// Normalize whitespace and trailing newlines
+    Normalize {
+        /// Input file path
+        #[arg(short, long)]
+        input: String,
+        /// Output file path (defaults to rewriting the input in place)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Report what would change and exit with status 1 instead of writing
+        #[arg(long)]
+        check: bool,
+        /// Keep trailing whitespace on each line
+        #[arg(long)]
+        keep_trailing_whitespace: bool,
+        /// Collapse runs of blank lines longer than this
+        #[arg(long)]
+        max_blank_lines: Option<usize>,
+        /// Do not enforce exactly one trailing newline
+        #[arg(long)]
+        no_final_newline: bool,
+        /// Expand leading tabs to this many spaces
+        #[arg(long, conflicts_with = "spaces_to_tabs")]
+        tabs_to_spaces: Option<usize>,
+        /// Replace each run of this many leading spaces with a tab
+        #[arg(long)]
+        spaces_to_tabs: Option<usize>,
+    },
+    /// Extract values from JSON or NDJSON with a jq/JSONPath-style query
+    Json {
+        /// Query such as `.items[*].name` or `$..id`
+        #[arg(short, long)]
+        query: String,
+        /// Input file path
+        #[arg(short, long)]
+        input: String,
+        /// Output file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Fail if the query matches nothing
+        #[arg(long)]
+        require_match: bool,
+    },
+    /// Pretty-print or minify JSON or NDJSON, keeping key order
+    JsonFormat {
+        /// Input file path
+        #[arg(short, long)]
+        input: String,
+        /// Output file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Spaces per nesting level
+        #[arg(long, default_value_t = 2, conflicts_with = "minify")]
+        indent: usize,
+        /// Remove all insignificant whitespace instead
+        #[arg(long)]
+        minify: bool,
+        /// Order object keys alphabetically
+        #[arg(long)]
+        sort_keys: bool,
+    },
+    /// Split a file into numbered shards such as `big.0001.jsonl`
+    #[command(group(clap::ArgGroup::new("split_by").required(true).args(["max_lines", "max_bytes", "delimiter"])))]
+    Split {
+        /// Input file path
+        #[arg(short, long)]
+        input: String,
+        /// Directory to write the shards to
+        #[arg(short, long)]
+        output: String,
+        /// Lines per shard
+        #[arg(long)]
+        max_lines: Option<usize>,
+        /// Bytes per shard; lines are never broken
+        #[arg(long)]
+        max_bytes: Option<usize>,
+        /// Regex that ends each record, e.g. '(?m)^---\n'
+        #[arg(long)]
+        delimiter: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CodecArg {
+    Base64,
+    Hex,
+}
+
+pub async fn run(command: TransformCommand) -> Result<ExitCode> {
+    match command {
+        TransformCommand::Encode { input, output, codec, decode, url_safe, no_padding } => {
+            let codec = match codec {
+                CodecArg::Base64 => Codec::Base64 { url_safe, padding: !no_padding },
+                CodecArg::Hex => Codec::Hex,
+            };
+            let direction = if decode { Direction::Decode } else { Direction::Encode };
+            let transform = EncodeTransform::new(codec, direction);
+
+            let reader = tokio::fs::File::open(&input)
+                .await
+                .with_context(|| format!("Failed to open {}", input))?;
+            match output {
+                Some(path) => {
+                    let writer = tokio::fs::File::create(&path)
+                        .await
+                        .with_context(|| format!("Failed to create {}", path))?;
+                    transform.apply_stream(reader, writer).await?;
+                }
+                None => {
+                    transform.apply_stream(reader, tokio::io::stdout()).await?;
+                }
+            }
+        }
+        TransformCommand::Normalize {
+            input,
+            output,
+            check,
+            keep_trailing_whitespace,
+            max_blank_lines,
+            no_final_newline,
+            tabs_to_spaces,
+            spaces_to_tabs,
+        } => {
+            let indent = match (tabs_to_spaces, spaces_to_tabs) {
+                (Some(width), _) => IndentConversion::TabsToSpaces(width),
+                (_, Some(width)) => IndentConversion::SpacesToTabs(width),
+                _ => IndentConversion::Keep,
+            };
+            let transform = NormalizeTransform {
+                trim_trailing_whitespace: !keep_trailing_whitespace,
+                max_blank_lines,
+                final_newline: !no_final_newline,
+                indent,
+            };
+
+            let content = FileReader::read_file(&input).await?;
+            let (normalized, report) = transform.apply(&content);
+            println!(
+                "{}: {} line(s) modified, {} line(s) removed{}",
+                input,
+                report.lines_modified,
+                report.lines_removed,
+                if report.final_newline_added { ", final newline added" } else { "" }
+            );
+
+            if check {
+                return Ok(if report.is_clean() { ExitCode::SUCCESS } else { ExitCode::from(1) });
+            }
+            let target = output.as_deref().unwrap_or(&input);
+            if !report.is_clean() || output.is_some() {
+                FileWriter::write_file(target, &normalized).await?;
+            }
+        }
+        TransformCommand::Json { query, input, output, require_match } => {
+            let transform = JsonQueryTransform::new(&query)?.error_on_empty(require_match);
+            let content = FileReader::read_file(&input).await?;
+            let matches = transform.apply(&content)?;
+            match output {
+                Some(path) => FileWriter::write_file(&path, &matches).await?,
+                None => print!("{}", matches),
+            }
+        }
+        TransformCommand::JsonFormat { input, output, indent, minify, sort_keys } => {
+            let transform = if minify { JsonFormatTransform::minify() } else { JsonFormatTransform::pretty(indent) };
+            let content = FileReader::read_file(&input).await?;
+            let formatted = transform.with_sort_keys(sort_keys).apply(&content).with_context(|| format!("Failed to format {}", input))?;
+            match output {
+                Some(path) => FileWriter::write_file(&path, &formatted).await?,
+                None => print!("{}", formatted),
+            }
+        }
+        TransformCommand::Split { input, output, max_lines, max_bytes, delimiter } => {
+            let split = match (max_lines, max_bytes, delimiter) {
+                (Some(lines), _, _) => SplitTransform::by_lines(lines),
+                (_, Some(bytes), _) => SplitTransform::by_bytes(bytes),
+                (_, _, Some(pattern)) => SplitTransform::by_delimiter(&pattern)?,
+                _ => unreachable!("clap requires one split mode"),
+            };
+            for path in FileTransformer::new().split(&input, &output, &split).await? {
+                println!("{}", path.display());
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
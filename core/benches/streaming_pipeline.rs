@@ -0,0 +1,41 @@
+// Streaming pipeline throughput; input is generated lazily so memory stays flat.
+// Set STREAM_BENCH_MB (e.g. 4096) to run over a larger input.
+use ai_agent_core::TransformPipeline;
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn generated_input(total: u64) -> impl futures::Stream<Item = std::io::Result<Bytes>> {
+    let line = b"2024-01-01T00:00:00Z INFO request handled in 12ms by worker-7\n";
+    let chunk: Bytes = line.iter().copied().cycle().take(CHUNK_SIZE / line.len() * line.len()).collect::<Vec<_>>().into();
+    futures::stream::unfold(0u64, move |sent| {
+        let chunk = chunk.clone();
+        async move { (sent < total).then(|| (Ok(chunk.clone()), sent + chunk.len() as u64)) }
+    })
+}
+
+fn bench_streaming_pipeline(c: &mut Criterion) {
+    let megabytes: u64 = std::env::var("STREAM_BENCH_MB").ok().and_then(|v| v.parse().ok()).unwrap_or(64);
+    let total = megabytes * 1024 * 1024;
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let pipeline = TransformPipeline::new()
+        .line_stage("filter", |line| Ok(line.contains("INFO").then(|| line.to_string())))
+        .line_stage("replace", |line| Ok(Some(line.replace("worker", "w"))));
+
+    let mut group = c.benchmark_group("streaming_pipeline");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(total));
+    group.bench_function(format!("filter_replace_{}mb", megabytes), |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let mut sink = tokio::io::sink();
+                pipeline.apply_streaming(generated_input(total), &mut sink).await.unwrap()
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_streaming_pipeline);
+criterion_main!(benches);
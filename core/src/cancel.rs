@@ -0,0 +1,10 @@
+// Cancellation helpers
+pub use tokio_util::sync::CancellationToken;
+
+/// Resolves when `token` is cancelled; never resolves if there is no token.
+pub(crate) async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
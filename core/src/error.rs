@@ -0,0 +1,14 @@
+// Core error types
+use thiserror::Error;
+
+/// Errors shared across the core subsystems that callers may want to match on.
+#[derive(Debug, Error)]
+pub enum CoreError {
+    /// The caller cancelled the operation. `partial` holds whatever output or
+    /// content had been produced before cancellation.
+    #[error("{operation} cancelled after {} bytes", partial.len())]
+    Cancelled { operation: String, partial: Vec<u8> },
+    /// `safe_mode` is on and `operation` would have changed something.
+    #[error("{operation} is not allowed in safe mode")]
+    SafeMode { operation: String },
+}
@@ -4,11 +4,43 @@
 pub mod reader;
 pub mod writer;
 pub mod transformer;
+pub mod progress;
+pub mod diff;
+pub mod mime;
+pub mod copy;
+pub mod fs;
+pub mod walker;
+pub mod watcher;
+pub mod index;
+pub mod rotate;
 
 // Re-export public APIs
-pub use reader::FileReader;
-pub use writer::FileWriter;
-pub use transformer::FileTransformer;
+pub use reader::{FileReader, InputError, DEFAULT_MAX_INPUT_SIZE};
+pub use writer::{AppendHandle, BufferedWriter, FileWriter, FlushMode, DEFAULT_WRITE_BUFFER, DEFAULT_WRITE_CONCURRENCY};
+pub use transformer::{
+    apply_patch_to_dir, Codec, ConcatEntry, ConcatTransform, Direction, EncodeError,
+    DEFAULT_CONCAT_HEADER, EncodeTransform, FileTransformer, FrontMatter, FrontMatterFormat,
+    FrontMatterTransform, HeuristicTokenizer, HtmlToTextTransform, IndentConversion, JsonFormatError,
+    JsonFormatTransform, JsonQueryError, JsonQueryTransform, LineTransform, Metadata, NormalizeReport, NormalizeTransform, PatchError,
+    PatchTransform, RedactReport, RedactTransform, Stage, StatsTransform, StreamTransform, TextStats,
+    TokenEstimator, TransformOutcome, TransformPipeline, shard_paths, SplitBy, SplitTransform,
+    Language, StripCommentsTransform, DetectLanguageTransform, LanguageGuess, NATURAL_LANGUAGE,
+    UNKNOWN_LANGUAGE, PluginBuffer, PluginManifestV1, PluginTransformV1, Transform, TransformError,
+    TransformRegistry, PLUGIN_ABI_VERSION, PLUGIN_ENTRY_POINT, TreeEntry, TreeOptions, TreeReport,
+};
+#[cfg(feature = "tiktoken")]
+pub use transformer::TiktokenTokenizer;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use transformer::{CompressTransform, Compression};
+pub use progress::ProgressThrottle;
+pub use copy::{copy_file, copy_file_with_progress, CopyError, CopyOptions, CopyReport, DEFAULT_COPY_CHUNK_SIZE};
+pub use diff::{diff_files, diff_text, format_unified, DiffLine, DiffTag};
+pub use mime::{detect_mime, is_text, is_text_mime, MIME_SNIFF_LEN, OCTET_STREAM, PLAIN_TEXT};
+pub use fs::{Filesystem, InMemoryFs, RealFs};
+pub use walker::{DirWalker, SkippedDuplicate, SortBy, WalkResult};
+pub use watcher::{FileWatcher, WatchStream, DEFAULT_DEBOUNCE};
+pub use index::{ProcessedIndex, ProcessedRecord, PROCESSED_INDEX_FILE};
+pub use rotate::{RotatingWriter, DEFAULT_ROTATED_FILES, DEFAULT_ROTATE_BYTES};
 
 #[cfg(test)]
 mod tests {
@@ -16,6 +48,8 @@ mod tests {
 
     #[test]
     fn test_file_processor_module_loads() {
-        assert!(true);
+        let _ = FileReader::new();
+        let _ = FileWriter::new();
+        let _ = FileTransformer::new();
     }
 }
\ No newline at end of file
@@ -0,0 +1,211 @@
+// Resumable, verified file copies
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use super::progress::ProgressThrottle;
+use super::writer::check_writable;
+
+/// Bytes read and written per step.
+pub const DEFAULT_COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum CopyError {
+    /// The copy finished but reads back differently; the file is left in place.
+    #[error("checksum mismatch copying to {}: expected {expected}, found {actual}", path.display())]
+    ChecksumMismatch { path: PathBuf, expected: String, actual: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    pub chunk_size: usize,
+    /// Keep a partial destination whose bytes match the start of the source
+    /// and copy only the rest.
+    pub resume: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self { chunk_size: DEFAULT_COPY_CHUNK_SIZE, resume: true }
+    }
+}
+
+impl CopyOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyReport {
+    /// Bytes in the finished file.
+    pub total_bytes: u64,
+    /// Bytes kept from a previous partial copy.
+    pub resumed_from: u64,
+    /// Hex SHA-256 of the source, which the destination was verified against.
+    pub sha256: String,
+}
+
+/// Copies `src` to `dst` in chunks and verifies the result.
+pub async fn copy_file(src: impl AsRef<Path>, dst: impl AsRef<Path>, options: &CopyOptions) -> Result<CopyReport> {
+    copy_file_with_progress(src, dst, options, |_, _| {}).await
+}
+
+/// Like `copy_file`, calling `on_progress(bytes_done, total_bytes)` as it
+/// goes; resumed bytes count as done. A destination that reads back with a
+/// different checksum fails with `CopyError::ChecksumMismatch` and is kept
+/// for inspection.
+pub async fn copy_file_with_progress<F>(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    options: &CopyOptions,
+    on_progress: F,
+) -> Result<CopyReport>
+where
+    F: Fn(u64, u64),
+{
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    check_writable(dst)?;
+    let mut source = File::open(src).await.with_context(|| format!("Failed to open {}", src.display()))?;
+    let total = source.metadata().await?.len();
+    if tokio::fs::canonicalize(dst).await.is_ok_and(|dst| src.canonicalize().is_ok_and(|src| src == dst)) {
+        bail!("Cannot copy {} onto itself", src.display());
+    }
+    let chunk_size = options.chunk_size.max(1);
+
+    let mut hasher = Sha256::new();
+    let resumed_from = if options.resume { matching_prefix(src, dst, total, chunk_size, &mut hasher).await? } else { 0 };
+    let mut dest = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dst)
+        .await
+        .with_context(|| format!("Failed to open {}", dst.display()))?;
+    dest.set_len(resumed_from).await?;
+    dest.seek(SeekFrom::Start(resumed_from)).await?;
+    source.seek(SeekFrom::Start(resumed_from)).await?;
+
+    let mut progress = ProgressThrottle::new(total, on_progress);
+    let mut done = resumed_from;
+    let mut buffer = vec![0u8; chunk_size];
+    loop {
+        let n = source.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        dest.write_all(&buffer[..n])
+            .await
+            .with_context(|| format!("Failed to write {}", dst.display()))?;
+        done += n as u64;
+        progress.update(done);
+    }
+    dest.sync_all().await?;
+    drop(dest);
+    progress.finish(done);
+
+    let expected = hex(hasher);
+    let mut written = Sha256::new();
+    hash_prefix(File::open(dst).await?, u64::MAX, chunk_size, &mut written).await?;
+    let actual = hex(written);
+    if actual != expected {
+        return Err(CopyError::ChecksumMismatch { path: dst.to_path_buf(), expected, actual }.into());
+    }
+    Ok(CopyReport { total_bytes: done, resumed_from, sha256: expected })
+}
+
+/// Length of an existing `dst` if it is a prefix of `src`, else 0. On a
+/// match `hasher` has consumed that prefix of the source.
+async fn matching_prefix(src: &Path, dst: &Path, total: u64, chunk_size: usize, hasher: &mut Sha256) -> Result<u64> {
+    let existing = match tokio::fs::metadata(dst).await {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => return Ok(0),
+    };
+    if existing == 0 || existing > total {
+        return Ok(0);
+    }
+    let mut source_hash = Sha256::new();
+    let mut dest_hash = Sha256::new();
+    hash_prefix(File::open(src).await?, existing, chunk_size, &mut source_hash).await?;
+    hash_prefix(File::open(dst).await?, existing, chunk_size, &mut dest_hash).await?;
+    if source_hash.clone().finalize() != dest_hash.finalize() {
+        tracing::warn!(path = %dst.display(), "partial copy does not match the source; starting over");
+        return Ok(0);
+    }
+    *hasher = source_hash;
+    Ok(existing)
+}
+
+async fn hash_prefix(reader: impl AsyncRead + Unpin, len: u64, chunk_size: usize, hasher: &mut Sha256) -> Result<()> {
+    let mut reader = reader.take(len);
+    let mut buffer = vec![0u8; chunk_size];
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..n]);
+    }
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_copy_resumes_and_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src.bin"), dir.path().join("dst.bin"));
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(&src, &data).await.unwrap();
+        let options = CopyOptions::new().with_chunk_size(1024);
+
+        let report = copy_file(&src, &dst, &options).await.unwrap();
+        assert_eq!((report.total_bytes, report.resumed_from), (10_000, 0));
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), data);
+
+        // A matching partial copy is continued.
+        tokio::fs::write(&dst, &data[..4000]).await.unwrap();
+        let reports = Mutex::new(Vec::new());
+        let resumed = copy_file_with_progress(&src, &dst, &options, |done, total| reports.lock().unwrap().push((done, total)))
+            .await
+            .unwrap();
+        assert_eq!(resumed.resumed_from, 4000);
+        assert_eq!(resumed.sha256, report.sha256);
+        assert_eq!(reports.lock().unwrap().last(), Some(&(10_000, 10_000)));
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), data);
+
+        // A partial copy that differs is redone from scratch.
+        tokio::fs::write(&dst, b"garbage").await.unwrap();
+        assert_eq!(copy_file(&src, &dst, &options).await.unwrap().resumed_from, 0);
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), data);
+
+        // A longer stale destination is truncated.
+        tokio::fs::write(&dst, [data.as_slice(), b"tail"].concat()).await.unwrap();
+        copy_file(&src, &dst, &options.clone().with_resume(false)).await.unwrap();
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), data);
+
+        assert!(copy_file(&src, &src, &options).await.is_err());
+        assert_eq!(tokio::fs::read(&src).await.unwrap(), data);
+    }
+}
@@ -0,0 +1,194 @@
+// Line-level text diffing
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use similar::{ChangeTag, TextDiff};
+
+/// How many leading bytes are inspected for NUL bytes when sniffing binaries.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTag {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    /// 1-based line number in the old text, absent for added lines.
+    pub old_line: Option<usize>,
+    /// 1-based line number in the new text, absent for removed lines.
+    pub new_line: Option<usize>,
+    /// Line content without its terminator.
+    pub text: String,
+    /// True when this is the last line of its file and has no trailing newline.
+    pub missing_newline: bool,
+}
+
+/// Diffs two files line by line. Binary or non-UTF-8 inputs are refused.
+pub async fn diff_files<A: AsRef<Path>, B: AsRef<Path>>(a: A, b: B) -> Result<Vec<DiffLine>> {
+    let old = read_text(a.as_ref()).await?;
+    let new = read_text(b.as_ref()).await?;
+    Ok(diff_text(&old, &new))
+}
+
+/// Diffs two in-memory texts line by line.
+pub fn diff_text(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let tag = match change.tag() {
+                ChangeTag::Equal => DiffTag::Context,
+                ChangeTag::Delete => DiffTag::Removed,
+                ChangeTag::Insert => DiffTag::Added,
+            };
+            let text = change.value();
+            let text = text.strip_suffix('\n').unwrap_or(text);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            DiffLine {
+                tag,
+                old_line: change.old_index().map(|i| i + 1),
+                new_line: change.new_index().map(|i| i + 1),
+                text: text.to_string(),
+                missing_newline: change.missing_newline(),
+            }
+        })
+        .collect()
+}
+
+/// Returns true if the diff contains any added or removed lines.
+pub fn has_changes(diff: &[DiffLine]) -> bool {
+    diff.iter().any(|line| line.tag != DiffTag::Context)
+}
+
+/// Renders a diff as unified-diff hunks with `context` lines around each change.
+pub fn format_unified(diff: &[DiffLine], context: usize) -> String {
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.tag != DiffTag::Context)
+        .map(|(i, _)| i)
+        .collect();
+
+    // Merge the context windows around each change into hunk ranges.
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(diff.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    // Number of old/new lines preceding each diff position.
+    let mut old_before = Vec::with_capacity(diff.len());
+    let mut new_before = Vec::with_capacity(diff.len());
+    let (mut old_seen, mut new_seen) = (0, 0);
+    for line in diff {
+        old_before.push(old_seen);
+        new_before.push(new_seen);
+        if line.tag != DiffTag::Added {
+            old_seen += 1;
+        }
+        if line.tag != DiffTag::Removed {
+            new_seen += 1;
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in ranges {
+        let hunk = &diff[start..end];
+        let old_len = hunk.iter().filter(|l| l.tag != DiffTag::Added).count();
+        let new_len = hunk.iter().filter(|l| l.tag != DiffTag::Removed).count();
+        let old_start = old_before[start] + usize::from(old_len > 0);
+        let new_start = new_before[start] + usize::from(new_len > 0);
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+
+        for line in hunk {
+            let marker = match line.tag {
+                DiffTag::Context => ' ',
+                DiffTag::Added => '+',
+                DiffTag::Removed => '-',
+            };
+            out.push(marker);
+            out.push_str(&line.text);
+            out.push('\n');
+            if line.missing_newline {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Heuristic binary check: a NUL byte near the start of the data.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+async fn read_text(path: &Path) -> Result<String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if looks_binary(&bytes) {
+        bail!("{} appears to be a binary file; refusing to diff", path.display());
+    }
+    String::from_utf8(bytes)
+        .with_context(|| format!("{} is not valid UTF-8; refusing to diff", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::PatchTransform;
+
+    #[test]
+    fn test_diff_text_tags_lines() {
+        let diff = diff_text("a\nb\nc\n", "a\nB\nc\n");
+        let tags: Vec<DiffTag> = diff.iter().map(|l| l.tag).collect();
+        assert_eq!(
+            tags,
+            vec![DiffTag::Context, DiffTag::Removed, DiffTag::Added, DiffTag::Context]
+        );
+        assert_eq!(diff[1].old_line, Some(2));
+        assert_eq!(diff[2].new_line, Some(2));
+    }
+
+    #[test]
+    fn test_format_unified_missing_newline_round_trips() {
+        let old = "one\ntwo\nthree";
+        let new = "one\ntwo\nthree\nfour\n";
+        let patch = format_unified(&diff_text(old, new), 1);
+        assert!(patch.contains("\\ No newline at end of file"));
+        assert_eq!(PatchTransform::new().apply(old, &patch).unwrap(), new);
+    }
+
+    #[test]
+    fn test_format_unified_splits_distant_hunks() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                _ => format!("{}\n", i),
+            })
+            .collect();
+        let patch = format_unified(&diff_text(&old, &new), 2);
+        assert_eq!(patch.matches("@@ -").count(), 2);
+        assert_eq!(PatchTransform::new().apply(&old, &patch).unwrap(), new);
+    }
+
+    #[tokio::test]
+    async fn test_diff_files_refuses_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("a.txt");
+        let binary = dir.path().join("b.bin");
+        tokio::fs::write(&text, "hello\n").await.unwrap();
+        tokio::fs::write(&binary, [0x89, b'P', b'N', b'G', 0, 0]).await.unwrap();
+
+        let err = diff_files(&text, &binary).await.unwrap_err();
+        assert!(err.to_string().contains("binary"));
+    }
+}
@@ -0,0 +1,197 @@
+// Filesystem backends
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use async_trait::async_trait;
+
+/// Storage backend used by `FileReader` and `FileWriter` instances.
+#[async_trait]
+pub trait Filesystem: Send + Sync {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>>;
+    async fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+    async fn exists(&self, path: &Path) -> Result<bool>;
+    /// Removes a file or an empty directory.
+    async fn remove(&self, path: &Path) -> Result<()>;
+    async fn create_dir_all(&self, path: &Path) -> Result<()>;
+}
+
+/// The host filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+#[async_trait]
+impl Filesystem for RealFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        tokio::fs::read(path).await
+    }
+
+    async fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        tokio::fs::write(path, contents).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        tokio::fs::try_exists(path).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<()> {
+        if tokio::fs::metadata(path).await?.is_dir() {
+            tokio::fs::remove_dir(path).await
+        } else {
+            tokio::fs::remove_file(path).await
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        tokio::fs::create_dir_all(path).await
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+}
+
+/// A process-local filesystem for tests. Paths are normalised lexically and
+/// relative paths are resolved against `/`, which always exists. Writing
+/// requires the parent directory to exist, as on a real filesystem.
+#[derive(Debug, Default)]
+pub struct InMemoryFs {
+    entries: Mutex<BTreeMap<PathBuf, Entry>>,
+}
+
+impl InMemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists every file path currently stored, in sorted order.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry, Entry::File(_)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn is_dir(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> bool {
+        path.parent().is_none() || matches!(entries.get(path), Some(Entry::Dir))
+    }
+}
+
+#[async_trait]
+impl Filesystem for InMemoryFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let path = normalize(path);
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&path) {
+            Some(Entry::File(contents)) => Ok(contents.clone()),
+            Some(Entry::Dir) => Err(Error::new(ErrorKind::IsADirectory, format!("{} is a directory", path.display()))),
+            None if Self::is_dir(&entries, &path) => {
+                Err(Error::new(ErrorKind::IsADirectory, format!("{} is a directory", path.display())))
+            }
+            None => Err(not_found(&path)),
+        }
+    }
+
+    async fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        let parent = path.parent().ok_or_else(|| Error::new(ErrorKind::IsADirectory, "cannot write to /"))?;
+        if !Self::is_dir(&entries, parent) {
+            return Err(not_found(parent));
+        }
+        if Self::is_dir(&entries, &path) {
+            return Err(Error::new(ErrorKind::IsADirectory, format!("{} is a directory", path.display())));
+        }
+        entries.insert(path, Entry::File(contents.to_vec()));
+        Ok(())
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        let path = normalize(path);
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.contains_key(&path) || path.parent().is_none())
+    }
+
+    async fn remove(&self, path: &Path) -> Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&path) {
+            None => Err(not_found(&path)),
+            Some(Entry::Dir) if entries.keys().any(|p| p != &path && p.starts_with(&path)) => Err(Error::new(
+                ErrorKind::DirectoryNotEmpty,
+                format!("{} is not empty", path.display()),
+            )),
+            Some(_) => {
+                entries.remove(&path);
+                Ok(())
+            }
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let path = normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        for dir in path.ancestors().filter(|p| p.parent().is_some()) {
+            match entries.get(dir) {
+                Some(Entry::File(_)) => {
+                    return Err(Error::new(ErrorKind::NotADirectory, format!("{} is a file", dir.display())))
+                }
+                Some(Entry::Dir) => {}
+                None => {
+                    entries.insert(dir.to_path_buf(), Entry::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+/// Resolves `.` and `..` lexically and roots the result at `/`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_fs_models_directories() {
+        let fs = InMemoryFs::new();
+        let file = Path::new("/data/notes.txt");
+
+        let err = fs.write(file, b"hi").await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        fs.create_dir_all(Path::new("/data")).await.unwrap();
+        fs.write(file, b"hi").await.unwrap();
+        assert_eq!(fs.read(Path::new("data/./notes.txt")).await.unwrap(), b"hi");
+        assert!(fs.exists(Path::new("/data")).await.unwrap());
+        assert_eq!(fs.read(Path::new("/data")).await.unwrap_err().kind(), ErrorKind::IsADirectory);
+
+        let err = fs.remove(Path::new("/data")).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DirectoryNotEmpty);
+        fs.remove(file).await.unwrap();
+        fs.remove(Path::new("/data")).await.unwrap();
+        assert_eq!(fs.read(file).await.unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(fs.files().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+// Remembering which inputs were already processed, across runs
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::fs::{Filesystem, RealFs};
+use super::reader::FileReader;
+use super::writer::FileWriter;
+use crate::system::PathUtils;
+
+/// File under `PathUtils::app_data_dir` where `ProcessedIndex::persistent`
+/// keeps its records.
+pub const PROCESSED_INDEX_FILE: &str = "processed-index.json";
+
+/// What was processed from one input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessedRecord {
+    /// Hex SHA-256 of the content that was processed.
+    pub sha256: String,
+    /// Seconds since the Unix epoch.
+    pub processed_at: u64,
+}
+
+/// The inputs already processed and the content each had then, so that a
+/// later run can skip those that have not changed. Paths are made absolute
+/// before use; content is compared by hash, not modification time. Records
+/// live in memory until `save`d to the index file as JSON. Files and the
+/// index go through a `Filesystem` backend, the host's unless given.
+pub struct ProcessedIndex {
+    fs: Arc<dyn Filesystem>,
+    location: Option<PathBuf>,
+    records: BTreeMap<PathBuf, ProcessedRecord>,
+}
+
+impl Default for ProcessedIndex {
+    fn default() -> Self {
+        Self { fs: Arc::new(RealFs), location: None, records: BTreeMap::new() }
+    }
+}
+
+impl ProcessedIndex {
+    /// An index kept only in memory; `save` does nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index at `PROCESSED_INDEX_FILE` in the app data directory.
+    pub async fn persistent() -> Result<Self> {
+        Self::open(PathUtils::app_data_dir()?.join(PROCESSED_INDEX_FILE)).await
+    }
+
+    /// The index saved at `location`, or an empty one that will be saved
+    /// there if it does not exist yet.
+    pub async fn open(location: impl Into<PathBuf>) -> Result<Self> {
+        Self::open_with_fs(Arc::new(RealFs), location).await
+    }
+
+    pub async fn open_with_fs(fs: Arc<dyn Filesystem>, location: impl Into<PathBuf>) -> Result<Self> {
+        let location = location.into();
+        let records = if fs.exists(&location).await.unwrap_or(false) {
+            let text = FileReader::with_fs(Arc::clone(&fs)).read_text(&location).await?;
+            serde_json::from_str(&text).with_context(|| format!("Invalid processed index {}", location.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { fs, location: Some(location), records })
+    }
+
+    /// Whether `path` holds the same content as when it was last marked
+    /// processed; `false` if it never was.
+    pub async fn is_unchanged(&self, path: impl AsRef<Path>) -> Result<bool> {
+        let key = key(path.as_ref())?;
+        let Some(record) = self.records.get(&key) else { return Ok(false) };
+        Ok(self.fingerprint(path).await? == record.sha256)
+    }
+
+    /// Records `path` as processed with its content as it is now.
+    pub async fn mark_processed(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let sha256 = self.fingerprint(path.as_ref()).await?;
+        self.mark_processed_as(path, sha256)
+    }
+
+    /// Records `path` as processed with content whose `fingerprint` was
+    /// taken earlier. To notice a file changing while it is processed,
+    /// fingerprint it before and after and only mark it if both agree; a
+    /// file that changed is then processed again next time.
+    pub fn mark_processed_as(&mut self, path: impl AsRef<Path>, sha256: String) -> Result<()> {
+        let processed_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.records.insert(key(path.as_ref())?, ProcessedRecord { sha256, processed_at });
+        Ok(())
+    }
+
+    /// Hex SHA-256 of what `path` holds now.
+    pub async fn fingerprint(&self, path: impl AsRef<Path>) -> Result<String> {
+        let content = FileReader::with_fs(Arc::clone(&self.fs)).read(path).await?;
+        Ok(Sha256::digest(content).iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn record(&self, path: impl AsRef<Path>) -> Option<&ProcessedRecord> {
+        self.records.get(&key(path.as_ref()).ok()?)
+    }
+
+    /// Drops the record for `path`, so it counts as changed. Returns
+    /// whether there was one.
+    pub fn forget(&mut self, path: impl AsRef<Path>) -> bool {
+        key(path.as_ref()).is_ok_and(|key| self.records.remove(&key).is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Writes the records to the index file, creating its directory.
+    pub async fn save(&self) -> Result<()> {
+        let Some(location) = &self.location else { return Ok(()) };
+        if let Some(dir) = location.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            self.fs.create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        FileWriter::with_fs(Arc::clone(&self.fs)).write(location, &serde_json::to_vec_pretty(&self.records)?).await
+    }
+}
+
+/// `path` made absolute against the working directory, without resolving
+/// symlinks, so that it names the same file from every run.
+fn key(path: &Path) -> Result<PathBuf> {
+    let absolute = std::path::absolute(path).with_context(|| format!("Failed to resolve {}", path.display()))?;
+    Ok(PathUtils::normalize(&absolute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::fs::InMemoryFs;
+
+    #[tokio::test]
+    async fn test_processed_index_round_trip() {
+        let fs: Arc<dyn Filesystem> = Arc::new(InMemoryFs::new());
+        fs.create_dir_all(Path::new("/data")).await.unwrap();
+        fs.write(Path::new("/data/a.txt"), b"one").await.unwrap();
+        fs.write(Path::new("/data/b.txt"), b"two").await.unwrap();
+
+        let mut index = ProcessedIndex::open_with_fs(Arc::clone(&fs), "/state/index.json").await.unwrap();
+        assert!(!index.is_unchanged("/data/a.txt").await.unwrap());
+        index.mark_processed("/data/a.txt").await.unwrap();
+        // Fingerprinted before processing, then changed.
+        let before = index.fingerprint("/data/b.txt").await.unwrap();
+        fs.write(Path::new("/data/b.txt"), b"three").await.unwrap();
+        index.mark_processed_as("/data/b.txt", before.clone()).unwrap();
+        assert_ne!(index.fingerprint("/data/b.txt").await.unwrap(), before);
+        assert!(index.is_unchanged("/data/./a.txt").await.unwrap());
+        assert!(!index.is_unchanged("/data/b.txt").await.unwrap());
+        index.save().await.unwrap();
+
+        let mut reopened = ProcessedIndex::open_with_fs(Arc::clone(&fs), "/state/index.json").await.unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.record("/data/a.txt").unwrap().sha256, reopened.fingerprint("/data/a.txt").await.unwrap());
+        fs.write(Path::new("/data/a.txt"), b"changed").await.unwrap();
+        assert!(!reopened.is_unchanged("/data/a.txt").await.unwrap());
+        assert!(reopened.forget("/data/b.txt"));
+        assert!(!reopened.forget("/data/b.txt"));
+        assert!(reopened.is_unchanged("/data/missing.txt").await.is_ok_and(|unchanged| !unchanged));
+
+        fs.write(Path::new("/state/index.json"), b"not json").await.unwrap();
+        assert!(ProcessedIndex::open_with_fs(fs, "/state/index.json").await.is_err());
+    }
+}
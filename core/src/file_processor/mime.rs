@@ -0,0 +1,181 @@
+// Content type detection from magic bytes and extensions
+use std::io::Read;
+use std::path::Path;
+use anyhow::{Context, Result};
+
+use super::diff::looks_binary;
+
+/// How many leading bytes of a file are inspected to identify it.
+pub const MIME_SNIFF_LEN: usize = 8192;
+/// Reported for binary content that is not recognised.
+pub const OCTET_STREAM: &str = "application/octet-stream";
+pub const PLAIN_TEXT: &str = "text/plain";
+
+/// Types known by extension, used when the content has no signature.
+const EXTENSION_TYPES: &[(&str, &str)] = &[
+    ("txt", PLAIN_TEXT),
+    ("log", PLAIN_TEXT),
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("json", "application/json"),
+    ("jsonl", "application/jsonl"),
+    ("toml", "application/toml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("ts", "text/x-typescript"),
+    ("rs", "text/x-rust"),
+    ("py", "text/x-python"),
+    ("go", "text/x-go"),
+    ("java", "text/x-java"),
+    ("c", "text/x-c"),
+    ("h", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("sh", "application/x-sh"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("pdf", "application/pdf"),
+    ("gz", "application/gzip"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("wasm", "application/wasm"),
+];
+
+/// Types other than `text/*` whose content is text.
+const TEXT_APPLICATION_TYPES: &[&str] = &[
+    "application/json",
+    "application/jsonl",
+    "application/xml",
+    "application/toml",
+    "application/yaml",
+    "application/javascript",
+    "application/x-sh",
+    "application/x-shellscript",
+    "image/svg+xml",
+];
+
+/// The MIME type of the file at `path`, judged from its first
+/// `MIME_SNIFF_LEN` bytes: a known signature (PNG, PDF, gzip, ...) wins
+/// over the extension, so a misnamed file is still identified. Text that
+/// parses as JSON is `application/json`; other text takes its type from the
+/// extension, or is `text/plain`. Unrecognised binary data is
+/// `application/octet-stream`.
+pub fn detect_mime<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let mut head = Vec::with_capacity(MIME_SNIFF_LEN);
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    file.take(MIME_SNIFF_LEN as u64 + 1)
+        .read_to_end(&mut head)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let complete = head.len() <= MIME_SNIFF_LEN;
+    head.truncate(MIME_SNIFF_LEN);
+    Ok(sniff(&head, complete, path.extension().and_then(|ext| ext.to_str())).to_string())
+}
+
+/// Whether the file at `path` holds text, per `detect_mime` and
+/// `is_text_mime`; false if it cannot be read.
+pub fn is_text<P: AsRef<Path>>(path: P) -> bool {
+    detect_mime(path).is_ok_and(|mime| is_text_mime(&mime))
+}
+
+/// Whether content of type `mime` is text: `text/*` and text-based formats
+/// such as JSON, XML, YAML and SVG.
+pub fn is_text_mime(mime: &str) -> bool {
+    mime.starts_with("text/") || TEXT_APPLICATION_TYPES.contains(&mime)
+}
+
+/// The MIME type of `head`, the start of a file (all of it when `complete`).
+fn sniff(head: &[u8], complete: bool, extension: Option<&str>) -> &'static str {
+    let by_extension = extension.and_then(|ext| {
+        EXTENSION_TYPES.iter().find(|(known, _)| known.eq_ignore_ascii_case(ext)).map(|(_, mime)| *mime)
+    });
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type();
+    }
+    if head.is_empty() {
+        return by_extension.unwrap_or(PLAIN_TEXT);
+    }
+    if looks_binary(head) || !is_utf8_prefix(head, complete) {
+        return by_extension.filter(|mime| !is_text_mime(mime)).unwrap_or(OCTET_STREAM);
+    }
+    if looks_like_json(head, complete) {
+        return "application/json";
+    }
+    by_extension.filter(|mime| is_text_mime(mime)).unwrap_or(PLAIN_TEXT)
+}
+
+/// Valid UTF-8, allowing a character cut off at the end of a partial read.
+fn is_utf8_prefix(bytes: &[u8], complete: bool) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => !complete && e.error_len().is_none(),
+    }
+}
+
+/// An object or array that parses, or that is only cut short by the end of
+/// a partial read.
+fn looks_like_json(bytes: &[u8], complete: bool) -> bool {
+    let trimmed = bytes.trim_ascii_start();
+    if !trimmed.starts_with(b"{") && !trimmed.starts_with(b"[") {
+        return false;
+    }
+    match serde_json::from_slice::<serde::de::IgnoredAny>(trimmed) {
+        Ok(_) => true,
+        Err(e) => !complete && e.is_eof(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+    const GZIP: &[u8] = b"\x1f\x8b\x08\0\0\0\0\0\0\x03\x01\0\0\xff\xff";
+
+    #[test]
+    fn test_detect_mime_ignores_wrong_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases: [(&str, &[u8], &str); 8] = [
+            ("image.txt", PNG, "image/png"),
+            ("report.json", b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n", "application/pdf"),
+            ("archive.md", GZIP, "application/gzip"),
+            ("data.txt", b"  {\"a\": [1, 2]}\n", "application/json"),
+            ("notes.txt", b"{ not json", PLAIN_TEXT),
+            ("notes", b"plain words\n", PLAIN_TEXT),
+            ("main.rs", b"fn main() {}\n", "text/x-rust"),
+            ("blob.rs", b"\0\x01\x02\x03", OCTET_STREAM),
+        ];
+        for (name, contents, expected) in cases {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            assert_eq!(detect_mime(&path).unwrap(), expected, "{}", name);
+        }
+        assert!(is_text(dir.path().join("data.txt")));
+        assert!(!is_text(dir.path().join("image.txt")));
+        assert!(!is_text(dir.path().join("missing")));
+        assert!(detect_mime(dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let mut json = b"[".to_vec();
+        json.extend(std::iter::repeat_n(&b"\"\xc3\xa9\", "[..], 2000).flatten());
+        let head = &json[..MIME_SNIFF_LEN];
+        assert_eq!(sniff(head, false, Some("log")), "application/json");
+        // A character split by the end of the read is only fine if more follows.
+        assert_eq!(sniff(b"caf\xc3", false, None), PLAIN_TEXT);
+        assert_eq!(sniff(b"caf\xc3", true, None), OCTET_STREAM);
+        assert_eq!(sniff(b"", true, Some("csv")), "text/csv");
+    }
+}
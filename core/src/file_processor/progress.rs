@@ -0,0 +1,40 @@
+// Progress reporting helpers
+use std::time::{Duration, Instant};
+
+/// Minimum time between two progress callback invocations.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Wraps an `on_progress(done, total)` callback and rate-limits it so that
+/// reporting never becomes the bottleneck of an I/O loop.
+pub struct ProgressThrottle<F: Fn(u64, u64)> {
+    callback: F,
+    total: u64,
+    last_report: Option<Instant>,
+}
+
+impl<F: Fn(u64, u64)> ProgressThrottle<F> {
+    pub fn new(total: u64, callback: F) -> Self {
+        Self {
+            callback,
+            total,
+            last_report: None,
+        }
+    }
+
+    /// Reports `done` bytes if at least `PROGRESS_INTERVAL` has elapsed since the last report.
+    pub fn update(&mut self, done: u64) {
+        let due = self
+            .last_report
+            .is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            (self.callback)(done, self.total);
+            self.last_report = Some(Instant::now());
+        }
+    }
+
+    /// Reports the final count unconditionally.
+    pub fn finish(&mut self, done: u64) {
+        (self.callback)(done, self.total);
+        self.last_report = Some(Instant::now());
+    }
+}
@@ -1,22 +1,273 @@
 // File reader implementation
-use std::path::Path;
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use std::io::SeekFrom;
+use thiserror::Error;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
 
-pub struct FileReader;
+use super::fs::{Filesystem, RealFs};
+use crate::cancel::{cancelled, CancellationToken};
+use crate::error::CoreError;
+
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+/// Largest input `check_input` accepts unless told otherwise.
+pub const DEFAULT_MAX_INPUT_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Why a path was refused as input before reading it.
+#[derive(Debug, Error)]
+pub enum InputError {
+    #[error("{} is {size} bytes, over the {limit}-byte limit", path.display())]
+    TooLarge { path: PathBuf, size: u64, limit: u64 },
+    #[error("{} is a directory, not a file", path.display())]
+    IsADirectory { path: PathBuf },
+    /// A FIFO, device or socket, which may never end or block forever.
+    #[error("{} is a {kind}, not a regular file", path.display())]
+    NotAFile { path: PathBuf, kind: &'static str },
+}
+
+/// Reads files. The associated functions always use the host filesystem;
+/// instance methods go through the reader's `Filesystem` backend.
+pub struct FileReader {
+    fs: Arc<dyn Filesystem>,
+}
 
 impl FileReader {
     pub fn new() -> Self {
-        Self
+        Self::with_fs(Arc::new(RealFs))
+    }
+
+    pub fn with_fs(fs: Arc<dyn Filesystem>) -> Self {
+        Self { fs }
+    }
+
+    pub async fn read(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        self.fs
+            .read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    pub async fn read_text(&self, path: impl AsRef<Path>) -> Result<String> {
+        let path = path.as_ref();
+        let bytes = self.read(path).await?;
+        String::from_utf8(bytes).with_context(|| format!("{} is not valid UTF-8", path.display()))
     }
     
-    pub async fn read_file<P: AsRef<Path>>(_path: P) -> Result<String> {
-        // TODO: Implement high-performance file reading
-        todo!("Implement in T017")
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))]
+    pub async fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
+        let path = path.as_ref();
+        tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Like `read_file`, but aborts with `CoreError::Cancelled` (carrying the bytes
+    /// read so far) when `cancel` fires.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))]
+    pub async fn read_file_cancellable<P: AsRef<Path>>(
+        path: P,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<String> {
+        let path = path.as_ref();
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut content = Vec::new();
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+
+        loop {
+            tokio::select! {
+                read = file.read(&mut chunk) => {
+                    let n = read.with_context(|| format!("Failed to read {}", path.display()))?;
+                    if n == 0 {
+                        break;
+                    }
+                    content.extend_from_slice(&chunk[..n]);
+                }
+                _ = cancelled(cancel) => {
+                    return Err(CoreError::Cancelled {
+                        operation: format!("reading {}", path.display()),
+                        partial: content,
+                    }
+                    .into());
+                }
+            }
+        }
+
+        String::from_utf8(content).with_context(|| format!("{} is not valid UTF-8", path.display()))
+    }
+
+    /// The file's lines as they are read, without their `\n` or `\r\n`.
+    /// Opening fails up front; a read error or invalid UTF-8 ends the
+    /// stream with that error.
+    pub async fn read_lines<P: AsRef<Path>>(path: P) -> Result<BoxStream<'static, Result<String>>> {
+        let path = path.as_ref().to_path_buf();
+        let file = tokio::fs::File::open(&path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let lines = BufReader::new(file).lines();
+        Ok(futures::stream::unfold(Some((lines, path)), |state| async move {
+            let (mut lines, path) = state?;
+            match lines.next_line().await {
+                Ok(Some(line)) => Some((Ok(line), Some((lines, path)))),
+                Ok(None) => None,
+                Err(e) => Some((Err(anyhow::Error::new(e).context(format!("Failed to read {}", path.display()))), None)),
+            }
+        })
+        .boxed())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))]
+    pub async fn read_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Checks that `path` is a regular file of at most `max_size` bytes,
+    /// without opening it, and returns its size. Symlinks are followed.
+    pub async fn check_input<P: AsRef<Path>>(path: P, max_size: u64) -> Result<u64> {
+        let path = path.as_ref();
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file_type = metadata.file_type();
+        if file_type.is_dir() {
+            return Err(InputError::IsADirectory { path: path.to_path_buf() }.into());
+        }
+        if !file_type.is_file() {
+            return Err(InputError::NotAFile { path: path.to_path_buf(), kind: special_kind(&file_type) }.into());
+        }
+        if metadata.len() > max_size {
+            return Err(InputError::TooLarge { path: path.to_path_buf(), size: metadata.len(), limit: max_size }.into());
+        }
+        Ok(metadata.len())
+    }
+
+    /// `read_bytes` after `check_input`. A file that grows past `max_size`
+    /// while being read fails with `InputError::TooLarge` too.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), max_size))]
+    pub async fn read_input<P: AsRef<Path>>(path: P, max_size: u64) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        Self::check_input(path, max_size).await?;
+        let mut content = Self::read_range(path, 0, usize::try_from(max_size.saturating_add(1)).unwrap_or(usize::MAX)).await?;
+        if content.len() as u64 > max_size {
+            let size = content.len() as u64;
+            content.clear();
+            return Err(InputError::TooLarge { path: path.to_path_buf(), size, limit: max_size }.into());
+        }
+        Ok(content)
     }
+
+    /// Reads up to `len` bytes starting at `offset`. Returns fewer bytes when
+    /// the file ends first, and none when `offset` is past the end.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), offset, len))]
+    pub async fn read_range<P: AsRef<Path>>(path: P, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        read_window(file, offset, len)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// The first `n` bytes of the file.
+    pub async fn read_head<P: AsRef<Path>>(path: P, n: usize) -> Result<Vec<u8>> {
+        Self::read_range(path, 0, n).await
+    }
+
+    /// The last `n` bytes of the file, read by seeking from the end.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), n))]
+    pub async fn read_tail<P: AsRef<Path>>(path: P, n: usize) -> Result<Vec<u8>> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let size = file
+            .metadata()
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        read_window(file, size.saturating_sub(n as u64), n)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+}
+
+#[cfg(unix)]
+fn special_kind(file_type: &std::fs::FileType) -> &'static str {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_fifo() {
+        "FIFO"
+    } else if file_type.is_char_device() {
+        "character device"
+    } else if file_type.is_block_device() {
+        "block device"
+    } else if file_type.is_socket() {
+        "socket"
+    } else {
+        "special file"
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_: &std::fs::FileType) -> &'static str {
+    "special file"
+}
+
+async fn read_window(mut file: tokio::fs::File, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut window = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+    file.take(len as u64).read_to_end(&mut window).await?;
+    Ok(window)
 }
 
 impl Default for FileReader {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_range_head_and_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.txt");
+        tokio::fs::write(&path, "0123456789").await.unwrap();
+
+        assert_eq!(FileReader::read_range(&path, 2, 3).await.unwrap(), b"234");
+        assert_eq!(FileReader::read_range(&path, 8, 10).await.unwrap(), b"89");
+        assert!(FileReader::read_range(&path, 20, 4).await.unwrap().is_empty());
+        assert_eq!(FileReader::read_head(&path, 4).await.unwrap(), b"0123");
+        assert_eq!(FileReader::read_tail(&path, 3).await.unwrap(), b"789");
+        assert_eq!(FileReader::read_tail(&path, 50).await.unwrap(), b"0123456789");
+        assert!(FileReader::read_head(dir.path().join("missing"), 4).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_input_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.txt");
+        tokio::fs::write(&path, "0123456789").await.unwrap();
+
+        assert_eq!(FileReader::read_input(&path, 10).await.unwrap(), b"0123456789");
+        let err = FileReader::read_input(&path, 9).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<InputError>(), Some(InputError::TooLarge { size: 10, limit: 9, .. })));
+        let err = FileReader::check_input(dir.path(), 100).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<InputError>(), Some(InputError::IsADirectory { .. })));
+        #[cfg(unix)]
+        {
+            let err = FileReader::read_input("/dev/zero", 100).await.unwrap_err();
+            assert_eq!(err.to_string(), "/dev/zero is a character device, not a regular file");
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// Log files that move aside once they grow past a size
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use anyhow::{Context, Result};
+
+/// Size past which `RotatingWriter` starts a new file by default.
+pub const DEFAULT_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated files `RotatingWriter` keeps by default.
+pub const DEFAULT_ROTATED_FILES: usize = 5;
+
+/// Appends to a log file and rotates it once the next write would take it
+/// past `max_bytes`: `app.log` becomes `app.log.1`, `app.log.1` becomes
+/// `app.log.2` and so on, and files numbered past `max_files` are deleted.
+/// Rotating only renames files, so each one is whole at all times. Clones
+/// share the file, and a single write never straddles a rotation, so
+/// threads can append whole lines through clones of one writer. Writers in
+/// other processes are not coordinated with.
+#[derive(Debug, Clone)]
+pub struct RotatingWriter {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    gzip: bool,
+    file: File,
+    size: u64,
+}
+
+impl RotatingWriter {
+    /// Appends to `path`, creating it and its directory if needed, with
+    /// `DEFAULT_ROTATE_BYTES` and `DEFAULT_ROTATED_FILES`.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let file = open_append(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let inner = Inner { path, max_bytes: DEFAULT_ROTATE_BYTES, max_files: DEFAULT_ROTATED_FILES, gzip: false, file, size };
+        Ok(Self { inner: Arc::new(Mutex::new(inner)) })
+    }
+
+    /// A write bigger than this still goes to a file of its own.
+    pub fn with_max_bytes(self, max_bytes: u64) -> Self {
+        self.lock().max_bytes = max_bytes.max(1);
+        self
+    }
+
+    /// With 0, the log starts over empty at each rotation.
+    pub fn with_max_files(self, max_files: usize) -> Self {
+        self.lock().max_files = max_files;
+        self
+    }
+
+    /// Compresses rotated files to `app.log.1.gz` and so on.
+    #[cfg(feature = "gzip")]
+    pub fn with_gzip(self, gzip: bool) -> Self {
+        self.lock().gzip = gzip;
+        self
+    }
+
+    /// The file being written.
+    pub fn path(&self) -> PathBuf {
+        self.lock().path.clone()
+    }
+
+    /// Rotated file `n`, where 1 is the newest.
+    pub fn rotated_path(&self, n: usize) -> PathBuf {
+        self.lock().rotated(n)
+    }
+
+    /// Appends `line` and a newline in one write.
+    pub fn write_line(&self, line: &str) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(line.len() + 1);
+        bytes.extend_from_slice(line.as_bytes());
+        bytes.push(b'\n');
+        self.lock().append(&bytes)
+    }
+
+    /// Rotates now, whatever the size of the current file.
+    pub fn rotate(&self) -> io::Result<()> {
+        self.lock().rotate()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for RotatingWriter {
+    /// Writes all of `buf` or nothing, to a single file.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().append(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().file.flush()
+    }
+}
+
+impl Inner {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + bytes.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            remove_if_exists(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                rename_if_exists(&self.rotated(n), &self.rotated(n + 1))?;
+            }
+            let newest = numbered(&self.path, 1, "");
+            std::fs::rename(&self.path, &newest)?;
+            if self.gzip {
+                compress(&newest, &self.rotated(1))?;
+            }
+        }
+        // Written to until here; appends now go to a file of its own.
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.prune()
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        numbered(&self.path, n, if self.gzip { ".gz" } else { "" })
+    }
+
+    /// Deletes rotated files numbered past `max_files`, such as those left
+    /// by a run that kept more.
+    fn prune(&self) -> io::Result<()> {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name().and_then(|name| name.to_str())) else {
+            return Ok(());
+        };
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(number) = file_name.to_str().and_then(|file| file.strip_prefix(name)?.strip_prefix('.')) else {
+                continue;
+            };
+            let number = number.strip_suffix(".gz").unwrap_or(number);
+            if number.parse::<usize>().is_ok_and(|n| n > self.max_files) {
+                remove_if_exists(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `path` with `.n` and `extension` appended: `app.log.2.gz`.
+fn numbered(path: &Path, n: usize, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}{}", n, extension));
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Replaces `plain` with its gzip compression at `gz`, which only appears
+/// once complete.
+#[cfg(feature = "gzip")]
+fn compress(plain: &Path, gz: &Path) -> io::Result<()> {
+    let partial = gz.with_extension("gz.partial");
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&partial)?, flate2::Compression::default());
+    io::copy(&mut File::open(plain)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::rename(&partial, gz)?;
+    std::fs::remove_file(plain)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_plain: &Path, _gz: &Path) -> io::Result<()> {
+    unreachable!("gzip is only set with the `gzip` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_rotating_writer_keeps_at_most_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/app.log");
+        // A leftover from a run that kept more files.
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(numbered(&path, 7, ""), "old\n").unwrap();
+
+        let writer = RotatingWriter::open(&path).unwrap().with_max_bytes(12).with_max_files(2);
+        for line in ["one", "two", "three", "four", "five", "six", "seven"] {
+            writer.write_line(line).unwrap();
+        }
+        assert_eq!(lines(&path), ["seven"]);
+        assert_eq!(lines(&writer.rotated_path(1)), ["five", "six"]);
+        assert_eq!(lines(&writer.rotated_path(2)), ["three", "four"]);
+        assert!(!writer.rotated_path(3).exists());
+        assert!(!numbered(&path, 7, "").exists());
+
+        // Reopening appends and counts what is already there.
+        let mut reopened = RotatingWriter::open(&path).unwrap().with_max_bytes(12).with_max_files(2);
+        writeln!(reopened, "eight").unwrap();
+        assert_eq!(lines(&path), ["seven", "eight"]);
+        writeln!(reopened, "nine").unwrap();
+        assert_eq!(lines(&path), ["nine"]);
+        assert_eq!(lines(&writer.rotated_path(1)), ["seven", "eight"]);
+    }
+
+    #[test]
+    fn test_rotating_writer_never_splits_concurrent_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let writer = RotatingWriter::open(&path).unwrap().with_max_bytes(200).with_max_files(100);
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let writer = writer.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        writer.write_line(&format!("thread {} line {:02}", t, i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let mut all = lines(&path);
+        for n in 1..=100 {
+            let rotated = writer.rotated_path(n);
+            if rotated.exists() {
+                assert!(std::fs::metadata(&rotated).unwrap().len() <= 200);
+                all.extend(lines(&rotated));
+            }
+        }
+        assert_eq!(all.len(), 400);
+        assert!(all.iter().all(|line| line.len() == "thread 0 line 00".len()));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_rotating_writer_compresses_rotated_files() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let writer = RotatingWriter::open(&path).unwrap().with_gzip(true).with_max_files(1);
+        writer.write_line("first").unwrap();
+        writer.rotate().unwrap();
+        writer.write_line("second").unwrap();
+        writer.rotate().unwrap();
+        assert_eq!(writer.rotated_path(1), dir.path().join("app.log.1.gz"));
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(writer.rotated_path(1)).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "second\n");
+        let mut names: Vec<_> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["app.log", "app.log.1.gz"]);
+    }
+}
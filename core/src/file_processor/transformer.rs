@@ -1,16 +1,294 @@
 // File transformer implementation
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use tokio_util::io::ReaderStream;
 
-pub struct FileTransformer;
+use super::diff::{diff_text, format_unified, looks_binary, DiffLine};
+use super::fs::{Filesystem, RealFs};
+use super::writer::{check_writable, FileWriter, DEFAULT_WRITE_CONCURRENCY};
+
+pub mod patch;
+pub mod encode;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub mod compress;
+pub mod pipeline;
+pub mod normalize;
+pub mod front_matter;
+pub mod html;
+pub mod json_query;
+pub mod json_format;
+pub mod streaming;
+pub mod stats;
+pub mod redact;
+pub mod concat;
+pub mod split;
+pub mod strip_comments;
+pub mod detect_language;
+pub mod plugin;
+pub mod tree;
+
+pub use patch::{apply_patch_to_dir, PatchError, PatchTransform};
+pub use encode::{Codec, Direction, EncodeError, EncodeTransform};
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use compress::{CompressTransform, Compression};
+pub use pipeline::{Metadata, Stage, TransformPipeline};
+pub use normalize::{IndentConversion, NormalizeReport, NormalizeTransform};
+pub use front_matter::{FrontMatter, FrontMatterFormat, FrontMatterTransform};
+pub use html::HtmlToTextTransform;
+pub use json_query::{JsonQueryError, JsonQueryTransform};
+pub use json_format::{JsonFormatError, JsonFormatTransform};
+pub use streaming::{LineTransform, StreamTransform};
+#[cfg(feature = "tiktoken")]
+pub use stats::TiktokenTokenizer;
+pub use redact::{RedactReport, RedactTransform};
+pub use concat::{ConcatEntry, ConcatTransform, DEFAULT_CONCAT_HEADER};
+pub use split::{shard_paths, SplitBy, SplitTransform};
+pub use tree::{TreeEntry, TreeOptions, TreeReport};
+pub use strip_comments::{Language, StripCommentsTransform};
+pub use detect_language::{DetectLanguageTransform, LanguageGuess, NATURAL_LANGUAGE, UNKNOWN_LANGUAGE};
+pub use plugin::{
+    PluginBuffer, PluginManifestV1, PluginTransformV1, Transform, TransformError, TransformRegistry,
+    PLUGIN_ABI_VERSION, PLUGIN_ENTRY_POINT,
+};
+pub use stats::{HeuristicTokenizer, StatsTransform, TextStats, TokenEstimator};
+
+pub struct FileTransformer {
+    pipeline: TransformPipeline,
+    fs: Arc<dyn Filesystem>,
+    /// Chunk size for running parallelizable pipelines on the rayon pool.
+    parallel_chunk_size: Option<usize>,
+}
+
+/// What `transform_file` produced, and whether it differs from what is on disk.
+pub struct TransformOutcome {
+    pub output_path: PathBuf,
+    /// Existing content of the output file, if it existed.
+    pub before: Option<Vec<u8>>,
+    pub after: Vec<u8>,
+    /// Values recorded by the pipeline's stages.
+    pub metadata: Metadata,
+    pub changed: bool,
+    pub written: bool,
+}
+
+impl TransformOutcome {
+    /// Line diff between the current output file and the transformed content,
+    /// or `None` if either side is binary.
+    pub fn diff(&self) -> Option<Vec<DiffLine>> {
+        let before = self.before.as_deref().unwrap_or_default();
+        if looks_binary(before) || looks_binary(&self.after) {
+            return None;
+        }
+        let before = std::str::from_utf8(before).ok()?;
+        let after = std::str::from_utf8(&self.after).ok()?;
+        Some(diff_text(before, after))
+    }
+
+    /// Unified diff with file headers, or `None` if either side is binary.
+    pub fn unified_diff(&self, context: usize) -> Option<String> {
+        let hunks = format_unified(&self.diff()?, context);
+        let old = match self.before {
+            Some(_) => self.output_path.display().to_string(),
+            None => "/dev/null".to_string(),
+        };
+        Some(format!("--- {}\n+++ {}\n{}", old, self.output_path.display(), hunks))
+    }
+}
 
 impl FileTransformer {
     pub fn new() -> Self {
-        Self
+        Self::with_pipeline(TransformPipeline::new())
+    }
+
+    pub fn with_pipeline(pipeline: TransformPipeline) -> Self {
+        Self {
+            pipeline,
+            fs: Arc::new(RealFs),
+            parallel_chunk_size: None,
+        }
+    }
+
+    /// A transformer running the named transforms from `registry` in order.
+    pub fn from_registry<S: AsRef<str>>(registry: &TransformRegistry, names: &[S]) -> Result<Self> {
+        Ok(Self::with_pipeline(registry.pipeline(names)?))
+    }
+
+    /// Reads and writes files through `fs` instead of the host filesystem.
+    pub fn with_fs(mut self, fs: Arc<dyn Filesystem>) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// Splits content into runs of whole lines of about `chunk_size` bytes
+    /// and transforms them on a thread pool when every stage is
+    /// line-independent (see `Transform::is_parallelizable`). Other
+    /// pipelines keep running serially.
+    pub fn with_parallel(mut self, chunk_size: usize) -> Self {
+        self.parallel_chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn pipeline(&self) -> &TransformPipeline {
+        &self.pipeline
+    }
+
+    /// Runs text through the pipeline. Text stages take it as it is, and
+    /// only the output of bytes stages is checked for UTF-8.
+    pub async fn transform_content(&self, content: &str) -> Result<String> {
+        if self.runs_parallel() {
+            let output = self.transform_bytes(content.as_bytes().to_vec())?;
+            return String::from_utf8(output).context("transform pipeline produced non-UTF-8 output");
+        }
+        Ok(self.pipeline.run_text_with_metadata(content.to_string())?.0)
+    }
+
+    pub fn transform_bytes(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self.transform_bytes_with_metadata(content)?.0)
+    }
+
+    pub fn transform_bytes_with_metadata(&self, content: Vec<u8>) -> Result<(Vec<u8>, Metadata)> {
+        match self.parallel_chunk_size {
+            // Parallelizable stages never record metadata.
+            Some(chunk_size) if self.pipeline.is_parallelizable() => {
+                Ok((self.pipeline.run_parallel(content, chunk_size)?, Metadata::new()))
+            }
+            _ => self.pipeline.run_with_metadata(content),
+        }
     }
-    
-    pub async fn transform_content(_content: &str) -> Result<String> {
-        // TODO: Implement file transformation logic
-        todo!("Implement in T019")
+
+    fn runs_parallel(&self) -> bool {
+        self.parallel_chunk_size.is_some() && self.pipeline.is_parallelizable()
+    }
+
+    /// Transforms `input` into `output`. With `dry_run` set nothing is written;
+    /// the returned outcome still reports whether the output would change.
+    /// The file is read as bytes: a pipeline of bytes stages copies binary
+    /// files faithfully, and one with text stages fails on input that is not
+    /// UTF-8 where the first text stage would receive it (see `Stage`).
+    pub async fn transform_file<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input: P,
+        output: Q,
+        dry_run: bool,
+    ) -> Result<TransformOutcome> {
+        let (input, output) = (input.as_ref(), output.as_ref());
+        let content = self
+            .fs
+            .read(input)
+            .await
+            .with_context(|| format!("Failed to read {}", input.display()))?;
+        let (after, metadata) = self.transform_bytes_with_metadata(content)?;
+
+        let before = match self.fs.read(output).await {
+            Ok(existing) => Some(existing),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", output.display()))
+            }
+        };
+        let changed = before.as_deref() != Some(after.as_slice());
+
+        let written = changed && !dry_run;
+        if written {
+            self.fs
+                .write(output, &after)
+                .await
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+        }
+
+        Ok(TransformOutcome {
+            output_path: output.to_path_buf(),
+            before,
+            after,
+            metadata,
+            changed,
+            written,
+        })
+    }
+
+    /// Streams `input` through the pipeline into `output` without holding
+    /// either file in memory. Every stage must support streaming. This always
+    /// uses the host filesystem. Returns the number of bytes written.
+    pub async fn transform_file_streaming<P: AsRef<Path>, Q: AsRef<Path>>(&self, input: P, output: Q) -> Result<u64> {
+        let (input, output) = (input.as_ref(), output.as_ref());
+        if !self.pipeline.is_streamable() {
+            bail!("pipeline stages {:?} cannot all run in streaming mode", self.pipeline.stage_names());
+        }
+        check_writable(output)?;
+        let reader = tokio::fs::File::open(input)
+            .await
+            .with_context(|| format!("Failed to open {}", input.display()))?;
+        let mut writer = tokio::io::BufWriter::new(
+            tokio::fs::File::create(output)
+                .await
+                .with_context(|| format!("Failed to create {}", output.display()))?,
+        );
+        self.pipeline.apply_streaming(ReaderStream::new(reader), &mut writer).await
+    }
+
+    /// Reads `paths`, runs each through the pipeline and joins them with
+    /// `concat`. Every file must be UTF-8 text.
+    pub async fn concat<P: AsRef<Path>>(&self, paths: &[P], concat: &ConcatTransform) -> Result<(String, Vec<ConcatEntry>)> {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = path.as_ref();
+            let content = self
+                .fs
+                .read(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let content = String::from_utf8(self.transform_bytes(content)?)
+                .with_context(|| format!("{} is not UTF-8 text", path.display()))?;
+            files.push((path.to_path_buf(), content));
+        }
+        Ok(concat.apply(&files))
+    }
+
+    /// Runs `input` through the pipeline and writes it to numbered shards in
+    /// `out_dir` (see `shard_paths`), returning their paths. If any write
+    /// fails, shards created by this call are removed again.
+    pub async fn split<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        input: P,
+        out_dir: Q,
+        split: &SplitTransform,
+    ) -> Result<Vec<PathBuf>> {
+        let (input, out_dir) = (input.as_ref(), out_dir.as_ref());
+        let content = self
+            .fs
+            .read(input)
+            .await
+            .with_context(|| format!("Failed to read {}", input.display()))?;
+        let content = self.transform_bytes(content)?;
+        let shards = split.split(&content)?;
+        let paths = shard_paths(input, out_dir, shards.len());
+
+        self.fs
+            .create_dir_all(out_dir)
+            .await
+            .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+        let mut created = Vec::new();
+        for path in &paths {
+            if !self.fs.exists(path).await? {
+                created.push(path.clone());
+            }
+        }
+
+        let files = paths.iter().cloned().zip(shards.iter().map(|s| s.to_vec())).collect();
+        let writer = FileWriter::with_fs(self.fs.clone());
+        if let Err(e) = writer.write_many(files, DEFAULT_WRITE_CONCURRENCY).await {
+            for path in &created {
+                let _ = self.fs.remove(path).await;
+            }
+            return Err(e);
+        }
+        Ok(paths)
+    }
+
+    /// Computes what `transform_file` would write without touching disk.
+    pub async fn preview<P: AsRef<Path>, Q: AsRef<Path>>(&self, input: P, output: Q) -> Result<TransformOutcome> {
+        self.transform_file(input, output, true).await
     }
 }
 
@@ -18,4 +296,119 @@ impl Default for FileTransformer {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dry_run_reports_changes_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        tokio::fs::write(&path, "hello\n").await.unwrap();
+
+        let transformer = FileTransformer::with_pipeline(
+            TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase())),
+        );
+        let outcome = transformer.preview(&path, &path).await.unwrap();
+        assert!(outcome.changed);
+        assert!(!outcome.written);
+        assert!(outcome.unified_diff(3).unwrap().contains("-hello\n+HELLO\n"));
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "hello\n");
+
+        let outcome = transformer.transform_file(&path, &path, false).await.unwrap();
+        assert!(outcome.written);
+        let outcome = transformer.preview(&path, &path).await.unwrap();
+        assert!(!outcome.changed);
+    }
+
+    #[tokio::test]
+    async fn test_transform_file_on_in_memory_fs() {
+        use crate::file_processor::fs::InMemoryFs;
+
+        let fs = Arc::new(InMemoryFs::new());
+        fs.write(Path::new("/in.txt"), b"abc").await.unwrap();
+        let transformer = FileTransformer::with_pipeline(
+            TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase())),
+        )
+        .with_fs(fs.clone());
+
+        let outcome = transformer.transform_file("/in.txt", "/out.txt", false).await.unwrap();
+        assert!(outcome.written && outcome.before.is_none());
+        assert_eq!(fs.read(Path::new("/out.txt")).await.unwrap(), b"ABC");
+        assert!(transformer.transform_file("/missing.txt", "/out.txt", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transform_file_keeps_binary_content_intact() {
+        use crate::file_processor::fs::InMemoryFs;
+
+        let fs = Arc::new(InMemoryFs::new());
+        let image: Vec<u8> = (0..=255).collect();
+        fs.write(Path::new("/in.bin"), &image).await.unwrap();
+        let reverse = TransformPipeline::new().bytes_stage("reverse", |b| Ok(b.iter().rev().copied().collect()));
+        let transformer = FileTransformer::with_pipeline(reverse).with_fs(fs.clone());
+        transformer.transform_file("/in.bin", "/out.bin", false).await.unwrap();
+        assert_eq!(fs.read(Path::new("/out.bin")).await.unwrap(), image.iter().rev().copied().collect::<Vec<_>>());
+
+        let text = FileTransformer::with_pipeline(TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase())))
+            .with_fs(fs.clone());
+        assert!(text.transform_file("/in.bin", "/out.bin", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concat_reads_through_fs() {
+        use crate::file_processor::fs::InMemoryFs;
+
+        let fs = Arc::new(InMemoryFs::new());
+        fs.write(Path::new("/b.txt"), b"two\n").await.unwrap();
+        fs.write(Path::new("/a.txt"), b"one\n").await.unwrap();
+        fs.write(Path::new("/bin"), &[0xff, 0x00]).await.unwrap();
+        let transformer = FileTransformer::new().with_fs(fs);
+
+        let (bundle, entries) = transformer.concat(&["/b.txt", "/a.txt"], &ConcatTransform::new()).await.unwrap();
+        assert_eq!(bundle, "===== /a.txt (1 lines) =====\none\n\n===== /b.txt (1 lines) =====\ntwo\n");
+        assert_eq!(entries.len(), 2);
+        assert!(transformer.concat(&["/bin"], &ConcatTransform::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_split_writes_shards_and_cleans_up_on_failure() {
+        use crate::file_processor::fs::InMemoryFs;
+
+        let fs = Arc::new(InMemoryFs::new());
+        fs.write(Path::new("/in.txt"), b"1\n2\n3\n4\n5\n").await.unwrap();
+        let transformer = FileTransformer::new().with_fs(fs.clone());
+
+        let paths = transformer.split("/in.txt", "/out", &SplitTransform::by_lines(2)).await.unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(fs.read(Path::new("/out/in.0003.txt")).await.unwrap(), b"5\n");
+
+        // A directory in the way of the second shard makes that write fail.
+        fs.create_dir_all(Path::new("/fail/in.0002.txt")).await.unwrap();
+        assert!(transformer.split("/in.txt", "/fail", &SplitTransform::by_lines(2)).await.is_err());
+        assert!(fs.files().iter().all(|p| !p.starts_with("/fail")));
+        assert!(fs.exists(Path::new("/fail/in.0002.txt")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_transform_file_streaming() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, output) = (dir.path().join("in.log"), dir.path().join("out.log"));
+        let lines: String = (0..50_000).map(|i| format!("{} {}\n", if i % 2 == 0 { "INFO" } else { "DEBUG" }, i)).collect();
+        tokio::fs::write(&input, &lines).await.unwrap();
+
+        let transformer = FileTransformer::with_pipeline(
+            TransformPipeline::new().line_stage("info_only", |line| Ok(line.starts_with("INFO").then(|| line.replace("INFO", "I")))),
+        );
+        let written = transformer.transform_file_streaming(&input, &output).await.unwrap();
+        let result = tokio::fs::read_to_string(&output).await.unwrap();
+        assert_eq!(written, result.len() as u64);
+        assert_eq!(result.lines().count(), 25_000);
+        assert!(result.starts_with("I 0\nI 2\n"));
+
+        let buffered = FileTransformer::with_pipeline(TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase())));
+        assert!(buffered.transform_file_streaming(&input, &output).await.is_err());
+    }
+}
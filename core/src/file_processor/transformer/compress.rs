@@ -0,0 +1,261 @@
+// gzip and zstd compression stages
+use std::io::Write;
+use anyhow::{Context, Result};
+
+use super::encode::Direction;
+use super::streaming::{StreamFactory, StreamTransform};
+
+/// Compression formats, each behind the cargo feature of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// The level used unless one is set: 6 for gzip, 3 for zstd.
+    pub fn default_level(self) -> u32 {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => 6,
+            #[cfg(feature = "zstd")]
+            Self::Zstd => 3,
+        }
+    }
+
+    /// The highest level the format has: 9 for gzip, 22 for zstd.
+    pub fn max_level(self) -> u32 {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => 9,
+            #[cfg(feature = "zstd")]
+            Self::Zstd => 22,
+        }
+    }
+}
+
+/// Compresses or decompresses bytes, incrementally when run as part of a
+/// streaming pipeline. Decompressing accepts several concatenated members
+/// or frames, as `gzip -d` and `zstd -d` do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressTransform {
+    pub compression: Compression,
+    pub direction: Direction,
+    /// Compression level, clamped to the format's range; ignored when
+    /// decompressing.
+    pub level: Option<u32>,
+}
+
+impl CompressTransform {
+    pub fn new(compression: Compression, direction: Direction) -> Self {
+        Self { compression, direction, level: None }
+    }
+
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Stage name used when the transform is added to a pipeline.
+    pub fn name(&self) -> &'static str {
+        match (self.compression, self.direction) {
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, Direction::Encode) => "gzip_compress",
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, Direction::Decode) => "gzip_decompress",
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, Direction::Encode) => "zstd_compress",
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, Direction::Decode) => "zstd_decompress",
+        }
+    }
+
+    pub fn apply(&self, input: &[u8]) -> Result<Vec<u8>> {
+        let mut state = CompressState::new(*self)?;
+        let mut out = Vec::new();
+        state.process(input, &mut out)?;
+        state.finish(&mut out)?;
+        Ok(out)
+    }
+
+    pub(crate) fn stream_factory(self) -> StreamFactory {
+        Box::new(move || match CompressState::new(self) {
+            Ok(state) => Box::new(state),
+            // Only zstd can fail to set up, when it cannot allocate its context.
+            Err(e) => Box::new(Failed(Some(e))),
+        })
+    }
+
+    fn level(&self) -> u32 {
+        self.level.unwrap_or(self.compression.default_level()).min(self.compression.max_level())
+    }
+}
+
+/// A codec writing into a buffer that is drained after every chunk.
+enum CompressState {
+    #[cfg(feature = "gzip")]
+    GzipEncode(flate2::write::GzEncoder<Vec<u8>>),
+    #[cfg(feature = "gzip")]
+    GzipDecode(flate2::write::MultiGzDecoder<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    ZstdEncode(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    ZstdDecode(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl CompressState {
+    fn new(transform: CompressTransform) -> Result<Self> {
+        Ok(match (transform.compression, transform.direction) {
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, Direction::Encode) => {
+                Self::GzipEncode(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(transform.level())))
+            }
+            #[cfg(feature = "gzip")]
+            (Compression::Gzip, Direction::Decode) => Self::GzipDecode(flate2::write::MultiGzDecoder::new(Vec::new())),
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, Direction::Encode) => {
+                Self::ZstdEncode(zstd::stream::write::Encoder::new(Vec::new(), transform.level().max(1) as i32)?)
+            }
+            #[cfg(feature = "zstd")]
+            (Compression::Zstd, Direction::Decode) => Self::ZstdDecode(zstd::stream::write::Decoder::new(Vec::new())?),
+        })
+    }
+
+    fn format(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::GzipEncode(_) | Self::GzipDecode(_) => "gzip",
+            #[cfg(feature = "zstd")]
+            Self::ZstdEncode(_) | Self::ZstdDecode(_) => "zstd",
+        }
+    }
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::GzipEncode(codec) => codec.get_mut(),
+            #[cfg(feature = "gzip")]
+            Self::GzipDecode(codec) => codec.get_mut(),
+            #[cfg(feature = "zstd")]
+            Self::ZstdEncode(codec) => codec.get_mut(),
+            #[cfg(feature = "zstd")]
+            Self::ZstdDecode(codec) => codec.get_mut(),
+        }
+    }
+}
+
+impl StreamTransform for CompressState {
+    fn process(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let written = match self {
+            #[cfg(feature = "gzip")]
+            Self::GzipEncode(codec) => codec.write_all(chunk),
+            #[cfg(feature = "gzip")]
+            Self::GzipDecode(codec) => codec.write_all(chunk),
+            #[cfg(feature = "zstd")]
+            Self::ZstdEncode(codec) => codec.write_all(chunk),
+            #[cfg(feature = "zstd")]
+            Self::ZstdDecode(codec) => codec.write_all(chunk),
+        };
+        written.with_context(|| format!("invalid {} input", self.format()))?;
+        out.append(self.buffer());
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let finished = match self {
+            #[cfg(feature = "gzip")]
+            Self::GzipEncode(codec) => codec.try_finish(),
+            #[cfg(feature = "gzip")]
+            Self::GzipDecode(codec) => codec.try_finish(),
+            #[cfg(feature = "zstd")]
+            Self::ZstdEncode(codec) => codec.do_finish(),
+            #[cfg(feature = "zstd")]
+            Self::ZstdDecode(codec) => codec.flush(),
+        };
+        finished.with_context(|| format!("truncated or invalid {} input", self.format()))?;
+        out.append(self.buffer());
+        Ok(())
+    }
+}
+
+/// Reports a codec that could not be set up on first use.
+struct Failed(Option<anyhow::Error>);
+
+impl StreamTransform for Failed {
+    fn process(&mut self, _chunk: &[u8], _out: &mut Vec<u8>) -> Result<()> {
+        Err(self.0.take().unwrap_or_else(|| anyhow::anyhow!("compression stage failed to start")))
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        self.process(&[], out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::transformer::TransformPipeline;
+
+    fn formats() -> Vec<Compression> {
+        vec![
+            #[cfg(feature = "gzip")]
+            Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ]
+    }
+
+    #[test]
+    fn test_compress_then_decompress_is_identity() {
+        let input: Vec<u8> = (0..20_000u32).flat_map(|i| [(i % 251) as u8, b'\n', 0, 0xff]).collect();
+        for compression in formats() {
+            for level in [0, 1, compression.max_level() + 5] {
+                let compressed = CompressTransform::new(compression, Direction::Encode).with_level(level).apply(&input).unwrap();
+                // gzip level 0 only stores.
+                assert!(level == 0 || compressed.len() < input.len(), "{} level {}", compression.name(), level);
+                let decompressed = CompressTransform::new(compression, Direction::Decode).apply(&compressed).unwrap();
+                assert_eq!(decompressed, input);
+            }
+            let pipeline = TransformPipeline::new()
+                .compress(CompressTransform::new(compression, Direction::Encode))
+                .compress(CompressTransform::new(compression, Direction::Decode));
+            assert_eq!(pipeline.run(Vec::new()).unwrap(), b"");
+            assert_eq!(pipeline.run(input.clone()).unwrap(), input);
+
+            let err = CompressTransform::new(compression, Direction::Decode).apply(b"not compressed at all").unwrap_err();
+            assert!(err.to_string().contains(compression.name()), "{:#}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decompress_transform_recompress_streams() {
+        for compression in formats() {
+            let original = "one fish\ntwo fish\n".repeat(500);
+            let compressed = CompressTransform::new(compression, Direction::Encode).apply(original.as_bytes()).unwrap();
+            let pipeline = TransformPipeline::new()
+                .compress(CompressTransform::new(compression, Direction::Decode))
+                .line_stage("upper", |line| Ok(Some(line.to_uppercase())))
+                .compress(CompressTransform::new(compression, Direction::Encode));
+            assert!(pipeline.is_streamable());
+            assert_eq!(pipeline.stage_names()[0], format!("{}_decompress", compression.name()));
+
+            let chunks: Vec<std::io::Result<bytes::Bytes>> =
+                compressed.chunks(7).map(|chunk| Ok(bytes::Bytes::copy_from_slice(chunk))).collect();
+            let mut streamed = Vec::new();
+            pipeline.apply_streaming(futures::stream::iter(chunks), &mut streamed).await.unwrap();
+            let decompressed = CompressTransform::new(compression, Direction::Decode).apply(&streamed).unwrap();
+            assert_eq!(decompressed, original.to_uppercase().as_bytes());
+        }
+    }
+}
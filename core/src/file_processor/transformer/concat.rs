@@ -0,0 +1,126 @@
+// Multi-file concatenation with per-file headers
+use std::path::PathBuf;
+
+/// Default header; see `ConcatTransform::header`.
+pub const DEFAULT_CONCAT_HEADER: &str = "===== {path} ({lines} lines) =====";
+
+/// A file's contribution to a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcatEntry {
+    pub path: PathBuf,
+    pub lines: usize,
+    pub chars: usize,
+    /// Characters dropped by the per-file budget.
+    pub truncated_chars: usize,
+}
+
+/// Joins documents into one bundle, each preceded by a header line. Inputs
+/// are ordered by path so the same set of files always yields the same
+/// output.
+#[derive(Debug, Clone)]
+pub struct ConcatTransform {
+    /// Header template; `{path}`, `{lines}` and `{chars}` describe the
+    /// original file.
+    pub header: String,
+    /// Truncate each file's content to this many characters.
+    pub max_chars_per_file: Option<usize>,
+}
+
+impl ConcatTransform {
+    pub fn new() -> Self {
+        Self {
+            header: DEFAULT_CONCAT_HEADER.to_string(),
+            max_chars_per_file: None,
+        }
+    }
+
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        self.header = header.into();
+        self
+    }
+
+    pub fn with_max_chars_per_file(mut self, max_chars: usize) -> Self {
+        self.max_chars_per_file = Some(max_chars);
+        self
+    }
+
+    pub fn apply(&self, files: &[(PathBuf, String)]) -> (String, Vec<ConcatEntry>) {
+        let mut files: Vec<_> = files.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = String::new();
+        let mut entries = Vec::with_capacity(files.len());
+        for (path, content) in files {
+            let entry = ConcatEntry {
+                path: path.clone(),
+                lines: content.lines().count(),
+                chars: content.chars().count(),
+                truncated_chars: 0,
+            };
+            let header = self
+                .header
+                .replace("{path}", &path.display().to_string())
+                .replace("{lines}", &entry.lines.to_string())
+                .replace("{chars}", &entry.chars.to_string());
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&header);
+            out.push('\n');
+
+            let (body, truncated_chars) = match self.max_chars_per_file {
+                Some(max) if entry.chars > max => {
+                    let end = content.char_indices().nth(max).map_or(content.len(), |(i, _)| i);
+                    (&content[..end], entry.chars - max)
+                }
+                _ => (content.as_str(), 0),
+            };
+            out.push_str(body);
+            if truncated_chars > 0 {
+                if !body.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&format!("[... {} more characters truncated]\n", truncated_chars));
+            } else if !body.is_empty() && !body.ends_with('\n') {
+                out.push('\n');
+            }
+            entries.push(ConcatEntry { truncated_chars, ..entry });
+        }
+        (out, entries)
+    }
+}
+
+impl Default for ConcatTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_ordering_and_truncation() {
+        let files = vec![
+            (PathBuf::from("src/b.rs"), "fn b() {}\n".to_string()),
+            (PathBuf::from("src/a.rs"), "línea uno\nlínea dos".to_string()),
+        ];
+        let (out, entries) = ConcatTransform::new().apply(&files);
+        assert_eq!(
+            out,
+            "===== src/a.rs (2 lines) =====\nlínea uno\nlínea dos\n\n===== src/b.rs (1 lines) =====\nfn b() {}\n"
+        );
+        assert_eq!(entries[0].path, PathBuf::from("src/a.rs"));
+
+        let (out, entries) = ConcatTransform::new()
+            .with_header("## {path} [{chars}]")
+            .with_max_chars_per_file(7)
+            .apply(&files);
+        assert_eq!(
+            out,
+            "## src/a.rs [19]\nlínea u\n[... 12 more characters truncated]\n\n## src/b.rs [10]\nfn b() \n[... 3 more characters truncated]\n"
+        );
+        assert_eq!(entries[1].truncated_chars, 3);
+    }
+}
@@ -0,0 +1,305 @@
+// Programming or natural language detection
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// Files recognised by their whole name.
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("Makefile", "make"),
+    ("makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("Dockerfile", "dockerfile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Rakefile", "ruby"),
+    ("Gemfile", "ruby"),
+    ("Cargo.lock", "toml"),
+];
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("java", "java"),
+    ("go", "go"),
+    ("rb", "ruby"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("mk", "make"),
+    ("md", "markdown"),
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("sql", "sql"),
+];
+
+/// Shebang interpreters, matched by prefix so `python3.12` counts as python.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("bash", "shell"),
+    ("sh", "shell"),
+    ("zsh", "shell"),
+    ("dash", "shell"),
+    ("node", "javascript"),
+    ("deno", "typescript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("make", "make"),
+];
+
+/// Tokens typical of each language, with a weight per occurrence.
+const MARKERS: &[(&str, &[(&str, u32)])] = &[
+    (
+        "rust",
+        &[
+            ("fn ", 2), ("let mut ", 3), ("impl ", 3), ("pub fn ", 3), ("use std::", 4),
+            ("#[derive(", 4), ("&str", 2), ("::new(", 2), ("Result<", 2),
+        ],
+    ),
+    (
+        "python",
+        &[
+            ("def ", 3), ("import ", 1), ("self.", 2), ("elif ", 4), ("None", 1), ("__init__", 4),
+            ("print(", 2), ("from ", 1), (" in range(", 4),
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            ("function ", 2), ("const ", 1), ("=> ", 2), ("console.log(", 4), ("require(", 3),
+            ("===", 3), ("module.exports", 4), ("document.", 3),
+        ],
+    ),
+    (
+        "typescript",
+        &[
+            ("interface ", 2), (": string", 3), (": number", 3), (": boolean", 3),
+            ("export type ", 4), ("readonly ", 2),
+        ],
+    ),
+    (
+        "c",
+        &[
+            ("#include <", 4), ("int main(", 4), ("printf(", 3), ("NULL", 2), ("malloc(", 4),
+            ("struct ", 1),
+        ],
+    ),
+    (
+        "cpp",
+        &[
+            ("std::", 3), ("#include <iostream>", 5), ("template <", 4), ("namespace ", 2),
+            ("cout <<", 4),
+        ],
+    ),
+    (
+        "go",
+        &[
+            ("package ", 3), ("func ", 3), (" := ", 3), ("fmt.", 4), ("err != nil", 5),
+        ],
+    ),
+    (
+        "java",
+        &[
+            ("public class ", 5), ("System.out.", 5), ("private ", 1), ("import java.", 5),
+            ("public static void", 4),
+        ],
+    ),
+    (
+        "shell",
+        &[
+            ("echo ", 2), ("fi\n", 3), ("then\n", 3), ("esac", 4), ("$(", 2), ("${", 1),
+            ("done\n", 2),
+        ],
+    ),
+    (
+        "make",
+        &[
+            (".PHONY:", 5), ("$(MAKE)", 5), ("\n\t", 1), ("$@", 4), ("$<", 4),
+        ],
+    ),
+    (
+        "markdown",
+        &[
+            ("\n# ", 3), ("\n## ", 3), ("```", 3), ("](", 2), ("\n- ", 1),
+        ],
+    ),
+    (
+        "html",
+        &[
+            ("<div", 3), ("</", 1), ("<!DOCTYPE", 5), ("<html", 5), ("href=", 2),
+        ],
+    ),
+];
+
+/// Very common English words; a high share of them suggests prose.
+const STOPWORDS: &[&str] = &[
+    "the", "and", "of", "to", "a", "in", "is", "that", "it", "for", "was", "on", "are", "with", "as",
+    "this", "be", "at", "by", "not", "or", "have", "from", "but", "an", "they", "which", "you", "we",
+];
+
+/// Label used for prose.
+pub const NATURAL_LANGUAGE: &str = "text";
+/// Label used when nothing points anywhere, e.g. for empty input.
+pub const UNKNOWN_LANGUAGE: &str = "unknown";
+
+/// The detected language and how sure the detector is, from 0.0 to 1.0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageGuess {
+    pub lang: String,
+    pub confidence: f64,
+}
+
+impl LanguageGuess {
+    fn new(lang: &str, confidence: f64) -> Self {
+        Self { lang: lang.to_string(), confidence }
+    }
+}
+
+/// Guesses the language of a document from its file name, extension,
+/// shebang and, failing those, keyword frequencies in the content. Prose is
+/// reported as `text`.
+#[derive(Debug, Clone, Default)]
+pub struct DetectLanguageTransform {
+    /// Name of the file being classified, if known.
+    pub path: Option<PathBuf>,
+}
+
+impl DetectLanguageTransform {
+    pub fn new() -> Self {
+        Self { path: None }
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn detect(&self, content: &str) -> LanguageGuess {
+        if let Some(lang) = self.path.as_deref().and_then(from_path) {
+            return LanguageGuess::new(lang, 0.95);
+        }
+        if let Some(lang) = from_shebang(content) {
+            return LanguageGuess::new(lang, 0.95);
+        }
+        from_content(content)
+    }
+}
+
+fn from_path(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    if let Some((_, lang)) = FILE_NAMES.iter().find(|(file, _)| *file == name) {
+        return Some(lang);
+    }
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS.iter().find(|(e, _)| *e == ext).map(|(_, lang)| *lang)
+}
+
+fn from_shebang(content: &str) -> Option<&'static str> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip options such as `env -S`.
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    INTERPRETERS.iter().find(|(name, _)| program.starts_with(name)).map(|(_, lang)| *lang)
+}
+
+fn from_content(content: &str) -> LanguageGuess {
+    let trimmed = content.trim_start();
+    if trimmed.is_empty() {
+        return LanguageGuess::new(UNKNOWN_LANGUAGE, 0.0);
+    }
+    if (trimmed.starts_with('{') || trimmed.starts_with('[')) && serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+        return LanguageGuess::new("json", 0.9);
+    }
+
+    // Leading newline so markers anchored at a line start match line one.
+    let text = format!("\n{}", content);
+    let scores: Vec<(&str, u32)> = MARKERS
+        .iter()
+        .map(|(lang, markers)| (*lang, markers.iter().map(|(m, weight)| text.matches(m).count() as u32 * weight).sum()))
+        .collect();
+    let total: u32 = scores.iter().map(|(_, score)| score).sum();
+    let (best, best_score) = scores.iter().copied().max_by_key(|(_, score)| *score).expect("MARKERS is not empty");
+
+    let words: Vec<String> = content
+        .split(|c: char| !c.is_alphabetic() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let prose = if words.is_empty() {
+        0.0
+    } else {
+        words.iter().filter(|word| STOPWORDS.contains(&word.as_str())).count() as f64 / words.len() as f64
+    };
+    let lines = content.lines().count().max(1) as f64;
+
+    // Prose has many stopwords and few code markers per line.
+    if best_score == 0 || (prose > 0.2 && (best_score as f64) / lines < 1.0) {
+        return LanguageGuess::new(NATURAL_LANGUAGE, round((prose * 3.0).clamp(0.3, 0.9)));
+    }
+    // Confidence grows with how much of the evidence points one way and with
+    // how much evidence there is, but content alone never beats a file name.
+    let share = best_score as f64 / total as f64;
+    let amount = 1.0 - (-(best_score as f64) / 10.0).exp();
+    LanguageGuess::new(best, round((share * amount).clamp(0.1, 0.9)))
+}
+
+fn round(confidence: f64) -> f64 {
+    (confidence * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(path: Option<&str>, content: &str) -> LanguageGuess {
+        let mut transform = DetectLanguageTransform::new();
+        if let Some(path) = path {
+            transform = transform.with_path(path);
+        }
+        transform.detect(content)
+    }
+
+    #[test]
+    fn test_names_extensions_and_shebangs() {
+        assert_eq!(detect(Some("build/Makefile"), "all:\n\tcc main.c\n").lang, "make");
+        assert_eq!(detect(Some("src/lib.rs"), "").lang, "rust");
+        assert_eq!(detect(Some("bin/tool"), "#!/usr/bin/env python3\nprint('hi')\n"), LanguageGuess::new("python", 0.95));
+        assert_eq!(detect(None, "#!/bin/bash\necho hi\n").lang, "shell");
+        assert_eq!(detect(None, "#!/usr/bin/env -S deno run\n").lang, "typescript");
+    }
+
+    #[test]
+    fn test_content_heuristics() {
+        let rust = "use std::io;\n\n#[derive(Debug)]\npub struct A;\n\nimpl A {\n    pub fn new() -> Self { let mut x = 1; A }\n}\n";
+        let guess = detect(None, rust);
+        assert_eq!(guess.lang, "rust");
+        assert!(guess.confidence > 0.5 && guess.confidence <= 0.9);
+
+        let python = "import os\n\nclass A:\n    def __init__(self):\n        self.x = None\n\n    def run(self):\n        for i in range(3):\n            print(i)\n";
+        assert_eq!(detect(None, python).lang, "python");
+        assert_eq!(detect(None, "{\"a\": [1, 2]}").lang, "json");
+
+        let prose = "The quick brown fox jumps over the lazy dog. It is a sentence that is used to test fonts, and it has been for a long time.";
+        assert_eq!(detect(None, prose).lang, NATURAL_LANGUAGE);
+        assert_eq!(detect(Some("notes.txt"), prose).lang, NATURAL_LANGUAGE);
+        assert_eq!(detect(None, "  \n"), LanguageGuess::new(UNKNOWN_LANGUAGE, 0.0));
+    }
+}
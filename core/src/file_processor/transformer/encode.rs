@@ -0,0 +1,291 @@
+// Base64 and hex encoding stages
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::{DecodeError, Engine};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::streaming::{StreamFactory, StreamTransform};
+
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Base64 { url_safe: bool, padding: bool },
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Encode,
+    Decode,
+}
+
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    #[error("invalid {codec} input at byte offset {offset}: {message}")]
+    InvalidInput {
+        codec: &'static str,
+        offset: u64,
+        message: String,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Converts bytes to or from base64/hex. Decoding skips ASCII whitespace, so
+/// line-wrapped input is accepted, and errors report offsets into the raw input.
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeTransform {
+    pub codec: Codec,
+    pub direction: Direction,
+}
+
+impl EncodeTransform {
+    pub fn new(codec: Codec, direction: Direction) -> Self {
+        Self { codec, direction }
+    }
+
+    /// Stage name used when the transform is added to a pipeline.
+    pub fn name(&self) -> &'static str {
+        match (self.codec, self.direction) {
+            (Codec::Base64 { .. }, Direction::Encode) => "base64_encode",
+            (Codec::Base64 { .. }, Direction::Decode) => "base64_decode",
+            (Codec::Hex, Direction::Encode) => "hex_encode",
+            (Codec::Hex, Direction::Decode) => "hex_decode",
+        }
+    }
+
+    pub fn apply(&self, input: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        let mut state = CodecState::new(*self);
+        let mut out = Vec::with_capacity(input.len() * 2);
+        state.feed(input, &mut out)?;
+        state.finish(&mut out)?;
+        Ok(out)
+    }
+
+    pub(crate) fn stream_factory(self) -> StreamFactory {
+        Box::new(move || Box::new(CodecState::new(self)))
+    }
+
+    /// Streams `reader` through the codec into `writer` in fixed-size chunks.
+    /// Returns the number of bytes written.
+    pub async fn apply_stream<R, W>(&self, mut reader: R, mut writer: W) -> Result<u64, EncodeError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut state = CodecState::new(*self);
+        let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+        let mut out = Vec::with_capacity(STREAM_CHUNK_SIZE * 2);
+        let mut written = 0u64;
+
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            state.feed(&buf[..n], &mut out)?;
+            writer.write_all(&out).await?;
+            written += out.len() as u64;
+            out.clear();
+        }
+
+        state.finish(&mut out)?;
+        writer.write_all(&out).await?;
+        writer.flush().await?;
+        Ok(written + out.len() as u64)
+    }
+}
+
+impl StreamTransform for CodecState {
+    fn process(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> anyhow::Result<()> {
+        Ok(self.feed(chunk, out)?)
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        Ok(CodecState::finish(self, out)?)
+    }
+}
+
+/// Incremental codec state shared by the buffered and streaming paths.
+struct CodecState {
+    transform: EncodeTransform,
+    engine: GeneralPurpose,
+    pending: Vec<u8>,
+    /// Raw input offset of each pending byte (decode only).
+    origins: Vec<u64>,
+    offset: u64,
+}
+
+impl CodecState {
+    fn new(transform: EncodeTransform) -> Self {
+        let (url_safe, padding) = match transform.codec {
+            Codec::Base64 { url_safe, padding } => (url_safe, padding),
+            Codec::Hex => (false, true),
+        };
+        let alphabet = if url_safe { &alphabet::URL_SAFE } else { &alphabet::STANDARD };
+        let config = GeneralPurposeConfig::new()
+            .with_encode_padding(padding)
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        Self {
+            transform,
+            engine: GeneralPurpose::new(alphabet, config),
+            pending: Vec::new(),
+            origins: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<(), EncodeError> {
+        match self.transform.direction {
+            Direction::Encode => {
+                self.pending.extend_from_slice(chunk);
+                let ready = match self.transform.codec {
+                    Codec::Base64 { .. } => self.pending.len() / 3 * 3,
+                    Codec::Hex => self.pending.len(),
+                };
+                self.encode(ready, out);
+            }
+            Direction::Decode => {
+                for (i, &byte) in chunk.iter().enumerate() {
+                    if !byte.is_ascii_whitespace() {
+                        self.pending.push(byte);
+                        self.origins.push(self.offset + i as u64);
+                    }
+                }
+                let ready = match self.transform.codec {
+                    Codec::Base64 { .. } => self.pending.len() / 4 * 4,
+                    Codec::Hex => self.pending.len() / 2 * 2,
+                };
+                self.decode(ready, out)?;
+            }
+        }
+        self.offset += chunk.len() as u64;
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+        let remaining = self.pending.len();
+        match self.transform.direction {
+            Direction::Encode => self.encode(remaining, out),
+            Direction::Decode => {
+                if self.transform.codec == Codec::Hex && remaining % 2 == 1 {
+                    return Err(self.invalid(self.origins[remaining - 1], "odd number of hex digits"));
+                }
+                self.decode(remaining, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode(&mut self, len: usize, out: &mut Vec<u8>) {
+        let data = &self.pending[..len];
+        match self.transform.codec {
+            Codec::Base64 { .. } => out.extend_from_slice(self.engine.encode(data).as_bytes()),
+            Codec::Hex => {
+                for &byte in data {
+                    out.push(HEX_DIGITS[(byte >> 4) as usize]);
+                    out.push(HEX_DIGITS[(byte & 0x0f) as usize]);
+                }
+            }
+        }
+        self.pending.drain(..len);
+    }
+
+    fn decode(&mut self, len: usize, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+        if len == 0 {
+            return Ok(());
+        }
+        let data = &self.pending[..len];
+        match self.transform.codec {
+            Codec::Base64 { .. } => {
+                let decoded = self.engine.decode(data).map_err(|err| {
+                    let index = match err {
+                        DecodeError::InvalidByte(i, _) | DecodeError::InvalidLastSymbol(i, _) => i,
+                        _ => len - 1,
+                    };
+                    self.invalid(self.origins[index.min(len - 1)], &err.to_string())
+                })?;
+                out.extend_from_slice(&decoded);
+            }
+            Codec::Hex => {
+                for (pair, digits) in data.chunks(2).enumerate() {
+                    let high = hex_value(digits[0]);
+                    let low = hex_value(digits[1]);
+                    match (high, low) {
+                        (Some(h), Some(l)) => out.push(h << 4 | l),
+                        (None, _) => return Err(self.invalid(self.origins[pair * 2], "invalid hex digit")),
+                        (_, None) => return Err(self.invalid(self.origins[pair * 2 + 1], "invalid hex digit")),
+                    }
+                }
+            }
+        }
+        self.pending.drain(..len);
+        self.origins.drain(..len);
+        Ok(())
+    }
+
+    fn invalid(&self, offset: u64, message: &str) -> EncodeError {
+        EncodeError::InvalidInput {
+            codec: match self.transform.codec {
+                Codec::Base64 { .. } => "base64",
+                Codec::Hex => "hex",
+            },
+            offset,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|v| v as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE64: Codec = Codec::Base64 { url_safe: false, padding: true };
+
+    #[test]
+    fn test_known_vectors() {
+        let encode = EncodeTransform::new(BASE64, Direction::Encode);
+        assert_eq!(encode.apply(b"hello!?").unwrap(), b"aGVsbG8hPw==");
+        let hex = EncodeTransform::new(Codec::Hex, Direction::Encode);
+        assert_eq!(hex.apply(&[0x00, 0xff, 0x10]).unwrap(), b"00ff10");
+        let url = EncodeTransform::new(Codec::Base64 { url_safe: true, padding: false }, Direction::Encode);
+        assert_eq!(url.apply(&[0xfb, 0xff]).unwrap(), b"-_8");
+    }
+
+    #[test]
+    fn test_decode_skips_whitespace_and_reports_offset() {
+        let decode = EncodeTransform::new(BASE64, Direction::Decode);
+        assert_eq!(decode.apply(b"aGVs\nbG8h\r\nPw==\n").unwrap(), b"hello!?");
+
+        let err = decode.apply(b"aGVs\nb*8h").unwrap_err();
+        assert!(matches!(err, EncodeError::InvalidInput { offset: 6, .. }), "{err}");
+
+        let hex = EncodeTransform::new(Codec::Hex, Direction::Decode);
+        let err = hex.apply(b"00 fg").unwrap_err();
+        assert!(matches!(err, EncodeError::InvalidInput { offset: 4, .. }), "{err}");
+        assert!(hex.apply(b"abc").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_matches_buffered() {
+        let input: Vec<u8> = (0..(STREAM_CHUNK_SIZE * 3 + 7)).map(|i| (i * 31 % 251) as u8).collect();
+        for codec in [BASE64, Codec::Hex] {
+            let encode = EncodeTransform::new(codec, Direction::Encode);
+            let mut streamed = Vec::new();
+            encode.apply_stream(&input[..], &mut streamed).await.unwrap();
+            assert_eq!(streamed, encode.apply(&input).unwrap());
+
+            let decode = EncodeTransform::new(codec, Direction::Decode);
+            let mut round_trip = Vec::new();
+            decode.apply_stream(&streamed[..], &mut round_trip).await.unwrap();
+            assert_eq!(round_trip, input);
+        }
+    }
+}
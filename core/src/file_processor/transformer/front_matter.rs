@@ -0,0 +1,180 @@
+// Markdown front matter extraction and injection
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    /// Delimited by `---` lines.
+    Yaml,
+    /// Delimited by `+++` lines.
+    Toml,
+}
+
+impl FrontMatterFormat {
+    fn delimiter(self) -> &'static str {
+        match self {
+            Self::Yaml => "---",
+            Self::Toml => "+++",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter {
+    /// `None` when the document has no front matter.
+    pub format: Option<FrontMatterFormat>,
+    /// Parsed metadata; an empty object when there is none.
+    pub metadata: Value,
+    /// Everything after the closing delimiter, byte for byte.
+    pub body: String,
+}
+
+/// Splits and rewrites front matter. Front matter is only recognised when the
+/// very first line of the document is a delimiter, so `---` rules or code
+/// fences later in the body are never mistaken for it.
+pub struct FrontMatterTransform;
+
+impl FrontMatterTransform {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn extract(input: &str) -> Result<FrontMatter> {
+        let Some((format, raw, body)) = split(input) else {
+            return Ok(FrontMatter {
+                format: None,
+                metadata: Value::Object(Map::new()),
+                body: input.to_string(),
+            });
+        };
+
+        let metadata = match format {
+            _ if raw.trim().is_empty() => Value::Object(Map::new()),
+            FrontMatterFormat::Yaml => serde_yaml::from_str(raw).context("invalid YAML front matter")?,
+            FrontMatterFormat::Toml => toml::from_str(raw).context("invalid TOML front matter")?,
+        };
+        if !metadata.is_object() {
+            bail!("front matter must be a mapping of fields");
+        }
+        Ok(FrontMatter {
+            format: Some(format),
+            metadata,
+            body: body.to_string(),
+        })
+    }
+
+    /// Sets top-level `fields` in the document's front matter, creating a YAML
+    /// block if there is none. A `null` value removes the field. The body is
+    /// left untouched.
+    pub fn inject(input: &str, fields: &Map<String, Value>) -> Result<String> {
+        let FrontMatter { format, metadata, body } = Self::extract(input)?;
+        let format = format.unwrap_or(FrontMatterFormat::Yaml);
+        let mut metadata = match metadata {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        for (key, value) in fields {
+            if value.is_null() {
+                metadata.remove(key);
+            } else {
+                metadata.insert(key.clone(), value.clone());
+            }
+        }
+
+        let serialized = match format {
+            FrontMatterFormat::Yaml if metadata.is_empty() => String::new(),
+            FrontMatterFormat::Yaml => serde_yaml::to_string(&metadata).context("failed to serialize YAML")?,
+            FrontMatterFormat::Toml => toml::to_string(&metadata).context("failed to serialize TOML")?,
+        };
+        let eol = if input.contains("\r\n") { "\r\n" } else { "\n" };
+        let serialized = if eol == "\n" { serialized } else { serialized.replace('\n', eol) };
+        let delimiter = format.delimiter();
+        Ok(format!("{delimiter}{eol}{serialized}{delimiter}{eol}{body}"))
+    }
+}
+
+impl Default for FrontMatterTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns (format, raw front matter, body) if the document opens with a
+/// delimiter that is closed again; an opening `---` left unclosed is a
+/// Markdown thematic break, not front matter.
+fn split(input: &str) -> Option<(FrontMatterFormat, &str, &str)> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let first_end = input.find('\n').map_or(input.len(), |i| i + 1);
+    let format = match input[..first_end].trim_end() {
+        "---" => FrontMatterFormat::Yaml,
+        "+++" => FrontMatterFormat::Toml,
+        _ => return None,
+    };
+
+    let mut offset = first_end;
+    for line in input[first_end..].split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        let closes = trimmed == format.delimiter() || (format == FrontMatterFormat::Yaml && trimmed == "...");
+        if closes {
+            let raw = &input[first_end..offset];
+            let body = &input[offset + line.len()..];
+            return Some((format, raw, body));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_yaml_and_toml() {
+        let doc = "---\ntitle: Hello\ntags: [a, b]\n---\n# Body\n";
+        let fm = FrontMatterTransform::extract(doc).unwrap();
+        assert_eq!(fm.format, Some(FrontMatterFormat::Yaml));
+        assert_eq!(fm.metadata, json!({"title": "Hello", "tags": ["a", "b"]}));
+        assert_eq!(fm.body, "# Body\n");
+
+        let doc = "+++\r\ntitle = \"Hi\"\r\n+++\r\nbody\r\n";
+        let fm = FrontMatterTransform::extract(doc).unwrap();
+        assert_eq!(fm.format, Some(FrontMatterFormat::Toml));
+        assert_eq!(fm.metadata, json!({"title": "Hi"}));
+        assert_eq!(fm.body, "body\r\n");
+    }
+
+    #[test]
+    fn test_missing_front_matter_and_fenced_rules() {
+        let doc = "# Title\n\n```yaml\n---\nkey: value\n---\n```\n";
+        let fm = FrontMatterTransform::extract(doc).unwrap();
+        assert_eq!(fm.format, None);
+        assert_eq!(fm.metadata, json!({}));
+        assert_eq!(fm.body, doc);
+
+        // A leading thematic break that is never closed is just Markdown.
+        let doc = "---\n\nNotes after a rule.\n";
+        let fm = FrontMatterTransform::extract(doc).unwrap();
+        assert_eq!(fm.format, None);
+        assert_eq!(fm.body, doc);
+        let fields = json!({"title": "Notes"});
+        let out = FrontMatterTransform::inject(doc, fields.as_object().unwrap()).unwrap();
+        assert!(out.ends_with(doc), "{}", out);
+    }
+
+    #[test]
+    fn test_inject_updates_fields_without_touching_body() {
+        let body = "Text\n\n```\n---\n```\n";
+        let doc = format!("---\ntitle: Old\ndraft: true\n---\n{}", body);
+        let fields = json!({"title": "New", "tags": ["x"], "draft": null});
+        let out = FrontMatterTransform::inject(&doc, fields.as_object().unwrap()).unwrap();
+        let fm = FrontMatterTransform::extract(&out).unwrap();
+        assert_eq!(fm.metadata, json!({"title": "New", "tags": ["x"]}));
+        assert_eq!(fm.body, body);
+
+        let crlf = "plain\r\nnote\r\n";
+        let out = FrontMatterTransform::inject(crlf, json!({"title": "T"}).as_object().unwrap()).unwrap();
+        assert_eq!(out, "---\r\ntitle: T\r\n---\r\nplain\r\nnote\r\n");
+    }
+}
@@ -0,0 +1,266 @@
+// HTML to readable text conversion
+use scraper::{Html, Node};
+use scraper::node::Element;
+use ego_tree::iter::Edge;
+use ego_tree::NodeRef;
+
+/// Elements whose content never reaches the output.
+const SKIPPED: &[&str] = &["script", "style", "nav", "noscript", "template", "head", "iframe", "svg"];
+const BLOCKS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "footer", "aside", "blockquote", "figure",
+    "figcaption", "table", "tr", "form", "fieldset", "address", "dl", "dt", "dd", "hr",
+];
+
+/// Renders HTML as plain text for a model prompt: headings become `#`-prefixed
+/// lines, lists keep their bullets and nesting, entities are decoded and
+/// non-content elements are dropped. Parsing is html5ever's error-tolerant
+/// algorithm, so malformed input degrades rather than failing.
+pub struct HtmlToTextTransform {
+    /// Emit `[n]` markers after links and list their targets at the end.
+    pub link_footnotes: bool,
+}
+
+impl HtmlToTextTransform {
+    pub fn new() -> Self {
+        Self { link_footnotes: false }
+    }
+
+    pub fn with_link_footnotes(mut self, enabled: bool) -> Self {
+        self.link_footnotes = enabled;
+        self
+    }
+
+    pub fn apply(&self, html: &str) -> String {
+        let document = Html::parse_document(html);
+        let mut renderer = Renderer {
+            footnotes_enabled: self.link_footnotes,
+            ..Renderer::default()
+        };
+        renderer.walk(document.tree.root());
+        renderer.finish()
+    }
+}
+
+impl Default for HtmlToTextTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    footnotes_enabled: bool,
+    footnotes: Vec<String>,
+    /// Ordered-list counters for each open list (`None` for bullet lists).
+    lists: Vec<Option<usize>>,
+    pre_depth: usize,
+    pending_space: bool,
+    /// No separating space is needed before the next word.
+    line_start: bool,
+}
+
+impl Renderer {
+    /// Renders the tree below `root` in document order. Iterative, as a
+    /// page may nest elements far deeper than the stack would allow.
+    fn walk(&mut self, root: NodeRef<'_, Node>) {
+        // Elements open inside one whose content is dropped.
+        let mut skipped = 0usize;
+        for edge in root.traverse() {
+            match edge {
+                Edge::Open(node) => match node.value() {
+                    Node::Element(element) if skipped > 0 || SKIPPED.contains(&element.name()) => skipped += 1,
+                    Node::Element(element) => self.open(element),
+                    Node::Text(text) if skipped == 0 => self.text(text),
+                    _ => {}
+                },
+                Edge::Close(node) => match node.value() {
+                    Node::Element(_) if skipped > 0 => skipped -= 1,
+                    Node::Element(element) => self.close(element),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn open(&mut self, element: &Element) {
+        let name = element.name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                self.block_break();
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+                self.line_start = true;
+            }
+            "ul" | "ol" => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+                self.lists.push((name == "ol").then_some(0));
+            }
+            "li" => {
+                self.line_break();
+                let depth = self.lists.len().max(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(counter)) => {
+                        *counter += 1;
+                        format!("{}. ", counter)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.out.push_str(&"  ".repeat(depth - 1));
+                self.out.push_str(&marker);
+                self.line_start = true;
+            }
+            "pre" => {
+                self.block_break();
+                self.pre_depth += 1;
+            }
+            "br" => self.line_break(),
+            "td" | "th" => self.pending_space = true,
+            "img" => {
+                if let Some(alt) = element.attr("alt").filter(|alt| !alt.trim().is_empty()) {
+                    self.text(&format!("[image: {}]", alt.trim()));
+                }
+            }
+            _ if BLOCKS.contains(&name) => self.block_break(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, element: &Element) {
+        let name = element.name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.block_break(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else {
+                    self.line_break();
+                }
+            }
+            "li" => self.line_break(),
+            "pre" => {
+                self.pre_depth -= 1;
+                self.block_break();
+            }
+            "td" | "th" => self.pending_space = true,
+            "a" => {
+                let href = element.attr("href").unwrap_or("").trim();
+                if self.footnotes_enabled && !href.is_empty() && !href.starts_with('#') && !href.starts_with("javascript:") {
+                    self.footnotes.push(href.to_string());
+                    self.out.push_str(&format!("[{}]", self.footnotes.len()));
+                    self.line_start = false;
+                }
+            }
+            _ if BLOCKS.contains(&name) => self.block_break(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre_depth > 0 {
+            self.out.push_str(text);
+            self.line_start = text.ends_with('\n');
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if self.pending_space && !self.line_start {
+                self.out.push(' ');
+            }
+            self.pending_space = false;
+            self.line_start = false;
+            self.out.push(c);
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.pending_space = false;
+        self.line_start = true;
+    }
+
+    fn block_break(&mut self) {
+        self.line_break();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+    }
+
+    fn finish(mut self) -> String {
+        let mut text = std::mem::take(&mut self.out).trim().to_string();
+        if !self.footnotes.is_empty() {
+            text.push_str("\n\n");
+            for (i, href) in self.footnotes.iter().enumerate() {
+                text.push_str(&format!("[{}]: {}\n", i + 1, href));
+            }
+        }
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_structure_and_entities() {
+        let html = "<html><head><title>x</title><style>p{}</style></head><body>\
+            <nav><a href=\"/\">Home</a></nav><h1>Caf&eacute; &amp; Bar</h1>\
+            <p>Hello <b>world</b>&nbsp;!</p><script>alert(1)</script>\
+            <ol><li>one<ul><li>nested</li></ul></li><li>two</li></ol></body></html>";
+        let text = HtmlToTextTransform::new().apply(html);
+        assert_eq!(text, "# Café & Bar\n\nHello world !\n\n1. one\n  - nested\n2. two\n");
+    }
+
+    #[test]
+    fn test_link_footnotes() {
+        let html = "<p>See <a href=\"https://example.com/a\">the docs</a> and <a href=\"#top\">top</a>.</p>";
+        let text = HtmlToTextTransform::new().with_link_footnotes(true).apply(html);
+        assert_eq!(text, "See the docs[1] and top.\n\n[1]: https://example.com/a\n");
+    }
+
+    #[test]
+    fn test_malformed_html_does_not_panic() {
+        for html in ["<div><p>unclosed <b>bold", "</li></ul><<>>&bogus;<h2", "", "<pre>  keep\n  this</pre"] {
+            let _ = HtmlToTextTransform::new().apply(html);
+        }
+    }
+
+    #[test]
+    fn test_deeply_nested_html_does_not_overflow() {
+        // Ten thousand elements deep, past what a recursive walk survives.
+        let depth = 5_000;
+        let html = format!("{}deep{}", "<div><span>".repeat(depth), "</span></div>".repeat(depth));
+        assert_eq!(HtmlToTextTransform::new().apply(&html), "deep\n");
+        let html = format!("<p>kept</p>{}<script>dropped</script>", "<nav><div>".repeat(3));
+        assert_eq!(HtmlToTextTransform::new().apply(&html), "kept\n");
+    }
+
+    #[test]
+    fn test_real_world_fixture_matches_golden() {
+        let html = include_str!("../../../tests/fixtures/html/article.html");
+        let golden = include_str!("../../../tests/fixtures/html/article.txt");
+        let text = HtmlToTextTransform::new().with_link_footnotes(true).apply(html);
+        assert_eq!(text, golden);
+    }
+}
@@ -0,0 +1,111 @@
+// JSON pretty-printing and minifying
+use serde::Serialize;
+use serde_json::ser::{CompactFormatter, PrettyFormatter, Serializer};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum JsonFormatError {
+    #[error("invalid JSON at line {line}, column {column}: {message}")]
+    InvalidJson { line: usize, column: usize, message: String },
+}
+
+impl From<serde_json::Error> for JsonFormatError {
+    fn from(error: serde_json::Error) -> Self {
+        // serde_json appends the location to its message; report it once.
+        let message = error.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(at) => message[..at].to_string(),
+            None => message,
+        };
+        Self::InvalidJson { line: error.line(), column: error.column(), message }
+    }
+}
+
+/// Re-emits JSON either indented or with all insignificant whitespace
+/// removed. Object keys keep their input order unless `sort_keys` is set.
+/// Input may hold several documents (NDJSON); each is formatted in turn and
+/// ends with a newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonFormatTransform {
+    /// Spaces per nesting level, or `None` to minify.
+    pub indent: Option<usize>,
+    /// Order the keys of every object lexicographically.
+    pub sort_keys: bool,
+}
+
+impl JsonFormatTransform {
+    pub fn pretty(indent: usize) -> Self {
+        Self { indent: Some(indent), sort_keys: false }
+    }
+
+    pub fn minify() -> Self {
+        Self { indent: None, sort_keys: false }
+    }
+
+    pub fn with_sort_keys(mut self, enabled: bool) -> Self {
+        self.sort_keys = enabled;
+        self
+    }
+
+    /// `json_pretty` or `json_minify`.
+    pub fn name(&self) -> &'static str {
+        match self.indent {
+            Some(_) => "json_pretty",
+            None => "json_minify",
+        }
+    }
+
+    pub fn apply(&self, input: &str) -> Result<String, JsonFormatError> {
+        let indent = " ".repeat(self.indent.unwrap_or(0));
+        let mut out = Vec::with_capacity(input.len());
+        for document in serde_json::Deserializer::from_str(input).into_iter::<Value>() {
+            let mut document = document?;
+            if self.sort_keys {
+                document.sort_all_objects();
+            }
+            // Writing a `Value` into a Vec cannot fail.
+            match self.indent {
+                Some(_) => {
+                    let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                    document.serialize(&mut Serializer::with_formatter(&mut out, formatter))
+                }
+                None => document.serialize(&mut Serializer::with_formatter(&mut out, CompactFormatter)),
+            }
+            .expect("serializing a JSON value");
+            out.push(b'\n');
+        }
+        Ok(String::from_utf8(out).expect("serde_json writes UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_and_minify_keep_key_order() {
+        let input = r#"{"b": 1, "a": [true, null, {"z": "x", "y": 2.5}]}"#;
+        assert_eq!(
+            JsonFormatTransform::pretty(2).apply(input).unwrap(),
+            "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null,\n    {\n      \"z\": \"x\",\n      \"y\": 2.5\n    }\n  ]\n}\n"
+        );
+        assert_eq!(JsonFormatTransform::pretty(4).apply("[1]").unwrap(), "[\n    1\n]\n");
+        assert_eq!(JsonFormatTransform::minify().apply(input).unwrap(), "{\"b\":1,\"a\":[true,null,{\"z\":\"x\",\"y\":2.5}]}\n");
+        assert_eq!(
+            JsonFormatTransform::minify().with_sort_keys(true).apply(input).unwrap(),
+            "{\"a\":[true,null,{\"y\":2.5,\"z\":\"x\"}],\"b\":1}\n"
+        );
+        // Each document of a stream is formatted on its own.
+        assert_eq!(JsonFormatTransform::minify().apply("{ \"a\" : 1 }\n[ 2 ]\n").unwrap(), "{\"a\":1}\n[2]\n");
+    }
+
+    #[test]
+    fn test_invalid_json_reports_location() {
+        let err = JsonFormatTransform::pretty(2).apply("{\n  \"a\": 1,\n  \"b\": }").unwrap_err();
+        let JsonFormatError::InvalidJson { line, column, message } = &err;
+        assert_eq!((*line, *column), (3, 8));
+        assert_eq!(message, "expected value");
+        assert_eq!(err.to_string(), "invalid JSON at line 3, column 8: expected value");
+    }
+}
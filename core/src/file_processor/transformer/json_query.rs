@@ -0,0 +1,247 @@
+// JSON path/query extraction
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JsonQueryError {
+    #[error("query parse error at position {position}: {message}")]
+    Parse { position: usize, message: String },
+    #[error("invalid JSON input: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("query matched nothing")]
+    NoMatches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Wildcard,
+    /// Applies the inner step to the current value and all of its descendants.
+    Recursive(Box<Step>),
+}
+
+/// Evaluates a practical subset of jq/JSONPath: `.field`, `["field"]`, `[n]`
+/// (negative counts from the end), `[*]` / `.*` wildcards and `..field`
+/// recursive descent. A leading `$` is accepted. Matches are emitted as NDJSON.
+/// Input may be a single document or a stream of documents (NDJSON), in which
+/// case the query runs against each one.
+#[derive(Debug, Clone)]
+pub struct JsonQueryTransform {
+    steps: Vec<Step>,
+    /// Treat an empty result as `JsonQueryError::NoMatches`.
+    pub error_on_empty: bool,
+}
+
+impl JsonQueryTransform {
+    pub fn new(query: &str) -> Result<Self, JsonQueryError> {
+        Ok(Self {
+            steps: parse_query(query)?,
+            error_on_empty: false,
+        })
+    }
+
+    pub fn error_on_empty(mut self, enabled: bool) -> Self {
+        self.error_on_empty = enabled;
+        self
+    }
+
+    /// Returns every match across all documents in `input`.
+    pub fn query(&self, input: &str) -> Result<Vec<Value>, JsonQueryError> {
+        let mut matches = Vec::new();
+        for document in serde_json::Deserializer::from_str(input).into_iter::<Value>() {
+            let document = document?;
+            matches.extend(self.evaluate(&document).into_iter().cloned());
+        }
+        if matches.is_empty() && self.error_on_empty {
+            return Err(JsonQueryError::NoMatches);
+        }
+        Ok(matches)
+    }
+
+    /// Runs the query and renders the matches as NDJSON.
+    pub fn apply(&self, input: &str) -> Result<String, JsonQueryError> {
+        let mut out = String::new();
+        for value in self.query(input)? {
+            out.push_str(&value.to_string());
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    pub fn evaluate<'a>(&self, document: &'a Value) -> Vec<&'a Value> {
+        self.steps.iter().fold(vec![document], |current, step| {
+            current.into_iter().flat_map(|value| apply_step(step, value)).collect()
+        })
+    }
+}
+
+fn apply_step<'a>(step: &Step, value: &'a Value) -> Vec<&'a Value> {
+    match step {
+        Step::Field(name) => value.get(name).into_iter().collect(),
+        Step::Index(index) => match value {
+            Value::Array(items) => {
+                let resolved = if *index < 0 { items.len() as i64 + index } else { *index };
+                usize::try_from(resolved).ok().and_then(|i| items.get(i)).into_iter().collect()
+            }
+            _ => Vec::new(),
+        },
+        Step::Wildcard => match value {
+            Value::Array(items) => items.iter().collect(),
+            Value::Object(map) => map.values().collect(),
+            _ => Vec::new(),
+        },
+        Step::Recursive(inner) => {
+            let mut descendants = Vec::new();
+            collect_descendants(value, &mut descendants);
+            descendants.into_iter().flat_map(|v| apply_step(inner, v)).collect()
+        }
+    }
+}
+
+fn collect_descendants<'a>(value: &'a Value, out: &mut Vec<&'a Value>) {
+    out.push(value);
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| collect_descendants(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_descendants(v, out)),
+        _ => {}
+    }
+}
+
+fn parse_query(query: &str) -> Result<Vec<Step>, JsonQueryError> {
+    let chars: Vec<char> = query.trim().chars().collect();
+    let mut pos = 0;
+    let mut steps = Vec::new();
+    let error = |position: usize, message: &str| JsonQueryError::Parse {
+        position,
+        message: message.to_string(),
+    };
+
+    if chars.first() == Some(&'$') {
+        pos += 1;
+    }
+    while pos < chars.len() {
+        match chars[pos] {
+            '.' if chars.get(pos + 1) == Some(&'.') => {
+                pos += 2;
+                let step = match chars.get(pos) {
+                    Some('[') => parse_bracket(&chars, &mut pos)?,
+                    Some('*') => {
+                        pos += 1;
+                        Step::Wildcard
+                    }
+                    Some(c) if is_ident(*c) => Step::Field(parse_ident(&chars, &mut pos)),
+                    _ => return Err(error(pos, "expected a field name, `*` or `[` after `..`")),
+                };
+                steps.push(Step::Recursive(Box::new(step)));
+            }
+            '.' => {
+                pos += 1;
+                match chars.get(pos) {
+                    None => {}
+                    Some('*') => {
+                        pos += 1;
+                        steps.push(Step::Wildcard);
+                    }
+                    Some('[') => steps.push(parse_bracket(&chars, &mut pos)?),
+                    Some(c) if is_ident(*c) => steps.push(Step::Field(parse_ident(&chars, &mut pos))),
+                    Some(_) => return Err(error(pos, "expected a field name after `.`")),
+                }
+            }
+            '[' => steps.push(parse_bracket(&chars, &mut pos)?),
+            _ => return Err(error(pos, "expected `.` or `[`")),
+        }
+    }
+    Ok(steps)
+}
+
+fn parse_bracket(chars: &[char], pos: &mut usize) -> Result<Step, JsonQueryError> {
+    let start = *pos;
+    let close = chars[start..]
+        .iter()
+        .position(|&c| c == ']')
+        .map(|offset| start + offset)
+        .ok_or_else(|| JsonQueryError::Parse {
+            position: start,
+            message: "unclosed `[`".to_string(),
+        })?;
+    let inner: String = chars[start + 1..close].iter().collect();
+    let inner = inner.trim();
+    *pos = close + 1;
+
+    if inner == "*" {
+        return Ok(Step::Wildcard);
+    }
+    if let Some(name) = inner
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+    {
+        return Ok(Step::Field(name.to_string()));
+    }
+    inner.parse::<i64>().map(Step::Index).map_err(|_| JsonQueryError::Parse {
+        position: start + 1,
+        message: format!("invalid index {:?}", inner),
+    })
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn parse_ident(chars: &[char], pos: &mut usize) -> String {
+    let start = *pos;
+    while *pos < chars.len() && is_ident(chars[*pos]) {
+        *pos += 1;
+    }
+    chars[start..*pos].iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DOC: &str = r#"{"items": [{"name": "a", "tags": {"name": "inner"}}, {"name": "b"}], "count": 2}"#;
+
+    fn run(query: &str) -> String {
+        JsonQueryTransform::new(query).unwrap().apply(DOC).unwrap()
+    }
+
+    #[test]
+    fn test_field_index_and_wildcard() {
+        assert_eq!(run(".count"), "2\n");
+        assert_eq!(run(".items[*].name"), "\"a\"\n\"b\"\n");
+        assert_eq!(run("$.items[-1]"), "{\"name\":\"b\"}\n");
+        assert_eq!(run(".items[0][\"name\"]"), "\"a\"\n");
+        assert_eq!(run("."), format!("{}\n", serde_json::from_str::<Value>(DOC).unwrap()));
+    }
+
+    #[test]
+    fn test_recursive_descent() {
+        assert_eq!(run("..name"), "\"a\"\n\"inner\"\n\"b\"\n");
+        let values = JsonQueryTransform::new("$..tags.name").unwrap().query(DOC).unwrap();
+        assert_eq!(values, vec![json!("inner")]);
+    }
+
+    #[test]
+    fn test_ndjson_input_runs_per_line() {
+        let input = "{\"id\": 1}\n{\"id\": 2}\n{\"other\": 3}\n";
+        let out = JsonQueryTransform::new(".id").unwrap().apply(input).unwrap();
+        assert_eq!(out, "1\n2\n");
+    }
+
+    #[test]
+    fn test_parse_errors_differ_from_no_matches() {
+        assert!(matches!(
+            JsonQueryTransform::new(".items[abc]"),
+            Err(JsonQueryError::Parse { position: 7, .. })
+        ));
+        assert!(matches!(JsonQueryTransform::new("items"), Err(JsonQueryError::Parse { position: 0, .. })));
+
+        let query = JsonQueryTransform::new(".missing").unwrap();
+        assert_eq!(query.apply(DOC).unwrap(), "");
+        let strict = query.error_on_empty(true);
+        assert!(matches!(strict.apply(DOC), Err(JsonQueryError::NoMatches)));
+    }
+}
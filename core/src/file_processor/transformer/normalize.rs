@@ -0,0 +1,187 @@
+// Whitespace and trailing-newline normalization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentConversion {
+    Keep,
+    /// Expand leading tabs to the given number of spaces.
+    TabsToSpaces(usize),
+    /// Collapse each run of the given number of leading spaces into a tab.
+    SpacesToTabs(usize),
+}
+
+/// Cleans up whitespace line by line. Indentation conversion only touches
+/// leading whitespace so string literals and aligned comments are left alone.
+/// Line terminators (LF or CRLF) are preserved.
+#[derive(Debug, Clone)]
+pub struct NormalizeTransform {
+    pub trim_trailing_whitespace: bool,
+    /// Collapse runs of blank lines longer than this.
+    pub max_blank_lines: Option<usize>,
+    /// Strip trailing blank lines and make sure the file ends with exactly one newline.
+    pub final_newline: bool,
+    pub indent: IndentConversion,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeReport {
+    /// Lines whose content was rewritten.
+    pub lines_modified: usize,
+    /// Blank lines dropped by blank-run collapsing or end-of-file cleanup.
+    pub lines_removed: usize,
+    /// A missing final newline was added.
+    pub final_newline_added: bool,
+}
+
+impl NormalizeReport {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl NormalizeTransform {
+    pub fn new() -> Self {
+        Self {
+            trim_trailing_whitespace: true,
+            max_blank_lines: None,
+            final_newline: true,
+            indent: IndentConversion::Keep,
+        }
+    }
+
+    pub fn apply(&self, input: &str) -> (String, NormalizeReport) {
+        let mut report = NormalizeReport::default();
+        let mut lines: Vec<(String, &str)> = Vec::new();
+        let mut blank_run = 0;
+
+        for raw in input.split_inclusive('\n') {
+            let (content, eol) = split_terminator(raw);
+            let mut line = content.to_string();
+
+            if self.trim_trailing_whitespace {
+                line.truncate(line.trim_end_matches([' ', '\t']).len());
+            }
+            line = self.convert_indent(line);
+            if line != content {
+                report.lines_modified += 1;
+            }
+
+            if line.trim().is_empty() {
+                blank_run += 1;
+                if self.max_blank_lines.is_some_and(|max| blank_run > max) {
+                    report.lines_removed += 1;
+                    continue;
+                }
+            } else {
+                blank_run = 0;
+            }
+            lines.push((line, eol));
+        }
+
+        if self.final_newline {
+            while lines.last().is_some_and(|(line, _)| line.trim().is_empty()) {
+                lines.pop();
+                report.lines_removed += 1;
+            }
+            if let Some((_, eol)) = lines.last_mut() {
+                if eol.is_empty() {
+                    *eol = if input.contains("\r\n") { "\r\n" } else { "\n" };
+                    report.final_newline_added = true;
+                }
+            }
+        }
+
+        let output = lines.iter().fold(String::with_capacity(input.len()), |mut out, (line, eol)| {
+            out.push_str(line);
+            out.push_str(eol);
+            out
+        });
+        (output, report)
+    }
+
+    fn convert_indent(&self, line: String) -> String {
+        let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let (indent, rest) = line.split_at(indent_len);
+        let converted = match self.indent {
+            IndentConversion::Keep => return line,
+            IndentConversion::TabsToSpaces(width) => {
+                let mut column = 0;
+                let mut out = String::new();
+                for c in indent.chars() {
+                    if c == '\t' {
+                        let pad = width - column % width.max(1);
+                        out.extend(std::iter::repeat_n(' ', pad));
+                        column += pad;
+                    } else {
+                        out.push(c);
+                        column += 1;
+                    }
+                }
+                out
+            }
+            IndentConversion::SpacesToTabs(width) => {
+                let spaces = " ".repeat(width.max(1));
+                indent.replace(&spaces, "\t")
+            }
+        };
+        format!("{}{}", converted, rest)
+    }
+}
+
+impl Default for NormalizeTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn split_terminator(raw: &str) -> (&str, &str) {
+    if let Some(content) = raw.strip_suffix("\r\n") {
+        (content, "\r\n")
+    } else if let Some(content) = raw.strip_suffix('\n') {
+        (content, "\n")
+    } else {
+        (raw, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_input_is_byte_identical() {
+        let clean = "fn main() {\n    println!(\"hi\");\n}\n\n// end\n";
+        let (out, report) = NormalizeTransform::new().apply(clean);
+        assert_eq!(out, clean);
+        assert!(report.is_clean());
+
+        let crlf = clean.replace('\n', "\r\n");
+        let (out, report) = NormalizeTransform::new().apply(&crlf);
+        assert_eq!(out, crlf);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_normalizes_whitespace() {
+        let transform = NormalizeTransform {
+            max_blank_lines: Some(1),
+            indent: IndentConversion::TabsToSpaces(4),
+            ..NormalizeTransform::new()
+        };
+        let (out, report) = transform.apply("a  \n\n\n\n\tb\t\n  \tc\n\n\n");
+        assert_eq!(out, "a\n\n    b\n    c\n");
+        assert_eq!(report.lines_modified, 3);
+        assert_eq!(report.lines_removed, 4);
+        assert!(!report.final_newline_added);
+    }
+
+    #[test]
+    fn test_adds_final_newline_and_spaces_to_tabs() {
+        let transform = NormalizeTransform {
+            indent: IndentConversion::SpacesToTabs(2),
+            ..NormalizeTransform::new()
+        };
+        let (out, report) = transform.apply("x\r\n    y = \"  \"");
+        assert_eq!(out, "x\r\n\t\ty = \"  \"\r\n");
+        assert!(report.final_newline_added);
+        assert_eq!(report.lines_modified, 1);
+    }
+}
@@ -0,0 +1,617 @@
+// Unified diff patch application
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+use crate::error::CoreError;
+use crate::safe_mode;
+
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error("malformed patch at line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("hunk #{hunk} failed to apply at line {line}: expected {expected:?}, found {found:?}")]
+    HunkMismatch {
+        hunk: usize,
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("patch path {0:?} escapes the target directory")]
+    UnsafePath(String),
+    #[error("cannot create {0}: file already exists")]
+    AlreadyExists(PathBuf),
+    #[error("cannot delete {0}: patch leaves content behind")]
+    NotEmptyAfterDelete(PathBuf),
+    /// `safe_mode` is on; nothing was written.
+    #[error(transparent)]
+    SafeMode(#[from] CoreError),
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Context,
+    Remove,
+    Add,
+}
+
+#[derive(Debug, Clone)]
+struct HunkLine {
+    kind: LineKind,
+    text: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Hunk {
+    old_start: usize,
+    lines: Vec<HunkLine>,
+    old_no_eol: bool,
+    new_no_eol: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    /// Git's `rename from`/`rename to` headers named the paths.
+    rename: bool,
+    hunks: Vec<Hunk>,
+}
+
+/// Applies unified diffs, tolerating up to `fuzz` lines of mismatched
+/// context at either end of a hunk (the same meaning as GNU patch's `-F`).
+pub struct PatchTransform {
+    fuzz: usize,
+}
+
+impl PatchTransform {
+    pub fn new() -> Self {
+        Self { fuzz: 0 }
+    }
+
+    pub fn with_fuzz(fuzz: usize) -> Self {
+        Self { fuzz }
+    }
+
+    /// Applies every hunk in `patch` to `original`. File headers are optional;
+    /// if the patch spans several files only the first one is applied.
+    pub fn apply(&self, original: &str, patch: &str) -> Result<String, PatchError> {
+        let files = parse_patch(patch)?;
+        match files.first() {
+            Some(file) => self.apply_file(original, file),
+            None => Ok(original.to_string()),
+        }
+    }
+
+    /// Applies a multi-file patch beneath `root`. Every file is patched in memory
+    /// first so a failing hunk leaves the tree untouched, and sections for the
+    /// same file apply in order. A file is only moved when git `rename from` and
+    /// `rename to` headers say so; otherwise, as with `patch(1)`, differing `---`
+    /// and `+++` paths (`foo.orig` and `foo`) patch whichever exists, the `+++`
+    /// one if both do. Returns the paths written or removed.
+    pub async fn apply_to_dir<P: AsRef<Path>>(&self, root: P, patch: &str) -> Result<Vec<PathBuf>, PatchError> {
+        let root = root.as_ref();
+        let mut planned = Plan::default();
+
+        for file in parse_patch(patch)? {
+            match (&file.old_path, &file.new_path) {
+                (None, Some(new)) => {
+                    let path = root.join(safe_relative_path(new)?);
+                    if planned.content(&path).await?.is_some() {
+                        return Err(PatchError::AlreadyExists(path));
+                    }
+                    let content = self.apply_file("", &file)?;
+                    planned.set(path, Some(content));
+                }
+                (Some(old), None) => {
+                    let path = root.join(safe_relative_path(old)?);
+                    let original = planned.existing(&path).await?;
+                    if !self.apply_file(&original, &file)?.is_empty() {
+                        return Err(PatchError::NotEmptyAfterDelete(path));
+                    }
+                    planned.set(path, None);
+                }
+                (Some(old), Some(new)) if file.rename && old != new => {
+                    let (source, path) = (root.join(safe_relative_path(old)?), root.join(safe_relative_path(new)?));
+                    let original = planned.existing(&source).await?;
+                    if planned.content(&path).await?.is_some() {
+                        return Err(PatchError::AlreadyExists(path));
+                    }
+                    let content = self.apply_file(&original, &file)?;
+                    planned.set(path, Some(content));
+                    planned.set(source, None);
+                }
+                (Some(old), Some(new)) => {
+                    let path = root.join(safe_relative_path(new)?);
+                    let (path, original) = match planned.content(&path).await? {
+                        Some(original) => (path, original),
+                        None if old != new => {
+                            let source = root.join(safe_relative_path(old)?);
+                            match planned.content(&source).await? {
+                                Some(original) => (source, original),
+                                None => (path.clone(), planned.existing(&path).await?),
+                            }
+                        }
+                        None => (path.clone(), planned.existing(&path).await?),
+                    };
+                    let content = self.apply_file(&original, &file)?;
+                    planned.set(path, Some(content));
+                }
+                (None, None) => {
+                    return Err(PatchError::Parse {
+                        line: 0,
+                        message: "directory patches need ---/+++ file headers".to_string(),
+                    })
+                }
+            }
+        }
+
+        for (path, _) in &planned.files {
+            safe_mode::check(|| format!("writing {}", path.display()))?;
+        }
+        let mut touched = Vec::with_capacity(planned.files.len());
+        for (path, content) in planned.files {
+            let io_err = |source| PatchError::Io { path: path.clone(), source };
+            match content {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await.map_err(io_err)?;
+                    }
+                    tokio::fs::write(&path, content).await.map_err(io_err)?;
+                }
+                None => tokio::fs::remove_file(&path).await.map_err(io_err)?,
+            }
+            touched.push(path);
+        }
+        Ok(touched)
+    }
+
+    fn apply_file(&self, original: &str, file: &FilePatch) -> Result<String, PatchError> {
+        let eol = if original.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut lines: Vec<String> = original.lines().map(|l| l.trim_end_matches('\r').to_string()).collect();
+        let mut trailing_newline = original.is_empty() || original.ends_with('\n');
+        let mut offset: isize = 0;
+
+        for (index, hunk) in file.hunks.iter().enumerate() {
+            let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+            let (start, old, new) = self.locate(&lines, hunk, expected, index + 1)?;
+            let touches_end = start + old.len() == lines.len();
+            lines.splice(start..start + old.len(), new.iter().cloned());
+            offset += new.len() as isize - old.len() as isize;
+
+            if touches_end {
+                if hunk.new_no_eol {
+                    trailing_newline = false;
+                } else if hunk.old_no_eol {
+                    trailing_newline = true;
+                }
+            }
+        }
+
+        let mut out = lines.join(eol);
+        if trailing_newline && !lines.is_empty() {
+            out.push_str(eol);
+        }
+        Ok(out)
+    }
+
+    /// Finds where `hunk` applies, returning the start index and the old/new line
+    /// runs to splice (which shrink when fuzz drops outer context lines).
+    fn locate(
+        &self,
+        lines: &[String],
+        hunk: &Hunk,
+        expected: usize,
+        hunk_number: usize,
+    ) -> Result<(usize, Vec<String>, Vec<String>), PatchError> {
+        let leading = hunk.lines.iter().take_while(|l| l.kind == LineKind::Context).count();
+        let trailing = hunk.lines.iter().rev().take_while(|l| l.kind == LineKind::Context).count();
+
+        for fuzz in 0..=self.fuzz {
+            let skip_front = fuzz.min(leading);
+            let skip_back = fuzz.min(trailing);
+            if skip_front + skip_back > hunk.lines.len() {
+                break;
+            }
+            let body = &hunk.lines[skip_front..hunk.lines.len() - skip_back];
+            let old: Vec<String> = body
+                .iter()
+                .filter(|l| l.kind != LineKind::Add)
+                .map(|l| l.text.clone())
+                .collect();
+            let new: Vec<String> = body
+                .iter()
+                .filter(|l| l.kind != LineKind::Remove)
+                .map(|l| l.text.clone())
+                .collect();
+
+            if let Some(start) = find_block(lines, &old, expected + skip_front) {
+                return Ok((start, old, new));
+            }
+        }
+
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Add)
+            .map(|l| l.text.as_str())
+            .collect();
+        let mismatch = old
+            .iter()
+            .enumerate()
+            .find(|(i, text)| lines.get(expected + i).map(String::as_str) != Some(**text))
+            .map(|(i, text)| (i, text.to_string()))
+            .unwrap_or((0, old.first().map(|s| s.to_string()).unwrap_or_default()));
+        Err(PatchError::HunkMismatch {
+            hunk: hunk_number,
+            line: expected + mismatch.0 + 1,
+            expected: mismatch.1,
+            found: lines.get(expected + mismatch.0).cloned().unwrap_or_else(|| "<end of file>".to_string()),
+        })
+    }
+}
+
+impl Default for PatchTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Applies a multi-file unified diff beneath `root` with no fuzz.
+pub async fn apply_patch_to_dir<P: AsRef<Path>>(root: P, patch: &str) -> Result<Vec<PathBuf>, PatchError> {
+    PatchTransform::new().apply_to_dir(root, patch).await
+}
+
+/// Searches outward from `expected` for the nearest position where `block` matches.
+fn find_block(lines: &[String], block: &[String], expected: usize) -> Option<usize> {
+    if block.is_empty() {
+        return Some(expected.min(lines.len()));
+    }
+    if block.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - block.len();
+    let matches = |start: usize| lines[start..start + block.len()] == *block;
+    let expected = expected.min(last);
+
+    for distance in 0..=last {
+        if let Some(start) = expected.checked_add(distance).filter(|s| *s <= last) {
+            if matches(start) {
+                return Some(start);
+            }
+        }
+        if let Some(start) = expected.checked_sub(distance) {
+            if distance > 0 && matches(start) {
+                return Some(start);
+            }
+        }
+    }
+    None
+}
+
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, PatchError> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    // Git's `rename from` and `rename to` paths for the section being read.
+    let mut rename: (Option<String>, Option<String>) = (None, None);
+    let mut lines = patch.lines().map(|l| l.trim_end_matches('\r')).enumerate().peekable();
+
+    while let Some((index, line)) = lines.next() {
+        if line.starts_with("diff --git ") {
+            files.extend(current.take());
+            files.extend(pure_rename(&mut rename));
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            rename.0 = Some(from.to_string());
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            rename.1 = Some(to.to_string());
+        } else if let Some(old) = line.strip_prefix("--- ") {
+            if let Some(file) = current.take() {
+                files.push(file);
+            }
+            let new = match lines.next() {
+                Some((_, next)) if next.starts_with("+++ ") => &next[4..],
+                _ => {
+                    return Err(PatchError::Parse {
+                        line: index + 2,
+                        message: "expected +++ header after ---".to_string(),
+                    })
+                }
+            };
+            let renamed = matches!(rename, (Some(_), Some(_)));
+            rename = (None, None);
+            current = Some(FilePatch {
+                old_path: header_path(old),
+                new_path: header_path(new),
+                rename: renamed,
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@") {
+            let (old_start, old_len, new_len) = parse_hunk_header(line).ok_or_else(|| PatchError::Parse {
+                line: index + 1,
+                message: format!("invalid hunk header {:?}", line),
+            })?;
+            let mut hunk = Hunk { old_start, ..Hunk::default() };
+            let (mut old_seen, mut new_seen) = (0, 0);
+
+            while old_seen < old_len || new_seen < new_len || matches!(lines.peek(), Some((_, l)) if l.starts_with('\\')) {
+                let Some((body_index, body)) = lines.next() else {
+                    return Err(PatchError::Parse {
+                        line: index + 1,
+                        message: "hunk is shorter than its header claims".to_string(),
+                    });
+                };
+                let kind = match body.chars().next() {
+                    Some(' ') | None => LineKind::Context,
+                    Some('-') => LineKind::Remove,
+                    Some('+') => LineKind::Add,
+                    Some('\\') => {
+                        match hunk.lines.last().map(|l| l.kind) {
+                            Some(LineKind::Add) => hunk.new_no_eol = true,
+                            Some(LineKind::Remove) => hunk.old_no_eol = true,
+                            _ => {
+                                hunk.old_no_eol = true;
+                                hunk.new_no_eol = true;
+                            }
+                        }
+                        continue;
+                    }
+                    Some(_) => {
+                        return Err(PatchError::Parse {
+                            line: body_index + 1,
+                            message: format!("unexpected line in hunk: {:?}", body),
+                        })
+                    }
+                };
+                match kind {
+                    LineKind::Context => {
+                        old_seen += 1;
+                        new_seen += 1;
+                    }
+                    LineKind::Remove => old_seen += 1,
+                    LineKind::Add => new_seen += 1,
+                }
+                let text = body.get(1..).unwrap_or("").to_string();
+                hunk.lines.push(HunkLine { kind, text });
+            }
+
+            current.get_or_insert_with(FilePatch::default).hunks.push(hunk);
+        }
+    }
+
+    files.extend(current);
+    files.extend(pure_rename(&mut rename));
+    Ok(files)
+}
+
+/// A section git wrote for a file moved without changes, which has rename
+/// headers but no `---`/`+++` ones.
+fn pure_rename(rename: &mut (Option<String>, Option<String>)) -> Option<FilePatch> {
+    match std::mem::take(rename) {
+        (Some(from), Some(to)) => Some(FilePatch { old_path: Some(from), new_path: Some(to), rename: true, hunks: Vec::new() }),
+        _ => None,
+    }
+}
+
+/// Parses `@@ -a,b +c,d @@`, returning (old start, old length, new length).
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.trim_start_matches('@').split_whitespace();
+    let old = parts.next()?.strip_prefix('-')?;
+    let new = parts.next()?.strip_prefix('+')?;
+    let range = |spec: &str| -> Option<(usize, usize)> {
+        match spec.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((spec.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = range(old)?;
+    let (_, new_len) = range(new)?;
+    Some((old_start, old_len, new_len))
+}
+
+fn header_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+fn safe_relative_path(path: &str) -> Result<PathBuf, PatchError> {
+    let relative = Path::new(path);
+    let safe = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if safe && !path.is_empty() {
+        Ok(relative.to_path_buf())
+    } else {
+        Err(PatchError::UnsafePath(path.to_string()))
+    }
+}
+
+/// What `apply_to_dir` will write (`Some`) or remove (`None`), one entry per
+/// path in the order first touched.
+#[derive(Debug, Default)]
+struct Plan {
+    files: Vec<(PathBuf, Option<String>)>,
+}
+
+impl Plan {
+    fn set(&mut self, path: PathBuf, content: Option<String>) {
+        match self.files.iter_mut().find(|(planned, _)| *planned == path) {
+            Some(entry) => entry.1 = content,
+            None => self.files.push((path, content)),
+        }
+    }
+
+    /// `path`'s content with the sections so far applied; `None` if it does
+    /// not exist.
+    async fn content(&self, path: &Path) -> Result<Option<String>, PatchError> {
+        if let Some((_, content)) = self.files.iter().find(|(planned, _)| planned == path) {
+            return Ok(content.clone());
+        }
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(PatchError::Io { path: path.to_path_buf(), source }),
+        }
+    }
+
+    /// `content`, failing if `path` does not exist.
+    async fn existing(&self, path: &Path) -> Result<String, PatchError> {
+        self.content(path).await?.ok_or_else(|| PatchError::Io {
+            path: path.to_path_buf(),
+            source: std::io::Error::from(std::io::ErrorKind::NotFound),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn test_apply_simple_hunk() {
+        let patch = "--- a/f.txt\n+++ b/f.txt\n@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n";
+        let out = PatchTransform::new().apply(ORIGINAL, patch).unwrap();
+        assert_eq!(out, "one\ntwo\nTHREE\nfour\nfive\n");
+    }
+
+    #[test]
+    fn test_apply_preserves_crlf() {
+        let original = ORIGINAL.replace('\n', "\r\n");
+        let patch = "@@ -1,2 +1,2 @@\n-one\n+ONE\n two\n";
+        let out = PatchTransform::new().apply(&original, patch).unwrap();
+        assert_eq!(out, "ONE\r\ntwo\r\nthree\r\nfour\r\nfive\r\n");
+    }
+
+    #[test]
+    fn test_apply_with_offset_and_fuzz() {
+        let shifted = format!("zero\n{}", ORIGINAL);
+        let patch = "@@ -2,3 +2,3 @@\n two\n-three\n+THREE\n FOUR\n";
+        let err = PatchTransform::new().apply(&shifted, patch).unwrap_err();
+        assert!(matches!(err, PatchError::HunkMismatch { hunk: 1, .. }));
+
+        let out = PatchTransform::with_fuzz(1).apply(&shifted, patch).unwrap();
+        assert_eq!(out, "zero\none\ntwo\nTHREE\nfour\nfive\n");
+    }
+
+    #[test]
+    fn test_apply_no_newline_marker() {
+        let patch = "@@ -5 +5 @@\n-five\n+FIVE\n\\ No newline at end of file\n";
+        let out = PatchTransform::new().apply(ORIGINAL, patch).unwrap();
+        assert_eq!(out, "one\ntwo\nthree\nfour\nFIVE");
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_to_dir_create_modify_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(dir.path().join("keep.txt"), ORIGINAL).await.unwrap();
+        tokio::fs::write(dir.path().join("gone.txt"), "bye\n").await.unwrap();
+
+        let patch = "\
+--- /dev/null
++++ b/new/created.txt
+@@ -0,0 +1,2 @@
++hello
++world
+--- a/keep.txt
++++ b/keep.txt
+@@ -1 +1 @@
+-one
++uno
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+        let touched = apply_patch_to_dir(dir.path(), patch).await.unwrap();
+        assert_eq!(touched.len(), 3);
+        let created = tokio::fs::read_to_string(dir.path().join("new/created.txt")).await.unwrap();
+        assert_eq!(created, "hello\nworld\n");
+        let kept = tokio::fs::read_to_string(dir.path().join("keep.txt")).await.unwrap();
+        assert!(kept.starts_with("uno\n"));
+        assert!(!dir.path().join("gone.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_to_dir_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(dir.path().join("old.txt"), ORIGINAL).await.unwrap();
+        let patch = "\
+diff --git a/old.txt b/sub/new.txt
+similarity index 80%
+rename from old.txt
+rename to sub/new.txt
+--- a/old.txt
++++ b/sub/new.txt
+@@ -1 +1 @@
+-one
++uno
+";
+        let touched = apply_patch_to_dir(dir.path(), patch).await.unwrap();
+        assert_eq!(touched, vec![dir.path().join("sub/new.txt"), dir.path().join("old.txt")]);
+        let renamed = tokio::fs::read_to_string(dir.path().join("sub/new.txt")).await.unwrap();
+        assert_eq!(renamed, ORIGINAL.replacen("one", "uno", 1));
+        assert!(!dir.path().join("old.txt").exists());
+
+        // Renaming onto an existing file is refused and changes nothing.
+        tokio::fs::write(dir.path().join("old.txt"), ORIGINAL).await.unwrap();
+        let err = apply_patch_to_dir(dir.path(), patch).await.unwrap_err();
+        assert!(matches!(err, PatchError::AlreadyExists(_)));
+        assert!(dir.path().join("old.txt").exists());
+
+        // A move without changes has no ---/+++ headers at all.
+        let patch = "diff --git a/old.txt b/moved.txt\nsimilarity index 100%\nrename from old.txt\nrename to moved.txt\n";
+        apply_patch_to_dir(dir.path(), patch).await.unwrap();
+        assert_eq!(tokio::fs::read_to_string(dir.path().join("moved.txt")).await.unwrap(), ORIGINAL);
+        assert!(!dir.path().join("old.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_to_dir_plain_diff_of_a_backup() {
+        // `diff -u foo.orig foo`: only the file being patched exists.
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(dir.path().join("foo"), ORIGINAL).await.unwrap();
+        let patch = "--- foo.orig\t2024-01-01\n+++ foo\t2024-01-02\n@@ -1 +1 @@\n-one\n+uno\n";
+        assert_eq!(apply_patch_to_dir(dir.path(), patch).await.unwrap(), vec![dir.path().join("foo")]);
+
+        // Both exist: the +++ file is patched and the backup left alone;
+        // a second section for the same file applies on top of the first.
+        tokio::fs::write(dir.path().join("foo.orig"), ORIGINAL).await.unwrap();
+        let patch = "\
+--- foo.orig
++++ foo
+@@ -2 +2 @@
+-two
++dos
+--- foo.orig
++++ foo
+@@ -3 +3 @@
+-three
++tres
+";
+        assert_eq!(apply_patch_to_dir(dir.path(), patch).await.unwrap(), vec![dir.path().join("foo")]);
+        let patched = tokio::fs::read_to_string(dir.path().join("foo")).await.unwrap();
+        assert_eq!(patched, "uno\ndos\ntres\nfour\nfive\n");
+        assert_eq!(tokio::fs::read_to_string(dir.path().join("foo.orig")).await.unwrap(), ORIGINAL);
+    }
+
+    #[tokio::test]
+    async fn test_apply_patch_to_dir_rejects_escape() {
+        let dir = tempfile::tempdir().unwrap();
+        let patch = "--- /dev/null\n+++ b/../evil.txt\n@@ -0,0 +1 @@\n+x\n";
+        let err = apply_patch_to_dir(dir.path(), patch).await.unwrap_err();
+        assert!(matches!(err, PatchError::UnsafePath(_)));
+    }
+}
@@ -0,0 +1,551 @@
+// Transform pipeline implementation
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use rayon::prelude::*;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use super::compress::CompressTransform;
+use super::detect_language::DetectLanguageTransform;
+use super::encode::EncodeTransform;
+use super::html::HtmlToTextTransform;
+use super::json_format::JsonFormatTransform;
+use super::json_query::JsonQueryTransform;
+use super::normalize::NormalizeTransform;
+use super::plugin::Transform;
+use super::redact::RedactTransform;
+use super::stats::StatsTransform;
+use super::streaming::{run_buffered, LineTransform, StreamFactory, StreamTransform};
+use super::strip_comments::StripCommentsTransform;
+
+/// Values recorded by stages during a run, keyed by stage name.
+pub type Metadata = serde_json::Map<String, serde_json::Value>;
+
+type BytesFn = Box<dyn Fn(Vec<u8>, &mut Metadata) -> Result<Vec<u8>> + Send + Sync>;
+type TextFn = Box<dyn Fn(&str, &mut Metadata) -> Result<String> + Send + Sync>;
+
+/// The work of one pipeline stage, on raw bytes or on text. A text stage
+/// after a bytes stage, or first in a pipeline run on bytes, fails unless
+/// its input is valid UTF-8; consecutive text stages hand each other text
+/// without checking it again, and a pipeline of bytes stages never
+/// inspects its content at all, so binary data passes through intact.
+pub enum Stage {
+    Bytes(BytesStage),
+    Text(TextStage),
+}
+
+pub type BytesStage = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+pub type TextStage = Box<dyn Fn(&str) -> Result<String> + Send + Sync>;
+
+impl Stage {
+    pub fn bytes<F>(stage: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        Self::Bytes(Box::new(stage))
+    }
+
+    pub fn text<F>(stage: F) -> Self
+    where
+        F: Fn(&str) -> Result<String> + Send + Sync + 'static,
+    {
+        Self::Text(Box::new(stage))
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text(_))
+    }
+}
+
+enum Run {
+    Bytes(BytesFn),
+    Text(TextFn),
+}
+
+struct Step {
+    name: String,
+    run: Run,
+    /// Set for stages that can also run incrementally.
+    streaming: Option<StreamFactory>,
+    /// Set for stages that may run on separate runs of whole lines.
+    parallel: bool,
+}
+
+impl Step {
+    fn new(name: impl Into<String>, run: Run) -> Self {
+        Self { name: name.into(), run, streaming: None, parallel: false }
+    }
+
+    fn apply(&self, content: Content, metadata: &mut Metadata) -> Result<Content> {
+        match &self.run {
+            Run::Bytes(run) => Ok(Content::Bytes(run(content.into_bytes(), metadata)?)),
+            Run::Text(run) => {
+                let text = match content {
+                    Content::Text(text) => text,
+                    Content::Bytes(bytes) => String::from_utf8(bytes)
+                        .with_context(|| format!("stage '{}' expects UTF-8 input", self.name))?,
+                };
+                Ok(Content::Text(run(&text, metadata)?))
+            }
+        }
+    }
+}
+
+/// Content between stages, kept as text once a text stage has checked it.
+enum Content {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl Content {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Bytes(bytes) => bytes,
+            Self::Text(text) => text.into_bytes(),
+        }
+    }
+}
+
+/// An ordered chain of named stages, each consuming the previous stage's output.
+pub struct TransformPipeline {
+    stages: Vec<Step>,
+}
+
+impl TransformPipeline {
+    pub fn new() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    /// Appends a stage operating on raw bytes, which it may change in place.
+    pub fn stage<F>(mut self, name: impl Into<String>, stage: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.stages.push(Step::new(name, Run::Bytes(Box::new(move |input, _| stage(input)))));
+        self
+    }
+
+    /// Appends `stage` under `name`.
+    pub fn push(mut self, name: impl Into<String>, stage: Stage) -> Self {
+        let run = match stage {
+            Stage::Bytes(stage) => Run::Bytes(Box::new(move |input, _| stage(&input))),
+            Stage::Text(stage) => Run::Text(Box::new(move |input, _| stage(input))),
+        };
+        self.stages.push(Step::new(name, run));
+        self
+    }
+
+    /// Appends a stage operating on raw bytes.
+    pub fn bytes_stage<F>(self, name: impl Into<String>, stage: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.push(name, Stage::bytes(stage))
+    }
+
+    /// Appends a stage that works on both whole buffers and streams. `factory`
+    /// is called once per run to create the stage's state.
+    pub fn streaming_stage<F>(mut self, name: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn StreamTransform> + Send + Sync + 'static,
+    {
+        let factory = Arc::new(factory);
+        let buffered = factory.clone();
+        self.stages.push(Step {
+            streaming: Some(Box::new(move || factory())),
+            ..Step::new(name, Run::Bytes(Box::new(move |input, _| run_buffered(buffered(), &input))))
+        });
+        self
+    }
+
+    /// Appends a stage that passes content through unchanged and records the
+    /// value returned by `inspect` in the run's metadata under `name`.
+    pub fn inspect_stage<F>(mut self, name: impl Into<String>, inspect: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<serde_json::Value> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let key = name.clone();
+        let run = move |input: Vec<u8>, metadata: &mut Metadata| {
+            metadata.insert(key.clone(), inspect(&input)?);
+            Ok(input)
+        };
+        self.stages.push(Step::new(name, Run::Bytes(Box::new(run))));
+        self
+    }
+
+    /// Appends a stage recording `TextStats` under the `stats` metadata key.
+    pub fn stats(self, transform: StatsTransform) -> Self {
+        self.inspect_stage("stats", move |input| {
+            Ok(serde_json::to_value(transform.compute(&String::from_utf8_lossy(input)))?)
+        })
+    }
+
+    /// Appends a language detection stage recording a `LanguageGuess` under
+    /// the `language` metadata key.
+    pub fn detect_language(self, transform: DetectLanguageTransform) -> Self {
+        self.inspect_stage("language", move |input| {
+            Ok(serde_json::to_value(transform.detect(&String::from_utf8_lossy(input)))?)
+        })
+    }
+
+    /// Appends a secret redaction stage. Per-kind counts are recorded under
+    /// the `redact` metadata key.
+    pub fn redact(mut self, transform: RedactTransform) -> Self {
+        let run = move |input: &str, metadata: &mut Metadata| {
+            let (redacted, report) = transform.apply(input);
+            metadata.insert("redact".to_string(), serde_json::to_value(report.counts)?);
+            Ok(redacted)
+        };
+        self.stages.push(Step::new("redact", Run::Text(Box::new(run))));
+        self
+    }
+
+    /// Appends a streamable stage applied to each line; see `LineTransform`.
+    /// Returning `None` drops the line.
+    pub fn line_stage<F>(self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(&str) -> Result<Option<String>> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.streaming_stage(name.clone(), LineTransform::factory(name, f)).parallel_last(true)
+    }
+
+    /// Appends a stage operating on text. Its input must be valid UTF-8.
+    pub fn text_stage<F>(self, name: impl Into<String>, stage: F) -> Self
+    where
+        F: Fn(&str) -> Result<String> + Send + Sync + 'static,
+    {
+        self.push(name, Stage::text(stage))
+    }
+
+    /// Appends a base64/hex encode or decode stage.
+    pub fn encode(self, transform: EncodeTransform) -> Self {
+        self.streaming_stage(transform.name(), transform.stream_factory())
+    }
+
+    /// Appends a gzip/zstd compress or decompress stage. Text stages after a
+    /// decompress and before a compress let a pipeline edit compressed files.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub fn compress(self, transform: CompressTransform) -> Self {
+        self.streaming_stage(transform.name(), transform.stream_factory())
+    }
+
+    /// Appends a named transform, e.g. one from a `TransformRegistry`.
+    pub fn transform(self, transform: Arc<dyn Transform>) -> Self {
+        let name = transform.name().to_string();
+        let parallel = transform.is_parallelizable();
+        self.text_stage(name, move |input| transform.apply(input)).parallel_last(parallel)
+    }
+
+    fn parallel_last(mut self, parallel: bool) -> Self {
+        if let Some(stage) = self.stages.last_mut() {
+            stage.parallel = parallel;
+        }
+        self
+    }
+
+    /// Appends a whitespace normalization stage.
+    pub fn normalize(self, transform: NormalizeTransform) -> Self {
+        self.text_stage("normalize", move |input| Ok(transform.apply(input).0))
+    }
+
+    /// Appends an HTML to plain text stage.
+    pub fn html_to_text(self, transform: HtmlToTextTransform) -> Self {
+        self.text_stage("html_to_text", move |input| Ok(transform.apply(input)))
+    }
+
+    /// Appends a comment stripping stage.
+    pub fn strip_comments(self, transform: StripCommentsTransform) -> Self {
+        self.text_stage("strip_comments", move |input| Ok(transform.apply(input)))
+    }
+
+    /// Appends a JSON query stage emitting matches as NDJSON.
+    pub fn json_query(self, transform: JsonQueryTransform) -> Self {
+        self.text_stage("json_query", move |input| Ok(transform.apply(input)?))
+    }
+
+    /// Appends a JSON formatting stage named after `transform`.
+    pub fn json_format(self, transform: JsonFormatTransform) -> Self {
+        self.text_stage(transform.name(), move |input| Ok(transform.apply(input)?))
+    }
+
+    /// Appends a stage re-indenting JSON by `indent` spaces, keeping key order.
+    pub fn json_pretty(self, indent: usize) -> Self {
+        self.json_format(JsonFormatTransform::pretty(indent))
+    }
+
+    /// Appends a stage stripping insignificant whitespace from JSON.
+    pub fn json_minify(self) -> Self {
+        self.json_format(JsonFormatTransform::minify())
+    }
+
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.stages.iter().map(|stage| stage.name.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Whether no stage works on text, so content of any kind passes
+    /// through without being checked for UTF-8.
+    pub fn is_binary_safe(&self) -> bool {
+        self.stages.iter().all(|stage| matches!(stage.run, Run::Bytes(_)))
+    }
+
+    pub fn run(&self, input: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self.run_with_metadata(input)?.0)
+    }
+
+    /// Like `run`, also returning whatever the stages recorded.
+    pub fn run_with_metadata(&self, input: Vec<u8>) -> Result<(Vec<u8>, Metadata)> {
+        let (output, metadata) = self.run_content(Content::Bytes(input))?;
+        Ok((output.into_bytes(), metadata))
+    }
+
+    /// Like `run_with_metadata` for text in and out. Output left by a text
+    /// stage is returned as is; only output of a trailing bytes stage is
+    /// checked for UTF-8.
+    pub fn run_text_with_metadata(&self, input: String) -> Result<(String, Metadata)> {
+        let (output, metadata) = self.run_content(Content::Text(input))?;
+        let output = match output {
+            Content::Text(text) => text,
+            Content::Bytes(bytes) => String::from_utf8(bytes).context("transform pipeline produced non-UTF-8 output")?,
+        };
+        Ok((output, metadata))
+    }
+
+    fn run_content(&self, input: Content) -> Result<(Content, Metadata)> {
+        let mut metadata = Metadata::new();
+        let output = self.stages.iter().try_fold(input, |data, stage| {
+            stage.apply(data, &mut metadata).with_context(|| format!("transform stage '{}' failed", stage.name))
+        })?;
+        Ok((output, metadata))
+    }
+
+    /// Whether every stage is line-independent, so `run_parallel` may be used.
+    pub fn is_parallelizable(&self) -> bool {
+        self.stages.iter().all(|stage| stage.parallel)
+    }
+
+    /// Runs the pipeline over runs of whole lines of about `chunk_size` bytes
+    /// on the rayon thread pool and joins the results in order. The output is
+    /// byte-identical to `run`. Fails before doing any work if a stage is not
+    /// line-independent.
+    pub fn run_parallel(&self, input: Vec<u8>, chunk_size: usize) -> Result<Vec<u8>> {
+        if let Some(stage) = self.stages.iter().find(|stage| !stage.parallel) {
+            bail!("transform stage '{}' cannot run in parallel", stage.name);
+        }
+        let outputs = line_chunks(&input, chunk_size)
+            .par_iter()
+            .map(|chunk| self.run(chunk.to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(outputs.concat())
+    }
+
+    /// Whether every stage can run incrementally via `apply_streaming`.
+    pub fn is_streamable(&self) -> bool {
+        self.stages.iter().all(|stage| stage.streaming.is_some())
+    }
+
+    /// Runs the pipeline chunk by chunk, so memory use is bounded by the chunk
+    /// size rather than the input size. The next chunk is only pulled once the
+    /// previous output has been written, which propagates backpressure from
+    /// `writer` to `input`. Fails before reading anything if a stage cannot
+    /// stream. Returns the number of bytes written.
+    pub async fn apply_streaming<S, W>(&self, input: S, writer: &mut W) -> Result<u64>
+    where
+        S: Stream<Item = std::io::Result<Bytes>>,
+        W: AsyncWrite + Unpin,
+    {
+        let mut stages = Vec::with_capacity(self.stages.len());
+        for stage in &self.stages {
+            match &stage.streaming {
+                Some(factory) => stages.push((stage.name.as_str(), factory())),
+                None => bail!("transform stage '{}' does not support streaming", stage.name),
+            }
+        }
+
+        let mut input = std::pin::pin!(input);
+        let mut written = 0u64;
+        while let Some(chunk) = input.next().await {
+            let chunk = chunk.context("Failed to read input stream")?;
+            let out = push_through(&mut stages, &chunk, false)?;
+            writer.write_all(&out).await?;
+            written += out.len() as u64;
+        }
+        let out = push_through(&mut stages, &[], true)?;
+        writer.write_all(&out).await?;
+        writer.flush().await?;
+        Ok(written + out.len() as u64)
+    }
+}
+
+/// Splits `input` after the first newline at or past every `chunk_size` bytes.
+fn line_chunks(input: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = input;
+    while rest.len() > chunk_size {
+        let Some(end) = rest[chunk_size.max(1) - 1..].iter().position(|&b| b == b'\n') else { break };
+        let (chunk, tail) = rest.split_at(chunk_size.max(1) + end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Feeds `input` through each stage in turn; with `finish` set, each stage is
+/// flushed after receiving its final input.
+fn push_through(stages: &mut [(&str, Box<dyn StreamTransform>)], input: &[u8], finish: bool) -> Result<Vec<u8>> {
+    let mut data = input.to_vec();
+    for (name, stage) in stages.iter_mut() {
+        let mut out = Vec::with_capacity(data.len());
+        let mut result = stage.process(&data, &mut out);
+        if finish {
+            result = result.and_then(|_| stage.finish(&mut out));
+        }
+        result.with_context(|| format!("transform stage '{}' failed", name))?;
+        data = out;
+    }
+    Ok(data)
+}
+
+impl Default for TransformPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::transformer::{Codec, Direction};
+
+    #[test]
+    fn test_pipeline_runs_stages_in_order() {
+        let base64 = Codec::Base64 { url_safe: false, padding: true };
+        let pipeline = TransformPipeline::new()
+            .text_stage("upper", |s| Ok(s.to_uppercase()))
+            .encode(EncodeTransform::new(base64, Direction::Encode))
+            .encode(EncodeTransform::new(base64, Direction::Decode));
+
+        assert_eq!(pipeline.stage_names(), vec!["upper", "base64_encode", "base64_decode"]);
+        assert_eq!(pipeline.run(b"abc".to_vec()).unwrap(), b"ABC");
+    }
+
+    #[test]
+    fn test_text_stage_rejects_invalid_utf8() {
+        let pipeline = TransformPipeline::new().text_stage("noop", |s| Ok(s.to_string()));
+        let err = pipeline.run(vec![0xff, 0xfe]).unwrap_err();
+        assert!(format!("{:#}", err).contains("expects UTF-8"));
+    }
+
+    #[test]
+    fn test_bytes_and_text_stages_mix() {
+        let invert = |input: &[u8]| Ok(input.iter().map(|b| !b).collect());
+        let binary = TransformPipeline::new().bytes_stage("invert", invert).push("invert_again", Stage::bytes(invert));
+        assert!(binary.is_binary_safe());
+        assert_eq!(binary.run(vec![0xff, 0x00, 0xc3]).unwrap(), [0xff, 0x00, 0xc3]);
+
+        let mixed = TransformPipeline::new()
+            .bytes_stage("invert", invert)
+            .push("upper", Stage::text(|s| Ok(s.to_uppercase())))
+            .bytes_stage("invert_again", invert);
+        assert!(!mixed.is_binary_safe());
+        let invert_all = |bytes: &[u8]| bytes.iter().map(|b| !b).collect::<Vec<u8>>();
+        assert_eq!(mixed.run(invert_all(b"abc")).unwrap(), invert_all(b"ABC"));
+        // Inverted, ASCII is no longer UTF-8.
+        let err = mixed.run(b"abc".to_vec()).unwrap_err();
+        assert!(format!("{:#}", err).contains("stage 'upper' expects UTF-8 input"));
+
+        let (text, _) = TransformPipeline::new()
+            .text_stage("upper", |s| Ok(s.to_uppercase()))
+            .text_stage("exclaim", |s| Ok(format!("{}!", s)))
+            .run_text_with_metadata("hi".to_string())
+            .unwrap();
+        assert_eq!(text, "HI!");
+        assert!(mixed.run_text_with_metadata("abc".to_string()).is_err());
+    }
+
+    fn chunks(parts: &[&'static [u8]]) -> impl Stream<Item = std::io::Result<Bytes>> {
+        let parts: Vec<_> = parts.iter().map(|part| Ok(Bytes::from_static(part))).collect();
+        futures::stream::iter(parts)
+    }
+
+    #[tokio::test]
+    async fn test_streaming_matches_buffered() {
+        let base64 = Codec::Base64 { url_safe: false, padding: true };
+        let pipeline = TransformPipeline::new()
+            .line_stage("filter", |line| Ok((!line.starts_with('#')).then(|| line.replace("cat", "dog"))))
+            .encode(EncodeTransform::new(base64, Direction::Encode));
+        assert!(pipeline.is_streamable());
+
+        // Chunk boundaries fall mid-line and inside a multi-byte character.
+        let parts: &[&'static [u8]] = &[b"# skip\nthe c", b"at sat\n#", b" also skip\n\xc3", b"\xa9 cat"];
+        let mut streamed = Vec::new();
+        let written = pipeline.apply_streaming(chunks(parts), &mut streamed).await.unwrap();
+        assert_eq!(written, streamed.len() as u64);
+        assert_eq!(streamed, pipeline.run(parts.concat()).unwrap());
+        let decoded = EncodeTransform::new(base64, Direction::Decode).apply(&streamed).unwrap();
+        assert_eq!(decoded, "the dog sat\n\u{e9} dog".as_bytes());
+    }
+
+    #[test]
+    fn test_inspect_stages_record_metadata() {
+        let pipeline = TransformPipeline::new()
+            .text_stage("upper", |s| Ok(s.to_uppercase()))
+            .stats(StatsTransform::new());
+        let (output, metadata) = pipeline.run_with_metadata(b"one two\nthree\n".to_vec()).unwrap();
+        assert_eq!(output, b"ONE TWO\nTHREE\n");
+        assert_eq!(metadata["stats"]["words"], 3);
+        assert_eq!(metadata["stats"]["longest_line"], 7);
+
+        let pipeline = TransformPipeline::new().redact(RedactTransform::new());
+        let (output, metadata) = pipeline.run_with_metadata(b"mail me: a@b.io\n".to_vec()).unwrap();
+        assert_eq!(output, b"mail me: [REDACTED:email]\n");
+        assert_eq!(metadata["redact"], serde_json::json!({"email": 1}));
+    }
+
+    #[test]
+    fn test_parallel_run_matches_serial() {
+        let mut registry = crate::file_processor::TransformRegistry::new();
+        let digits = regex::Regex::new(r"\d+").unwrap();
+        registry.register_parallel_fn("mask", move |input| Ok(digits.replace_all(input, "#").into_owned()));
+        let pipeline = registry
+            .pipeline(&["mask"])
+            .unwrap()
+            .line_stage("trim", |line| Ok((!line.is_empty()).then(|| line.trim_end().to_string())));
+        assert!(pipeline.is_parallelizable());
+
+        let input: String = (0..500).map(|i| format!("row {} \u{e9}\n{}", i, if i % 7 == 0 { "\n" } else { "" })).collect();
+        let serial = pipeline.run(input.clone().into_bytes()).unwrap();
+        for chunk_size in [1, 10, 64, 1 << 20] {
+            assert_eq!(pipeline.run_parallel(input.clone().into_bytes(), chunk_size).unwrap(), serial);
+        }
+        assert_eq!(line_chunks(b"ab\ncd\nef", 2), vec![&b"ab\n"[..], b"cd\n", b"ef"]);
+
+        let pipeline = TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase()));
+        assert!(!pipeline.is_parallelizable());
+        assert!(pipeline.run_parallel(b"a\nb\n".to_vec(), 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streaming_rejects_buffered_stages() {
+        let pipeline = TransformPipeline::new()
+            .line_stage("noop", |line| Ok(Some(line.to_string())))
+            .text_stage("upper", |s| Ok(s.to_uppercase()));
+        assert!(!pipeline.is_streamable());
+        let err = pipeline.apply_streaming(chunks(&[b"abc"]), &mut Vec::new()).await.unwrap_err();
+        assert!(err.to_string().contains("'upper' does not support streaming"));
+    }
+}
@@ -0,0 +1,438 @@
+// Named transforms and runtime-loaded transform plugins
+//
+// Plugin libraries (feature `dynamic-plugins`, Unix only) export one C-ABI
+// entry point returning a static manifest:
+//
+//     typedef struct { uint8_t *ptr; size_t len; } PluginBuffer;
+//     typedef struct {
+//         const char *name;  /* NUL-terminated UTF-8, valid while loaded */
+//         /* 0 on success with the UTF-8 result in *output; non-zero on
+//            failure with an optional UTF-8 error message in *output */
+//         int32_t (*apply)(const uint8_t *input, size_t input_len, PluginBuffer *output);
+//         void (*free)(PluginBuffer buffer);  /* releases what apply wrote */
+//     } PluginTransformV1;
+//     typedef struct {
+//         uint32_t abi_version;  /* PLUGIN_ABI_VERSION */
+//         const PluginTransformV1 *transforms;
+//         size_t count;
+//     } PluginManifestV1;
+//
+//     const PluginManifestV1 *ai_agent_transform_plugin(void);
+//
+// Libraries are never unloaded while a transform from them is registered.
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use thiserror::Error;
+
+use super::html::HtmlToTextTransform;
+use super::json_format::JsonFormatTransform;
+use super::normalize::NormalizeTransform;
+use super::pipeline::TransformPipeline;
+use super::redact::RedactTransform;
+
+/// Layout version of the plugin structs below.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+/// Symbol every plugin library must export.
+pub const PLUGIN_ENTRY_POINT: &str = "ai_agent_transform_plugin";
+
+/// A text transform that can be looked up by name.
+pub trait Transform: Send + Sync {
+    fn name(&self) -> &str;
+    fn apply(&self, input: &str) -> Result<String>;
+
+    /// Whether the transform treats every line on its own, so that applying
+    /// it to consecutive runs of whole lines and concatenating the results
+    /// gives exactly the output of one `apply` over all of them. Lets
+    /// `FileTransformer` spread large inputs over threads.
+    fn is_parallelizable(&self) -> bool {
+        false
+    }
+}
+
+impl Transform for NormalizeTransform {
+    fn name(&self) -> &str {
+        "normalize"
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        Ok(NormalizeTransform::apply(self, input).0)
+    }
+}
+
+impl Transform for HtmlToTextTransform {
+    fn name(&self) -> &str {
+        "html_to_text"
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        Ok(HtmlToTextTransform::apply(self, input))
+    }
+}
+
+impl Transform for RedactTransform {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        Ok(RedactTransform::apply(self, input).0)
+    }
+}
+
+impl Transform for JsonFormatTransform {
+    fn name(&self) -> &str {
+        JsonFormatTransform::name(self)
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        Ok(JsonFormatTransform::apply(self, input)?)
+    }
+}
+
+struct FnTransform<F> {
+    name: String,
+    f: F,
+    parallel: bool,
+}
+
+impl<F> Transform for FnTransform<F>
+where
+    F: Fn(&str) -> Result<String> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        (self.f)(input)
+    }
+
+    fn is_parallelizable(&self) -> bool {
+        self.parallel
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TransformError {
+    #[error("unknown transform '{name}'; registered transforms: {}", available.join(", "))]
+    UnknownTransform { name: String, available: Vec<String> },
+}
+
+/// Transforms available by name, e.g. to build a pipeline from a CLI flag.
+#[derive(Clone, Default)]
+pub struct TransformRegistry {
+    transforms: BTreeMap<String, Arc<dyn Transform>>,
+}
+
+impl TransformRegistry {
+    pub fn new() -> Self {
+        Self { transforms: BTreeMap::new() }
+    }
+
+    /// A registry holding `normalize`, `html_to_text` and `redact` with their
+    /// default settings, plus `json_pretty` (two-space indent) and `json_minify`.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(NormalizeTransform::new());
+        registry.register(HtmlToTextTransform::new());
+        registry.register(RedactTransform::new());
+        registry.register(JsonFormatTransform::pretty(2));
+        registry.register(JsonFormatTransform::minify());
+        registry
+    }
+
+    /// Adds or replaces a transform, returning the previous one of that name.
+    pub fn register<T: Transform + 'static>(&mut self, transform: T) -> Option<Arc<dyn Transform>> {
+        self.transforms.insert(transform.name().to_string(), Arc::new(transform))
+    }
+
+    pub fn register_fn<F>(&mut self, name: impl Into<String>, f: F) -> Option<Arc<dyn Transform>>
+    where
+        F: Fn(&str) -> Result<String> + Send + Sync + 'static,
+    {
+        self.register(FnTransform { name: name.into(), f, parallel: false })
+    }
+
+    /// Like `register_fn`, for a closure that is line-independent in the
+    /// sense of `Transform::is_parallelizable`.
+    pub fn register_parallel_fn<F>(&mut self, name: impl Into<String>, f: F) -> Option<Arc<dyn Transform>>
+    where
+        F: Fn(&str) -> Result<String> + Send + Sync + 'static,
+    {
+        self.register(FnTransform { name: name.into(), f, parallel: true })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Transform>, TransformError> {
+        self.transforms.get(name).cloned().ok_or_else(|| TransformError::UnknownTransform {
+            name: name.to_string(),
+            available: self.names(),
+        })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.transforms.keys().cloned().collect()
+    }
+
+    /// A pipeline running the named transforms in order.
+    pub fn pipeline<S: AsRef<str>>(&self, names: &[S]) -> Result<TransformPipeline> {
+        names
+            .iter()
+            .try_fold(TransformPipeline::new(), |pipeline, name| Ok(pipeline.transform(self.get(name.as_ref())?)))
+    }
+
+    /// Loads a plugin library and registers every transform in it, returning
+    /// their names.
+    #[cfg(all(feature = "dynamic-plugins", unix))]
+    pub fn load_library(&mut self, path: impl AsRef<Path>) -> Result<Vec<String>> {
+        let path = path.as_ref();
+        let library = Arc::new(dl::Library::open(path)?);
+        let entry = library.symbol(PLUGIN_ENTRY_POINT)?;
+        // SAFETY: the plugin contract documented above fixes this signature.
+        let transforms = unsafe {
+            let entry: unsafe extern "C" fn() -> *const PluginManifestV1 = std::mem::transmute(entry);
+            native_transforms(entry(), Some(library))
+        }
+        .with_context(|| format!("Invalid transform plugin {}", path.display()))?;
+        Ok(self.register_native(transforms))
+    }
+
+    /// Registers the transforms of a plugin manifest linked into this binary,
+    /// returning their names.
+    ///
+    /// # Safety
+    /// `manifest` must be null or point to a valid `PluginManifestV1` that,
+    /// with everything it references, lives for the rest of the program.
+    pub unsafe fn register_manifest(&mut self, manifest: *const PluginManifestV1) -> Result<Vec<String>> {
+        let transforms = native_transforms(manifest, None)?;
+        Ok(self.register_native(transforms))
+    }
+
+    #[cfg(not(all(feature = "dynamic-plugins", unix)))]
+    pub fn load_library(&mut self, path: impl AsRef<Path>) -> Result<Vec<String>> {
+        bail!(
+            "cannot load {}: transform plugins need the dynamic-plugins feature on a Unix platform",
+            path.as_ref().display()
+        )
+    }
+
+    fn register_native(&mut self, transforms: Vec<NativeTransform>) -> Vec<String> {
+        transforms
+            .into_iter()
+            .map(|transform| {
+                let name = transform.name.clone();
+                self.register(transform);
+                name
+            })
+            .collect()
+    }
+}
+
+#[repr(C)]
+pub struct PluginBuffer {
+    pub ptr: *mut u8,
+    pub len: usize,
+}
+
+#[repr(C)]
+pub struct PluginTransformV1 {
+    pub name: *const c_char,
+    pub apply: unsafe extern "C" fn(input: *const u8, input_len: usize, output: *mut PluginBuffer) -> i32,
+    pub free: unsafe extern "C" fn(buffer: PluginBuffer),
+}
+
+#[repr(C)]
+pub struct PluginManifestV1 {
+    pub abi_version: u32,
+    pub transforms: *const PluginTransformV1,
+    pub count: usize,
+}
+
+/// A transform implemented by plugin code.
+struct NativeTransform {
+    name: String,
+    apply: unsafe extern "C" fn(*const u8, usize, *mut PluginBuffer) -> i32,
+    free: unsafe extern "C" fn(PluginBuffer),
+    /// Keeps the library that owns `apply` and `free` loaded.
+    _library: Option<Arc<dyn std::any::Any + Send + Sync>>,
+}
+
+impl Transform for NativeTransform {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, input: &str) -> Result<String> {
+        let mut buffer = PluginBuffer { ptr: std::ptr::null_mut(), len: 0 };
+        // SAFETY: per the plugin contract `apply` reads `input` and fills
+        // `buffer`, which stays valid until handed back to `free`.
+        let (status, output) = unsafe {
+            let status = (self.apply)(input.as_ptr(), input.len(), &mut buffer);
+            let output = if buffer.ptr.is_null() {
+                Vec::new()
+            } else {
+                let output = std::slice::from_raw_parts(buffer.ptr, buffer.len).to_vec();
+                (self.free)(buffer);
+                output
+            };
+            (status, output)
+        };
+        let output = String::from_utf8(output)
+            .with_context(|| format!("transform '{}' returned invalid UTF-8", self.name))?;
+        if status != 0 {
+            bail!("transform '{}' failed with status {}: {}", self.name, status, output);
+        }
+        Ok(output)
+    }
+}
+
+/// Reads a plugin manifest.
+///
+/// # Safety
+/// `manifest` must be null or point to a `PluginManifestV1` that, together
+/// with everything it references, stays valid while `library` is alive.
+unsafe fn native_transforms(
+    manifest: *const PluginManifestV1,
+    library: Option<Arc<dyn std::any::Any + Send + Sync>>,
+) -> Result<Vec<NativeTransform>> {
+    let Some(manifest) = manifest.as_ref() else {
+        bail!("plugin returned no manifest");
+    };
+    if manifest.abi_version != PLUGIN_ABI_VERSION {
+        bail!("plugin ABI version {} is not supported (expected {})", manifest.abi_version, PLUGIN_ABI_VERSION);
+    }
+    if manifest.count == 0 {
+        return Ok(Vec::new());
+    }
+    std::slice::from_raw_parts(manifest.transforms, manifest.count)
+        .iter()
+        .map(|transform| {
+            let name = CStr::from_ptr(transform.name).to_str().context("plugin transform name is not UTF-8")?;
+            Ok(NativeTransform {
+                name: name.to_string(),
+                apply: transform.apply,
+                free: transform.free,
+                _library: library.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(feature = "dynamic-plugins", unix))]
+mod dl {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use anyhow::{anyhow, Result};
+
+    /// A `dlopen` handle, closed on drop.
+    pub struct Library(*mut libc::c_void);
+
+    // SAFETY: the handle is only passed to dlsym and dlclose, which are thread-safe.
+    unsafe impl Send for Library {}
+    unsafe impl Sync for Library {}
+
+    impl Library {
+        pub fn open(path: &Path) -> Result<Self> {
+            let c_path = CString::new(path.as_os_str().as_bytes())?;
+            // SAFETY: `c_path` is NUL-terminated. Loading runs the library's
+            // initialisers, which is inherent to using a plugin at all.
+            let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+            if handle.is_null() {
+                return Err(anyhow!("Failed to load {}: {}", path.display(), last_error()));
+            }
+            Ok(Self(handle))
+        }
+
+        pub fn symbol(&self, name: &str) -> Result<*mut libc::c_void> {
+            let c_name = CString::new(name)?;
+            // SAFETY: the handle is open and `c_name` is NUL-terminated.
+            let symbol = unsafe { libc::dlsym(self.0, c_name.as_ptr()) };
+            if symbol.is_null() {
+                return Err(anyhow!("symbol '{}' not found: {}", name, last_error()));
+            }
+            Ok(symbol)
+        }
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            // SAFETY: the handle came from dlopen and is closed exactly once.
+            unsafe { libc::dlclose(self.0) };
+        }
+    }
+
+    fn last_error() -> String {
+        // SAFETY: dlerror returns null or a NUL-terminated message.
+        let message = unsafe { libc::dlerror() };
+        if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_builds_pipelines() {
+        let mut registry = TransformRegistry::builtin();
+        registry.register_fn("upper", |input| Ok(input.to_uppercase()));
+        let pipeline = registry.pipeline(&["normalize", "upper"]).unwrap();
+        assert_eq!(pipeline.stage_names(), vec!["normalize", "upper"]);
+        assert_eq!(pipeline.run(b"a  \nb".to_vec()).unwrap(), b"A\nB\n");
+
+        let Err(err) = registry.pipeline(&["upper", "rot13"]) else {
+            panic!("expected an unknown transform error");
+        };
+        assert_eq!(
+            err.to_string(),
+            "unknown transform 'rot13'; registered transforms: html_to_text, json_minify, json_pretty, normalize, redact, upper"
+        );
+
+        let pipeline = registry.pipeline(&["json_minify", "json_pretty"]).unwrap();
+        assert_eq!(pipeline.run(b"{ \"a\" : [1] }".to_vec()).unwrap(), b"{\n  \"a\": [\n    1\n  ]\n}\n");
+        let err = pipeline.run(b"{\"a\": }".to_vec()).unwrap_err();
+        assert_eq!(format!("{:#}", err), "transform stage 'json_minify' failed: invalid JSON at line 1, column 7: expected value");
+    }
+
+    unsafe extern "C" fn reverse(input: *const u8, len: usize, output: *mut PluginBuffer) -> i32 {
+        let text = std::str::from_utf8(std::slice::from_raw_parts(input, len)).unwrap();
+        let (status, result) = match text {
+            "fail" => (1, "cannot reverse 'fail'".to_string()),
+            _ => (0, text.chars().rev().collect::<String>()),
+        };
+        let mut bytes = result.into_bytes().into_boxed_slice();
+        *output = PluginBuffer { ptr: bytes.as_mut_ptr(), len: bytes.len() };
+        std::mem::forget(bytes);
+        status
+    }
+
+    unsafe extern "C" fn free(buffer: PluginBuffer) {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(buffer.ptr, buffer.len)));
+    }
+
+    #[test]
+    fn test_native_transforms() {
+        let transforms = [PluginTransformV1 { name: c"reverse".as_ptr(), apply: reverse, free }];
+        let mut manifest = PluginManifestV1 { abi_version: PLUGIN_ABI_VERSION, transforms: transforms.as_ptr(), count: 1 };
+
+        let mut registry = TransformRegistry::new();
+        assert_eq!(unsafe { registry.register_manifest(&manifest) }.unwrap(), vec!["reverse"]);
+        let reverse = registry.get("reverse").unwrap();
+        assert_eq!(reverse.apply("héllo").unwrap(), "olléh");
+        assert_eq!(
+            reverse.apply("fail").unwrap_err().to_string(),
+            "transform 'reverse' failed with status 1: cannot reverse 'fail'"
+        );
+
+        manifest.abi_version = 2;
+        assert!(unsafe { registry.register_manifest(&manifest) }.is_err());
+    }
+}
@@ -0,0 +1,130 @@
+// Splitting one input into numbered shards
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use regex::bytes::Regex;
+
+#[derive(Debug, Clone)]
+pub enum SplitBy {
+    /// At most this many lines per shard.
+    Lines(usize),
+    /// At most this many bytes per shard, never breaking a line. A single
+    /// line longer than the limit gets a shard of its own.
+    Bytes(usize),
+    /// One record per shard; each match ends a record and stays with it.
+    Delimiter(Regex),
+}
+
+/// Splits content into shards whose concatenation is the original input.
+#[derive(Debug, Clone)]
+pub struct SplitTransform {
+    pub by: SplitBy,
+}
+
+impl SplitTransform {
+    pub fn new(by: SplitBy) -> Self {
+        Self { by }
+    }
+
+    pub fn by_lines(max_lines: usize) -> Self {
+        Self::new(SplitBy::Lines(max_lines))
+    }
+
+    pub fn by_bytes(max_bytes: usize) -> Self {
+        Self::new(SplitBy::Bytes(max_bytes))
+    }
+
+    pub fn by_delimiter(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("invalid delimiter pattern '{}'", pattern))?;
+        Ok(Self::new(SplitBy::Delimiter(regex)))
+    }
+
+    pub fn split<'a>(&self, input: &'a [u8]) -> Result<Vec<&'a [u8]>> {
+        let mut shards = Vec::new();
+        match &self.by {
+            SplitBy::Lines(0) | SplitBy::Bytes(0) => bail!("split size must be at least 1"),
+            SplitBy::Lines(max) => {
+                let mut start = 0;
+                let mut lines = 0;
+                for (i, _) in input.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+                    lines += 1;
+                    if lines == *max {
+                        shards.push(&input[start..=i]);
+                        start = i + 1;
+                        lines = 0;
+                    }
+                }
+                if start < input.len() {
+                    shards.push(&input[start..]);
+                }
+            }
+            SplitBy::Bytes(max) => {
+                let mut start = 0;
+                let mut end = 0;
+                for line in input.split_inclusive(|&b| b == b'\n') {
+                    if end > start && end - start + line.len() > *max {
+                        shards.push(&input[start..end]);
+                        start = end;
+                    }
+                    end += line.len();
+                }
+                if start < input.len() {
+                    shards.push(&input[start..]);
+                }
+            }
+            SplitBy::Delimiter(regex) => {
+                let mut start = 0;
+                for m in regex.find_iter(input) {
+                    // An empty match at a shard boundary would give an empty shard.
+                    if m.end() == start {
+                        continue;
+                    }
+                    shards.push(&input[start..m.end()]);
+                    start = m.end();
+                }
+                if start < input.len() {
+                    shards.push(&input[start..]);
+                }
+            }
+        }
+        Ok(shards)
+    }
+}
+
+/// Shard paths for `input` inside `dir`: `name.0001.ext`, `name.0002.ext`, …
+/// The counter is zero-padded to at least four digits.
+pub fn shard_paths(input: &Path, dir: &Path, count: usize) -> Vec<PathBuf> {
+    let stem = input.file_stem().map_or("shard".into(), |s| s.to_string_lossy().into_owned());
+    let ext = input.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let width = count.to_string().len().max(4);
+    (1..=count)
+        .map(|n| dir.join(format!("{}.{:0width$}{}", stem, n, ext, width = width)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(transform: SplitTransform, input: &str) -> Vec<String> {
+        let shards = transform.split(input.as_bytes()).unwrap();
+        assert_eq!(shards.concat(), input.as_bytes());
+        shards.iter().map(|s| String::from_utf8(s.to_vec()).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_split_modes() {
+        assert_eq!(split(SplitTransform::by_lines(2), "a\nb\nc\nd\ne"), vec!["a\nb\n", "c\nd\n", "e"]);
+        assert_eq!(split(SplitTransform::by_bytes(6), "ab\ncd\nlonger line\nx\n"), vec!["ab\ncd\n", "longer line\n", "x\n"]);
+        let records = split(SplitTransform::by_delimiter(r"(?m)^---\n").unwrap(), "one\n---\ntwo\n---\nthree\n");
+        assert_eq!(records, vec!["one\n---\n", "two\n---\n", "three\n"]);
+        assert!(split(SplitTransform::by_lines(3), "").is_empty());
+        assert!(SplitTransform::by_lines(0).split(b"x").is_err());
+    }
+
+    #[test]
+    fn test_shard_paths() {
+        let paths = shard_paths(Path::new("data/big.jsonl"), Path::new("shards"), 2);
+        assert_eq!(paths, vec![PathBuf::from("shards/big.0001.jsonl"), PathBuf::from("shards/big.0002.jsonl")]);
+        assert_eq!(shard_paths(Path::new("log"), Path::new("o"), 12345)[0], PathBuf::from("o/log.00001"));
+    }
+}
@@ -0,0 +1,135 @@
+// Text statistics and token estimation
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+
+/// Counts tokens for a model's tokenizer.
+pub trait TokenEstimator: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Approximates BPE tokenizers without a vocabulary: each run of letters or
+/// digits costs one token per four characters (rounded up) and every other
+/// non-whitespace character costs one. Typically within ~20% of cl100k for
+/// English prose and code.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl TokenEstimator for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        let mut tokens = 0;
+        let mut run = 0usize;
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                run += 1;
+                continue;
+            }
+            tokens += run.div_ceil(4);
+            run = 0;
+            if !c.is_whitespace() {
+                tokens += 1;
+            }
+        }
+        tokens + run.div_ceil(4)
+    }
+}
+
+/// Exact counts with OpenAI's `cl100k_base` encoding.
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenTokenizer {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenTokenizer {
+    pub fn cl100k() -> anyhow::Result<Self> {
+        Ok(Self { bpe: tiktoken_rs::cl100k_base()? })
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl TokenEstimator for TiktokenTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+    pub estimated_tokens: usize,
+    /// Length of the longest line in characters, excluding its line ending.
+    pub longest_line: usize,
+}
+
+/// Computes `TextStats` for a document. In a pipeline it records the stats
+/// as metadata and leaves the content unchanged.
+#[derive(Clone)]
+pub struct StatsTransform {
+    tokenizer: Arc<dyn TokenEstimator>,
+}
+
+impl StatsTransform {
+    pub fn new() -> Self {
+        Self::with_tokenizer(Arc::new(HeuristicTokenizer))
+    }
+
+    pub fn with_tokenizer(tokenizer: Arc<dyn TokenEstimator>) -> Self {
+        Self { tokenizer }
+    }
+
+    pub fn compute(&self, text: &str) -> TextStats {
+        let mut stats = TextStats {
+            bytes: text.len(),
+            estimated_tokens: self.tokenizer.count_tokens(text),
+            ..TextStats::default()
+        };
+        for line in text.lines() {
+            let chars = line.chars().count();
+            stats.lines += 1;
+            stats.chars += chars;
+            stats.words += line.split_whitespace().count();
+            stats.longest_line = stats.longest_line.max(chars);
+        }
+        // `lines()` drops line endings; count them back in.
+        stats.chars += text.chars().filter(|&c| c == '\n' || c == '\r').count();
+        stats
+    }
+}
+
+impl Default for StatsTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let stats = StatsTransform::new().compute("héllo world\r\n\nthe end");
+        assert_eq!(
+            stats,
+            TextStats {
+                lines: 3,
+                words: 4,
+                chars: 21,
+                bytes: 22,
+                estimated_tokens: 6,
+                longest_line: 11,
+            }
+        );
+        assert_eq!(StatsTransform::new().compute(""), TextStats::default());
+    }
+
+    #[test]
+    fn test_heuristic_tokenizer() {
+        assert_eq!(HeuristicTokenizer.count_tokens("fn main() {}"), 6);
+        assert_eq!(HeuristicTokenizer.count_tokens("internationalization"), 5);
+        assert_eq!(HeuristicTokenizer.count_tokens("  \n\t "), 0);
+    }
+}
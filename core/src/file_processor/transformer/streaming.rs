@@ -0,0 +1,105 @@
+// Incremental (chunk-at-a-time) transform stages
+use anyhow::{Context, Result};
+
+/// A stage that can process its input incrementally. A fresh instance is
+/// created for each run, so implementations may keep state between chunks.
+pub trait StreamTransform: Send {
+    /// Processes one chunk, appending any ready output to `out`.
+    fn process(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<()>;
+    /// Flushes whatever is still buffered once the input is exhausted.
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()>;
+}
+
+/// Creates a fresh `StreamTransform` for each pipeline run.
+pub type StreamFactory = Box<dyn Fn() -> Box<dyn StreamTransform> + Send + Sync>;
+
+type LineFn = Box<dyn Fn(&str) -> Result<Option<String>> + Send + Sync>;
+
+/// Applies a function to each line. Lines are passed without their line
+/// ending; returning `None` drops the line. The original ending (`\n` or
+/// `\r\n`) is kept, and a final line without one stays without one.
+pub struct LineTransform {
+    name: String,
+    f: std::sync::Arc<LineFn>,
+    pending: Vec<u8>,
+}
+
+impl LineTransform {
+    pub(crate) fn factory<F>(name: String, f: F) -> StreamFactory
+    where
+        F: Fn(&str) -> Result<Option<String>> + Send + Sync + 'static,
+    {
+        let f: std::sync::Arc<LineFn> = std::sync::Arc::new(Box::new(f));
+        Box::new(move || {
+            Box::new(LineTransform {
+                name: name.clone(),
+                f: f.clone(),
+                pending: Vec::new(),
+            })
+        })
+    }
+
+    fn emit(&self, line: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let (body, ending): (&[u8], &[u8]) = match line {
+            [rest @ .., b'\r', b'\n'] => (rest, b"\r\n"),
+            [rest @ .., b'\n'] => (rest, b"\n"),
+            _ => (line, b""),
+        };
+        let text = std::str::from_utf8(body).with_context(|| format!("stage '{}' expects UTF-8 input", self.name))?;
+        if let Some(replaced) = (self.f)(text)? {
+            out.extend_from_slice(replaced.as_bytes());
+            out.extend_from_slice(ending);
+        }
+        Ok(())
+    }
+}
+
+impl StreamTransform for LineTransform {
+    fn process(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        self.pending.extend_from_slice(chunk);
+        let Some(last_newline) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Ok(());
+        };
+        let rest = self.pending.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        for line in complete.split_inclusive(|&b| b == b'\n') {
+            self.emit(line, out)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let rest = std::mem::take(&mut self.pending);
+        if !rest.is_empty() {
+            self.emit(&rest, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a whole buffer through a streaming stage, for the buffered pipeline path.
+pub(crate) fn run_buffered(mut stage: Box<dyn StreamTransform>, input: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len());
+    stage.process(input, &mut out)?;
+    stage.finish(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_split_across_chunks() {
+        let factory = LineTransform::factory("grep".into(), |line| {
+            Ok(line.contains("keep").then(|| line.replace("keep", "kept")))
+        });
+        let mut stage = factory();
+        let mut out = Vec::new();
+        for chunk in ["kee", "p 1\r\ndrop\nke", "ep é", "\nkeep last"] {
+            stage.process(chunk.as_bytes(), &mut out).unwrap();
+        }
+        stage.finish(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "kept 1\r\nkept é\nkept last");
+    }
+}
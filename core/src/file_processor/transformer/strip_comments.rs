@@ -0,0 +1,569 @@
+// Comment and docstring stripping for source code
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+
+/// Comment syntax families understood by `StripCommentsTransform`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    /// JavaScript and TypeScript.
+    JavaScript,
+    /// C, C++, Java, C# and Go.
+    CLike,
+}
+
+impl Language {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "mts" | "cts" | "tsx" => Some(Self::JavaScript),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "cs" | "go" => Some(Self::CLike),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|ext| ext.to_str()).and_then(Self::from_extension)
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    /// Accepts language names as well as file extensions.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "rust" => Ok(Self::Rust),
+            "python" => Ok(Self::Python),
+            "javascript" | "typescript" => Ok(Self::JavaScript),
+            "c-like" | "clike" | "c++" | "csharp" => Ok(Self::CLike),
+            other => Self::from_extension(other).ok_or_else(|| anyhow!("unknown language '{}'", s)),
+        }
+    }
+}
+
+/// Removes line and block comments (and Python docstrings) while leaving
+/// string and character literals intact. Lines left empty by a removed
+/// comment are dropped, and trailing whitespace before a removed comment is
+/// trimmed. This is a lexer-level state machine rather than a parser, so
+/// Python functions whose body is only a docstring end up without a body.
+#[derive(Debug, Clone)]
+pub struct StripCommentsTransform {
+    pub language: Language,
+    /// Keep `///`, `//!`, `/** */` and `/*! */` comments and Python docstrings.
+    pub keep_doc_comments: bool,
+}
+
+impl StripCommentsTransform {
+    pub fn new(language: Language) -> Self {
+        Self { language, keep_doc_comments: false }
+    }
+
+    /// Picks the language from the file extension.
+    pub fn for_path(path: &Path) -> Result<Self> {
+        Language::from_path(path)
+            .map(Self::new)
+            .ok_or_else(|| anyhow!("cannot infer the language of {}; pass it explicitly", path.display()))
+    }
+
+    pub fn with_keep_doc_comments(mut self, keep: bool) -> Self {
+        self.keep_doc_comments = keep;
+        self
+    }
+
+    pub fn apply(&self, input: &str) -> String {
+        let mut stripper = Stripper::new(input);
+        while stripper.pos < input.len() {
+            match self.language {
+                Language::Rust => self.step_rust(&mut stripper),
+                Language::CLike => self.step_c_like(&mut stripper),
+                Language::JavaScript => self.step_javascript(&mut stripper),
+                Language::Python => self.step_python(&mut stripper),
+            }
+        }
+        stripper.finish()
+    }
+
+    fn step_rust(&self, s: &mut Stripper) {
+        if s.starts_with("//") {
+            let doc = (s.starts_with("///") && !s.starts_with("////")) || s.starts_with("//!");
+            s.comment(s.line_end(), doc && self.keep_doc_comments);
+        } else if s.starts_with("/*") {
+            let doc = (s.starts_with("/**") && !s.starts_with("/**/") && !s.starts_with("/***")) || s.starts_with("/*!");
+            s.comment(s.block_end(true), doc && self.keep_doc_comments);
+        } else if let Some(end) = s.raw_string_end() {
+            s.copy_literal(end);
+        } else if s.peek(0) == Some(b'"') {
+            s.copy_literal(s.quoted_end(b'"'));
+        } else if s.peek(0) == Some(b'\'') {
+            // A quote is a char literal only if it closes right after one
+            // (possibly escaped) character; otherwise it starts a lifetime.
+            match s.char_literal_end() {
+                Some(end) => s.copy_literal(end),
+                None => s.copy_code(),
+            }
+        } else {
+            s.copy_code();
+        }
+    }
+
+    fn step_c_like(&self, s: &mut Stripper) {
+        if s.starts_with("//") {
+            let doc = s.starts_with("///") && !s.starts_with("////");
+            s.comment(s.line_end(), doc && self.keep_doc_comments);
+        } else if s.starts_with("/*") {
+            let doc = s.starts_with("/**") && !s.starts_with("/**/");
+            s.comment(s.block_end(false), doc && self.keep_doc_comments);
+        } else if let Some(quote @ (b'"' | b'\'')) = s.peek(0) {
+            s.copy_literal(s.quoted_end(quote));
+        } else if s.peek(0) == Some(b'`') {
+            // Go raw strings; backticks are not otherwise valid in this family.
+            let end = s.find_from(s.pos + 1, b"`").map_or(s.src.len(), |i| i + 1);
+            s.copy_literal(end);
+        } else {
+            s.copy_code();
+        }
+    }
+
+    fn step_javascript(&self, s: &mut Stripper) {
+        if s.starts_with("//") {
+            // `///` also covers TypeScript's triple-slash directives.
+            let doc = s.starts_with("///") && !s.starts_with("////");
+            s.comment(s.line_end(), doc && self.keep_doc_comments);
+        } else if s.starts_with("/*") {
+            let doc = s.starts_with("/**") && !s.starts_with("/**/");
+            s.comment(s.block_end(false), doc && self.keep_doc_comments);
+        } else if let Some(quote @ (b'"' | b'\'' | b'`')) = s.peek(0) {
+            s.copy_literal(s.quoted_end(quote));
+        } else if s.peek(0) == Some(b'/') && s.regex_allowed() {
+            match s.regex_end() {
+                Some(end) => s.copy_literal(end),
+                None => s.copy_code(),
+            }
+        } else {
+            s.copy_code();
+        }
+    }
+
+    fn step_python(&self, s: &mut Stripper) {
+        if s.pos == 0 && s.starts_with("#!") {
+            let end = s.line_end();
+            s.copy_literal(end);
+            // A module docstring may still follow the shebang.
+            s.python.docstring_allowed = true;
+            s.python.statement_start = s.out.len();
+        } else if s.peek(0) == Some(b'#') {
+            s.comment(s.line_end(), false);
+        } else if let Some(end) = s.python_string_end() {
+            if s.is_docstring(end) {
+                s.python.docstring_allowed = false;
+                s.comment(end, self.keep_doc_comments);
+            } else {
+                s.copy_literal(end);
+            }
+        } else if s.peek(0).is_some_and(is_ident_byte) {
+            // Copy whole identifiers so string prefixes are only recognised
+            // at the start of a word.
+            let end = (s.pos..s.src.len()).find(|&i| !is_ident_byte(s.bytes[i])).unwrap_or(s.src.len());
+            s.copy_literal(end);
+        } else {
+            s.copy_code();
+        }
+    }
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// Python statement tracking used to recognise docstrings.
+#[derive(Default)]
+struct PythonState {
+    /// Bracket nesting; newlines inside brackets do not end a statement.
+    depth: usize,
+    /// Start (in the output) of the current logical statement.
+    statement_start: usize,
+    /// The next statement is the first one of a module, class or function.
+    docstring_allowed: bool,
+}
+
+struct Stripper<'a> {
+    src: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    out: String,
+    /// Start of the current line in `out`.
+    line_start: usize,
+    /// A comment was removed from the current line.
+    touched: bool,
+    python: PythonState,
+}
+
+impl<'a> Stripper<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            bytes: src.as_bytes(),
+            pos: 0,
+            out: String::with_capacity(src.len()),
+            line_start: 0,
+            touched: false,
+            python: PythonState { docstring_allowed: true, ..PythonState::default() },
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.bytes[self.pos..].starts_with(prefix.as_bytes())
+    }
+
+    fn find_from(&self, from: usize, needle: &[u8]) -> Option<usize> {
+        self.bytes.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| from + i)
+    }
+
+    /// End of the current line, excluding its terminator.
+    fn line_end(&self) -> usize {
+        let end = self.find_from(self.pos, b"\n").unwrap_or(self.src.len());
+        if end > self.pos && self.bytes[end - 1] == b'\r' {
+            end - 1
+        } else {
+            end
+        }
+    }
+
+    fn block_end(&self, nested: bool) -> usize {
+        let mut depth = 1;
+        let mut i = self.pos + 2;
+        while i < self.src.len() {
+            if nested && self.bytes[i..].starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else if self.bytes[i..].starts_with(b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        self.src.len()
+    }
+
+    /// End of a string opened by `quote` at the current position, honouring
+    /// backslash escapes.
+    fn quoted_end(&self, quote: u8) -> usize {
+        let mut i = self.pos + 1;
+        while i < self.src.len() {
+            match self.bytes[i] {
+                b'\\' => i += 2,
+                b if b == quote => return i + 1,
+                _ => i += 1,
+            }
+        }
+        self.src.len()
+    }
+
+    /// Rust raw strings: `r"..."`, `r#"..."#`, `br##"..."##`.
+    fn raw_string_end(&self) -> Option<usize> {
+        if self.pos > 0 && is_ident_byte(self.bytes[self.pos - 1]) {
+            return None;
+        }
+        let mut i = self.pos;
+        if self.bytes.get(i) == Some(&b'b') {
+            i += 1;
+        }
+        if self.bytes.get(i) != Some(&b'r') {
+            return None;
+        }
+        i += 1;
+        let hashes = self.bytes[i..].iter().take_while(|&&b| b == b'#').count();
+        i += hashes;
+        if self.bytes.get(i) != Some(&b'"') {
+            return None;
+        }
+        let closing = [b"\"".as_slice(), &vec![b'#'; hashes]].concat();
+        Some(self.find_from(i + 1, &closing).map_or(self.src.len(), |end| end + closing.len()))
+    }
+
+    fn char_literal_end(&self) -> Option<usize> {
+        let start = self.pos + 1;
+        let end = if self.bytes.get(start) == Some(&b'\\') {
+            // Skip the escaped character itself, which may be a quote.
+            self.find_from(start + 2, b"'")?
+        } else {
+            let c = self.src.get(start..)?.chars().next()?;
+            if c == '\'' || c == '\n' {
+                return None;
+            }
+            start + c.len_utf8()
+        };
+        (self.bytes.get(end) == Some(&b'\'') && !self.src[start..end].contains('\n')).then_some(end + 1)
+    }
+
+    /// Whether a `/` here starts a regex literal rather than a division,
+    /// judged by the last significant character emitted.
+    fn regex_allowed(&self) -> bool {
+        let code = self.out.trim_end();
+        match code.bytes().last() {
+            None => true,
+            Some(b) if b"(,=:[!&|?{};+-*%<>~^".contains(&b) => true,
+            Some(_) => ["return", "typeof", "case", "do", "else", "in", "of", "void", "yield"].iter().any(|kw| {
+                code.ends_with(kw) && !code[..code.len() - kw.len()].bytes().last().is_some_and(is_ident_byte)
+            }),
+        }
+    }
+
+    fn regex_end(&self) -> Option<usize> {
+        let mut in_class = false;
+        let mut i = self.pos + 1;
+        while i < self.src.len() {
+            match self.bytes[i] {
+                b'\\' => i += 1,
+                b'\n' => return None,
+                b'[' => in_class = true,
+                b']' => in_class = false,
+                b'/' if !in_class => return Some(i + 1),
+                _ => {}
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Python string literals with optional prefixes such as `r`, `b`, `f`
+    /// or `rb`, single or triple quoted.
+    fn python_string_end(&self) -> Option<usize> {
+        let prefix = self.bytes[self.pos..].iter().take_while(|b| b"rRbBuUfF".contains(b)).count();
+        if prefix > 2 {
+            return None;
+        }
+        let open = self.pos + prefix;
+        let quote = *self.bytes.get(open).filter(|&&b| b == b'"' || b == b'\'')?;
+        let triple = [quote; 3];
+        if self.bytes[open..].starts_with(&triple) {
+            let mut i = open + 3;
+            while i < self.src.len() {
+                if self.bytes[i] == b'\\' {
+                    i += 2;
+                } else if self.bytes[i..].starts_with(&triple) {
+                    return Some(i + 3);
+                } else {
+                    i += 1;
+                }
+            }
+            return Some(self.src.len());
+        }
+        let mut i = open + 1;
+        while i < self.src.len() {
+            match self.bytes[i] {
+                b'\\' => i += 2,
+                b'\n' => return Some(i),
+                b if b == quote => return Some(i + 1),
+                _ => i += 1,
+            }
+        }
+        Some(self.src.len())
+    }
+
+    /// A string forming a whole statement at the start of a module, class or
+    /// function body.
+    fn is_docstring(&self, end: usize) -> bool {
+        let rest = self.src[end..].trim_start_matches([' ', '\t']);
+        self.python.docstring_allowed
+            && self.python.depth == 0
+            && self.out[self.line_start..].trim().is_empty()
+            && (rest.is_empty() || rest.starts_with(['\n', '\r', '#']))
+    }
+
+    /// Removes `pos..end`, or copies it when `keep` is set.
+    fn comment(&mut self, end: usize, keep: bool) {
+        if keep {
+            self.copy_literal(end);
+            return;
+        }
+        self.touched = true;
+        self.pos = end;
+        // Keep tokens on either side of an inline block comment apart, but
+        // do not leave a double space where it was.
+        let before = self.out[self.line_start..].chars().last();
+        let after = self.src[end..].chars().next();
+        match (before, after) {
+            (Some(b), Some(a)) if !b.is_whitespace() && !a.is_whitespace() => self.out.push(' '),
+            (Some(' ' | '\t'), Some(' ' | '\t')) => {
+                self.pos += self.src[end..].len() - self.src[end..].trim_start_matches([' ', '\t']).len();
+            }
+            _ => {}
+        }
+    }
+
+    /// Copies `pos..end` verbatim, e.g. a string literal.
+    fn copy_literal(&mut self, end: usize) {
+        let text = &self.src[self.pos..end];
+        self.out.push_str(text);
+        if let Some(i) = text.rfind('\n') {
+            self.line_start = self.out.len() - (text.len() - i - 1);
+            self.touched = false;
+        }
+        self.python.docstring_allowed = false;
+        self.pos = end;
+    }
+
+    /// Copies one character of code, handling line ends.
+    fn copy_code(&mut self) {
+        let newline = if self.starts_with("\r\n") { "\r\n" } else { "\n" };
+        if self.starts_with(newline) {
+            if !self.end_line() {
+                self.out.push_str(newline);
+            }
+            self.line_start = self.out.len();
+            self.end_statement();
+            self.pos += newline.len();
+            return;
+        }
+
+        let c = self.src[self.pos..].chars().next().expect("pos is within the input");
+        match c {
+            '(' | '[' | '{' => self.python.depth += 1,
+            ')' | ']' | '}' => self.python.depth = self.python.depth.saturating_sub(1),
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            self.python.docstring_allowed = false;
+        }
+        self.out.push(c);
+        self.pos += c.len_utf8();
+    }
+
+    /// Trims a line a comment was removed from. Returns true if that left it
+    /// empty, in which case the line is dropped.
+    fn end_line(&mut self) -> bool {
+        let touched = std::mem::take(&mut self.touched);
+        if !touched {
+            return false;
+        }
+        let kept = self.out[self.line_start..].trim_end().len();
+        self.out.truncate(self.line_start + kept);
+        kept == 0
+    }
+
+    fn end_statement(&mut self) {
+        if self.python.depth > 0 {
+            return;
+        }
+        let statement = self.out[self.python.statement_start..].trim();
+        if !statement.is_empty() {
+            let header = ["def ", "class ", "async def "].iter().any(|kw| statement.starts_with(kw));
+            self.python.docstring_allowed = header && statement.ends_with(':');
+            self.python.statement_start = self.out.len();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.end_line();
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(language: Language, input: &str) -> String {
+        StripCommentsTransform::new(language).apply(input)
+    }
+
+    #[test]
+    fn test_rust() {
+        let source = r##"//! Crate docs
+/// Item docs
+fn main() { // trailing
+    let s = "http://x"; /* inline */ let t = r#"/* not a comment */"#;
+    let c = '"'; let q = '\''; let u = '/';
+    /* outer /* nested */ still comment */
+    fn f<'a>(x: &'a str) -> &'a str { x } // lifetimes are not chars
+}
+"##;
+        let expected = r##"fn main() {
+    let s = "http://x"; let t = r#"/* not a comment */"#;
+    let c = '"'; let q = '\''; let u = '/';
+    fn f<'a>(x: &'a str) -> &'a str { x }
+}
+"##;
+        assert_eq!(strip(Language::Rust, source), expected);
+
+        let kept = StripCommentsTransform::new(Language::Rust).with_keep_doc_comments(true).apply(source);
+        assert!(kept.starts_with("//! Crate docs\n/// Item docs\nfn main() {\n"));
+        assert_eq!(strip(Language::Rust, "a/**/b"), "a b");
+    }
+
+    #[test]
+    fn test_python() {
+        let source = r#"#!/usr/bin/env python3
+"""Module docstring."""
+import os  # comment
+
+def f(x):
+    '''Docstring with # hash.'''
+    s = """not a docstring
+    # still string
+    """
+    return s + '#' + "it's"  # done
+
+class A:
+
+    r"""Raw docstring."""
+    value = {
+        "k":
+            "v",
+    }
+"#;
+        let expected = r#"#!/usr/bin/env python3
+import os
+
+def f(x):
+    s = """not a docstring
+    # still string
+    """
+    return s + '#' + "it's"
+
+class A:
+
+    value = {
+        "k":
+            "v",
+    }
+"#;
+        assert_eq!(strip(Language::Python, source), expected);
+        let kept = StripCommentsTransform::new(Language::Python).with_keep_doc_comments(true).apply(source);
+        assert!(kept.contains("    '''Docstring with # hash.'''\n    s = "));
+        assert!(!kept.contains("# comment"));
+    }
+
+    #[test]
+    fn test_javascript_and_c_like() {
+        let js = "const url = `http://${host}/*x*/`; // c\nconst re = /\\/\\/[/*]/g; /** doc */\nlet n = a / b / c;\n";
+        assert_eq!(
+            strip(Language::JavaScript, js),
+            "const url = `http://${host}/*x*/`;\nconst re = /\\/\\/[/*]/g;\nlet n = a / b / c;\n"
+        );
+        let c = "/*\n * License\n */\nchar q = '\"'; // quote\r\nputs(\"/* hi */\");\r\n";
+        assert_eq!(strip(Language::CLike, c), "char q = '\"';\r\nputs(\"/* hi */\");\r\n");
+    }
+
+    #[test]
+    fn test_language_selection() {
+        assert_eq!(StripCommentsTransform::for_path(Path::new("src/app.tsx")).unwrap().language, Language::JavaScript);
+        assert!(StripCommentsTransform::for_path(Path::new("notes.txt")).is_err());
+        assert_eq!("python".parse::<Language>().unwrap(), Language::Python);
+        assert_eq!("rs".parse::<Language>().unwrap(), Language::Rust);
+        assert!("cobol".parse::<Language>().is_err());
+    }
+}
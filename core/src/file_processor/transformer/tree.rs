@@ -0,0 +1,204 @@
+// Transforming a whole directory tree into a mirror of it
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use glob::{MatchOptions, Pattern};
+
+use super::FileTransformer;
+use crate::file_processor::walker::DirWalker;
+use crate::file_processor::writer::DEFAULT_WRITE_CONCURRENCY;
+
+/// Which files `FileTransformer::transform_tree` takes and how.
+#[derive(Debug, Clone)]
+pub struct TreeOptions {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// Files transformed at once.
+    pub concurrency: usize,
+    /// Stop at the first file that fails instead of going on with the rest.
+    pub fail_fast: bool,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self { include: Vec::new(), exclude: Vec::new(), concurrency: DEFAULT_WRITE_CONCURRENCY, fail_fast: false }
+    }
+}
+
+impl TreeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only takes files matching one of the included globs, if any are
+    /// given. A glob without `/` matches file names anywhere in the tree,
+    /// such as `*.rs`; one with `/` matches paths from the source root, in
+    /// which `*` stays within a directory and `**` spans any number, such
+    /// as `src/**/*.rs`.
+    pub fn with_include(mut self, glob: &str) -> Result<Self> {
+        self.include.push(Pattern::new(glob).with_context(|| format!("Invalid pattern '{}'", glob))?);
+        Ok(self)
+    }
+
+    /// Leaves out files matching `glob`, even if included.
+    pub fn with_exclude(mut self, glob: &str) -> Result<Self> {
+        self.exclude.push(Pattern::new(glob).with_context(|| format!("Invalid pattern '{}'", glob))?);
+        Ok(self)
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Whether the file at `relative`, a path from the source root, is taken.
+    pub fn selects(&self, relative: &Path) -> bool {
+        let path = relative.to_string_lossy().replace('\\', "/");
+        let name = path.rsplit('/').next().unwrap_or_default();
+        let matches = |pattern: &Pattern| {
+            let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+            match pattern.as_str().contains('/') {
+                true => pattern.matches_with(&path, options),
+                false => pattern.matches_with(name, options),
+            }
+        };
+        (self.include.is_empty() || self.include.iter().any(matches)) && !self.exclude.iter().any(matches)
+    }
+}
+
+/// One file of a `transform_tree` run.
+#[derive(Debug)]
+pub struct TreeEntry {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    /// Whether the output was written, which it is not when it already
+    /// held the transformed content, or why the file failed.
+    pub result: Result<bool>,
+}
+
+/// The files `transform_tree` took, in path order.
+#[derive(Debug, Default)]
+pub struct TreeReport {
+    pub entries: Vec<TreeEntry>,
+    /// Files left out by the include and exclude globs.
+    pub skipped: usize,
+}
+
+impl TreeReport {
+    /// Outputs written.
+    pub fn written(&self) -> usize {
+        self.entries.iter().filter(|entry| matches!(entry.result, Ok(true))).count()
+    }
+
+    /// Outputs that already held what the pipeline produced.
+    pub fn unchanged(&self) -> usize {
+        self.entries.iter().filter(|entry| matches!(entry.result, Ok(false))).count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &TreeEntry> {
+        self.entries.iter().filter(|entry| entry.result.is_err())
+    }
+
+    pub fn all_succeeded(&self) -> bool {
+        self.entries.iter().all(|entry| entry.result.is_ok())
+    }
+}
+
+impl FileTransformer {
+    /// Runs every file under `src_root` through the pipeline into the same
+    /// relative path under `dst_root`, creating directories as needed, up
+    /// to `options.concurrency` files at a time. Files are listed by
+    /// `DirWalker` before any is written, and those under `dst_root`, when
+    /// it lies within `src_root`, are left out. A file that fails is
+    /// recorded in the report and the rest go on; with `options.fail_fast`
+    /// the first failure is returned instead, leaving what was written so
+    /// far in place. Like `transform_file`, an output already holding the
+    /// transformed content is not written again. The tree is listed from
+    /// disk, whichever `Filesystem` the files are then read through.
+    pub async fn transform_tree<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        src_root: P,
+        dst_root: Q,
+        options: &TreeOptions,
+    ) -> Result<TreeReport> {
+        let (src_root, dst_root) = (src_root.as_ref(), dst_root.as_ref());
+        let walk = DirWalker::new(src_root).walk().await?;
+        let mut report = TreeReport::default();
+        let mut files = Vec::new();
+        for source in walk.files {
+            if source.starts_with(dst_root) {
+                continue;
+            }
+            let relative = source.strip_prefix(src_root).unwrap_or(&source).to_path_buf();
+            if options.selects(&relative) {
+                files.push((source, dst_root.join(relative)));
+            } else {
+                report.skipped += 1;
+            }
+        }
+
+        let mut results = futures::stream::iter(files)
+            .map(|(source, output)| async move {
+                let result = self.transform_into(&source, &output).await;
+                TreeEntry { source, output, result }
+            })
+            .buffered(options.concurrency.max(1));
+        while let Some(entry) = results.next().await {
+            let entry = match entry.result {
+                Err(e) if options.fail_fast => {
+                    return Err(e.context(format!("Failed to transform {}", entry.source.display())));
+                }
+                result => TreeEntry { result, ..entry },
+            };
+            report.entries.push(entry);
+        }
+        Ok(report)
+    }
+
+    async fn transform_into(&self, source: &Path, output: &Path) -> Result<bool> {
+        if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            self.fs.create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        Ok(self.transform_file(source, output, false).await?.written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::TransformPipeline;
+
+    #[tokio::test]
+    async fn test_transform_tree_mirrors_selected_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::create_dir_all(src.join("sub/deep")).unwrap();
+        std::fs::write(src.join("a.txt"), "a").unwrap();
+        std::fs::write(src.join("sub/deep/b.txt"), "b").unwrap();
+        std::fs::write(src.join("sub/c.log"), "c").unwrap();
+        std::fs::write(src.join("sub/skip.txt"), "s").unwrap();
+        std::fs::write(src.join("bad.txt"), [0xff, 0xfe]).unwrap();
+        let transformer =
+            FileTransformer::with_pipeline(TransformPipeline::new().text_stage("upper", |s| Ok(s.to_uppercase())));
+        let options = TreeOptions::new().with_include("*.txt").unwrap().with_exclude("sub/*.txt").unwrap().with_concurrency(2);
+
+        let report = transformer.transform_tree(&src, &dst, &options).await.unwrap();
+        assert_eq!((report.written(), report.unchanged(), report.skipped), (2, 0, 2));
+        let failed: Vec<_> = report.failures().map(|entry| entry.source.clone()).collect();
+        assert_eq!(failed, vec![src.join("bad.txt")]);
+        assert_eq!(std::fs::read_to_string(dst.join("a.txt")).unwrap(), "A");
+        assert_eq!(std::fs::read_to_string(dst.join("sub/deep/b.txt")).unwrap(), "B");
+        assert!(!dst.join("sub/c.log").exists() && !dst.join("sub/skip.txt").exists());
+
+        let report = transformer.transform_tree(&src, &dst, &options).await.unwrap();
+        assert_eq!((report.written(), report.unchanged()), (0, 2));
+
+        let error = transformer.transform_tree(&src, &dst, &options.with_fail_fast(true)).await.unwrap_err();
+        assert!(error.to_string().contains("bad.txt"), "{:#}", error);
+    }
+}
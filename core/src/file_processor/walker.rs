@@ -0,0 +1,161 @@
+// Deterministic directory traversal
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+/// Order in which `DirWalker` returns files. Ties are broken by path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    /// Byte-wise path order, independent of locale.
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+/// A file skipped because its content matches an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedDuplicate {
+    pub path: PathBuf,
+    /// The file that was kept.
+    pub original: PathBuf,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkResult {
+    pub files: Vec<PathBuf>,
+    pub duplicates: Vec<SkippedDuplicate>,
+}
+
+#[derive(Debug, Clone)]
+struct FileInfo {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Recursively lists the regular files under a root in a reproducible order.
+/// Symbolic links are not followed.
+#[derive(Debug, Clone)]
+pub struct DirWalker {
+    root: PathBuf,
+    sort: SortBy,
+    dedup: bool,
+}
+
+impl DirWalker {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), sort: SortBy::default(), dedup: false }
+    }
+
+    pub fn with_sort(mut self, sort: SortBy) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Skips files whose SHA-256 matches a file earlier in the sort order.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub async fn walk(&self) -> Result<WalkResult> {
+        let mut files = Vec::new();
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir)
+                .await
+                .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    pending.push(entry.path());
+                } else if file_type.is_file() {
+                    let metadata = entry.metadata().await?;
+                    files.push(FileInfo {
+                        path: entry.path(),
+                        size: metadata.len(),
+                        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    });
+                }
+            }
+        }
+
+        match self.sort {
+            SortBy::Name => files.sort_by(|a, b| a.path.cmp(&b.path)),
+            SortBy::Size => files.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.path.cmp(&b.path))),
+            SortBy::Modified => {
+                files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)))
+            }
+        }
+
+        if !self.dedup {
+            return Ok(WalkResult { files: files.into_iter().map(|f| f.path).collect(), duplicates: Vec::new() });
+        }
+        dedup(files).await
+    }
+}
+
+async fn dedup(files: Vec<FileInfo>) -> Result<WalkResult> {
+    // Only files sharing a size can be duplicates, so unique sizes are never read.
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    for file in &files {
+        *size_counts.entry(file.size).or_default() += 1;
+    }
+
+    let mut seen: HashMap<(u64, [u8; 32]), PathBuf> = HashMap::new();
+    let mut result = WalkResult::default();
+    for file in files {
+        if size_counts[&file.size] > 1 {
+            let contents = tokio::fs::read(&file.path)
+                .await
+                .with_context(|| format!("Failed to read file: {}", file.path.display()))?;
+            let digest: [u8; 32] = Sha256::digest(&contents).into();
+            if let Some(original) = seen.get(&(file.size, digest)) {
+                result.duplicates.push(SkippedDuplicate { path: file.path, original: original.clone() });
+                continue;
+            }
+            seen.insert((file.size, digest), file.path.clone());
+        }
+        result.files.push(file.path);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sorted_walk_and_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        tokio::fs::create_dir_all(root.join("b/nested")).await.unwrap();
+        tokio::fs::write(root.join("c.txt"), "same").await.unwrap();
+        tokio::fs::write(root.join("a.txt"), "longer text").await.unwrap();
+        tokio::fs::write(root.join("b/nested/d.txt"), "same").await.unwrap();
+        tokio::fs::write(root.join("B.txt"), "x").await.unwrap();
+
+        let names = |paths: &[PathBuf]| -> Vec<String> {
+            paths.iter().map(|p| p.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/")).collect()
+        };
+
+        let walk = DirWalker::new(root).walk().await.unwrap();
+        assert_eq!(names(&walk.files), ["B.txt", "a.txt", "b/nested/d.txt", "c.txt"]);
+
+        let walk = DirWalker::new(root).with_sort(SortBy::Size).walk().await.unwrap();
+        assert_eq!(names(&walk.files), ["B.txt", "b/nested/d.txt", "c.txt", "a.txt"]);
+
+        let walk = DirWalker::new(root).with_dedup(true).walk().await.unwrap();
+        assert_eq!(names(&walk.files), ["B.txt", "a.txt", "b/nested/d.txt"]);
+        assert_eq!(walk.duplicates, [SkippedDuplicate { path: root.join("c.txt"), original: root.join("b/nested/d.txt") }]);
+
+        assert!(DirWalker::new(root.join("missing")).walk().await.is_err());
+    }
+}
@@ -0,0 +1,107 @@
+// Watching a directory tree for changed files
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use futures::Stream;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+/// How long the tree must be quiet before a batch of changes is emitted.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+/// Batches held for a slow consumer before changes wait in the watcher.
+const BATCH_BUFFER: usize = 16;
+
+pub struct FileWatcher {
+    root: PathBuf,
+    debounce: Duration,
+}
+
+impl FileWatcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), debounce: DEFAULT_DEBOUNCE }
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Starts watching the tree; must be called inside a Tokio runtime. Each
+    /// item of the stream is the sorted set of files created or modified
+    /// since the previous one, emitted once no change has been seen for the
+    /// debounce interval, so a burst of writes to one file yields it once.
+    /// Files removed before the batch is emitted are left out.
+    pub fn watch(&self) -> Result<WatchStream> {
+        let (changes_tx, mut changes) = mpsc::unbounded_channel::<Vec<PathBuf>>();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                let _ = changes_tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("file watcher error: {}", e),
+        })
+        .context("Failed to start the file watcher")?;
+        watcher
+            .watch(&self.root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", self.root.display()))?;
+
+        let (tx, batches) = mpsc::channel(BATCH_BUFFER);
+        let debounce = self.debounce;
+        // Ends when the stream, and with it the watcher and `changes_tx`, is dropped.
+        tokio::spawn(async move {
+            while let Some(paths) = changes.recv().await {
+                let mut pending: BTreeSet<PathBuf> = paths.into_iter().collect();
+                while let Ok(Some(paths)) = tokio::time::timeout(debounce, changes.recv()).await {
+                    pending.extend(paths);
+                }
+                let files: Vec<PathBuf> = pending.into_iter().filter(|path| path.is_file()).collect();
+                if !files.is_empty() && tx.send(files).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(WatchStream { batches, _watcher: watcher })
+    }
+}
+
+/// Batches of changed files; dropping the stream stops watching.
+pub struct WatchStream {
+    batches: mpsc::Receiver<Vec<PathBuf>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Stream for WatchStream {
+    type Item = Vec<PathBuf>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Vec<PathBuf>>> {
+        self.batches.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_changes_are_debounced_and_coalesced() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        let mut stream = FileWatcher::new(&root).with_debounce(Duration::from_millis(150)).watch().unwrap();
+
+        for i in 0..5 {
+            std::fs::write(root.join("a.txt"), format!("{}", i)).unwrap();
+            std::fs::write(root.join("sub/b.txt"), "b").unwrap();
+        }
+        std::fs::write(root.join("gone.txt"), "x").unwrap();
+        std::fs::remove_file(root.join("gone.txt")).unwrap();
+
+        let batch = tokio::time::timeout(Duration::from_secs(5), stream.next()).await.unwrap().unwrap();
+        assert_eq!(batch, vec![root.join("a.txt"), root.join("sub/b.txt")]);
+        assert!(tokio::time::timeout(Duration::from_millis(400), stream.next()).await.is_err());
+    }
+}
@@ -1,22 +1,498 @@
 // File writer implementation
-use std::path::Path;
-use anyhow::Result;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 
-pub struct FileWriter;
+use super::fs::{Filesystem, RealFs};
+use super::progress::ProgressThrottle;
+use crate::safe_mode;
+
+/// Size of each chunk handed to the OS by the chunked write paths.
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+/// Files written at once by `write_many`.
+pub const DEFAULT_WRITE_CONCURRENCY: usize = 8;
+/// Bytes a `BufferedWriter` holds before writing them out.
+pub const DEFAULT_WRITE_BUFFER: usize = 64 * 1024;
+
+/// Writes files. The associated functions always use the host filesystem;
+/// instance methods go through the writer's `Filesystem` backend. In
+/// `safe_mode` every write fails with `CoreError::SafeMode`.
+pub struct FileWriter {
+    fs: Arc<dyn Filesystem>,
+}
 
 impl FileWriter {
     pub fn new() -> Self {
-        Self
+        Self::with_fs(Arc::new(RealFs))
+    }
+
+    pub fn with_fs(fs: Arc<dyn Filesystem>) -> Self {
+        Self { fs }
+    }
+
+    pub async fn write(&self, path: impl AsRef<Path>, content: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        check_writable(path)?;
+        self.fs
+            .write(path, content)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub async fn write_text(&self, path: impl AsRef<Path>, content: &str) -> Result<()> {
+        self.write(path, content.as_bytes()).await
+    }
+
+    /// Writes several files with up to `concurrency` writes in flight. Stops
+    /// at the first failure; files already written are left in place.
+    pub async fn write_many(&self, files: Vec<(PathBuf, Vec<u8>)>, concurrency: usize) -> Result<()> {
+        futures::stream::iter(files)
+            .map(|(path, content)| async move { self.write(&path, &content).await })
+            .buffer_unordered(concurrency.max(1))
+            .try_collect::<()>()
+            .await
     }
     
-    pub async fn write_file<P: AsRef<Path>>(_path: P, _content: &str) -> Result<()> {
-        // TODO: Implement high-performance file writing
-        todo!("Implement in T018")
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), bytes = content.len()))]
+    pub async fn write_file<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
+        let path = path.as_ref();
+        check_writable(path)?;
+        tokio::fs::write(path, content)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), bytes = content.len()))]
+    pub async fn write_bytes<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        check_writable(path)?;
+        tokio::fs::write(path, content)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Writes `content` in chunks, calling `on_progress(bytes_written, total_bytes)`
+    /// periodically and once more when the write completes.
+    pub async fn write_file_with_progress<P, F>(path: P, content: &str, on_progress: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(u64, u64),
+    {
+        let total = content.len() as u64;
+        Self::write_stream_with_progress(path, content.as_bytes(), total, on_progress).await?;
+        Ok(())
+    }
+
+    /// Streams `reader` into `path`, reporting progress against `total` (which may be
+    /// an estimate). Returns the number of bytes written.
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), total = total))]
+    pub async fn write_stream_with_progress<P, R, F>(
+        path: P,
+        mut reader: R,
+        total: u64,
+        on_progress: F,
+    ) -> Result<u64>
+    where
+        P: AsRef<Path>,
+        R: AsyncRead + Unpin,
+        F: Fn(u64, u64),
+    {
+        let path = path.as_ref();
+        check_writable(path)?;
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut progress = ProgressThrottle::new(total, on_progress);
+        let mut buf = vec![0u8; WRITE_CHUNK_SIZE];
+        let mut written = 0u64;
+
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n])
+                .await
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            written += n as u64;
+            progress.update(written);
+        }
+
+        file.flush().await?;
+        progress.finish(written);
+        tracing::debug!(bytes = written, "stream written");
+        Ok(written)
+    }
+
+    /// Appends `line` plus a newline (unless it already ends with one) to
+    /// `path`, creating the file if needed. The file is opened, locked,
+    /// written and closed on every call; see `AppendHandle` for the locking
+    /// semantics and for keeping the file open between lines.
+    pub async fn append_line<P: AsRef<Path>>(path: P, line: &str) -> Result<()> {
+        AppendHandle::open(path).await?.append_line(line).await
+    }
+
+    /// Appends `content` as-is to `path`, creating the file if needed.
+    pub async fn append<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<()> {
+        AppendHandle::open(path).await?.append(content.to_vec()).await
+    }
+}
+
+/// An open log file that whole lines are appended to, safe to share between
+/// tasks and processes.
+///
+/// Each line goes out in one write while an exclusive advisory lock is held:
+/// `flock` on Unix, `LockFileEx` on Windows (via `std::fs::File::lock`). The
+/// lock is advisory, so it only keeps out writers that also lock; on
+/// Windows it is mandatory and readers may briefly see the range as busy.
+/// Where the filesystem cannot lock (some network mounts), the line is
+/// written unlocked and relies on append mode alone, which keeps short lines
+/// intact on local filesystems but not on NFS.
+#[derive(Clone)]
+pub struct AppendHandle {
+    path: PathBuf,
+    file: Arc<std::fs::File>,
+    /// Serialises appends through this handle; the OS lock covers other handles.
+    in_process: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl AppendHandle {
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        check_writable(&path)?;
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?
+            .into_std()
+            .await;
+        Ok(Self {
+            path,
+            file: Arc::new(file),
+            in_process: Arc::new(tokio::sync::Mutex::new(())),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `line`, adding a trailing newline if it has none.
+    pub async fn append_line(&self, line: &str) -> Result<()> {
+        let mut record = line.as_bytes().to_vec();
+        if !record.ends_with(b"\n") {
+            record.push(b'\n');
+        }
+        self.append(record).await
+    }
+
+    /// Appends `record` in one locked write.
+    pub async fn append(&self, record: Vec<u8>) -> Result<()> {
+        let _guard = self.in_process.lock().await;
+        let file = Arc::clone(&self.file);
+        tokio::task::spawn_blocking(move || locked_append(&file, &record))
+            .await?
+            .with_context(|| format!("Failed to append to {}", self.path.display()))
+    }
+}
+
+fn locked_append(file: &std::fs::File, record: &[u8]) -> io::Result<()> {
+    let locked = match file.lock() {
+        Ok(()) => true,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            tracing::debug!("file locking unsupported, appending without a lock");
+            false
+        }
+        Err(e) => return Err(e),
+    };
+    let mut writer = file;
+    let written = writer.write_all(record).and_then(|_| writer.flush());
+    if locked {
+        file.unlock()?;
+    }
+    written
+}
+
+/// When a `BufferedWriter` hands what it holds to the OS.
+///
+/// Bytes handed to the OS survive the process crashing but not the machine
+/// losing power; only `sync` (or `Sync` mode) makes them durable. `Buffered`
+/// is by far the fastest, one system call per buffer; `Line` costs a call
+/// per line written and suits logs that are tailed or must survive a crash;
+/// `Sync` waits for the disk on every write, often milliseconds each, and
+/// is only worth it when losing the last record is not acceptable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushMode {
+    /// Write out when the buffer is full or on `flush`.
+    #[default]
+    Buffered,
+    /// Also write out every complete line as soon as it is written; a
+    /// partial line stays buffered until its newline arrives.
+    Line,
+    /// Write out and fsync on every write.
+    Sync,
+}
+
+/// A file written through an in-memory buffer, on the host filesystem.
+///
+/// Dropping the writer writes out what is still buffered, only logging a
+/// failure; call `flush` first to see errors.
+pub struct BufferedWriter {
+    path: PathBuf,
+    capacity: usize,
+    mode: FlushMode,
+    state: Arc<std::sync::Mutex<BufferState>>,
+}
+
+struct BufferState {
+    file: std::fs::File,
+    buffer: Vec<u8>,
+}
+
+impl BufferedWriter {
+    /// Creates `path`, truncating it if it exists.
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open(path, tokio::fs::OpenOptions::new().create(true).write(true).truncate(true)).await
+    }
+
+    /// Opens `path` for appending, creating it if needed.
+    pub async fn append<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open(path, tokio::fs::OpenOptions::new().create(true).append(true)).await
+    }
+
+    async fn open<P: AsRef<Path>>(path: P, options: &tokio::fs::OpenOptions) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        check_writable(&path)?;
+        let file = options
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?
+            .into_std()
+            .await;
+        Ok(Self {
+            path,
+            capacity: DEFAULT_WRITE_BUFFER,
+            mode: FlushMode::default(),
+            state: Arc::new(std::sync::Mutex::new(BufferState { file, buffer: Vec::new() })),
+        })
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_flush_mode(mut self, mode: FlushMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn write(&self, bytes: &[u8]) -> Result<()> {
+        {
+            let mut state = self.lock();
+            if !self.must_write(&state.buffer, bytes) {
+                state.buffer.extend_from_slice(bytes);
+                return Ok(());
+            }
+        }
+        let bytes = bytes.to_vec();
+        let (capacity, mode) = (self.capacity, self.mode);
+        self.blocking(move |state| {
+            state.buffer.extend_from_slice(&bytes);
+            let cut = match mode {
+                FlushMode::Line if state.buffer.len() < capacity => {
+                    state.buffer.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+                }
+                _ => state.buffer.len(),
+            };
+            state.write_out(cut)?;
+            if mode == FlushMode::Sync {
+                state.file.sync_data()?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Writes `line`, adding a trailing newline if it has none.
+    pub async fn write_line(&self, line: &str) -> Result<()> {
+        if line.ends_with('\n') {
+            return self.write(line.as_bytes()).await;
+        }
+        let mut record = Vec::with_capacity(line.len() + 1);
+        record.extend_from_slice(line.as_bytes());
+        record.push(b'\n');
+        self.write(&record).await
+    }
+
+    /// Hands everything buffered to the OS.
+    pub async fn flush(&self) -> Result<()> {
+        self.blocking(|state| state.write_out(state.buffer.len())).await
+    }
+
+    /// Flushes, then waits until the file's contents and metadata are on
+    /// durable storage.
+    pub async fn sync(&self) -> Result<()> {
+        self.blocking(|state| {
+            state.write_out(state.buffer.len())?;
+            state.file.sync_all()
+        })
+        .await
+    }
+
+    /// Whether writing `bytes` after `buffer` writes something out, rather
+    /// than only buffering.
+    fn must_write(&self, buffer: &[u8], bytes: &[u8]) -> bool {
+        let full = buffer.len() + bytes.len() >= self.capacity;
+        match self.mode {
+            FlushMode::Buffered => full,
+            FlushMode::Line => full || bytes.contains(&b'\n'),
+            FlushMode::Sync => true,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BufferState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn blocking<F>(&self, op: F) -> Result<()>
+    where
+        F: FnOnce(&mut BufferState) -> io::Result<()> + Send + 'static,
+    {
+        let state = Arc::clone(&self.state);
+        tokio::task::spawn_blocking(move || op(&mut state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())))
+            .await?
+            .with_context(|| format!("Failed to write {}", self.path.display()))
     }
 }
 
+impl BufferState {
+    /// Writes the first `len` buffered bytes to the file.
+    fn write_out(&mut self, len: usize) -> io::Result<()> {
+        if len > 0 {
+            self.file.write_all(&self.buffer[..len])?;
+            self.buffer.drain(..len);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BufferedWriter {
+    fn drop(&mut self) {
+        let mut state = self.lock();
+        let len = state.buffer.len();
+        if let Err(e) = state.write_out(len) {
+            tracing::warn!(path = %self.path.display(), error = %e, "buffered writes lost on drop");
+        }
+    }
+}
+
+/// Fails with `CoreError::SafeMode` if `path` may not be written.
+pub(crate) fn check_writable(path: &Path) -> Result<()> {
+    Ok(safe_mode::check(|| format!("writing {}", path.display()))?)
+}
+
 impl Default for FileWriter {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_write_file_with_progress_reports_completion() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        let content = "x".repeat(3 * WRITE_CHUNK_SIZE + 17);
+        let reports = Mutex::new(Vec::new());
+
+        FileWriter::write_file_with_progress(&path, &content, |done, total| {
+            reports.lock().unwrap().push((done, total));
+        })
+        .await
+        .unwrap();
+
+        let reports = reports.into_inner().unwrap();
+        let total = content.len() as u64;
+        assert_eq!(reports.last(), Some(&(total, total)));
+        assert!(reports.iter().all(|&(_, t)| t == total));
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_appends_keep_lines_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.log");
+        let handle = AppendHandle::open(&path).await.unwrap();
+
+        let mut tasks = Vec::new();
+        for task in 0..8 {
+            let handle = handle.clone();
+            let path = path.clone();
+            tasks.push(tokio::spawn(async move {
+                for i in 0..25 {
+                    let line = format!("{}-{}-{}", task, i, "x".repeat(4096));
+                    if i % 2 == 0 {
+                        handle.append_line(&line).await.unwrap();
+                    } else {
+                        FileWriter::append_line(&path, &line).await.unwrap();
+                    }
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let content = tokio::fs::read_to_string(&path).await.unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 200);
+        assert!(lines.iter().all(|line| line.ends_with(&"x".repeat(4096)) && line.len() > 4096));
+    }
+
+    #[tokio::test]
+    async fn test_buffered_writer_flush_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let on_disk = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+
+        let writer = BufferedWriter::create(dir.path().join("buffered")).await.unwrap().with_capacity(8);
+        writer.write(b"abc\n").await.unwrap();
+        assert_eq!(on_disk("buffered"), "");
+        writer.write(b"defg").await.unwrap();
+        assert_eq!(on_disk("buffered"), "abc\ndefg");
+        writer.write(b"h").await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(on_disk("buffered"), "abc\ndefgh");
+
+        let log = BufferedWriter::append(dir.path().join("line")).await.unwrap().with_flush_mode(FlushMode::Line);
+        log.write(b"partial").await.unwrap();
+        assert_eq!(on_disk("line"), "");
+        log.write(b" line\nnext").await.unwrap();
+        assert_eq!(on_disk("line"), "partial line\n");
+        log.write_line("").await.unwrap();
+        assert_eq!(on_disk("line"), "partial line\nnext\n");
+        log.write(b"tail").await.unwrap();
+        drop(log);
+        assert_eq!(on_disk("line"), "partial line\nnext\ntail");
+
+        let durable = BufferedWriter::append(dir.path().join("line")).await.unwrap().with_flush_mode(FlushMode::Sync);
+        durable.write(b"!").await.unwrap();
+        assert_eq!(on_disk("line"), "partial line\nnext\ntail!");
+        durable.write(b"?").await.unwrap();
+        durable.sync().await.unwrap();
+        assert_eq!(on_disk("line"), "partial line\nnext\ntail!?");
+    }
+}
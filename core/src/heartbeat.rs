@@ -0,0 +1,124 @@
+// Periodic liveness events while long work is running
+use std::future::Future;
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+
+/// How often a heartbeat is sent when nothing else is configured.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One sign of life from work still in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Heartbeat {
+    /// Counts from 1.
+    pub beat: u64,
+    /// Time since the work started.
+    pub elapsed: Duration,
+}
+
+/// Runs `work`, sending a `Heartbeat` on `beats` every `interval` until it
+/// finishes, whether it succeeds or fails. The first one is sent after one
+/// `interval`, and none after `work` has finished. Nothing is sent if the
+/// receiver is gone or `interval` is zero.
+pub async fn with_heartbeat<F: Future>(work: F, interval: Duration, beats: mpsc::UnboundedSender<Heartbeat>) -> F::Output {
+    if interval.is_zero() {
+        return work.await;
+    }
+    let started = tokio::time::Instant::now();
+    let mut ticks = tokio::time::interval_at(started + interval, interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    tokio::pin!(work);
+    let mut beat = 0;
+    loop {
+        tokio::select! {
+            // Checked first, so work that is done never beats again.
+            biased;
+            output = &mut work => return output,
+            _ = ticks.tick(), if !beats.is_closed() => {
+                beat += 1;
+                let _ = beats.send(Heartbeat { beat, elapsed: started.elapsed() });
+            }
+        }
+    }
+}
+
+/// Calls a callback with a `Heartbeat` every `interval` from a thread of
+/// its own, for work that blocks. Dropping it stops the heartbeat at once,
+/// waiting for a callback already running to return.
+#[derive(Debug)]
+pub struct HeartbeatThread {
+    stop: Option<std_mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeartbeatThread {
+    /// Starts beating; a zero `interval` never calls `on_beat`.
+    pub fn start(interval: Duration, mut on_beat: impl FnMut(Heartbeat) + Send + 'static) -> Self {
+        if interval.is_zero() {
+            return Self { stop: None, thread: None };
+        }
+        let (stop, stopped) = std_mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let started = Instant::now();
+            let mut beat = 0;
+            // Both a stop message and the sender being dropped end the loop.
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                beat += 1;
+                on_beat(Heartbeat { beat, elapsed: started.elapsed() });
+            }
+        });
+        Self { stop: Some(stop), thread: Some(thread) }
+    }
+}
+
+impl Drop for HeartbeatThread {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_heartbeat_stops_with_the_work() {
+        let (tx, mut beats) = mpsc::unbounded_channel();
+        let work = async {
+            tokio::time::sleep(Duration::from_millis(110)).await;
+            Err::<(), _>("failed")
+        };
+        assert_eq!(with_heartbeat(work, Duration::from_millis(20), tx).await, Err("failed"));
+        let mut seen = Vec::new();
+        // The sender went with the work, so this ends once the beats are read.
+        while let Some(heartbeat) = beats.recv().await {
+            seen.push(heartbeat.beat);
+        }
+        assert!((3..=6).contains(&seen.len()), "{:?}", seen);
+        assert_eq!(seen, (1..=seen.len() as u64).collect::<Vec<_>>());
+
+        let (tx, mut beats) = mpsc::unbounded_channel();
+        assert_eq!(with_heartbeat(async { 7 }, Duration::ZERO, tx).await, 7);
+        assert!(beats.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_thread_stops_when_dropped() {
+        let beats = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&beats);
+        let heartbeat = HeartbeatThread::start(Duration::from_millis(20), move |beat| recorded.lock().unwrap().push(beat.beat));
+        std::thread::sleep(Duration::from_millis(110));
+        drop(heartbeat);
+        let count = beats.lock().unwrap().len();
+        assert!((3..=6).contains(&count), "{} beats", count);
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(beats.lock().unwrap().len(), count);
+    }
+}
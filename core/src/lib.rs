@@ -4,11 +4,24 @@
 pub mod file_processor;
 pub mod tools;
 pub mod system;
+pub mod error;
+pub mod cancel;
+pub mod models;
+pub mod task;
+pub mod plan;
+pub mod safe_mode;
+pub mod heartbeat;
 
 // Re-export main functionality
 pub use file_processor::*;
 pub use tools::*;
 pub use system::*;
+pub use error::CoreError;
+pub use cancel::CancellationToken;
+pub use models::{ModelBackend, ModelError, ModelRegistry, ModelSpec, AUTO_MODEL};
+pub use task::TaskResult;
+pub use heartbeat::{with_heartbeat, Heartbeat, HeartbeatThread, DEFAULT_HEARTBEAT_INTERVAL};
+pub use plan::{execute_task_plan, plan_task, PlannedAction, MAX_PLANNED_ACTIONS};
 
 #[cfg(test)]
 mod tests {
@@ -16,7 +29,9 @@ mod tests {
 
     #[test]
     fn test_core_library_loads() {
-        // Basic test to ensure library compiles
-        assert!(true);
+        // Basic test to ensure the re-exports resolve
+        let _ = FileReader::new();
+        let _ = ToolExecutor::new();
+        let _ = PathUtils::new();
     }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+// Model selection: maps the names accepted by `--model` to backends
+use std::collections::BTreeMap;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Resolves to the registry's default model.
+pub const AUTO_MODEL: &str = "auto";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ModelError {
+    #[error("unknown model '{name}'; available models: {}", available.join(", "))]
+    UnknownModel { name: String, available: Vec<String> },
+}
+
+/// Where inference for a model runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModelBackend {
+    /// A Hugging Face `transformers` checkpoint loaded by the Python side.
+    Transformers { checkpoint: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelSpec {
+    pub name: String,
+    pub description: String,
+    pub backend: ModelBackend,
+}
+
+impl ModelSpec {
+    pub fn transformers(name: impl Into<String>, checkpoint: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            backend: ModelBackend::Transformers { checkpoint: checkpoint.into() },
+        }
+    }
+}
+
+/// Known models plus the one `auto` stands for.
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    models: BTreeMap<String, ModelSpec>,
+    default: String,
+}
+
+impl ModelRegistry {
+    /// The models the Python backend ships with; `auto` means `gpt-2`.
+    pub fn builtin() -> Self {
+        let mut registry = Self {
+            models: BTreeMap::new(),
+            default: "gpt-2".to_string(),
+        };
+        registry.register(ModelSpec::transformers("gpt-2", "gpt2", "GPT-2 small (124M parameters)"));
+        registry.register(ModelSpec::transformers("distilgpt2", "distilgpt2", "Distilled GPT-2 (82M parameters)"));
+        registry
+    }
+
+    /// Adds or replaces a model, returning the previous spec of that name.
+    pub fn register(&mut self, spec: ModelSpec) -> Option<ModelSpec> {
+        self.models.insert(spec.name.clone(), spec)
+    }
+
+    /// Makes `auto` resolve to `name`, which must already be registered.
+    pub fn with_default(mut self, name: &str) -> Result<Self, ModelError> {
+        self.default = self.lookup(name)?.name.clone();
+        Ok(self)
+    }
+
+    pub fn default_model(&self) -> &str {
+        &self.default
+    }
+
+    /// Looks up `name`, following `auto` to the default model.
+    pub fn resolve(&self, name: &str) -> Result<&ModelSpec, ModelError> {
+        if name == AUTO_MODEL {
+            return self.lookup(&self.default);
+        }
+        self.lookup(name)
+    }
+
+    /// Every accepted name, `auto` first.
+    pub fn names(&self) -> Vec<String> {
+        std::iter::once(AUTO_MODEL.to_string()).chain(self.models.keys().cloned()).collect()
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = &ModelSpec> {
+        self.models.values()
+    }
+
+    fn lookup(&self, name: &str) -> Result<&ModelSpec, ModelError> {
+        self.models.get(name).ok_or_else(|| ModelError::UnknownModel {
+            name: name.to_string(),
+            available: self.names(),
+        })
+    }
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_default() {
+        let registry = ModelRegistry::builtin();
+        assert_eq!(registry.resolve("auto").unwrap().name, "gpt-2");
+        assert_eq!(
+            registry.resolve("distilgpt2").unwrap().backend,
+            ModelBackend::Transformers { checkpoint: "distilgpt2".into() }
+        );
+        assert_eq!(registry.names(), vec!["auto", "distilgpt2", "gpt-2"]);
+
+        let err = registry.resolve("gpt-5").unwrap_err();
+        assert_eq!(err.to_string(), "unknown model 'gpt-5'; available models: auto, distilgpt2, gpt-2");
+
+        let registry = registry.with_default("distilgpt2").unwrap();
+        assert_eq!(registry.resolve("auto").unwrap().name, "distilgpt2");
+        assert!(ModelRegistry::builtin().with_default("auto").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+// The tool calls a task makes before it reaches the model
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::tools::ToolRegistry;
+
+/// Most tool calls a plan holds; later mentions are dropped.
+pub const MAX_PLANNED_ACTIONS: usize = 16;
+
+/// One tool call the agent would make for a task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedAction {
+    pub tool: String,
+    pub arguments: Value,
+    /// What in the task led to the call.
+    pub reason: String,
+}
+
+impl PlannedAction {
+    fn new(tool: &str, arguments: Value, reason: String) -> Self {
+        Self { tool: tool.to_string(), arguments, reason }
+    }
+}
+
+/// `plan_task` against the built-in tools.
+pub fn execute_task_plan(task: &str) -> Vec<PlannedAction> {
+    plan_task(task, &ToolRegistry::builtin())
+}
+
+/// The tool calls that gather context for `task`, in the order the task
+/// mentions what they look at: a path with an extension or a `/` is read
+/// with `read_file` (`list_directory` if it ends in `/`), and a quoted or
+/// backticked phrase that is not a path is looked up with `search_files`.
+/// The plan depends only on the text and on which tools `registry` has,
+/// so the same task always plans the same calls; nothing is run.
+pub fn plan_task(task: &str, registry: &ToolRegistry) -> Vec<PlannedAction> {
+    let mut actions: Vec<PlannedAction> = Vec::new();
+    for mention in mentions(task) {
+        let action = match mention {
+            Mention::Path(path) if path.ends_with('/') => {
+                let dir = path.trim_end_matches('/');
+                let dir = if dir.is_empty() { "/" } else { dir };
+                PlannedAction::new("list_directory", json!({ "path": dir }), format!("the task mentions {}", path))
+            }
+            Mention::Path(path) => {
+                PlannedAction::new("read_file", json!({ "path": path }), format!("the task mentions {}", path))
+            }
+            Mention::Phrase(phrase) => PlannedAction::new(
+                "search_files",
+                json!({ "pattern": regex::escape(&phrase), "path": "." }),
+                format!("the task quotes \"{}\"", phrase),
+            ),
+        };
+        if registry.get(&action.tool).is_some() && !actions.iter().any(|a| a.tool == action.tool && a.arguments == action.arguments) {
+            actions.push(action);
+        }
+        if actions.len() == MAX_PLANNED_ACTIONS {
+            break;
+        }
+    }
+    actions
+}
+
+enum Mention {
+    Path(String),
+    Phrase(String),
+}
+
+/// Paths and quoted phrases in `task`, in order.
+fn mentions(task: &str) -> Vec<Mention> {
+    let mut found = Vec::new();
+    let mut rest = task;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let quote = rest.chars().next().filter(|c| matches!(c, '"' | '`'));
+        let closing = quote.and_then(|q| rest[1..].find(q).map(|end| end + 1));
+        let (word, next) = match closing {
+            Some(end) => (&rest[1..end], &rest[end + 1..]),
+            None => {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        rest = next;
+        let word = if closing.is_some() { word.trim() } else { trim_punctuation(word) };
+        if is_path(word) {
+            found.push(Mention::Path(word.to_string()));
+        } else if closing.is_some() && !word.is_empty() {
+            found.push(Mention::Phrase(word.to_string()));
+        }
+    }
+    found
+}
+
+/// `word` without quotes and brackets around it, or sentence punctuation
+/// after it (keeping a trailing `/`).
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_start_matches(['(', '[', '\'', '"', '`'])
+        .trim_end_matches([')', ']', '\'', '"', '`', ',', ';', ':', '!', '?'])
+        .trim_end_matches('.')
+}
+
+/// Looks like a file or directory: contains a `/` or ends in an extension,
+/// and is not a URL, an option or an abbreviation such as `e.g`.
+fn is_path(word: &str) -> bool {
+    if word.is_empty() || word.contains("://") || word.starts_with('-') || word.contains(char::is_whitespace) {
+        return false;
+    }
+    if word.split('.').all(|part| part.chars().count() <= 1) {
+        return false;
+    }
+    if word.contains('/') {
+        return word.chars().any(|c| c.is_alphanumeric() || c == '.');
+    }
+    match word.rsplit_once('.') {
+        Some((stem, extension)) => {
+            !stem.is_empty()
+                && (1..=8).contains(&extension.len())
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+                && extension.chars().any(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_task() {
+        let task = "Explain src/main.rs (and Cargo.toml), list docs/, find `parse_size` and \"max size\". See https://x.io/a.html, v1.2 or e.g.";
+        let plan = execute_task_plan(task);
+        let calls: Vec<_> = plan.iter().map(|a| format!("{} {}", a.tool, a.arguments)).collect();
+        assert_eq!(
+            calls,
+            [
+                r#"read_file {"path":"src/main.rs"}"#,
+                r#"read_file {"path":"Cargo.toml"}"#,
+                r#"list_directory {"path":"docs"}"#,
+                r#"search_files {"pattern":"parse_size","path":"."}"#,
+                r#"search_files {"pattern":"max size","path":"."}"#,
+            ]
+        );
+        assert_eq!(plan[0].reason, "the task mentions src/main.rs");
+        assert_eq!(execute_task_plan(task), plan);
+
+        assert!(execute_task_plan("write a haiku").is_empty());
+        assert_eq!(execute_task_plan("read a.txt then a.txt again").len(), 1);
+        assert!(plan_task("read a.txt", &ToolRegistry::new()).is_empty());
+        let many: String = (0..40).map(|i| format!("f{}.rs ", i)).collect();
+        assert_eq!(execute_task_plan(&many).len(), MAX_PLANNED_ACTIONS);
+    }
+}
@@ -0,0 +1,27 @@
+// Process-wide switch that refuses anything with side effects
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::CoreError;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns safe mode on for the rest of the process. From then on `FileWriter`
+/// refuses to write, `ProcessManager` refuses to spawn and the executor runs
+/// only registry tools marked `ToolSpec::read_only`; each fails with
+/// `CoreError::SafeMode`. Reads are unaffected. There is deliberately no way
+/// to turn it off again.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// `CoreError::SafeMode` naming `operation` if safe mode is on.
+pub(crate) fn check(operation: impl FnOnce() -> String) -> Result<(), CoreError> {
+    if is_enabled() {
+        return Err(CoreError::SafeMode { operation: operation() });
+    }
+    Ok(())
+}
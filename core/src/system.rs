@@ -5,8 +5,8 @@ pub mod environment;
 pub mod paths;
 
 // Re-export public APIs
-pub use environment::EnvironmentManager;
-pub use paths::PathUtils;
+pub use environment::{EnvironmentManager, ProxySettings, ShellFormat, PRESERVED_ENV_VARS, REDACTED_VALUE, SENSITIVE_ENV_PATTERNS};
+pub use paths::{PathError, PathUtils, SearchPath, DEFAULT_MAX_LINKS};
 
 #[cfg(test)]
 mod tests {
@@ -14,6 +14,7 @@ mod tests {
 
     #[test]
     fn test_system_module_loads() {
-        assert!(true);
+        let _ = EnvironmentManager::new();
+        let _ = PathUtils::new();
     }
 }
\ No newline at end of file
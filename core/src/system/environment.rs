@@ -1,22 +1,323 @@
 // Environment manager implementation
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use glob::{MatchOptions, Pattern};
 use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Keys whose values `redact_sensitive` masks by default.
+pub const SENSITIVE_ENV_PATTERNS: &[&str] = &["*_KEY", "*_TOKEN", "*_SECRET"];
+/// What a masked value is replaced with.
+pub const REDACTED_VALUE: &str = "***";
+/// Variables a tool keeps when it runs with a cleared environment.
+#[cfg(not(windows))]
+pub const PRESERVED_ENV_VARS: &[&str] = &["PATH", "HOME"];
+/// Variables a tool keeps when it runs with a cleared environment. Windows
+/// programs commonly fail without `SYSTEMROOT`.
+#[cfg(windows)]
+pub const PRESERVED_ENV_VARS: &[&str] = &["PATH", "PATHEXT", "SYSTEMROOT", "USERPROFILE", "TEMP", "TMP"];
 
 pub struct EnvironmentManager;
 
+/// Proxies set through the conventional variables. Each is read lowercase
+/// first (`https_proxy`), then uppercase; `ALL_PROXY` fills in for both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxySettings {
+    /// Proxy for `http://` URLs.
+    pub http: Option<String>,
+    /// Proxy for `https://` URLs.
+    pub https: Option<String>,
+    /// Comma-separated hosts and domains reached directly.
+    pub no_proxy: Option<String>,
+}
+
+impl ProxySettings {
+    /// Settings from the variables `lookup` returns; empty values count as unset.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| {
+            [name.to_ascii_lowercase(), name.to_string()]
+                .iter()
+                .find_map(|key| lookup(key).filter(|value| !value.is_empty()))
+        };
+        let all = var("ALL_PROXY");
+        Self {
+            http: var("HTTP_PROXY").or_else(|| all.clone()),
+            https: var("HTTPS_PROXY").or(all),
+            no_proxy: var("NO_PROXY"),
+        }
+    }
+}
+
+/// The syntax `export_to_shell` writes variable assignments in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellFormat {
+    /// `export KEY='value'`, for sh, bash and zsh.
+    Posix,
+    /// `set -gx KEY 'value'`.
+    Fish,
+    /// `$env:KEY='value'`.
+    PowerShell,
+}
+
+impl Default for ShellFormat {
+    /// POSIX on Unix, PowerShell on Windows.
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::PowerShell
+        } else {
+            Self::Posix
+        }
+    }
+}
+
+impl FromStr for ShellFormat {
+    type Err = anyhow::Error;
+
+    /// Accepts format names as well as shell names.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "posix" | "sh" | "bash" | "zsh" => Ok(Self::Posix),
+            "fish" => Ok(Self::Fish),
+            "powershell" | "pwsh" => Ok(Self::PowerShell),
+            _ => Err(anyhow!("unknown shell '{}' (expected bash, zsh, fish or powershell)", s)),
+        }
+    }
+}
+
+impl ShellFormat {
+    /// `value` as a single-quoted string literal of this shell.
+    pub fn quote(self, value: &str) -> String {
+        let mut quoted = String::with_capacity(value.len() + 2);
+        quoted.push('\'');
+        for c in value.chars() {
+            match (self, c) {
+                // Single quotes cannot be escaped inside single quotes: close,
+                // add an escaped quote, reopen.
+                (Self::Posix, '\'') => quoted.push_str("'\\''"),
+                (Self::Fish, '\'' | '\\') => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                // PowerShell also ends strings at typographic single quotes.
+                (Self::PowerShell, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') => {
+                    quoted.push(c);
+                    quoted.push(c);
+                }
+                _ => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        quoted
+    }
+
+    fn assignment(self, key: &str, value: &str) -> String {
+        match self {
+            Self::Posix => format!("export {}={}", key, self.quote(value)),
+            Self::Fish => format!("set -gx {} {}", key, self.quote(value)),
+            Self::PowerShell => format!("$env:{}={}", key, self.quote(value)),
+        }
+    }
+}
+
 impl EnvironmentManager {
     pub fn new() -> Self {
         Self
     }
-    
+
+    /// All variables of this process. Variables that are not valid Unicode
+    /// are skipped.
     pub fn get_env_vars() -> Result<HashMap<String, String>> {
-        // TODO: Implement environment variable management
-        todo!("Implement in T024")
+        Ok(std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect())
     }
+
+    /// The `PRESERVED_ENV_VARS` that are set in this process.
+    pub fn preserved_env_vars() -> HashMap<String, String> {
+        PRESERVED_ENV_VARS
+            .iter()
+            .filter_map(|key| Some((key.to_string(), std::env::var(key).ok()?)))
+            .collect()
+    }
+
+    /// Variables whose key matches a glob such as `AWS_*`.
+    pub fn get_env_vars_matching(pattern: &str) -> Result<HashMap<String, String>> {
+        let pattern = Pattern::new(pattern).with_context(|| format!("Invalid pattern '{}'", pattern))?;
+        let mut vars = Self::get_env_vars()?;
+        vars.retain(|key, _| pattern.matches(key));
+        Ok(vars)
+    }
+
+    /// Replaces the value of every key matching one of `sensitive` (ignoring
+    /// case) with `REDACTED_VALUE`. Returns how many were masked.
+    pub fn redact_sensitive(vars: &mut HashMap<String, String>, sensitive: &[&str]) -> Result<usize> {
+        let is_sensitive = sensitive_matcher(sensitive)?;
+        let mut masked = 0;
+        for (key, value) in vars.iter_mut() {
+            if is_sensitive(key) {
+                *value = REDACTED_VALUE.to_string();
+                masked += 1;
+            }
+        }
+        Ok(masked)
+    }
+
+    /// The non-empty values of this process's variables whose key matches
+    /// one of `sensitive`, for scrubbing from text that leaves the process.
+    pub fn sensitive_values(sensitive: &[&str]) -> Result<Vec<String>> {
+        let is_sensitive = sensitive_matcher(sensitive)?;
+        Ok(Self::get_env_vars()?
+            .into_iter()
+            .filter(|(key, value)| !value.is_empty() && is_sensitive(key))
+            .map(|(_, value)| value)
+            .collect())
+    }
+
+    /// The keys of this process's variables that match one of `sensitive`,
+    /// for keeping them from the processes it starts.
+    pub fn sensitive_keys(sensitive: &[&str]) -> Result<Vec<String>> {
+        let is_sensitive = sensitive_matcher(sensitive)?;
+        Ok(std::env::vars_os()
+            .filter_map(|(key, _)| key.into_string().ok())
+            .filter(|key| is_sensitive(key))
+            .collect())
+    }
+
+    /// One line per variable, sorted by key, that sets `vars` when evaluated
+    /// by the shell `format` is for. Values may hold any characters,
+    /// including quotes and newlines. Keys that are not plain identifiers
+    /// (letters, digits and `_`, not starting with a digit) cannot be set
+    /// this way and are left out with a comment saying so.
+    pub fn export_to_shell(vars: &HashMap<String, String>, format: ShellFormat) -> String {
+        let mut keys: Vec<_> = vars.keys().collect();
+        keys.sort();
+        let mut script = String::new();
+        for key in keys {
+            if is_identifier(key) {
+                script.push_str(&format.assignment(key, &vars[key]));
+            } else {
+                script.push_str(&format!("# skipped {}: not a valid variable name", key.escape_debug()));
+            }
+            script.push('\n');
+        }
+        script
+    }
+
+    /// The proxies configured in this process's environment.
+    pub fn proxy_settings() -> ProxySettings {
+        ProxySettings::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Resident memory of this process in bytes, where the platform exposes
+    /// it cheaply (`/proc/self/status` on Linux); `None` elsewhere.
+    pub fn process_memory() -> Option<u64> {
+        #[cfg(target_os = "linux")]
+        {
+            let status = std::fs::read_to_string("/proc/self/status").ok()?;
+            let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+            let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+            Some(kib * 1024)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether a key matches one of `sensitive`, ignoring case.
+fn sensitive_matcher(sensitive: &[&str]) -> Result<impl Fn(&str) -> bool> {
+    let patterns = sensitive
+        .iter()
+        .map(|p| Pattern::new(p).with_context(|| format!("Invalid pattern '{}'", p)))
+        .collect::<Result<Vec<_>>>()?;
+    let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+    Ok(move |key: &str| patterns.iter().any(|p| p.matches_with(key, options)))
 }
 
 impl Default for EnvironmentManager {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_and_redaction() {
+        std::env::set_var("ENVTEST_REGION", "eu-west-1");
+        std::env::set_var("ENVTEST_ACCESS_KEY", "AKIAEXAMPLE");
+        std::env::set_var("ENVTEST_session_token", "abc");
+
+        let mut vars = EnvironmentManager::get_env_vars_matching("ENVTEST_*").unwrap();
+        assert_eq!(vars.len(), 3);
+        assert_eq!(EnvironmentManager::redact_sensitive(&mut vars, SENSITIVE_ENV_PATTERNS).unwrap(), 2);
+        assert_eq!(vars["ENVTEST_REGION"], "eu-west-1");
+        assert_eq!(vars["ENVTEST_ACCESS_KEY"], REDACTED_VALUE);
+        assert_eq!(vars["ENVTEST_session_token"], REDACTED_VALUE);
+        let secrets = EnvironmentManager::sensitive_values(SENSITIVE_ENV_PATTERNS).unwrap();
+        assert!(secrets.contains(&"AKIAEXAMPLE".to_string()) && !secrets.contains(&"eu-west-1".to_string()));
+
+        assert!(EnvironmentManager::get_env_vars_matching("[").is_err());
+    }
+
+    #[test]
+    fn test_export_to_shell_escapes_values() {
+        let vars: HashMap<String, String> = [("B", "it's \"quoted\"\nback\\slash $HOME"), ("A", "plain"), ("BAD-KEY", "x")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(
+            EnvironmentManager::export_to_shell(&vars, ShellFormat::Posix),
+            "export A='plain'\nexport B='it'\\''s \"quoted\"\nback\\slash $HOME'\n# skipped BAD-KEY: not a valid variable name\n"
+        );
+        assert_eq!(
+            EnvironmentManager::export_to_shell(&vars, ShellFormat::Fish).lines().nth(1),
+            Some("set -gx B 'it\\'s \"quoted\"")
+        );
+        assert_eq!(ShellFormat::Fish.quote("back\\slash"), "'back\\\\slash'");
+        assert_eq!(ShellFormat::PowerShell.quote("it's \u{2019}x"), "'it''s \u{2019}\u{2019}x'");
+        assert_eq!(
+            EnvironmentManager::export_to_shell(&vars, ShellFormat::PowerShell).lines().next(),
+            Some("$env:A='plain'")
+        );
+        assert_eq!("zsh".parse::<ShellFormat>().unwrap(), ShellFormat::Posix);
+        assert_eq!("PWSH".parse::<ShellFormat>().unwrap(), ShellFormat::PowerShell);
+        assert!("csh".parse::<ShellFormat>().is_err());
+    }
+
+    /// Evaluating the exported lines in a real shell gives back the values.
+    #[cfg(unix)]
+    #[test]
+    fn test_export_to_shell_round_trips_through_sh() {
+        let value = "it's \"quoted\"\n\tnext line\\ $HOME `id` !";
+        let vars: HashMap<String, String> = [("ROUND_TRIP".to_string(), value.to_string())].into_iter().collect();
+        let script = format!("{}printf %s \"$ROUND_TRIP\"", EnvironmentManager::export_to_shell(&vars, ShellFormat::Posix));
+        let output = std::process::Command::new("sh").args(["-c", &script]).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+    }
+
+    #[test]
+    fn test_proxy_settings() {
+        let vars: HashMap<&str, &str> =
+            [("https_proxy", "http://lower:3128"), ("HTTPS_PROXY", "http://upper:3128"), ("ALL_PROXY", "socks5://all:1080"), ("NO_PROXY", "")]
+                .into_iter()
+                .collect();
+        let proxies = ProxySettings::from_lookup(|key| vars.get(key).map(|v| v.to_string()));
+        assert_eq!(proxies.https.as_deref(), Some("http://lower:3128"));
+        assert_eq!(proxies.http.as_deref(), Some("socks5://all:1080"));
+        assert_eq!(proxies.no_proxy, None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_memory() {
+        assert!(EnvironmentManager::process_memory().unwrap() > 0);
+    }
+}
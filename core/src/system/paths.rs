@@ -1,6 +1,109 @@
 // Path utilities implementation
-use std::path::{Path, PathBuf};
-use anyhow::Result;
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use anyhow::{Context, Result};
+use thiserror::Error;
+
+/// Symlinks `canonicalize_safe` follows in one path before giving up; the
+/// same as Linux's `MAXSYMLINKS`.
+pub const DEFAULT_MAX_LINKS: usize = 40;
+
+/// Why a path could not be canonicalized.
+#[derive(Debug, Error)]
+pub enum PathError {
+    /// Following the links in `chain` leads back to where it started.
+    #[error("symlink loop at {}: {}", path.display(), describe_chain(chain))]
+    SymlinkLoop { path: PathBuf, chain: Vec<PathBuf> },
+    #[error("{} goes through more than {max} symlinks", path.display())]
+    TooManyLinks { path: PathBuf, max: usize },
+    /// `missing` is the first component of the path that does not exist.
+    #[error("{} does not exist (no {})", path.display(), missing.display())]
+    NotFound { path: PathBuf, missing: PathBuf },
+    #[error("{} is not a directory, in {}", not_dir.display(), path.display())]
+    NotADirectory { path: PathBuf, not_dir: PathBuf },
+    #[error("failed to inspect {}: {source}", at.display())]
+    Io { at: PathBuf, source: std::io::Error },
+}
+
+fn describe_chain(chain: &[PathBuf]) -> String {
+    chain.iter().map(|link| link.display().to_string()).collect::<Vec<_>>().join(" -> ")
+}
+
+/// Where executables are looked up: `shim_dir` first, then `dirs`, or the
+/// inherited `PATH` if `dirs` is not set. Lets a locked-down setup pin the
+/// tools the agent runs to the versions bundled in a directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPath {
+    /// Searched before everything else, so its tools shadow any others.
+    pub shim_dir: Option<PathBuf>,
+    /// Searched instead of `PATH`.
+    pub dirs: Option<Vec<PathBuf>>,
+}
+
+impl SearchPath {
+    /// The inherited `PATH`, with no shims.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_shim_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.shim_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.dirs = Some(dirs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Whether this is just the inherited `PATH`.
+    pub fn is_inherited(&self) -> bool {
+        self.shim_dir.is_none() && self.dirs.is_none()
+    }
+
+    /// The directories searched, in order.
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        let dirs = match &self.dirs {
+            Some(dirs) => dirs.clone(),
+            None => std::env::var_os("PATH").map(|path| std::env::split_paths(&path).collect()).unwrap_or_default(),
+        };
+        self.shim_dir.iter().cloned().chain(dirs).collect()
+    }
+
+    /// `dirs` joined as a `PATH` value, for the processes started with it.
+    pub fn to_env(&self) -> Result<OsString> {
+        std::env::join_paths(self.dirs()).context("a search path directory contains the path separator")
+    }
+
+    /// The first executable called `name` in `dirs`, trying the `PATHEXT`
+    /// extensions on Windows, made absolute. Names containing a separator
+    /// are checked as given.
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+            return is_executable(Path::new(name)).then(|| PathBuf::from(name));
+        }
+        let extensions: Vec<String> = if cfg!(windows) {
+            std::env::var("PATHEXT")
+                .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+                .split(';')
+                .map(str::to_string)
+                .chain(std::iter::once(String::new()))
+                .collect()
+        } else {
+            vec![String::new()]
+        };
+        self.dirs()
+            .into_iter()
+            .flat_map(|dir| extensions.iter().map(move |ext| dir.join(format!("{}{}", name, ext))))
+            .find(|candidate| is_executable(candidate))
+            .map(|found| std::path::absolute(&found).unwrap_or(found))
+    }
+}
 
 pub struct PathUtils;
 
@@ -8,10 +111,195 @@ impl PathUtils {
     pub fn new() -> Self {
         Self
     }
-    
-    pub fn resolve_path<P: AsRef<Path>>(_path: P) -> Result<PathBuf> {
-        // TODO: Implement path resolution utilities
-        todo!("Implement in T025")
+
+    /// Makes `path` absolute against the current directory, removes `.` and
+    /// `..` components and resolves symlinks in the part that exists, so the
+    /// result says where the path really points even if it does not exist yet.
+    pub fn resolve_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir().context("Failed to read the current directory")?.join(path)
+        };
+        let normalized = Self::normalize(&absolute);
+
+        // Canonicalize the deepest existing ancestor and re-attach the rest.
+        let mut existing = normalized.as_path();
+        let mut missing = Vec::new();
+        loop {
+            match Self::canonicalize_safe(existing) {
+                Ok(resolved) => return Ok(missing.iter().rev().fold(resolved, |acc, part| acc.join(part))),
+                Err(e) if matches!(e.downcast_ref(), Some(PathError::SymlinkLoop { .. } | PathError::TooManyLinks { .. })) => {
+                    return Err(e);
+                }
+                Err(_) => match (existing.parent(), existing.file_name()) {
+                    (Some(parent), Some(name)) => {
+                        missing.push(name.to_os_string());
+                        existing = parent;
+                    }
+                    _ => return Ok(normalized),
+                },
+            }
+        }
+    }
+
+    /// `canonicalize_safe` with at most `DEFAULT_MAX_LINKS` symlinks.
+    pub fn canonicalize_safe<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+        Self::canonicalize_with_max_links(path, DEFAULT_MAX_LINKS)
+    }
+
+    /// Like `std::fs::canonicalize`, but resolves one component at a time so
+    /// failures say which part of the path is at fault: a symlink cycle is
+    /// `PathError::SymlinkLoop` with the links involved, more than
+    /// `max_links` links is `PathError::TooManyLinks`, and a missing or
+    /// non-directory component is named in the error. Relative paths are
+    /// taken from the current directory. The path must exist.
+    pub fn canonicalize_with_max_links<P: AsRef<Path>>(path: P, max_links: usize) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir().context("Failed to read the current directory")?.join(path)
+        };
+        let mut pending: VecDeque<PathBuf> = absolute.components().map(|c| PathBuf::from(c.as_os_str())).collect();
+        let mut resolved = PathBuf::new();
+        let mut followed: Vec<PathBuf> = Vec::new();
+        let mut seen: HashSet<(PathBuf, Vec<OsString>)> = HashSet::new();
+
+        while let Some(part) = pending.pop_front() {
+            let Some(component) = part.components().next() else { continue };
+            let name = match component {
+                Component::Prefix(_) | Component::RootDir => {
+                    resolved.push(component);
+                    continue;
+                }
+                Component::CurDir => continue,
+                Component::ParentDir => {
+                    resolved.pop();
+                    continue;
+                }
+                Component::Normal(name) => name,
+            };
+            let candidate = resolved.join(name);
+            let meta = std::fs::symlink_metadata(&candidate).map_err(|source| match source.kind() {
+                std::io::ErrorKind::NotFound => PathError::NotFound { path: path.to_path_buf(), missing: candidate.clone() },
+                _ => PathError::Io { at: candidate.clone(), source },
+            })?;
+            if meta.file_type().is_symlink() {
+                let rest: Vec<OsString> = pending.iter().map(|part| part.as_os_str().to_os_string()).collect();
+                if !seen.insert((candidate.clone(), rest)) {
+                    let start = followed.iter().position(|link| *link == candidate).unwrap_or(0);
+                    let mut chain = followed.split_off(start);
+                    chain.push(candidate);
+                    return Err(PathError::SymlinkLoop { path: path.to_path_buf(), chain }.into());
+                }
+                if followed.len() == max_links {
+                    return Err(PathError::TooManyLinks { path: path.to_path_buf(), max: max_links }.into());
+                }
+                let target = std::fs::read_link(&candidate).map_err(|source| PathError::Io { at: candidate.clone(), source })?;
+                followed.push(candidate);
+                // An absolute target starts over from its root.
+                for part in target.components().rev() {
+                    pending.push_front(PathBuf::from(part.as_os_str()));
+                }
+                continue;
+            }
+            if !pending.is_empty() && !meta.is_dir() {
+                return Err(PathError::NotADirectory { path: path.to_path_buf(), not_dir: candidate }.into());
+            }
+            resolved = candidate;
+        }
+        Ok(resolved)
+    }
+
+    /// Looks `name` up on `PATH` the way a shell would, trying the `PATHEXT`
+    /// extensions on Windows. Names containing a separator are checked as given.
+    pub fn find_executable(name: &str) -> Option<PathBuf> {
+        SearchPath::new().find(name)
+    }
+
+    /// Where the agent keeps its own files: `$AI_AGENT_DATA_DIR` if set,
+    /// otherwise an `ai-agent` directory under `%APPDATA%` on Windows,
+    /// `~/Library/Application Support` on macOS and `$XDG_DATA_HOME` (or
+    /// `~/.local/share`) elsewhere. The directory is not created.
+    pub fn app_data_dir() -> Result<PathBuf> {
+        Self::app_data_dir_from(env_path)
+    }
+
+    fn app_data_dir_from(var: impl Fn(&str) -> Option<PathBuf>) -> Result<PathBuf> {
+        user_dir(var, "AI_AGENT_DATA_DIR", "APPDATA", "Library/Application Support", ("XDG_DATA_HOME", ".local/share"))
+    }
+
+    /// Where the agent keeps data it can rebuild: `$AI_AGENT_CACHE_DIR` if
+    /// set, otherwise an `ai-agent` directory under `%LOCALAPPDATA%` on
+    /// Windows, `~/Library/Caches` on macOS and `$XDG_CACHE_HOME` (or
+    /// `~/.cache`) elsewhere. The directory is not created.
+    pub fn cache_dir() -> Result<PathBuf> {
+        Self::cache_dir_from(env_path)
+    }
+
+    fn cache_dir_from(var: impl Fn(&str) -> Option<PathBuf>) -> Result<PathBuf> {
+        user_dir(var, "AI_AGENT_CACHE_DIR", "LOCALAPPDATA", "Library/Caches", ("XDG_CACHE_HOME", ".cache"))
+    }
+
+    /// Removes `.` and `..` components without touching the filesystem. `..`
+    /// at the root stays at the root.
+    pub fn normalize(path: &Path) -> PathBuf {
+        let mut out = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !out.pop() {
+                        out.push(component);
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+        out
+    }
+}
+
+fn env_path(key: &str) -> Option<PathBuf> {
+    std::env::var_os(key).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+/// `override_var` if set, else `ai-agent` under the platform's directory:
+/// `windows_var`, `~/<macos_dir>`, or the XDG variable with its `~` default.
+fn user_dir(
+    var: impl Fn(&str) -> Option<PathBuf>,
+    override_var: &str,
+    windows_var: &str,
+    macos_dir: &str,
+    (xdg_var, xdg_default): (&str, &str),
+) -> Result<PathBuf> {
+    if let Some(dir) = var(override_var) {
+        return Ok(dir);
+    }
+    let base = if cfg!(windows) {
+        var(windows_var).with_context(|| format!("{} is not set", windows_var))?
+    } else if cfg!(target_os = "macos") {
+        var("HOME").context("HOME is not set")?.join(macos_dir)
+    } else {
+        match var(xdg_var) {
+            Some(dir) => dir,
+            None => var("HOME").with_context(|| format!("neither {} nor HOME is set", xdg_var))?.join(xdg_default),
+        }
+    };
+    Ok(base.join("ai-agent"))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
     }
 }
 
@@ -19,4 +307,82 @@ impl Default for PathUtils {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("a")).unwrap();
+
+        assert_eq!(PathUtils::resolve_path(root.join("a/./b/../c.txt")).unwrap(), root.join("a/c.txt"));
+        assert_eq!(PathUtils::resolve_path(root.join("a/../../x")).unwrap(), root.parent().unwrap().join("x"));
+        assert!(PathUtils::resolve_path("relative").unwrap().is_absolute());
+
+        #[cfg(target_os = "linux")]
+        {
+            let vars = |set: &'static [(&'static str, &'static str)]| {
+                move |key: &str| set.iter().find(|(k, _)| *k == key).map(|(_, v)| PathBuf::from(v))
+            };
+            let data_dir = |set| PathUtils::app_data_dir_from(vars(set)).unwrap();
+            assert_eq!(data_dir(&[("AI_AGENT_DATA_DIR", "/d"), ("HOME", "/h")]), Path::new("/d"));
+            assert_eq!(data_dir(&[("XDG_DATA_HOME", "/x"), ("HOME", "/h")]), Path::new("/x/ai-agent"));
+            assert_eq!(data_dir(&[("HOME", "/h")]), Path::new("/h/.local/share/ai-agent"));
+            assert!(PathUtils::app_data_dir_from(vars(&[])).is_err());
+            assert_eq!(PathUtils::cache_dir_from(vars(&[("HOME", "/h")])).unwrap(), Path::new("/h/.cache/ai-agent"));
+        }
+
+        #[cfg(unix)]
+        {
+            assert!(PathUtils::find_executable("sh").is_some());
+            assert!(PathUtils::find_executable("no-such-tool-xyz").is_none());
+            std::os::unix::fs::symlink("/", root.join("a/up")).unwrap();
+            assert_eq!(PathUtils::resolve_path(root.join("a/up/etc")).unwrap(), Path::new("/etc").canonicalize().unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_canonicalize_safe_detects_loops() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("real")).unwrap();
+        std::fs::write(root.join("real/file.txt"), "x").unwrap();
+        symlink("real", root.join("to_real")).unwrap();
+        symlink(".", root.join("here")).unwrap();
+        symlink("b", root.join("a")).unwrap();
+        symlink("a", root.join("b")).unwrap();
+        symlink("deeper/x", root.join("deeper")).unwrap();
+
+        let file = root.join("here/here/to_real/../to_real/file.txt");
+        assert_eq!(PathUtils::canonicalize_safe(&file).unwrap(), file.canonicalize().unwrap());
+        assert_eq!(PathUtils::canonicalize_safe(&file).unwrap(), root.join("real/file.txt"));
+
+        let err = PathUtils::canonicalize_safe(root.join("a/file")).unwrap_err();
+        match err.downcast_ref::<PathError>() {
+            Some(PathError::SymlinkLoop { chain, .. }) => {
+                assert_eq!(chain, &[root.join("a"), root.join("b"), root.join("a")]);
+            }
+            other => panic!("expected a loop, got {:?}", other),
+        }
+        assert!(err.to_string().contains(" -> "));
+        let err = PathUtils::canonicalize_safe(root.join("deeper")).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PathError::TooManyLinks { max: DEFAULT_MAX_LINKS, .. })));
+        let err = PathUtils::canonicalize_with_max_links(&file, 2).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PathError::TooManyLinks { max: 2, .. })));
+
+        let err = PathUtils::canonicalize_safe(root.join("real/nope/deep")).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PathError::NotFound { missing, .. }) if *missing == root.join("real/nope")));
+        let err = PathUtils::canonicalize_safe(root.join("real/file.txt/x")).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PathError::NotADirectory { .. })));
+
+        assert!(PathUtils::resolve_path(root.join("a/new.txt")).is_err());
+        assert_eq!(PathUtils::resolve_path(root.join("to_real/new.txt")).unwrap(), root.join("real/new.txt"));
+    }
+}
@@ -0,0 +1,79 @@
+// The outcome of running a task on a model
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// What a task produced, with the metadata callers report on. Token counts
+/// are `None` when the backend does not provide them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskResult {
+    pub task: String,
+    pub output: String,
+    /// The model the task ran on, with `auto` resolved.
+    pub model: String,
+    pub duration: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+    pub success: bool,
+}
+
+impl TaskResult {
+    /// A successful result with no duration or token counts yet.
+    pub fn new(task: impl Into<String>, output: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            task: task.into(),
+            output: output.into(),
+            model: model.into(),
+            duration: Duration::ZERO,
+            prompt_tokens: None,
+            completion_tokens: None,
+            success: true,
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_tokens(mut self, prompt: Option<u64>, completion: Option<u64>) -> Self {
+        self.prompt_tokens = prompt;
+        self.completion_tokens = completion;
+        self
+    }
+
+    pub fn with_success(mut self, success: bool) -> Self {
+        self.success = success;
+        self
+    }
+
+    /// The model's output.
+    pub fn text(&self) -> &str {
+        &self.output
+    }
+
+    /// Prompt plus completion tokens, if both are known.
+    pub fn total_tokens(&self) -> Option<u64> {
+        Some(self.prompt_tokens? + self.completion_tokens?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_result_json() {
+        let result = TaskResult::new("t", "out", "gpt-2").with_duration(Duration::from_millis(1500));
+        assert_eq!(result.text(), "out");
+        assert_eq!(result.total_tokens(), None);
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json.get("prompt_tokens").is_none());
+        assert_eq!(json["success"], true);
+        assert_eq!(serde_json::from_value::<TaskResult>(json).unwrap(), result);
+
+        let counted = result.with_tokens(Some(3), Some(4));
+        assert_eq!(counted.total_tokens(), Some(7));
+    }
+}
@@ -1,19 +1,1399 @@
 // Tool execution module
 // High-performance tool and process execution
 
+pub mod cache;
+pub mod capture;
+pub mod confirm;
+pub mod dry_run;
 pub mod executor;
+pub mod file_tools;
+pub mod history;
+pub mod hooks;
+pub mod limits;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod parallel;
+pub mod policy;
+pub mod probe;
 pub mod process;
+pub mod rate_limit;
+pub mod registry;
+pub mod retry;
+pub mod sandbox;
+pub mod shell;
+pub mod stdin;
+pub mod stream;
+pub mod template;
+pub mod tty;
 
 // Re-export public APIs
-pub use executor::ToolExecutor;
-pub use process::ProcessManager;
+pub use cache::{CacheStats, ToolCache, DEFAULT_CACHE_BYTES, TOOL_CACHE_DIR};
+pub use capture::{OutputLimit, DEFAULT_MAX_OUTPUT_BYTES};
+pub use confirm::{Confirmation, ConfirmationGate, ConfirmationHook, ConfirmationRequest, DangerLevel};
+pub use executor::{ExecOptions, ToolError, ToolExecutor, ToolOutput, DEFAULT_KILL_GRACE};
+pub use file_tools::MAX_FILE_TOOL_BYTES;
+pub use history::{ExecutionFilter, ExecutionLog, ExecutionRecord, EXECUTION_LOG_FILE, LOG_OUTPUT_LIMIT};
+#[cfg(feature = "http")]
+pub use http::{http_request_tool, HttpToolConfig};
+pub use hooks::{AfterHook, BeforeHook, ToolHooks, ToolTask};
+pub use limits::{ResourceKind, ResourceLimits};
+pub use parallel::{BatchRun, ExecutionReport, ToolInvocation};
+pub use policy::{ExecPolicy, NETWORK_COMMANDS};
+pub use rate_limit::{RateLimitMode, RateLimiter};
+pub use retry::{RetryOn, RetryPolicy};
+pub use sandbox::{SandboxConfig, DEFAULT_SANDBOX_READ_ONLY};
+pub use shell::{ShellKind, SHELL_SCRIPT_VAR};
+pub use stdin::StdinSource;
+pub use tty::TerminalSize;
+pub use stream::{ToolEvent, ToolStream};
+pub use template::{CommandTemplate, Placeholder, TemplateError};
+pub use probe::{ToolProbe, VersionProbe, DEFAULT_VERSION_PATTERN, PROBE_TIMEOUT};
+pub use process::{
+    BackgroundSpec, CommandSpec, DetachedProcess, EnvMode, ExitInfo, OutputLine, OutputSource, PipelineHandle, PipelineOutput, Priority, PriorityError, ProcessEvent,
+    ProcessHandle, ProcessManager, ProcessStatus, ProcessUsage, RestartPolicy, SpawnOptions, StageResult, StdioConfig, StdioMode, Supervision,
+    DEFAULT_STOP_GRACE, EXIT_TAIL_BYTES, FOLLOW_POLL_INTERVAL, MAX_RESTART_BACKOFF, PROCESS_STATE_DIR, RESTART_BACKOFF,
+};
+pub use registry::{
+    BuiltinHandler, Describer, ParamType, ToolArgs, ToolCallError, ToolHandler, ToolParameter, ToolRegistry, ToolSpec,
+    MANIFEST_VERSION,
+};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use crate::system::{EnvironmentManager, SearchPath};
 
     #[test]
     fn test_tools_module_loads() {
-        assert!(true);
+        let _ = ToolExecutor::new();
+        let _ = ProcessManager::new();
     }
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    fn shell(script: &str) -> (&'static str, Vec<&str>) {
+        ("sh", vec!["-c", script])
+    }
+
+    #[cfg(windows)]
+    fn shell(script: &str) -> (&'static str, Vec<&str>) {
+        ("cmd", vec!["/c", script])
+    }
+
+    #[tokio::test]
+    async fn test_execute_tool_structured_output() {
+        let (program, args) = shell("echo out&& echo err 1>&2&& exit 3");
+        let output = ToolExecutor::execute_tool(program, &args).await.unwrap();
+        assert_eq!(output.stdout_lossy().trim_end(), "out");
+        assert_eq!(output.stderr_lossy().trim_end(), "err");
+        assert_eq!(output.exit_code, 3);
+        assert!(!output.success());
+        assert!(output.duration > std::time::Duration::ZERO);
+
+        let (program, args) = shell("echo hello");
+        assert_eq!(ToolExecutor::execute_tool_simple(program, &args).await.unwrap().trim_end(), "hello");
+        let (program, args) = shell("echo partial&& exit 4");
+        match ToolExecutor::execute_tool_simple(program, &args).await.unwrap_err() {
+            ToolError::NonZeroExit { code, output } => {
+                assert_eq!(code, 4);
+                assert_eq!(output.stdout_lossy().trim_end(), "partial");
+            }
+            other => panic!("expected NonZeroExit, got {:?}", other),
+        }
+
+        match ToolExecutor::execute_tool("no-such-tool-xyz", &[]).await.unwrap_err() {
+            ToolError::NotFound { tool } => assert_eq!(tool, "no-such-tool-xyz"),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_kills_tool_and_keeps_partial_output() {
+        use crate::CancellationToken;
+        use std::time::{Duration, Instant};
+
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let err = ToolExecutor::execute_tool_cancellable("sh", &["-c", "echo partial; sleep 10"], Some(&token))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        match err {
+            ToolError::Cancelled { partial_stdout, .. } => assert_eq!(partial_stdout, "partial\n"),
+            other => panic!("expected cancellation, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_output() {
+        let output = ToolExecutor::execute_tool("printf", &["caf\\351 ok"]).await.unwrap();
+        assert_eq!(output.stdout_bytes(), b"caf\xe9 ok");
+        assert_eq!(output.stdout_lossy(), "caf\u{fffd} ok");
+        assert!(output.stdout_utf8().is_err());
+        assert_eq!(ToolExecutor::execute_tool_simple("printf", &["caf\\351"]).await.unwrap(), "caf\u{fffd}");
+
+        // Binary output survives a serialization round trip; text stays a plain string.
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["stdout"], serde_json::json!({"base64": "Y2Fm6SBvaw=="}));
+        assert_eq!(json["stderr"], "");
+        assert_eq!(serde_json::from_value::<ToolOutput>(json).unwrap(), output);
+    }
+
+    #[tokio::test]
+    async fn test_policy_checked_before_spawning() {
+        let options = ExecOptions::new().with_policy(ExecPolicy::confined_to(std::env::current_dir().unwrap()));
+        // The tool does not exist, so reaching the spawn would fail differently.
+        let err = ToolExecutor::execute_tool_with_options("no-such-tool-xyz", &["../outside"], None, &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ToolError::PolicyViolation { .. }));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streaming_events_arrive_in_order() {
+        use futures::StreamExt;
+
+        let script = "echo out1; sleep 0.2; echo err1 >&2; sleep 0.2; printf 'out2'; exit 3";
+        let mut stream = ToolExecutor::execute_streaming("sh", &["-c", script], &ExecOptions::new()).await.unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = stream.next().await {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            vec![
+                ToolEvent::Stdout("out1".into()),
+                ToolEvent::Stderr("err1".into()),
+                ToolEvent::Stdout("out2".into()),
+                ToolEvent::Exited(3),
+            ]
+        );
+        let output = stream.finish().await.unwrap();
+        assert_eq!((output.stdout_utf8().unwrap().as_str(), output.stderr_lossy().as_ref(), output.exit_code), ("out1\nout2", "err1\n", 3));
+
+        // Unconsumed events do not stop the tool from finishing.
+        let stream = ToolExecutor::execute_streaming("sh", &["-c", "seq 1 2000"], &ExecOptions::new()).await.unwrap();
+        assert_eq!(stream.finish().await.unwrap().stdout_lossy().lines().count(), 2000);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_parallel_runs_concurrently_in_order() {
+        use std::time::{Duration, Instant};
+
+        let sleeps: Vec<_> = (1..=4)
+            .map(|i| {
+                ToolInvocation::new("sh", ["-c", "sleep 0.5; echo $N"])
+                    .with_options(ExecOptions::new().with_env("N", i.to_string()))
+            })
+            .collect();
+        let started = Instant::now();
+        let results = ToolExecutor::execute_parallel(sleeps, 4).await;
+        assert!(started.elapsed() < Duration::from_millis(1500), "took {:?}", started.elapsed());
+        let stdout: Vec<_> = results.iter().map(|r| r.as_ref().unwrap().stdout_lossy().trim().to_string()).collect();
+        assert_eq!(stdout, vec!["1", "2", "3", "4"]);
+
+        let batch = || {
+            vec![
+                ToolInvocation::new("sleep", ["5"]),
+                ToolInvocation::new("sh", ["-c", "exit 2"]),
+                ToolInvocation::new("echo", ["later"]),
+            ]
+        };
+        let started = Instant::now();
+        let results = ToolExecutor::execute_parallel_fail_fast(batch(), 2).await;
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(matches!(results[0], Err(ToolError::Cancelled { .. })));
+        assert_eq!(results[1].as_ref().unwrap().exit_code, 2);
+        assert!(results[2].is_err());
+
+        let results = ToolExecutor::execute_parallel(batch().split_off(1), 1).await;
+        assert_eq!(results[0].as_ref().unwrap().exit_code, 2);
+        assert_eq!(results[1].as_ref().unwrap().stdout_lossy(), "later\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_many_reports_which_runs_failed() {
+        let report = ToolExecutor::execute_many(
+            vec![
+                ToolInvocation::new("echo", ["ok"]),
+                ToolInvocation::new("sh", ["-c", "exit 2"]).with_label("exits two"),
+                ToolInvocation::new("no-such-tool-xyz", ["--flag"]),
+            ],
+            2,
+        )
+        .await;
+        assert_eq!((report.succeeded(), report.failed(), report.errored()), (1, 1, 1));
+        assert!(!report.all_succeeded());
+        let failures: Vec<_> = report.failures().map(|run| (run.index, run.label.as_str())).collect();
+        assert_eq!(failures, vec![(1, "exits two"), (2, "no-such-tool-xyz --flag")]);
+
+        let text = report.to_string();
+        assert!(text.starts_with("3 run(s) in "), "{}", text);
+        assert!(text.contains("1 succeeded, 1 failed, 1 errored"));
+        assert!(text.contains("\n  #1 exits two: exit code 2"));
+        assert!(text.contains("\n  #2 no-such-tool-xyz --flag: "));
+        assert!(!text.contains("#0"));
+
+        let results = report.into_results();
+        assert_eq!(results[0].as_ref().unwrap().stdout_lossy(), "ok\n");
+        assert!(matches!(results[2], Err(ToolError::NotFound { .. })));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hooks_run_around_each_execution() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hooks = {
+            let seen = Arc::clone(&seen);
+            ToolHooks::new()
+                .before_execute(|task| match task.args.first().map(String::as_str) {
+                    Some("blocked") => anyhow::bail!("not on this branch"),
+                    _ => Ok(()),
+                })
+                .after_execute(move |task, result| {
+                    let code = result.as_ref().ok().map(|output| output.exit_code);
+                    seen.lock().unwrap().push((task.args.join(" "), task.task_id.clone(), code));
+                })
+        };
+        let options = ExecOptions::new().with_hooks(Arc::new(hooks)).with_task_id("audit");
+        let report = ToolExecutor::execute_many(
+            vec![
+                ToolInvocation::new("echo", ["fine"]).with_options(options.clone()),
+                ToolInvocation::new("echo", ["blocked"]).with_options(options.clone()),
+            ],
+            2,
+        )
+        .await;
+        let results = report.into_results();
+        assert_eq!(results[0].as_ref().unwrap().stdout_lossy(), "fine\n");
+        match &results[1] {
+            Err(ToolError::PolicyViolation { command, reason }) => {
+                assert_eq!(command, "echo");
+                assert_eq!(reason, "not on this branch");
+            }
+            other => panic!("expected a policy violation, got {:?}", other),
+        }
+
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        let task_id = Some("audit".to_string());
+        assert_eq!(seen, vec![("blocked".to_string(), task_id.clone(), None), ("fine".to_string(), task_id, Some(0))]);
+
+        let dry = options.with_dry_run(true);
+        ToolExecutor::execute_tool_with_options("echo", &["blocked"], None, &dry).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cwd_and_env_isolation() {
+        use std::collections::HashMap;
+
+        async fn env_of(options: &ExecOptions) -> HashMap<String, String> {
+            let output = ToolExecutor::execute_tool_with_options("env", &[], None, options).await.unwrap();
+            output
+                .stdout_lossy()
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        }
+
+        let inherited = env_of(&ExecOptions::new().with_env("AGENT_TEST", "1")).await;
+        assert_eq!(inherited.get("AGENT_TEST").map(String::as_str), Some("1"));
+        assert!(inherited.len() > 2);
+
+        let isolated = env_of(&ExecOptions::new().with_clear_env(true).with_env("AGENT_TEST", "2")).await;
+        let mut keys: Vec<_> = isolated.keys().map(String::as_str).collect();
+        keys.sort();
+        let mut expected: Vec<_> = EnvironmentManager::preserved_env_vars().into_keys().collect();
+        expected.push("AGENT_TEST".to_string());
+        expected.sort();
+        assert_eq!(keys, expected);
+        assert_eq!(isolated["AGENT_TEST"], "2");
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/file.txt"), "x").unwrap();
+        let options = ExecOptions::new().with_cwd(root.join("sub")).with_policy(ExecPolicy::confined_to(&root));
+        let output = ToolExecutor::execute_tool_with_options("pwd", &[], None, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy().trim(), root.join("sub").display().to_string());
+        // Relative paths are checked against the tool's directory, not ours.
+        let output = ToolExecutor::execute_tool_with_options("cat", &["./file.txt"], None, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "x");
+        let err = ToolExecutor::execute_tool_with_options("cat", &["../../etc"], None, &options).await.unwrap_err();
+        assert!(matches!(err, ToolError::PolicyViolation { .. }));
+
+        let missing = ExecOptions::new().with_cwd(root.join("missing"));
+        let err = ToolExecutor::execute_tool_with_options("pwd", &[], None, &missing).await.unwrap_err();
+        assert!(matches!(err, ToolError::MissingWorkingDirectory { .. }));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shim_dir_shadows_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let shims = dir.path().join("shims");
+        std::fs::create_dir(&shims).unwrap();
+        std::fs::write(shims.join("uname"), "#!/bin/sh\necho shimmed \"$PATH\"\n").unwrap();
+        std::fs::set_permissions(shims.join("uname"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let system = ToolExecutor::execute_tool_simple("uname", &[]).await.unwrap();
+        assert!(!system.starts_with("shimmed"));
+        let options = ExecOptions::new().with_search_path(SearchPath::new().with_shim_dir(&shims));
+        let output = ToolExecutor::execute_tool_with_options("uname", &[], None, &options).await.unwrap();
+        let path = output.stdout_lossy().trim().strip_prefix("shimmed ").unwrap().to_string();
+        assert_eq!(std::env::split_paths(&path).next().unwrap(), shims);
+        // Tools outside the shim dir still resolve from `PATH`.
+        let output = ToolExecutor::execute_tool_with_options("echo", &["hi"], None, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "hi\n");
+
+        let pinned = ExecOptions::new().with_search_path(SearchPath::new().with_shim_dir(&shims).with_dirs(Vec::<std::path::PathBuf>::new()));
+        let result = ToolExecutor::execute_tool_with_options("echo", &["hi"], None, &pinned).await;
+        assert!(matches!(result, Err(ToolError::NotFound { ref tool }) if tool == "echo"));
+        let description = ToolExecutor::execute_tool_with_options("uname", &[], None, &pinned.with_dry_run(true)).await.unwrap();
+        assert!(description.stdout_lossy().contains(&format!("executable: {}", shims.join("uname").display())));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dry_run_spawns_nothing() {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("ran");
+        let script = format!("touch '{}'", marker.display());
+        let options = ExecOptions::new().with_dry_run(true).with_env("MODE", "test");
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", &script], None, &options).await.unwrap();
+        let text = output.stdout_lossy();
+        assert!(text.starts_with("would run: sh -c "), "{}", text);
+        assert!(text.contains("env: MODE=test"));
+        assert!(text.contains("policy: none"));
+
+        let mut stream = ToolExecutor::execute_streaming("sh", &["-c", &script], &options).await.unwrap();
+        assert!(matches!(stream.next().await, Some(ToolEvent::Stdout(line)) if line.starts_with("would run:")));
+        assert_eq!(stream.finish().await.unwrap().exit_code, 0);
+        assert!(!marker.exists());
+
+        let registry = ToolRegistry::builtin();
+        let args = serde_json::json!({ "path": "Cargo.toml", "length": 10 });
+        let output = ToolExecutor::execute_registered(&registry, "read_file", &args, &options).await.unwrap();
+        let path = crate::system::PathUtils::resolve_path("Cargo.toml").unwrap();
+        assert_eq!(
+            output.stdout_lossy().lines().next().unwrap(),
+            format!("would call built-in 'read_file': read up to 10 bytes of {} starting at byte 0", path.display())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_probe_all_reports_versions_and_missing_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = dir.path().join("calls");
+        let script = format!("echo probed >> '{}'; echo 'tool v9.8.7 (build 12.1)'", calls.display());
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            ToolSpec::new("versioned", "Reports a version", "sh")
+                .version_probe(VersionProbe::new(["-c", script.as_str()]).with_pattern(r"v(\d+\.\d+\.\d+)").unwrap()),
+        );
+        registry.register(ToolSpec::new("missing", "Not installed", "no-such-tool-xyz"));
+        registry.register(ToolSpec::new("hangs", "Never answers", "sleep").version_probe(
+            VersionProbe::new(["30"]).with_timeout(std::time::Duration::from_millis(100)),
+        ));
+        registry.register(ToolSpec::builtin("inline", "Runs in-process", |_| async { Ok(ToolOutput::new("", "", 0, Default::default())) }));
+
+        let probes = registry.probe_all().await;
+        let names: Vec<_> = probes.iter().map(|probe| probe.name.as_str()).collect();
+        assert_eq!(names, ["hangs", "inline", "missing", "versioned"]);
+        let [hangs, inline, missing, versioned] = &probes[..] else { unreachable!() };
+        assert!(hangs.available && hangs.version.is_none());
+        assert_eq!((inline.available, &inline.resolved_path, &inline.version), (true, &None, &None));
+        assert!(!missing.available && missing.resolved_path.is_none());
+        assert!(versioned.resolved_path.as_ref().is_some_and(|path| path.ends_with("sh")));
+        assert_eq!(versioned.version.as_deref(), Some("9.8.7"));
+
+        // The version is asked for once per process.
+        assert_eq!(registry.probe_all().await, probes);
+        assert_eq!(std::fs::read_to_string(&calls).unwrap(), "probed\n");
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_gate_holds_destructive_tools() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out.txt");
+        let registry = ToolRegistry::builtin();
+        let write = serde_json::json!({ "path": target, "content": "x" });
+        let read = serde_json::json!({ "path": target });
+
+        // The hook answers from a script and keeps what it was asked.
+        let answers = Arc::new(Mutex::new(vec![Confirmation::Deny, Confirmation::Allow, Confirmation::AlwaysAllow]));
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let gate = {
+            let (answers, asked) = (Arc::clone(&answers), Arc::clone(&asked));
+            ConfirmationGate::new(move |request: ConfirmationRequest| {
+                asked.lock().unwrap().push(request);
+                let answer = answers.lock().unwrap().remove(0);
+                async move { answer }
+            })
+        };
+        let options = ExecOptions::new().with_confirmation(Arc::new(gate));
+
+        let err = ToolExecutor::execute_registered(&registry, "write_file", &write, &options).await.unwrap_err();
+        assert!(matches!(err, ToolError::ConfirmationDenied { ref tool, .. } if tool == "write_file"));
+        assert!(!target.exists());
+        let request = asked.lock().unwrap()[0].clone();
+        assert_eq!(request.danger, DangerLevel::Destructive);
+        assert!(request.description.starts_with("would call built-in 'write_file'"), "{}", request.description);
+
+        ToolExecutor::execute_registered(&registry, "write_file", &write, &options).await.unwrap();
+        assert!(target.exists());
+        // Safe tools, and tools below the threshold, are never held.
+        ToolExecutor::execute_registered(&registry, "read_file", &read, &options).await.unwrap();
+        ToolExecutor::execute_registered(&registry, "append_file", &write, &options).await.unwrap();
+        assert_eq!(asked.lock().unwrap().len(), 2);
+
+        // After "always", the hook is not asked about write_file again.
+        ToolExecutor::execute_registered(&registry, "write_file", &write, &options).await.unwrap();
+        ToolExecutor::execute_registered(&registry, "write_file", &write, &options).await.unwrap();
+        assert_eq!(asked.lock().unwrap().len(), 3);
+        assert!(answers.lock().unwrap().is_empty());
+
+        // With no one to ask, anything held is refused; dry runs still describe it.
+        let unattended = ExecOptions::new()
+            .with_confirmation(Arc::new(ConfirmationGate::non_interactive().with_threshold(DangerLevel::Caution)));
+        let err = ToolExecutor::execute_registered(&registry, "append_file", &write, &unattended).await.unwrap_err();
+        assert!(matches!(err, ToolError::ConfirmationDenied { .. }));
+        assert!(err.to_string().contains("--yes"));
+        let preview = unattended.with_dry_run(true);
+        ToolExecutor::execute_registered(&registry, "write_file", &write, &preview).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_tool_passes_args_positionally() {
+        use futures::StreamExt;
+        use serde_json::json;
+        use std::time::Duration;
+
+        let registry = ToolRegistry::builtin();
+        let call = json!({ "script": r#"printf '%s|' "$@"; echo; echo "$#" >&2"#, "args": ["a b", "$(touch pwned); *"] });
+        let err = ToolExecutor::execute_registered(&registry, "shell", &call, &ExecOptions::new()).await.unwrap_err();
+        assert!(err.to_string().contains("shell tool is disabled"), "{}", err);
+
+        let dir = tempfile::tempdir().unwrap();
+        let policy = ExecPolicy { allow_shell: true, ..ExecPolicy::confined_to(dir.path()) };
+        let options = ExecOptions::new().with_policy(policy).with_cwd(dir.path());
+        let output = ToolExecutor::execute_registered(&registry, "shell", &call, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "a b|$(touch pwned); *|\n");
+        assert_eq!(output.stderr_lossy(), "2\n");
+        assert!(!dir.path().join("pwned").exists());
+
+        // Arguments are still held to the policy; the script cannot be.
+        let escape = json!({ "script": "cat \"$1\"", "args": ["/etc/passwd"] });
+        let err = ToolExecutor::execute_registered(&registry, "shell", &escape, &options).await.unwrap_err();
+        assert!(matches!(err, ToolError::PolicyViolation { .. }));
+
+        let timed = options.clone().with_timeout(Duration::from_millis(200));
+        let err = ToolExecutor::execute_shell(ShellKind::Bash, "echo started; sleep 30", &[], &timed).await.unwrap_err();
+        assert!(matches!(err, ToolError::Timeout { .. }));
+
+        let mut stream = ToolExecutor::execute_shell_streaming(ShellKind::Bash, "echo \"$1\"; exit 3", &["hi"], &options).await.unwrap();
+        assert_eq!(stream.next().await, Some(ToolEvent::Stdout("hi".into())));
+        assert_eq!(stream.finish().await.unwrap().exit_code, 3);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rate_limit_shared_across_runs() {
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        let mut registry = ToolRegistry::new();
+        registry.register(ToolSpec::new("say", "Echo a word", "echo").arg("hi"));
+        registry.set_rate_limit("say", RateLimiter::per_second(1).with_mode(RateLimitMode::Error)).unwrap();
+        assert!(registry.set_rate_limit("sya", RateLimiter::per_second(1)).is_err());
+        let args = serde_json::json!({});
+        ToolExecutor::execute_json(&registry, "say", args.clone()).await.unwrap();
+        let err = ToolExecutor::execute_json(&registry, "say", args).await.unwrap_err();
+        assert!(matches!(err, ToolError::RateLimited { .. }));
+
+        // Two tokens up front, then one every 100ms: five runs need about 300ms
+        // however many run at once.
+        let limiter = Arc::new(RateLimiter::new(2, Duration::from_millis(200)));
+        let options = ExecOptions::new().with_rate_limit(limiter);
+        let runs: Vec<_> = (0..5).map(|_| ToolInvocation::new("true", Vec::<String>::new()).with_options(options.clone())).collect();
+        let started = Instant::now();
+        let results = ToolExecutor::execute_parallel(runs, 5).await;
+        assert!(results.iter().all(|r| r.as_ref().unwrap().success()));
+        assert!(started.elapsed() >= Duration::from_millis(250), "took {:?}", started.elapsed());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let counter = dir.path().join("attempts");
+        // Fails on the first two runs, succeeds on the third.
+        let script = format!(
+            "n=$(cat '{0}' 2>/dev/null || echo 0); n=$((n+1)); echo $n > '{0}'; echo try $n >&2; [ $n -ge 3 ]",
+            counter.display()
+        );
+        let retry = RetryPolicy::new(5).with_backoff(Duration::from_millis(10), 2.0);
+        let options = ExecOptions::new().with_retry(retry.clone());
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", &script], None, &options).await.unwrap();
+        assert!(output.success());
+        assert_eq!(output.attempts, 3);
+
+        // Gives up once the attempts run out, reporting the last failure.
+        std::fs::remove_file(&counter).unwrap();
+        let options = ExecOptions::new().with_retry(RetryPolicy { max_attempts: 2, ..retry.clone() });
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", &script], None, &options).await.unwrap();
+        assert_eq!((output.exit_code, output.attempts), (1, 2));
+
+        // A stderr pattern that does not match stops after the first attempt.
+        std::fs::remove_file(&counter).unwrap();
+        let pattern = RetryOn::StderrMatches(regex::Regex::new("timed out").unwrap());
+        let options = ExecOptions::new().with_retry(retry.with_retry_on(pattern));
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", &script], None, &options).await.unwrap();
+        assert_eq!(output.attempts, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_kills_tool() {
+        use std::time::{Duration, Instant};
+
+        let options = ExecOptions::new().with_timeout(Duration::from_millis(200));
+        let started = Instant::now();
+        let err = ToolExecutor::execute_tool_with_options("sleep", &["30"], None, &options).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(err, ToolError::Timeout { .. }));
+
+        // The shell's `sleep` child holds stdout open; only killing the group ends the run.
+        let err = ToolExecutor::execute_tool_with_options("sh", &["-c", "echo partial; sleep 30"], None, &options)
+            .await
+            .unwrap_err();
+        match &err {
+            ToolError::Timeout { elapsed, partial_stdout } => {
+                assert!(*elapsed >= Duration::from_millis(200));
+                assert_eq!(partial_stdout, "partial\n");
+            }
+            other => panic!("expected timeout, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_timeout_leaves_the_process_running() {
+        use std::time::Duration;
+
+        let manager = ProcessManager::new();
+        let slow = manager.spawn_process("sleep", &["30"]).await.unwrap();
+        let quick = manager.spawn_process("sh", &["-c", "sleep 0.2; exit 3"]).await.unwrap();
+        assert_eq!(slow.wait_timeout(Duration::from_millis(50)).await.unwrap(), None);
+        assert_eq!(slow.try_wait().unwrap(), None);
+        assert_eq!(quick.wait_timeout(Duration::from_secs(10)).await.unwrap().unwrap().code(), Some(3));
+        // Waiting again sees the same exit.
+        assert_eq!(quick.wait_timeout(Duration::ZERO).await.unwrap().unwrap().code(), Some(3));
+
+        let outcomes = manager.wait_all(Duration::from_millis(100)).await;
+        let still_running: Vec<_> = outcomes.iter().map(|(handle, status)| (handle.id(), status.as_ref().unwrap().is_none())).collect();
+        assert_eq!(still_running, vec![(slow.id(), true)]);
+        slow.kill().await.unwrap();
+        assert!(slow.wait_timeout(Duration::from_secs(5)).await.unwrap().is_some());
+        assert!(manager.wait_all(Duration::from_secs(1)).await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_processes_and_tools_run_at_their_priority() {
+        use tokio::io::AsyncReadExt;
+
+        let manager = ProcessManager::new();
+        let stdio = StdioConfig { stdout: StdioMode::Piped, ..StdioConfig::null() };
+        let options = SpawnOptions::new().with_stdio(stdio).with_priority(Priority::Idle);
+        let handle = manager.spawn_process_with("nice", &[], options).await.unwrap();
+        let mut stdout = String::new();
+        handle.take_stdout().unwrap().read_to_string(&mut stdout).await.unwrap();
+        assert_eq!(stdout, "19\n");
+        assert!(handle.wait().await.unwrap().success());
+
+        let options = ExecOptions::new().with_priority(Priority::BelowNormal);
+        let output = ToolExecutor::execute_tool_with_options("nice", &[], None, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "10\n");
+
+        // A registered tool runs at its own priority unless the caller picks one.
+        let mut registry = ToolRegistry::new();
+        registry.register(ToolSpec::new("niceness", "Print the niceness", "nice").priority(Priority::Idle));
+        let args = serde_json::json!({});
+        let output = ToolExecutor::execute_registered(&registry, "niceness", &args, &ExecOptions::new()).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "19\n");
+        let output = ToolExecutor::execute_registered(&registry, "niceness", &args, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "10\n");
+        let dry = ExecOptions::new().with_dry_run(true);
+        let output = ToolExecutor::execute_registered(&registry, "niceness", &args, &dry).await.unwrap();
+        assert!(output.stdout_lossy().contains("priority: idle\n"));
+
+        // The bulk command tools the agent ships with run at idle priority.
+        let builtin = ToolRegistry::builtin();
+        let idle: Vec<_> = builtin.list().into_iter().filter(|spec| spec.priority == Priority::Idle).map(|spec| spec.name.as_str()).collect();
+        assert_eq!(idle, ["grep", "wc"]);
+        let args = serde_json::json!({ "pattern": "x", "paths": ["."] });
+        let output = ToolExecutor::execute_registered(&builtin, "grep", &args, &dry).await.unwrap();
+        assert!(output.stdout_lossy().contains("priority: idle\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_tool_pty_runs_on_a_terminal() {
+        use std::time::{Duration, Instant};
+
+        let script = "[ -t 1 ] && echo terminal || echo pipe; stty size; echo oops >&2";
+        let size = TerminalSize { rows: 30, cols: 100 };
+        let output = ToolExecutor::execute_tool_pty_with_options("sh", &["-c", script], size, &ExecOptions::new()).await.unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout_lossy(), "terminal\r\n30 100\r\noops\r\n");
+        assert!(output.stderr_bytes().is_empty());
+        let piped = ToolExecutor::execute_tool("sh", &["-c", script]).await.unwrap();
+        assert!(piped.stdout_lossy().starts_with("pipe\n"));
+
+        // Left running on the terminal, ignoring its hangup: killed with it.
+        let started = Instant::now();
+        let output = ToolExecutor::execute_tool_pty("sh", &["-c", "(trap '' HUP; sleep 30) & echo started; exit 4"]).await.unwrap();
+        assert_eq!((output.exit_code, output.stdout_lossy().as_ref()), (4, "started\r\n"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let options = ExecOptions::new().with_timeout(Duration::from_millis(200));
+        let err = ToolExecutor::execute_tool_pty_with_options("sleep", &["30"], TerminalSize::default(), &options).await.unwrap_err();
+        assert!(matches!(err, ToolError::Timeout { .. }), "{:?}", err);
+    }
+
+    /// Whether `pid` exits within a second, signals being delivered
+    /// asynchronously; zombies left for an absent reaper count as gone.
+    #[cfg(target_os = "linux")]
+    async fn process_gone(pid: &str) -> bool {
+        for _ in 0..50 {
+            if std::fs::read_to_string(format!("/proc/{}/stat", pid)).map_or(true, |stat| stat.contains(") Z ")) {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cancel_terminates_tool_tree() {
+        use std::time::{Duration, Instant};
+        use futures::StreamExt;
+        use crate::CancellationToken;
+
+        let cancel_soon = || {
+            let token = CancellationToken::new();
+            let canceller = token.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                canceller.cancel();
+            });
+            token
+        };
+        let started = Instant::now();
+        let options = ExecOptions::new().with_cancel(cancel_soon());
+        let err = ToolExecutor::execute_tool_with_options("sh", &["-c", "sleep 30 & echo $!; wait"], None, &options)
+            .await
+            .unwrap_err();
+        let sleeper = match &err {
+            ToolError::Cancelled { partial_stdout, .. } => partial_stdout.trim().to_string(),
+            other => panic!("expected cancellation, got {:?}", other),
+        };
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(process_gone(&sleeper).await);
+
+        // A tool ignoring SIGTERM is killed once the grace period is over.
+        let options = ExecOptions::new().with_cancel(cancel_soon()).with_kill_grace(Duration::from_millis(200));
+        let script = "trap '' TERM; sleep 30 & echo $!; wait";
+        let mut stream = ToolExecutor::execute_streaming("sh", &["-c", script], &options).await.unwrap();
+        let Some(ToolEvent::Stdout(sleeper)) = stream.next().await else { panic!("expected the sleeper's pid") };
+        assert!(matches!(stream.finish().await.unwrap_err(), ToolError::Cancelled { .. }));
+        assert!(process_gone(&sleeper).await);
+
+        // Nothing starts once the token has fired.
+        let token = CancellationToken::new();
+        token.cancel();
+        let runs = vec![ToolInvocation::new("sleep", ["30"]).with_options(ExecOptions::new().with_cancel(token)); 2];
+        for result in ToolExecutor::execute_parallel(runs, 2).await {
+            assert!(matches!(result.unwrap_err(), ToolError::Cancelled { .. }));
+        }
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_kills_spawned_process() {
+        use crate::{CancellationToken, CoreError};
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let manager = ProcessManager::new();
+        let err = manager.spawn_process_cancellable("sleep", &["10"], Some(&token)).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<CoreError>(), Some(CoreError::Cancelled { .. })));
+        assert!(manager.list().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawned_process_handle() {
+        use tokio::io::AsyncReadExt;
+
+        let manager = ProcessManager::new();
+        let handle = manager.spawn_process("sleep", &["0.3"]).await.unwrap();
+        assert!(handle.pid() > 0);
+        assert_eq!(handle.stdio(), StdioConfig::default());
+        assert!(handle.try_wait().unwrap().is_none());
+        assert_eq!(manager.get(handle.id()).unwrap().pid(), handle.pid());
+        assert!(handle.wait().await.unwrap().success());
+        assert!(handle.try_wait().unwrap().unwrap().success());
+        assert!(manager.get(handle.id()).is_none());
+
+        let stdio = StdioConfig { stdout: StdioMode::Piped, ..StdioConfig::null() };
+        let options = SpawnOptions::new().with_stdio(stdio);
+        let handle = manager.spawn_process_with("sh", &["-c", "echo hi; exit 3"], options).await.unwrap();
+        let mut stdout = String::new();
+        handle.take_stdout().unwrap().read_to_string(&mut stdout).await.unwrap();
+        assert_eq!(stdout, "hi\n");
+        assert!(handle.take_stdout().is_none() && handle.take_stdin().is_none());
+        assert_eq!(handle.wait().await.unwrap().code(), Some(3));
+
+        let sleeper = manager.spawn_process("sleep", &["30"]).await.unwrap();
+        assert_eq!(manager.list().iter().map(ProcessHandle::id).collect::<Vec<_>>(), vec![sleeper.id()]);
+        sleeper.kill().await.unwrap();
+        assert!(!sleeper.try_wait().unwrap().unwrap().success());
+        assert!(manager.list().is_empty());
+        // Killing again is harmless.
+        sleeper.kill().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawned_environment_drops_secrets() {
+        use std::collections::HashMap;
+        use tokio::io::AsyncReadExt;
+
+        std::env::set_var("SPAWNTEST_API_TOKEN", "hunter22");
+        std::env::set_var("SPAWNTEST_REGION", "eu-west-1");
+        let manager = ProcessManager::new();
+        let env_of = |env_mode: EnvMode| {
+            let options = SpawnOptions::new()
+                .with_stdio(StdioConfig { stdout: StdioMode::Piped, ..StdioConfig::null() })
+                .with_env_mode(env_mode);
+            let manager = &manager;
+            async move {
+                let handle = manager.spawn_process_with("env", &[], options).await.unwrap();
+                let mut env = String::new();
+                handle.take_stdout().unwrap().read_to_string(&mut env).await.unwrap();
+                handle.wait().await.unwrap();
+                env.lines().filter_map(|line| line.split_once('=')).map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>()
+            }
+        };
+
+        let inherited = env_of(EnvMode::default()).await;
+        assert!(!inherited.contains_key("SPAWNTEST_API_TOKEN"));
+        assert_eq!(inherited["SPAWNTEST_REGION"], "eu-west-1");
+        assert_eq!(inherited["PATH"], std::env::var("PATH").unwrap());
+
+        let allowed = env_of(EnvMode::InheritAllowlist(vec!["PATH".into(), "SPAWNTEST_API_TOKEN".into(), "SPAWNTEST_UNSET".into()])).await;
+        assert_eq!(allowed.keys().count(), 2);
+        assert_eq!(allowed["SPAWNTEST_API_TOKEN"], "hunter22");
+
+        let extra = HashMap::from([("SPAWNTEST_MODE".to_string(), "clean".to_string())]);
+        let clean = env_of(EnvMode::Clean { extra }).await;
+        assert!(!clean.contains_key("SPAWNTEST_API_TOKEN") && !clean.contains_key("SPAWNTEST_REGION"));
+        assert_eq!(clean["SPAWNTEST_MODE"], "clean");
+        assert!(clean.contains_key("PATH"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shutdown_escalates_when_term_is_ignored() {
+        use std::time::{Duration, Instant};
+
+        let manager = ProcessManager::new();
+        let options = SpawnOptions::new().with_stdio(StdioConfig::null()).with_new_process_group(true);
+        let polite = manager
+            .spawn_process_with("sh", &["-c", "trap 'exit 0' TERM; while :; do sleep 0.05; done"], options.clone())
+            .await
+            .unwrap();
+        let stubborn = manager
+            .spawn_process_with("sh", &["-c", "trap '' TERM; while :; do sleep 0.05; done"], options)
+            .await
+            .unwrap();
+        // Let the shells install their traps.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let started = Instant::now();
+        let outcomes = manager.shutdown_all(Duration::from_millis(500)).await;
+        assert!(started.elapsed() < Duration::from_secs(3), "took {:?}", started.elapsed());
+        let graceful: Vec<_> = outcomes.iter().map(|(handle, outcome)| (handle.id(), *outcome.as_ref().unwrap())).collect();
+        assert_eq!(graceful, vec![(polite.id(), true), (stubborn.id(), false)]);
+        assert!(polite.try_wait().unwrap().unwrap().success());
+        assert!(!stubborn.try_wait().unwrap().unwrap().success());
+        assert!(manager.list().is_empty());
+        // Shutting down what already exited is graceful and immediate.
+        assert!(stubborn.shutdown(Duration::from_secs(5)).await.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_matches_shell() {
+        use std::time::Duration;
+
+        let input = "pear\napple\nfig\napple\nkiwi\npear\n";
+        let manager = ProcessManager::new();
+        let pipeline = manager
+            .pipeline(vec![
+                CommandSpec::new("printf", [input]),
+                CommandSpec::new("sort", ["-u"]),
+                CommandSpec::new("head", ["-n", "3"]),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(pipeline.stages().len(), 3);
+        let output = pipeline.wait().await.unwrap();
+        let shell = ToolExecutor::execute_tool("sh", &["-c", &format!("printf '{}' | sort -u | head -n 3", input)]).await.unwrap();
+        assert_eq!(output.stdout_lossy(), shell.stdout_lossy());
+        assert_eq!(output.stdout_lossy(), "apple\nfig\nkiwi\n");
+        assert_eq!(output.exit_codes(), vec![Some(0); 3]);
+        assert!(output.success() && manager.list().is_empty());
+
+        // An early stage failing ends the input of the later ones, and an
+        // endless writer is stopped by its reader exiting.
+        let failing = vec![
+            CommandSpec::new("sh", ["-c", "echo oops >&2; exit 3"]),
+            CommandSpec::new("cat", Vec::<String>::new()),
+            CommandSpec::new("wc", ["-l"]),
+        ];
+        let output = tokio::time::timeout(Duration::from_secs(10), manager.pipeline(failing).await.unwrap().wait())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.exit_codes(), vec![Some(3), Some(0), Some(0)]);
+        assert_eq!(output.stdout_lossy().trim(), "0");
+        assert_eq!(output.stages[0].stderr, b"oops\n");
+        assert!(!output.success());
+        let endless = vec![CommandSpec::new("yes", Vec::<String>::new()), CommandSpec::new("head", ["-n", "2"])];
+        let output = tokio::time::timeout(Duration::from_secs(10), manager.pipeline(endless).await.unwrap().wait())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.stdout_lossy(), "y\ny\n");
+        assert_eq!(output.stages[1].exit_code, Some(0));
+
+        let missing = vec![CommandSpec::new("sleep", ["30"]), CommandSpec::new("no-such-tool-xyz", Vec::<String>::new())];
+        let err = manager.pipeline(missing).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("stage 2"), "{:#}", err);
+        assert!(manager.list().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_kill_tree_takes_down_grandchildren() {
+        use std::time::{Duration, Instant};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        // Killed processes linger as zombies until reaped, which counts as gone.
+        fn alive(pid: u32) -> bool {
+            match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+                Ok(stat) => stat.rsplit_once(") ").is_some_and(|(_, rest)| !rest.starts_with('Z')),
+                Err(_) => false,
+            }
+        }
+
+        let manager = ProcessManager::new();
+        let options = SpawnOptions::new()
+            .with_stdio(StdioConfig { stdout: StdioMode::Piped, ..StdioConfig::null() })
+            .with_new_process_group(true);
+        let parent = manager.spawn_process_with("sh", &["-c", "sleep 30 & echo $!; wait"], options).await.unwrap();
+        let mut line = String::new();
+        BufReader::new(parent.take_stdout().unwrap()).read_line(&mut line).await.unwrap();
+        let child: u32 = line.trim().parse().unwrap();
+        assert!(alive(parent.pid()) && alive(child));
+
+        parent.kill_tree().await.unwrap();
+        let started = Instant::now();
+        while (alive(parent.pid()) || alive(child)) && started.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!alive(parent.pid()), "parent {} still running", parent.pid());
+        assert!(!alive(child), "grandchild {} still running", child);
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_windows_spawn_wait_and_kill_tree() {
+        use std::time::{Duration, Instant};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        async fn alive(pid: u32) -> bool {
+            let filter = format!("PID eq {}", pid);
+            let output = tokio::process::Command::new("tasklist").args(["/FI", &filter, "/NH"]).output().await.unwrap();
+            String::from_utf8_lossy(&output.stdout).split_whitespace().any(|word| word == pid.to_string())
+        }
+
+        let manager = ProcessManager::new();
+        let handle = manager.spawn_process("powershell", &["-NoProfile", "-c", "exit 3"]).await.unwrap();
+        assert_eq!(handle.wait().await.unwrap().code(), Some(3));
+
+        let options = SpawnOptions::new()
+            .with_stdio(StdioConfig { stdout: StdioMode::Piped, ..StdioConfig::null() })
+            .with_new_process_group(true);
+        let script = "$p = Start-Process timeout.exe -ArgumentList '/t','30','/nobreak' -NoNewWindow -PassThru; $p.Id; Start-Sleep 30";
+        let parent = manager.spawn_process_with("powershell", &["-NoProfile", "-c", script], options).await.unwrap();
+        let mut line = String::new();
+        BufReader::new(parent.take_stdout().unwrap()).read_line(&mut line).await.unwrap();
+        let child: u32 = line.trim().parse().unwrap();
+        assert!(alive(child).await);
+
+        parent.kill_tree().await.unwrap();
+        assert!(parent.try_wait().unwrap().is_some());
+        let started = Instant::now();
+        while alive(child).await && started.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!alive(child).await, "grandchild {} still running", child);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_named_processes_survive_their_manager() {
+        use std::time::Duration;
+
+        let state = tempfile::tempdir().unwrap();
+        let first = ProcessManager::new().with_state_dir(state.path());
+        let server = || BackgroundSpec::new(CommandSpec::new("sleep", ["30"]));
+        first.start_named("dev-server", server()).await.unwrap();
+        let err = first.start_named("dev-server", server()).await.unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+        assert!(first.start_named("../escape", server()).await.is_err());
+        let status = first.status("dev-server").await.unwrap();
+        assert!(status.running && status.last_exit.is_none());
+        assert_eq!(status.command, "sleep 30");
+        // Interactive mode's shutdown leaves named processes alone.
+        assert!(first.shutdown_all(Duration::from_millis(100)).await.is_empty());
+
+        first.start_named("dev-server", server().with_replace(true)).await.unwrap();
+        let replaced = first.status("dev-server").await.unwrap();
+        assert_ne!(replaced.pid, status.pid);
+
+        // A later manager finds it through its pid file and can stop it.
+        let second = ProcessManager::new().with_state_dir(state.path());
+        assert_eq!(second.status("dev-server").await.unwrap().pid, replaced.pid);
+        assert!(second.stop("dev-server", Duration::from_secs(5)).await.unwrap());
+        assert!(second.list_named().await.unwrap().is_empty());
+        assert!(second.status("dev-server").await.is_err());
+        let ended = first.status("dev-server").await.unwrap();
+        assert!(!ended.running && ended.uptime.is_zero());
+
+        let quick = BackgroundSpec::new(CommandSpec::new("sh", ["-c", "exit 3"]));
+        first.start_named("quick", quick).await.unwrap();
+        while first.status("quick").await.unwrap().running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let names: Vec<_> = first.list_named().await.unwrap().into_iter().map(|status| (status.name, status.last_exit)).collect();
+        assert_eq!(names, vec![("dev-server".to_string(), None), ("quick".to_string(), Some(3))]);
+        assert!(first.stop("quick", Duration::from_secs(1)).await.unwrap());
+        assert!(first.stop("quick", Duration::from_secs(1)).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_on_exit_reports_status_and_output_tails() {
+        use futures::StreamExt;
+        use std::time::Duration;
+
+        let manager = ProcessManager::new();
+        let options = SpawnOptions::new().with_stdio(StdioConfig::piped());
+        let script = "i=0; while [ $i -lt 2000 ]; do echo line $i; i=$((i+1)); done; echo oops >&2; exit 2";
+        let handle = manager.spawn_process_with("sh", &["-c", script], options).await.unwrap();
+        let exited = handle.on_exit();
+        // Only one line is taken; the rest is still read into the tails.
+        let mut lines = handle.events();
+        lines.next().await.unwrap().unwrap();
+        let info = exited.await.unwrap();
+        assert_eq!(info.code(), Some(2));
+        assert!(!info.success() && info.duration > Duration::ZERO);
+        assert!(info.stdout_tail.len() <= EXIT_TAIL_BYTES && info.tail_lossy(OutputSource::Stdout).ends_with("line 1999\n"));
+        assert_eq!(info.tail_lossy(OutputSource::Stderr), "oops\n");
+        // Asking after the exit answers right away.
+        assert_eq!(handle.on_exit().await.unwrap(), info);
+        drop(lines);
+
+        // A named process's tails come from its log files.
+        let state = tempfile::tempdir().unwrap();
+        let manager = ProcessManager::new().with_state_dir(state.path());
+        let mut events = manager.subscribe();
+        let crash = BackgroundSpec::new(CommandSpec::new("sh", ["-c", "echo starting; echo 'model not found' >&2; exit 1"]));
+        manager.start_named("worker", crash).await.unwrap();
+        let (name, info) = loop {
+            if let ProcessEvent::NamedExited { name, info } = events.recv().await.unwrap() {
+                break (name, info);
+            }
+        };
+        assert_eq!((name.as_str(), info.code()), ("worker", Some(1)));
+        assert_eq!((info.stdout_tail.as_slice(), info.stderr_tail.as_slice()), (&b"starting\n"[..], &b"model not found\n"[..]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_detached_processes_outlive_their_spawner() {
+        use std::time::Duration;
+
+        let state = tempfile::tempdir().unwrap();
+        let server = || BackgroundSpec::new(CommandSpec::new("sh", ["-c", "echo listening; sleep 30"]));
+        let dir = state.path().to_path_buf();
+        let spawner = tokio::spawn(async move { ProcessManager::new().with_state_dir(dir).spawn_detached("model-server", server()).await });
+        let pid = spawner.await.unwrap().unwrap();
+        // SAFETY: plain syscalls reading process ids.
+        unsafe {
+            assert_ne!(libc::getsid(pid as libc::pid_t), libc::getsid(0));
+            assert_eq!(libc::getpgid(pid as libc::pid_t), pid as libc::pid_t);
+        }
+
+        let manager = ProcessManager::new().with_state_dir(state.path());
+        let pid_file = state.path().join("model-server.json");
+        let adopted = manager.adopt(&pid_file).await.unwrap();
+        assert_eq!(adopted.pid(), pid);
+        assert!(adopted.is_running().await);
+        assert!(manager.status("model-server").await.unwrap().running);
+        let err = manager.spawn_detached("model-server", server()).await.unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+        let (stdout, _) = adopted.log_files().unwrap();
+        for _ in 0..100 {
+            if std::fs::read_to_string(&stdout).unwrap_or_default() == "listening\n" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(std::fs::read_to_string(&stdout).unwrap(), "listening\n");
+
+        // A pid now running some other executable is not signalled.
+        let mut record: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&pid_file).unwrap()).unwrap();
+        record["exe"] = "/no/such/server".into();
+        let impostor = state.path().join("impostor.json");
+        std::fs::write(&impostor, record.to_string()).unwrap();
+        assert!(manager.adopt(&impostor).await.is_err());
+
+        assert!(adopted.stop(Duration::from_secs(5)).await.unwrap());
+        assert!(!adopted.is_running().await);
+        assert!(!pid_file.exists());
+        assert!(manager.adopt(&pid_file).await.is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[tokio::test]
+    async fn test_usage_samples_a_busy_process() {
+        use std::time::Duration;
+
+        let manager = ProcessManager::new();
+        let busy = manager.spawn_process("sh", &["-c", "while :; do :; done"]).await.unwrap();
+        let idle = manager.spawn_process("sleep", &["30"]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let usage = busy.usage().unwrap();
+        assert!(usage.cpu_percent > 10.0, "{:?}", usage);
+        assert!(usage.rss_bytes > 0 && usage.num_threads >= 1);
+        assert!(usage.uptime >= Duration::from_millis(300), "{:?}", usage);
+        // Later samples cover the time since the one before.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(busy.usage().unwrap().cpu_percent > 10.0);
+
+        let all = manager.usage_all();
+        assert_eq!(all.len(), 2);
+        let idle_usage = all[1].1.as_ref().unwrap();
+        assert!(idle_usage.cpu_percent < 10.0 && idle_usage.rss_bytes > 0, "{:?}", idle_usage);
+        assert_eq!(ProcessUsage::of_pid(idle.pid()).unwrap().num_threads, idle_usage.num_threads);
+
+        busy.kill().await.unwrap();
+        idle.kill().await.unwrap();
+        busy.wait().await.ok();
+        assert!(busy.usage().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pty_drives_interactive_programs() {
+        use futures::{Stream, StreamExt};
+        use std::time::Duration;
+
+        async fn read_until(output: &mut (impl Stream<Item = anyhow::Result<bytes::Bytes>> + Unpin), seen: &mut String, expected: &str) {
+            let read = async {
+                while !seen.contains(expected) {
+                    let chunk = output.next().await.expect("terminal closed").unwrap();
+                    seen.push_str(&String::from_utf8_lossy(&chunk));
+                }
+            };
+            if tokio::time::timeout(Duration::from_secs(10), read).await.is_err() {
+                panic!("no {:?} in {:?}", expected, seen);
+            }
+        }
+
+        let manager = ProcessManager::new();
+        let options = SpawnOptions::new().with_pty(true);
+        let shell = manager
+            .spawn_process_with("sh", &["-c", "read line; stty size; printf '\\033[1m%s\\033[0m' \"$line\""], options.clone())
+            .await
+            .unwrap();
+        let mut output = Box::pin(shell.pty_output());
+        shell.resize(30, 100).unwrap();
+        shell.write_input("go\n").await.unwrap();
+        let mut seen = String::new();
+        read_until(&mut output, &mut seen, "30 100").await;
+        // Escape sequences arrive as written.
+        read_until(&mut output, &mut seen, "\x1b[1mgo\x1b[0m").await;
+        assert!(shell.wait().await.unwrap().success());
+        assert!(shell.pty_output().next().await.unwrap().is_err());
+
+        let piped = manager.spawn_process_with("true", &[], SpawnOptions::new()).await.unwrap();
+        assert!(piped.write_input("x").await.is_err());
+        assert!(piped.resize(24, 80).is_err());
+
+        if crate::system::PathUtils::find_executable("python3").is_none() {
+            return;
+        }
+        let python = manager.spawn_process_with("python3", &["-i", "-q"], options).await.unwrap();
+        let mut output = Box::pin(python.pty_output());
+        let mut seen = String::new();
+        read_until(&mut output, &mut seen, ">>> ").await;
+        python.write_input("print(6 * 7)\n").await.unwrap();
+        read_until(&mut output, &mut seen, "42").await;
+        python.write_input("exit()\n").await.unwrap();
+        assert!(python.wait().await.unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_process_output_lines() {
+        use futures::StreamExt;
+
+        // Far more stderr than a pipe holds, all written before any stdout.
+        let manager = ProcessManager::new();
+        let script = "i=0; while [ $i -lt 20000 ]; do echo err-$i >&2; i=$((i+1)); done; echo done";
+        let options = SpawnOptions::new().with_stdio(StdioConfig { stdin: StdioMode::Null, ..StdioConfig::piped() });
+        let handle = manager.spawn_process_with("sh", &["-c", script], options.clone()).await.unwrap();
+        let (stdout, stderr) = (handle.stdout_lines(), handle.stderr_lines());
+        let stdout: Vec<String> = stdout.map(Result::unwrap).collect().await;
+        assert_eq!(stdout, vec!["done"]);
+        assert_eq!(stderr.count().await, 20000);
+        assert!(handle.stdout_lines().next().await.unwrap().is_err());
+
+        let handle = manager.spawn_process_with("sh", &["-c", "echo a; echo b >&2; printf 'c\r\nd'"], options).await.unwrap();
+        let mut lines: Vec<_> = handle.events().map(|line| line.map(|l| (l.source, l.line)).unwrap()).collect().await;
+        lines.sort_by_key(|(source, _)| *source == OutputSource::Stderr);
+        let expected = [(OutputSource::Stdout, "a"), (OutputSource::Stdout, "c"), (OutputSource::Stdout, "d"), (OutputSource::Stderr, "b")];
+        assert_eq!(lines, expected.map(|(source, line)| (source, line.to_string())));
+
+        // Named processes log to their state directory, and are followed until they exit.
+        let state = tempfile::tempdir().unwrap();
+        let manager = ProcessManager::new().with_state_dir(state.path());
+        let spec = BackgroundSpec::new(CommandSpec::new("sh", ["-c", "echo one; echo two >&2; sleep 0.5; echo three"]));
+        manager.start_named("talker", spec).await.unwrap();
+        let followed: Vec<_> = manager.logs("talker", true).await.unwrap().map(|line| line.unwrap().line).collect().await;
+        assert_eq!(followed.len(), 3, "{:?}", followed);
+        assert!(followed.contains(&"three".to_string()));
+        let later = ProcessManager::new().with_state_dir(state.path());
+        assert_eq!(later.logs("talker", false).await.unwrap().count().await, 3);
+        assert!(later.logs("nobody", false).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restart_policies() {
+        use std::time::Duration;
+
+        // Fails twice, then succeeds.
+        let dir = tempfile::tempdir().unwrap();
+        let count = dir.path().join("count");
+        let script = format!(
+            "n=$(cat '{0}' 2>/dev/null || echo 0); n=$((n+1)); echo $n > '{0}'; [ $n -ge 3 ] && exit 0; exit 1",
+            count.display()
+        );
+        let manager = ProcessManager::new();
+        let mut events = manager.subscribe();
+        let window = Duration::from_secs(60);
+        let options = SpawnOptions::new()
+            .with_stdio(StdioConfig::null())
+            .with_restart(RestartPolicy::OnFailure { max_restarts: 5, window });
+        let handle = manager.spawn_process_with("sh", &["-c", &script], options.clone()).await.unwrap();
+        assert!(handle.wait().await.unwrap().success());
+        assert_eq!((handle.restarts(), handle.supervision()), (2, Supervision::Exited));
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(match event {
+                ProcessEvent::Started { .. } => "started",
+                ProcessEvent::Exited { code: Some(0), .. } => "succeeded",
+                ProcessEvent::Exited { .. } => "failed",
+                ProcessEvent::Restarting { .. } => "restarting",
+                ProcessEvent::GaveUp { .. } => "gave up",
+                ProcessEvent::NamedExited { .. } => "named exited",
+            });
+        }
+        let failure = ["started", "failed", "restarting"];
+        assert_eq!(seen, [&failure[..], &failure[..], &["started", "succeeded"][..]].concat());
+
+        // Out of restarts before the third run.
+        std::fs::remove_file(&count).unwrap();
+        let options = options.with_restart(RestartPolicy::OnFailure { max_restarts: 1, window });
+        let handle = manager.spawn_process_with("sh", &["-c", &script], options.clone()).await.unwrap();
+        assert_eq!(handle.wait().await.unwrap().code(), Some(1));
+        assert_eq!((handle.restarts(), handle.supervision()), (1, Supervision::GaveUp));
+        assert!(matches!(events.recv().await.unwrap(), ProcessEvent::Started { .. }));
+
+        // Stopping it is final, whatever the policy.
+        let options = options.with_restart(RestartPolicy::Always);
+        let handle = manager.spawn_process_with("sleep", &["30"], options).await.unwrap();
+        handle.kill().await.unwrap();
+        assert!(!handle.wait().await.unwrap().success());
+        assert_eq!((handle.restarts(), handle.supervision()), (0, Supervision::Exited));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tool_error_variants() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+        use crate::CancellationToken;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("not-executable.sh");
+        std::fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let err = ToolExecutor::execute_tool(script.to_str().unwrap(), &[]).await.unwrap_err();
+        assert!(matches!(err, ToolError::SpawnFailed { ref tool, .. } if tool.ends_with("not-executable.sh")), "{:?}", err);
+
+        let confined = ExecOptions::new().with_policy(ExecPolicy::confined_to(dir.path()));
+        let err = ToolExecutor::execute_tool_with_options("cat", &["/etc/hostname"], None, &confined).await.unwrap_err();
+        assert!(matches!(err, ToolError::PolicyViolation { .. }));
+
+        let registry = ToolRegistry::builtin();
+        let err = ToolExecutor::execute_json(&registry, "read_file", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidArgs(ToolCallError::MissingArgument(_))));
+        let missing = serde_json::json!({ "path": dir.path().join("missing.txt") });
+        let err = ToolExecutor::execute_json(&registry, "read_file", missing).await.unwrap_err();
+        assert!(matches!(err, ToolError::Other(_)), "{:?}", err);
+
+        let quick = ExecOptions::new().with_timeout(Duration::from_millis(100));
+        let err = ToolExecutor::execute_tool_with_options("sleep", &["5"], None, &quick).await.unwrap_err();
+        assert!(matches!(err, ToolError::Timeout { .. }));
+        let token = CancellationToken::new();
+        token.cancel();
+        let err = ToolExecutor::execute_tool_with_options("sleep", &["5"], None, &ExecOptions::new().with_cancel(token)).await.unwrap_err();
+        assert!(matches!(err, ToolError::Cancelled { .. }));
+
+        // Typed errors survive a trip through anyhow, context and all.
+        let wrapped = anyhow::Error::from(ToolError::NotFound { tool: "x".into() }).context("while planning");
+        assert!(matches!(ToolError::from(wrapped), ToolError::NotFound { .. }));
+        let io = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(matches!(ToolError::from(io), ToolError::Io(_)));
+        assert!(matches!(ToolError::from(anyhow::anyhow!("odd")), ToolError::Other(_)));
+    }
+
+    /// A tool copying its stdin to stdout.
+    #[cfg(unix)]
+    const PASSTHROUGH: (&str, &[&str]) = ("cat", &[]);
+    #[cfg(windows)]
+    const PASSTHROUGH: (&str, &[&str]) = ("findstr", &["^"]);
+
+    #[tokio::test]
+    async fn test_stdin_sources_pass_large_input() {
+        use futures::StreamExt;
+        use crate::FileReader;
+
+        // 10MB, well past any pipe buffer: written while the output is read.
+        let line = "0123456789abcdef".repeat(4);
+        let text: String = std::iter::repeat_n(format!("{}\n", line), 10 * 1024 * 1024 / 65).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.txt");
+        std::fs::write(&path, &text).unwrap();
+        let (tool, args) = PASSTHROUGH;
+
+        let unlimited = || ExecOptions::new().with_max_output_bytes(OutputLimit::UNLIMITED);
+        for source in [StdinSource::Text(text.clone()), StdinSource::Bytes(text.clone().into_bytes()), StdinSource::File(path.clone())] {
+            let options = unlimited().with_stdin(source);
+            let output = ToolExecutor::execute_tool_with_options(tool, args, None, &options).await.unwrap();
+            let stdout = output.stdout_lossy();
+            assert_eq!(stdout.lines().count(), text.lines().count());
+            assert!(stdout.lines().all(|l| l == line));
+        }
+        let output = ToolExecutor::execute_tool_with_options(tool, args, None, &ExecOptions::new()).await.unwrap();
+        assert!(output.stdout_bytes().is_empty());
+        let missing = ExecOptions::new().with_stdin(StdinSource::File(dir.path().join("missing")));
+        assert!(ToolExecutor::execute_tool_with_options(tool, args, None, &missing).await.is_err());
+
+        let lines = FileReader::read_lines(&path).await.unwrap();
+        let mut stream = ToolExecutor::execute_streaming_with_input(tool, args, lines, &unlimited()).await.unwrap();
+        let mut echoed = 0;
+        while let Some(event) = stream.next().await {
+            match event {
+                ToolEvent::Stdout(out) => {
+                    assert_eq!(out, line);
+                    echoed += 1;
+                }
+                ToolEvent::Exited(code) => assert_eq!(code, 0),
+                ToolEvent::Stderr(err) => panic!("unexpected stderr: {}", err),
+            }
+        }
+        assert_eq!(echoed, text.lines().count());
+
+        let dry = ExecOptions::new().with_dry_run(true).with_stdin(StdinSource::File(path.clone()));
+        let output = ToolExecutor::execute_tool_with_options(tool, args, None, &dry).await.unwrap();
+        assert!(output.stdout_lossy().contains(&format!("stdin: from {}", path.display())));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_limit_truncates_and_drains() {
+        use futures::StreamExt;
+
+        let script = "head -c 3000000 /dev/zero | tr '\\0' x; echo é; echo oops >&2";
+        let options = ExecOptions::new().with_max_output_bytes(OutputLimit::new(1000, 3));
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", script], None, &options).await.unwrap();
+        assert!(output.success());
+        assert!(output.stdout_truncated && output.stderr_truncated);
+        assert_eq!((output.stdout_total_bytes, output.stderr_total_bytes), (3_000_003, 5));
+        assert_eq!(output.stdout_lossy(), format!("{}\n[... truncated 2999003 bytes]", "x".repeat(1000)));
+        assert_eq!(output.stderr_lossy(), "oop\n[... truncated 2 bytes]");
+
+        let mut stream = ToolExecutor::execute_streaming("sh", &["-c", script], &options).await.unwrap();
+        let mut stdout_events = 0;
+        while let Some(event) = stream.next().await {
+            if let ToolEvent::Stdout(line) = event {
+                assert_eq!(line.len(), 1000);
+                stdout_events += 1;
+            }
+        }
+        assert_eq!(stdout_events, 1);
+
+        let output = ToolExecutor::execute_tool("echo", &["small"]).await.unwrap();
+        assert!(!output.stdout_truncated);
+        assert_eq!(output.stdout_total_bytes, 6);
+    }
+}
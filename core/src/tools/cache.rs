@@ -0,0 +1,546 @@
+// Cache for tool output, in memory and optionally on disk
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use super::executor::{ExecOptions, ToolOutput};
+use super::registry::{ToolCallError, ToolHandler, ToolSpec};
+use crate::file_processor::DirWalker;
+use crate::system::PathUtils;
+
+/// Default lifetime of a cache entry.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Default upper bound on the number of cached entries.
+pub const DEFAULT_CACHE_ENTRIES: usize = 1000;
+/// Default upper bound on the output bytes a `ToolCache` holds, per store.
+pub const DEFAULT_CACHE_BYTES: u64 = 64 << 20;
+/// Subdirectory of `PathUtils::cache_dir` holding `ToolCache` entries.
+pub const TOOL_CACHE_DIR: &str = "tools";
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch when the entry was stored.
+    created_at: u64,
+    output: ToolOutput,
+}
+
+/// Entries as JSON files in one directory, named by key.
+#[derive(Debug, Clone)]
+struct DiskStore {
+    dir: PathBuf,
+}
+
+impl DiskStore {
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The stored output, unless it is missing, unreadable or older than `ttl`.
+    async fn get(&self, key: &str, ttl: Duration) -> Option<ToolOutput> {
+        let path = self.entry_path(key);
+        let bytes = tokio::fs::read(&path).await.ok()?;
+        // Unreadable entries (e.g. from an older format) are treated as misses.
+        let entry: CacheEntry = match serde_json::from_slice(&bytes) {
+            Ok(entry) => entry,
+            Err(_) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return None;
+            }
+        };
+        let age = now_secs().saturating_sub(entry.created_at);
+        if age >= ttl.as_secs() {
+            let _ = tokio::fs::remove_file(&path).await;
+            return None;
+        }
+        Some(entry.output)
+    }
+
+    /// Stores `output`, then evicts the oldest entries beyond `max_entries`
+    /// or `max_bytes`.
+    async fn put(&self, key: &str, output: &ToolOutput, max_entries: usize, max_bytes: u64) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create cache directory {}", self.dir.display()))?;
+        let entry = CacheEntry {
+            created_at: now_secs(),
+            output: output.clone(),
+        };
+        // Write then rename so concurrent readers never see a partial entry.
+        let path = self.entry_path(key);
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(&entry)?)
+            .await
+            .with_context(|| format!("Failed to write cache entry {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &path).await?;
+        self.evict(max_entries, max_bytes).await
+    }
+
+    /// Deletes the oldest entries until at most `max_entries` remain,
+    /// taking up at most `max_bytes`.
+    async fn evict(&self, max_entries: usize, max_bytes: u64) -> Result<()> {
+        let mut entries = self.entries().await?;
+        let mut bytes: u64 = entries.iter().map(|entry| entry.2).sum();
+        if entries.len() <= max_entries && bytes <= max_bytes {
+            return Ok(());
+        }
+        entries.sort_by_key(|(_, modified, _)| *modified);
+        let mut remaining = entries.len();
+        for (path, _, size) in entries {
+            if remaining <= max_entries && bytes <= max_bytes {
+                break;
+            }
+            remove_if_present(&path).await?;
+            remaining -= 1;
+            bytes = bytes.saturating_sub(size);
+        }
+        Ok(())
+    }
+
+    /// Removes every entry and returns how many were deleted.
+    async fn clear(&self) -> Result<usize> {
+        let entries = self.entries().await?;
+        for (path, _, _) in &entries {
+            remove_if_present(path).await?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Each entry's path, modification time and size.
+    async fn entries(&self) -> Result<Vec<(PathBuf, SystemTime, u64)>> {
+        let mut dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let metadata = entry.metadata().await?;
+                entries.push((path, metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len()));
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Output of cacheable registry tools (`ToolSpec::cacheable`), reused while
+/// the call is unchanged. Set it with `ExecOptions::with_cache`; share the
+/// `Arc` to share entries between runs.
+///
+/// Entries live in memory, least recently used evicted first, and with
+/// `with_disk` also in a directory so they outlive the process. A call's key
+/// covers the tool, its normalised arguments, the options that affect what
+/// it sees (working directory, environment, policy, sandbox, limits, output
+/// limit) and the current state of every path argument: a file's contents
+/// and modification time, or for a directory the size and modification time
+/// of everything under it. Editing a referenced file therefore misses rather
+/// than returning stale output. Only successful runs are stored.
+pub struct ToolCache {
+    memory: Mutex<MemoryStore>,
+    disk: Option<DiskStore>,
+    pub ttl: Duration,
+    pub max_entries: usize,
+    pub max_bytes: u64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    entries: HashMap<String, MemoryEntry>,
+    /// Keys by last use, oldest first.
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    bytes: u64,
+}
+
+struct MemoryEntry {
+    stored: Instant,
+    used: u64,
+    size: u64,
+    output: ToolOutput,
+}
+
+impl MemoryStore {
+    fn get(&mut self, key: &str, ttl: Duration) -> Option<ToolOutput> {
+        let entry = self.entries.get(key)?;
+        if entry.stored.elapsed() >= ttl {
+            self.remove(key);
+            return None;
+        }
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used);
+        entry.used = self.clock;
+        self.recency.insert(self.clock, key.to_string());
+        Some(entry.output.clone())
+    }
+
+    fn put(&mut self, key: &str, output: &ToolOutput, max_entries: usize, max_bytes: u64) {
+        self.remove(key);
+        let size = (output.stdout_bytes().len() + output.stderr_bytes().len()) as u64;
+        if size > max_bytes || max_entries == 0 {
+            return;
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, key.to_string());
+        self.entries.insert(key.to_string(), MemoryEntry { stored: Instant::now(), used: self.clock, size, output: output.clone() });
+        self.bytes += size;
+        while self.entries.len() > max_entries || self.bytes > max_bytes {
+            let Some((_, oldest)) = self.recency.pop_first() else { break };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.bytes -= entry.size;
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+            self.bytes -= entry.size;
+        }
+    }
+}
+
+/// What a `ToolCache` holds and how often it has been used.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub memory_entries: usize,
+    pub memory_bytes: u64,
+    pub disk_entries: usize,
+    pub disk_bytes: u64,
+    /// Lookups answered from the cache since it was created.
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ToolCache {
+    /// An in-memory cache with the default TTL and size limits.
+    pub fn new() -> Self {
+        Self {
+            memory: Mutex::default(),
+            disk: None,
+            ttl: DEFAULT_CACHE_TTL,
+            max_entries: DEFAULT_CACHE_ENTRIES,
+            max_bytes: DEFAULT_CACHE_BYTES,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// An in-memory cache backed by `TOOL_CACHE_DIR` under `PathUtils::cache_dir`.
+    pub fn persistent() -> Result<Self> {
+        Ok(Self::new().with_disk(PathUtils::cache_dir()?.join(TOOL_CACHE_DIR)))
+    }
+
+    /// Also keeps entries as files in `dir`, consulted on a memory miss.
+    pub fn with_disk(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk = Some(DiskStore { dir: dir.into() });
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Caps the entries held in memory and on disk, each.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Caps the bytes held in memory (output) and on disk (entry files), each.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The directory entries are kept in, if any.
+    pub fn disk_dir(&self) -> Option<&Path> {
+        self.disk.as_ref().map(|store| store.dir.as_path())
+    }
+
+    /// The fresh output stored under `key`, marked `cached`.
+    pub async fn get(&self, key: &str) -> Option<ToolOutput> {
+        let mut found = self.memory().get(key, self.ttl);
+        if found.is_none() {
+            if let Some(disk) = &self.disk {
+                found = disk.get(key, self.ttl).await;
+                if let Some(output) = &found {
+                    self.memory().put(key, output, self.max_entries, self.max_bytes);
+                }
+            }
+        }
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found.map(ToolOutput::mark_cached)
+    }
+
+    /// Stores `output` under `key`, evicting older entries beyond the limits.
+    pub async fn put(&self, key: &str, output: &ToolOutput) -> Result<()> {
+        let mut output = output.clone();
+        output.cached = false;
+        self.memory().put(key, &output, self.max_entries, self.max_bytes);
+        match &self.disk {
+            Some(disk) => disk.put(key, &output, self.max_entries, self.max_bytes).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Removes every entry, in memory and on disk, and returns how many
+    /// were removed from each store combined.
+    pub async fn clear(&self) -> Result<usize> {
+        let removed = {
+            let mut memory = self.memory();
+            let count = memory.entries.len();
+            *memory = MemoryStore::default();
+            count
+        };
+        match &self.disk {
+            Some(disk) => Ok(removed + disk.clear().await?),
+            None => Ok(removed),
+        }
+    }
+
+    pub async fn stats(&self) -> Result<CacheStats> {
+        let (memory_entries, memory_bytes) = {
+            let memory = self.memory();
+            (memory.entries.len(), memory.bytes)
+        };
+        let disk = match &self.disk {
+            Some(disk) => disk.entries().await?,
+            None => Vec::new(),
+        };
+        Ok(CacheStats {
+            memory_entries,
+            memory_bytes,
+            disk_entries: disk.len(),
+            disk_bytes: disk.iter().map(|entry| entry.2).sum(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
+
+    /// The key for calling `spec` with `arguments` under `options`. Invalid
+    /// arguments fail as they would when running the tool.
+    pub async fn key(&self, spec: &ToolSpec, arguments: &Value, options: &ExecOptions) -> Result<String, ToolCallError> {
+        // Built-ins resolve paths against our directory; commands against theirs.
+        let cwd = match spec.handler {
+            ToolHandler::Builtin(_) => None,
+            _ => options.cwd.as_deref(),
+        };
+        let args = spec.validate_in(arguments, cwd)?;
+        let mut key = KeyHasher::new();
+        key.field(spec.name.as_bytes());
+        key.field(format!("{:?}", spec.handler).as_bytes());
+        key.field(spec.args.join("\0").as_bytes());
+        for param in &spec.parameters {
+            let Some(value) = args.get(&param.name) else {
+                // An omitted path defaults to the working directory.
+                if param.path {
+                    let dir = cwd.map(Path::to_path_buf).or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+                    key.field(&fingerprint(&dir).await);
+                }
+                continue;
+            };
+            key.field(param.name.as_bytes());
+            key.field(canonical(value).to_string().as_bytes());
+            if param.path {
+                let paths = value.as_array().map_or_else(|| vec![value], |items| items.iter().collect());
+                for path in paths.into_iter().filter_map(Value::as_str) {
+                    key.field(&fingerprint(Path::new(path)).await);
+                }
+            }
+        }
+        key.field(format!("{:?}", cwd).as_bytes());
+        let env: BTreeMap<_, _> = options.env.iter().collect();
+        key.field(format!("{:?} {}", env, options.clear_env).as_bytes());
+        key.field(format!("{:?} {:?} {:?} {:?}", options.policy, options.sandbox, options.limits, options.max_output_bytes).as_bytes());
+        Ok(key.finish())
+    }
+
+    fn memory(&self) -> std::sync::MutexGuard<'_, MemoryStore> {
+        self.memory.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ToolCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ToolCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolCache")
+            .field("disk", &self.disk_dir())
+            .field("ttl", &self.ttl)
+            .field("max_entries", &self.max_entries)
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a path argument currently refers to: a file's modification time
+/// and contents, or a directory's modification time with the size and
+/// modification time of each file under it.
+async fn fingerprint(path: &Path) -> Vec<u8> {
+    let mut hasher = KeyHasher::new();
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => {
+            hasher.field(&mtime_nanos(&metadata).to_le_bytes());
+            match tokio::fs::read(path).await {
+                Ok(contents) => hasher.field(&contents),
+                Err(_) => hasher.field(b"unreadable"),
+            }
+        }
+        Ok(metadata) if metadata.is_dir() => {
+            hasher.field(&mtime_nanos(&metadata).to_le_bytes());
+            let files = DirWalker::new(path).walk().await.map(|walk| walk.files).unwrap_or_default();
+            for file in files {
+                let Ok(metadata) = tokio::fs::metadata(&file).await else { continue };
+                hasher.field(file.to_string_lossy().as_bytes());
+                hasher.field(&metadata.len().to_le_bytes());
+                hasher.field(&mtime_nanos(&metadata).to_le_bytes());
+            }
+        }
+        Ok(_) => hasher.field(b"other"),
+        Err(_) => hasher.field(b"missing"),
+    }
+    hasher.finish().into_bytes()
+}
+
+fn mtime_nanos(metadata: &std::fs::Metadata) -> u128 {
+    metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_nanos())
+}
+
+/// `value` with object keys sorted at every level.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let sorted: BTreeMap<_, _> = map.iter().map(|(key, value)| (key.clone(), canonical(value))).collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Hex SHA-256 over length-prefixed fields, so `("a", "bc")` and
+/// `("ab", "c")` hash differently.
+struct KeyHasher(Sha256);
+
+impl KeyHasher {
+    fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    fn field(&mut self, bytes: &[u8]) {
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+    }
+
+    fn finish(self) -> String {
+        self.0.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+async fn remove_if_present(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tool_cache_lru_and_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ToolCache::new().with_max_entries(2).with_disk(dir.path());
+        let output = |text: &str| ToolOutput::new(text, "", 0, Duration::ZERO);
+        cache.put("a", &output("1")).await.unwrap();
+        cache.put("b", &output("2")).await.unwrap();
+        assert!(cache.get("a").await.unwrap().cached);
+        cache.put("c", &output("3")).await.unwrap();
+
+        // "b" was least recently used, so memory dropped it; disk still has it.
+        assert_eq!(cache.memory().entries.len(), 2);
+        assert!(!cache.memory().entries.contains_key("b"));
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.disk_entries, stats.hits, stats.misses), (2, 1, 0));
+
+        let reopened = ToolCache::new().with_disk(dir.path());
+        assert_eq!(reopened.get("c").await.unwrap().stdout_lossy(), "3");
+        assert!(reopened.get("missing").await.is_none());
+        assert_eq!(reopened.clear().await.unwrap(), 3);
+        assert_eq!(reopened.stats().await.unwrap().disk_entries, 0);
+
+        let small = ToolCache::new().with_max_bytes(3);
+        small.put("a", &output("12")).await.unwrap();
+        small.put("b", &output("34")).await.unwrap();
+        assert!(small.get("a").await.is_none());
+
+        let expired = ToolCache::new().with_ttl(Duration::ZERO).with_disk(dir.path());
+        expired.put("a", &output("1")).await.unwrap();
+        assert!(expired.get("a").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_key_tracks_file_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "one").unwrap();
+        let registry = super::super::ToolRegistry::builtin();
+        let spec = registry.get("read_file").unwrap();
+        let cache = ToolCache::new();
+        let options = ExecOptions::new();
+        let args = serde_json::json!({ "path": file });
+
+        let first = cache.key(spec, &args, &options).await.unwrap();
+        assert_eq!(cache.key(spec, &args, &options).await.unwrap(), first);
+        std::fs::write(&file, "two").unwrap();
+        assert_ne!(cache.key(spec, &args, &options).await.unwrap(), first);
+        assert!(cache.key(spec, &serde_json::json!({}), &options).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registered_tools_use_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "one").unwrap();
+        let registry = super::super::ToolRegistry::builtin();
+        let cache = std::sync::Arc::new(ToolCache::new());
+        let options = ExecOptions::new().with_cache(cache.clone());
+        let read = serde_json::json!({ "path": file });
+        let run = |name, args| super::super::ToolExecutor::execute_registered(&registry, name, args, &options);
+
+        assert!(!run("read_file", &read).await.unwrap().cached);
+        let hit = run("read_file", &read).await.unwrap();
+        assert!(hit.cached);
+        assert_eq!(hit.stdout_lossy(), "one");
+
+        // Writes are never cached, and the edit invalidates the read.
+        let write = serde_json::json!({ "path": file, "content": "two" });
+        assert!(!run("write_file", &write).await.unwrap().cached);
+        assert!(!run("write_file", &write).await.unwrap().cached);
+        let fresh = run("read_file", &read).await.unwrap();
+        assert!(!fresh.cached);
+        assert_eq!(fresh.stdout_lossy(), "two");
+        assert_eq!(cache.stats().await.unwrap().hits, 1);
+    }
+}
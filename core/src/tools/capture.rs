@@ -0,0 +1,135 @@
+// Bounded capture of tool output
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Bytes of each output stream kept by default.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Chunk read from a pipe at a time.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Most bytes of a tool's stdout and stderr kept in its `ToolOutput`. The
+/// rest is read and discarded, so the tool is never blocked on a full
+/// pipe, and the kept output ends with a `[... truncated N bytes]` marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputLimit {
+    pub stdout: usize,
+    pub stderr: usize,
+}
+
+impl OutputLimit {
+    pub const UNLIMITED: OutputLimit = OutputLimit { stdout: usize::MAX, stderr: usize::MAX };
+
+    pub fn new(stdout: usize, stderr: usize) -> Self {
+        Self { stdout, stderr }
+    }
+}
+
+impl Default for OutputLimit {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_OUTPUT_BYTES, DEFAULT_MAX_OUTPUT_BYTES)
+    }
+}
+
+/// One output stream as read so far: up to `limit` bytes kept, and a count
+/// of everything the tool wrote.
+#[derive(Debug)]
+pub(super) struct Capture {
+    kept: Vec<u8>,
+    limit: usize,
+    total: u64,
+}
+
+impl Capture {
+    pub(super) fn new(limit: usize) -> Self {
+        Self { kept: Vec::new(), limit, total: 0 }
+    }
+
+    pub(super) fn push(&mut self, bytes: &[u8]) {
+        self.total += bytes.len() as u64;
+        let room = self.room();
+        self.kept.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    /// Bytes that can still be kept.
+    pub(super) fn room(&self) -> usize {
+        self.limit.saturating_sub(self.kept.len())
+    }
+
+    /// The bytes kept so far.
+    pub(super) fn kept(&self) -> &[u8] {
+        &self.kept
+    }
+
+    /// The kept bytes, with a marker if anything was dropped; the cut is
+    /// moved back to a UTF-8 boundary so text output stays valid.
+    pub(super) fn finish(self) -> Captured {
+        let Capture { mut kept, total, .. } = self;
+        let truncated = total > kept.len() as u64;
+        if truncated {
+            kept.truncate(utf8_boundary(&kept));
+            let marker = format!("\n[... truncated {} bytes]", total - kept.len() as u64);
+            kept.extend_from_slice(marker.as_bytes());
+        }
+        Captured { bytes: kept, total, truncated }
+    }
+}
+
+/// What `Capture::finish` produced for one stream.
+pub(super) struct Captured {
+    pub(super) bytes: Vec<u8>,
+    pub(super) total: u64,
+    pub(super) truncated: bool,
+}
+
+/// Reads `reader` to the end into `capture`.
+pub(super) async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, capture: &mut Capture) -> std::io::Result<()> {
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+    loop {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        capture.push(&chunk[..n]);
+    }
+}
+
+/// The length of `bytes` without a UTF-8 sequence cut off at the end.
+fn utf8_boundary(bytes: &[u8]) -> usize {
+    let tail = bytes.len().saturating_sub(3);
+    for start in (tail..bytes.len()).rev() {
+        let width = match bytes[start] {
+            0x80..=0xbf => continue,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        return if start + width > bytes.len() { start } else { bytes.len() };
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_truncates_at_char_boundary() {
+        let mut capture = Capture::new(8);
+        capture.push("abcdefgé".as_bytes());
+        capture.push(b"more");
+        let captured = capture.finish();
+        assert!(captured.truncated);
+        assert_eq!(captured.total, 13);
+        assert_eq!(String::from_utf8(captured.bytes).unwrap(), "abcdefg\n[... truncated 6 bytes]");
+
+        let mut capture = Capture::new(8);
+        capture.push("abcdeé".as_bytes());
+        let captured = capture.finish();
+        assert!(!captured.truncated);
+        assert_eq!(captured.bytes, "abcdeé".as_bytes());
+        assert_eq!(utf8_boundary(b"\xff\xfe"), 2);
+    }
+}
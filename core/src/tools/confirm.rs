@@ -0,0 +1,120 @@
+// Asking before tools with side effects run
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use super::executor::ToolError;
+
+/// How much harm a tool can do, in increasing order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DangerLevel {
+    /// Reads only; never needs confirming.
+    #[default]
+    Safe,
+    /// Changes things, but additively, such as appending to a file.
+    Caution,
+    /// May overwrite or delete data, or run arbitrary code.
+    Destructive,
+}
+
+impl fmt::Display for DangerLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Safe => "safe",
+            Self::Caution => "caution",
+            Self::Destructive => "destructive",
+        })
+    }
+}
+
+/// A tool run waiting for approval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfirmationRequest {
+    pub tool: String,
+    pub danger: DangerLevel,
+    /// What the run would do, as a dry run reports it.
+    pub description: String,
+}
+
+/// A hook's answer to a `ConfirmationRequest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Allow,
+    Deny,
+    /// Allow this run and every later run of the same tool through the gate.
+    AlwaysAllow,
+}
+
+/// Decides whether a tool run may go ahead, e.g. by asking on a terminal.
+pub type ConfirmationHook = Arc<dyn Fn(ConfirmationRequest) -> BoxFuture<'static, Confirmation> + Send + Sync>;
+
+/// Holds registered tools at or above `threshold` until a hook approves
+/// them. Share one gate through an `Arc` so `AlwaysAllow` answers stick.
+pub struct ConfirmationGate {
+    /// The lowest `DangerLevel` that needs confirming; `Safe` tools never do.
+    pub threshold: DangerLevel,
+    hook: Option<ConfirmationHook>,
+    always_allowed: Mutex<HashSet<String>>,
+}
+
+impl fmt::Debug for ConfirmationGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfirmationGate")
+            .field("threshold", &self.threshold)
+            .field("interactive", &self.hook.is_some())
+            .finish()
+    }
+}
+
+impl ConfirmationGate {
+    /// A gate asking `hook` about `Destructive` tools.
+    pub fn new<F, Fut>(hook: F) -> Self
+    where
+        F: Fn(ConfirmationRequest) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Confirmation> + Send + 'static,
+    {
+        let hook: ConfirmationHook = Arc::new(move |request| Box::pin(hook(request)));
+        Self { hook: Some(hook), ..Self::non_interactive() }
+    }
+
+    /// A gate for contexts with no one to ask: every run that needs
+    /// confirming fails with `ToolError::ConfirmationDenied`.
+    pub fn non_interactive() -> Self {
+        Self { threshold: DangerLevel::Destructive, hook: None, always_allowed: Mutex::default() }
+    }
+
+    pub fn with_threshold(mut self, threshold: DangerLevel) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Whether a run of `tool` at `danger` has to be approved first.
+    pub fn requires(&self, tool: &str, danger: DangerLevel) -> bool {
+        danger > DangerLevel::Safe
+            && danger >= self.threshold
+            && !self.always_allowed.lock().unwrap_or_else(|e| e.into_inner()).contains(tool)
+    }
+
+    /// Asks the hook about `request`, failing with
+    /// `ToolError::ConfirmationDenied` unless it allows the run.
+    pub(super) async fn confirm(&self, request: ConfirmationRequest) -> Result<(), ToolError> {
+        let tool = request.tool.clone();
+        let Some(hook) = &self.hook else {
+            return Err(ToolError::ConfirmationDenied {
+                tool,
+                reason: "confirmation is required but no one can be asked; allow it explicitly (e.g. --yes)".to_string(),
+            });
+        };
+        match hook(request).await {
+            Confirmation::Allow => Ok(()),
+            Confirmation::AlwaysAllow => {
+                self.always_allowed.lock().unwrap_or_else(|e| e.into_inner()).insert(tool);
+                Ok(())
+            }
+            Confirmation::Deny => Err(ToolError::ConfirmationDenied { tool, reason: "denied".to_string() }),
+        }
+    }
+}
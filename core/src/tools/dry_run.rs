@@ -0,0 +1,119 @@
+// Explaining a tool run instead of performing it
+use std::fmt::Write as _;
+use std::time::Duration;
+use anyhow::Result;
+
+use super::executor::{ExecOptions, ToolOutput};
+use super::process::Priority;
+use crate::system::PRESERVED_ENV_VARS;
+
+/// The output returned for a dry run: `description` as stdout, exit code 0.
+pub(super) fn dry_run_output(description: String) -> ToolOutput {
+    ToolOutput::new(description, Vec::new(), 0, Duration::ZERO)
+}
+
+/// Describes what running `tool_name` with `args` would do. Policy
+/// violations fail exactly as a real run would.
+pub(super) fn describe_command(tool_name: &str, args: &[&str], stdin: Option<&[u8]>, options: &ExecOptions) -> Result<String> {
+    options.check_cwd()?;
+    if let Some(policy) = &options.policy {
+        policy.check_in(tool_name, options.checked_args(args), options.cwd.as_deref())?;
+    }
+    let mut text = String::new();
+    let search_path = options.search_path.clone().unwrap_or_default();
+    let executable = match search_path.find(tool_name) {
+        Some(path) => path.display().to_string(),
+        None if search_path.is_inherited() => "(not found on PATH)".to_string(),
+        None => "(not found on the search path)".to_string(),
+    };
+    let argv: Vec<_> = std::iter::once(tool_name).chain(args.iter().copied()).map(quote).collect();
+    let _ = writeln!(text, "would run: {}", argv.join(" "));
+    let _ = writeln!(text, "executable: {}", executable);
+    if !search_path.is_inherited() {
+        let dirs: Vec<_> = search_path.dirs().iter().map(|dir| dir.display().to_string()).collect();
+        let _ = writeln!(text, "search path: {}", dirs.join(", "));
+    }
+    if let Some(cwd) = options.cwd.clone().or_else(|| std::env::current_dir().ok()) {
+        let _ = writeln!(text, "working directory: {}", cwd.display());
+    }
+    if options.clear_env {
+        let _ = writeln!(text, "environment: cleared, keeping {}", PRESERVED_ENV_VARS.join(", "));
+    } else if options.env.is_empty() {
+        let _ = writeln!(text, "environment: inherited");
+    }
+    let mut env: Vec<_> = options.env.iter().collect();
+    env.sort();
+    for (key, value) in env {
+        let _ = writeln!(text, "env: {}={}", key, quote(value));
+    }
+    if let Some(input) = stdin {
+        let _ = writeln!(text, "stdin: {} bytes", input.len());
+    } else if let Some(source) = options.stdin.describe() {
+        let _ = writeln!(text, "stdin: {}", source);
+    }
+    if let Some(limit) = options.timeout {
+        let _ = writeln!(text, "timeout: {:?}", limit);
+    }
+    if let Some(limits) = &options.limits {
+        let _ = writeln!(text, "limits: {}", limits.describe().join(", "));
+    }
+    if let Some(priority) = options.priority.filter(|priority| *priority != Priority::Normal) {
+        let _ = writeln!(text, "priority: {}", priority);
+    }
+    if let Some(sandbox) = &options.sandbox {
+        let network = if sandbox.network { "on" } else { "off" };
+        let _ = writeln!(text, "sandbox: namespaces with a private root, network {}", network);
+    }
+    if let Some(retry) = &options.retry {
+        let _ = writeln!(text, "retry: up to {} attempts", retry.max_attempts);
+    }
+    write_policy(&mut text, options);
+    Ok(text)
+}
+
+/// Appends which policy rules the run passed.
+pub(super) fn write_policy(text: &mut String, options: &ExecOptions) {
+    match &options.policy {
+        None => {
+            let _ = writeln!(text, "policy: none");
+        }
+        Some(policy) => {
+            let rules = policy.rules();
+            if rules.is_empty() {
+                let _ = writeln!(text, "policy: passed (no restrictions)");
+            }
+            for rule in rules {
+                let _ = writeln!(text, "policy: passed: {}", rule);
+            }
+        }
+    }
+}
+
+/// Quotes `arg` for a POSIX shell when it contains anything unusual.
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ExecPolicy;
+
+    #[test]
+    fn test_describe_command() {
+        let options = ExecOptions::new().with_env("LANG", "C").with_policy(ExecPolicy::confined_to("."));
+        let text = describe_command("grep", &["-n", "two words", "./src"], None, &options).unwrap();
+        assert!(text.contains("would run: grep -n 'two words' ./src\n"), "{}", text);
+        assert!(text.contains("env: LANG=C\n"));
+        assert!(text.contains("policy: passed: command does not need network access\n"));
+
+        assert!(describe_command("curl", &[], None, &options).is_err());
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}
@@ -1,21 +1,1026 @@
 // Tool executor implementation
-use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+use tracing::field::Empty;
+
+use crate::cancel::{cancelled, CancellationToken};
+use crate::safe_mode;
+use crate::system::{EnvironmentManager, SearchPath};
+use super::dry_run::{describe_command, dry_run_output, write_policy};
+use super::history::{ExecutionLog, ExecutionRecord};
+use super::hooks::{ToolHooks, ToolTask};
+use super::cache::ToolCache;
+use super::capture::{self, Capture, Captured, OutputLimit};
+use super::confirm::{ConfirmationGate, ConfirmationRequest};
+use super::limits::{self, ResourceKind, ResourceLimits};
+use super::policy::ExecPolicy;
+use super::process::{Priority, PriorityError};
+use super::rate_limit::RateLimiter;
+use super::retry::RetryPolicy;
+use super::sandbox::{self, SandboxConfig};
+use super::shell::ShellKind;
+use super::stdin::{self, StdinFeed, StdinSource};
+use super::registry::{BuiltinHandler, ToolCallError, ToolHandler, ToolRegistry, ToolSpec};
 
 pub struct ToolExecutor;
 
+/// How long a cancelled tool has to exit after `SIGTERM` before it is killed.
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Everything a finished tool run produced. Output is kept as the raw bytes
+/// the tool wrote; tools are not obliged to write UTF-8 (binary data, legacy
+/// Windows code pages). Serialized, each stream is a string when it is valid
+/// UTF-8 and `{"base64": "..."}` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolOutput {
+    #[serde(with = "text_or_base64")]
+    stdout: Vec<u8>,
+    #[serde(with = "text_or_base64")]
+    stderr: Vec<u8>,
+    /// Exit code, or -1 if the tool was terminated by a signal.
+    pub exit_code: i32,
+    pub duration: Duration,
+    /// How many times the tool ran, counting retries.
+    #[serde(default = "one_attempt")]
+    pub attempts: u32,
+    /// Returned from a cache instead of running the tool.
+    #[serde(default)]
+    pub cached: bool,
+    /// The tool wrote more than `ExecOptions::max_output_bytes` allowed, so
+    /// the stream was cut short and ends with a truncation marker.
+    #[serde(default)]
+    pub stdout_truncated: bool,
+    #[serde(default)]
+    pub stderr_truncated: bool,
+    /// Bytes the tool wrote, whether kept or not.
+    #[serde(default)]
+    pub stdout_total_bytes: u64,
+    #[serde(default)]
+    pub stderr_total_bytes: u64,
+}
+
+fn one_attempt() -> u32 {
+    1
+}
+
+impl ToolOutput {
+    pub fn new(stdout: impl Into<Vec<u8>>, stderr: impl Into<Vec<u8>>, exit_code: i32, duration: Duration) -> Self {
+        let (stdout, stderr) = (stdout.into(), stderr.into());
+        Self {
+            stdout_total_bytes: stdout.len() as u64,
+            stderr_total_bytes: stderr.len() as u64,
+            stdout,
+            stderr,
+            exit_code,
+            duration,
+            attempts: 1,
+            cached: false,
+            stdout_truncated: false,
+            stderr_truncated: false,
+        }
+    }
+
+    /// The output of a run whose streams were read through `Capture`s.
+    pub(super) fn captured(stdout: Capture, stderr: Capture, exit_code: i32, duration: Duration) -> Self {
+        let (Captured { bytes: out, total: out_total, truncated: out_cut }, Captured { bytes: err, total: err_total, truncated: err_cut }) =
+            (stdout.finish(), stderr.finish());
+        Self {
+            stdout_truncated: out_cut,
+            stderr_truncated: err_cut,
+            stdout_total_bytes: out_total,
+            stderr_total_bytes: err_total,
+            ..Self::new(out, err, exit_code, duration)
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    pub(super) fn mark_cached(mut self) -> Self {
+        self.cached = true;
+        self
+    }
+
+    pub fn stdout_bytes(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Stdout with invalid UTF-8 replaced by U+FFFD.
+    pub fn stdout_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// Stdout, failing if it is not valid UTF-8.
+    pub fn stdout_utf8(&self) -> Result<String> {
+        String::from_utf8(self.stdout.clone()).context("tool stdout is not valid UTF-8")
+    }
+
+    pub fn stderr_bytes(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Stderr with invalid UTF-8 replaced by U+FFFD.
+    pub fn stderr_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Stderr, failing if it is not valid UTF-8.
+    pub fn stderr_utf8(&self) -> Result<String> {
+        String::from_utf8(self.stderr.clone()).context("tool stderr is not valid UTF-8")
+    }
+
+    /// Logs a warning if either stream would need replacement characters.
+    pub(super) fn warn_if_not_utf8(&self, tool_name: &str) {
+        for (stream, bytes) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if let Err(e) = std::str::from_utf8(bytes) {
+                tracing::warn!(
+                    tool = %tool_name,
+                    stream,
+                    valid_up_to = e.valid_up_to(),
+                    "tool output is not valid UTF-8; text views replace invalid bytes"
+                );
+            }
+        }
+    }
+}
+
+mod text_or_base64 {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Binary { base64: String },
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Repr::Text(text.to_string()),
+            Err(_) => Repr::Binary { base64: STANDARD.encode(bytes) },
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => Ok(text.into_bytes()),
+            Repr::Binary { base64 } => STANDARD.decode(base64).map_err(de::Error::custom),
+        }
+    }
+}
+
+/// Limits applied to a single tool run.
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    /// Kill the tool and everything it started once this much time has passed.
+    pub timeout: Option<Duration>,
+    /// Checked before anything is spawned; `None` runs anything.
+    pub policy: Option<ExecPolicy>,
+    /// Directory the tool starts in; it must exist. Defaults to ours.
+    pub cwd: Option<PathBuf>,
+    /// Variables set for the tool, over the inherited environment.
+    pub env: HashMap<String, String>,
+    /// Start from an empty environment keeping only `PRESERVED_ENV_VARS`.
+    pub clear_env: bool,
+    /// Where the tool is looked up instead of the inherited `PATH`; it also
+    /// becomes the tool's `PATH`.
+    pub search_path: Option<SearchPath>,
+    /// Reruns failed attempts; the timeout applies to each attempt.
+    pub retry: Option<RetryPolicy>,
+    /// Every attempt takes a token first; share the `Arc` to share the limit.
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// Spawn nothing; the output's stdout describes what would have run.
+    pub dry_run: bool,
+    /// Isolate the tool from the host; see `SandboxConfig`.
+    pub sandbox: Option<SandboxConfig>,
+    /// Caps on memory, CPU time, open files and processes; see `ResourceLimits`.
+    pub limits: Option<ResourceLimits>,
+    /// Scheduling priority; `None` is the registered tool's own, see
+    /// `ToolSpec::priority`, or else `Priority::Normal`.
+    pub priority: Option<Priority>,
+    /// Every run, including ones that fail to start, is recorded here.
+    pub log: Option<Arc<ExecutionLog>>,
+    /// The task the run belongs to, as recorded in `log`.
+    pub task_id: Option<String>,
+    /// Reuses the output of cacheable registry tools; see `ToolCache`.
+    pub cache: Option<Arc<ToolCache>>,
+    /// Stops the tool when cancelled: its process group is sent `SIGTERM`,
+    /// then killed if it is still running after `kill_grace`.
+    pub cancel: Option<CancellationToken>,
+    /// Defaults to `DEFAULT_KILL_GRACE`.
+    pub kill_grace: Option<Duration>,
+    /// What the tool reads; input passed to a call directly takes precedence.
+    pub stdin: StdinSource,
+    /// Output kept per stream; the rest is drained and counted. Defaults to
+    /// `DEFAULT_MAX_OUTPUT_BYTES` each.
+    pub max_output_bytes: OutputLimit,
+    /// Asks before registered tools at or above its threshold run; see
+    /// `ToolSpec::danger`. Dry runs are never held.
+    pub confirm: Option<Arc<ConfirmationGate>>,
+    /// Called around each run; see `ToolHooks`.
+    pub hooks: Option<Arc<ToolHooks>>,
+    /// How many leading arguments the executor supplied itself; the policy
+    /// checks only the ones after them.
+    pub(super) trusted_args: usize,
+    /// The run is of a tool the registry marks `ToolSpec::read_only`, so
+    /// `safe_mode` lets it spawn.
+    pub(super) read_only: bool,
+}
+
+impl ExecOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ExecPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn with_cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn with_clear_env(mut self, clear_env: bool) -> Self {
+        self.clear_env = clear_env;
+        self
+    }
+
+    pub fn with_search_path(mut self, search_path: SearchPath) -> Self {
+        self.search_path = Some(search_path);
+        self
+    }
+
+    /// Fails with `ToolError::MissingWorkingDirectory` if `cwd` is set but
+    /// is not a directory.
+    pub(super) fn check_cwd(&self) -> Result<(), ToolError> {
+        match &self.cwd {
+            Some(cwd) if !cwd.is_dir() => Err(ToolError::MissingWorkingDirectory { path: cwd.clone() }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The arguments the policy vets: those after `trusted_args`.
+    pub(super) fn checked_args<'a>(&self, args: &'a [&'a str]) -> &'a [&'a str] {
+        &args[self.trusted_args.min(args.len())..]
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limit = Some(limiter);
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_sandbox(mut self, sandbox: SandboxConfig) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// `ToolError::ResourceLimit` if the tool that exited with `status`
+    /// ran into one of `limits`.
+    pub(super) fn check_limits(&self, status: &std::process::ExitStatus, stderr: &[u8]) -> Result<(), ToolError> {
+        match self.limits.as_ref().and_then(|limits| limits.exceeded(status, stderr)) {
+            Some(kind) => Err(ToolError::ResourceLimit { kind }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn with_log(mut self, log: Arc<ExecutionLog>) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub fn with_task_id(mut self, task_id: impl Into<String>) -> Self {
+        self.task_id = Some(task_id.into());
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Arc<ToolCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn with_kill_grace(mut self, grace: Duration) -> Self {
+        self.kill_grace = Some(grace);
+        self
+    }
+
+    pub fn with_stdin(mut self, stdin: StdinSource) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    pub fn with_max_output_bytes(mut self, limit: OutputLimit) -> Self {
+        self.max_output_bytes = limit;
+        self
+    }
+
+    pub fn with_confirmation(mut self, gate: Arc<ConfirmationGate>) -> Self {
+        self.confirm = Some(gate);
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: Arc<ToolHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Empty captures for the tool's stdout and stderr.
+    pub(super) fn captures(&self) -> (Capture, Capture) {
+        (Capture::new(self.max_output_bytes.stdout), Capture::new(self.max_output_bytes.stderr))
+    }
+
+    /// The stdin setup for a run given `input` directly, or else `stdin`.
+    pub(super) async fn open_stdin(&self, input: Option<&[u8]>) -> Result<(Stdio, Option<StdinFeed>)> {
+        match input {
+            Some(input) => Ok((Stdio::piped(), Some(StdinFeed::Bytes(input.to_vec())))),
+            None => self.stdin.open().await,
+        }
+    }
+
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Awaits `run` unless `timeout` passes or `cancel` fires first.
+    pub(super) async fn supervise<F: std::future::Future>(&self, run: F) -> Result<F::Output, Interrupted> {
+        let deadline = async {
+            match self.timeout {
+                Some(limit) => tokio::time::sleep(limit).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            output = run => Ok(output),
+            _ = deadline => Err(Interrupted::Timeout),
+            _ = cancelled(self.cancel.as_ref()) => Err(Interrupted::Cancelled),
+        }
+    }
+
+    /// Stops `child` after `supervise` gave up on it, returning the error
+    /// for the run with the stdout captured so far.
+    pub(super) async fn interrupt(&self, child: &mut Child, why: Interrupted, tool: &str, started: Instant, stdout: &[u8]) -> ToolError {
+        let elapsed = started.elapsed();
+        let partial_stdout = String::from_utf8_lossy(stdout).into_owned();
+        match why {
+            Interrupted::Timeout => {
+                kill_tree(child).await;
+                tracing::debug!(tool = %tool, elapsed_ms = elapsed.as_millis() as u64, "tool timed out");
+                ToolError::Timeout { elapsed, partial_stdout }
+            }
+            Interrupted::Cancelled => {
+                terminate_tree(child, self.kill_grace.unwrap_or(DEFAULT_KILL_GRACE)).await;
+                tracing::debug!(tool = %tool, elapsed_ms = elapsed.as_millis() as u64, "tool cancelled");
+                ToolError::Cancelled { elapsed, partial_stdout }
+            }
+        }
+    }
+
+    /// Runs `run` between the hooks, recording it in `log` as `tool` with
+    /// `argv` unless this is a dry run.
+    pub(super) async fn recorded<F>(&self, tool: &str, argv: &[&str], run: F) -> Result<ToolOutput>
+    where
+        F: std::future::Future<Output = Result<ToolOutput>>,
+    {
+        let hooked = self.hooks.as_ref().filter(|_| !self.dry_run).map(|hooks| (hooks, ToolTask::new(tool, argv, self)));
+        let run = async {
+            if let Some((hooks, task)) = &hooked {
+                hooks.before(task)?;
+            }
+            run.await
+        };
+        let result = self.logged(tool, argv, run).await;
+        if let Some((hooks, task)) = &hooked {
+            hooks.after(task, &result);
+        }
+        result
+    }
+
+    async fn logged<F>(&self, tool: &str, argv: &[&str], run: F) -> Result<ToolOutput>
+    where
+        F: std::future::Future<Output = Result<ToolOutput>>,
+    {
+        match self.log.as_ref().filter(|_| !self.dry_run) {
+            Some(log) => {
+                let record = ExecutionRecord::start(tool, argv, self);
+                let result = run.await;
+                log.record(record.finish(&result)).await;
+                result
+            }
+            None => run.await,
+        }
+    }
+}
+
+/// Why `ExecOptions::supervise` stopped waiting for a tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Interrupted {
+    Timeout,
+    Cancelled,
+}
+
+/// Why a tool run failed, returned by every `ToolExecutor` entry point so
+/// callers can branch on the kind of failure; `?` still turns it into an
+/// `anyhow::Error` where that is all a caller needs.
+#[derive(Debug, Error)]
+pub enum ToolError {
+    /// The tool's executable could not be found.
+    #[error("tool '{tool}' not found")]
+    NotFound { tool: String },
+    /// The tool exists but could not be started, e.g. for lack of permission.
+    #[error("failed to spawn tool '{tool}': {source}")]
+    SpawnFailed {
+        tool: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Only from the APIs that treat a non-zero exit as failure, such as
+    /// `execute_tool_simple`; the others report it in `ToolOutput::exit_code`.
+    #[error("tool exited with code {code}")]
+    NonZeroExit { code: i32, output: Box<ToolOutput> },
+    /// Reading from or writing to the tool failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The arguments of a registered tool call did not validate, or no such
+    /// tool is registered.
+    #[error(transparent)]
+    InvalidArgs(#[from] ToolCallError),
+    /// The tool ran past `ExecOptions::timeout` and was killed.
+    #[error("tool timed out after {elapsed:?}")]
+    Timeout { elapsed: Duration, partial_stdout: String },
+    /// The execution policy refused the command or one of its arguments.
+    #[error("policy violation running '{command}': {reason}")]
+    PolicyViolation { command: String, reason: String },
+    /// The tool's `RateLimiter` is in `RateLimitMode::Error` and out of tokens.
+    #[error("tool '{tool}' is rate limited; next run allowed in {retry_after:?}")]
+    RateLimited { tool: String, retry_after: Duration },
+    /// `ExecOptions::cwd` does not exist or is not a directory.
+    #[error("working directory {} does not exist", path.display())]
+    MissingWorkingDirectory { path: PathBuf },
+    /// `ExecOptions::cancel` fired before the tool finished; it was stopped.
+    #[error("tool cancelled after {elapsed:?}")]
+    Cancelled { elapsed: Duration, partial_stdout: String },
+    /// The tool ran into one of `ExecOptions::limits`.
+    #[error("tool exceeded its {kind} limit")]
+    ResourceLimit { kind: ResourceKind },
+    /// The tool needed confirming and `ExecOptions::confirm` did not allow it.
+    #[error("tool '{tool}' was not run: {reason}")]
+    ConfirmationDenied { tool: String, reason: String },
+    /// The system would not run the tool at `ExecOptions::priority`.
+    #[error(transparent)]
+    PriorityRefused(#[from] PriorityError),
+    /// A requested capability, such as `ExecOptions::sandbox`, is unavailable.
+    #[error("{0} is not supported")]
+    Unsupported(String),
+    /// Anything else, such as a failing built-in tool.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl ToolError {
+    /// Why `tool` did not start at `priority`.
+    pub(super) fn spawn_at(tool: &str, priority: Priority, source: std::io::Error) -> Self {
+        if priority.refused(&source) {
+            return Self::PriorityRefused(PriorityError { program: tool.to_string(), priority, source });
+        }
+        Self::spawn(tool, source)
+    }
+
+    pub(super) fn spawn(tool: &str, source: std::io::Error) -> Self {
+        match source.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound { tool: tool.to_string() },
+            _ => Self::SpawnFailed { tool: tool.to_string(), source },
+        }
+    }
+}
+
+/// Recovers the typed error an `anyhow::Error` carries, if any, dropping
+/// context added on the way; anything untyped becomes `Other`.
+impl From<anyhow::Error> for ToolError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ToolError>() {
+            Ok(tool_error) => return tool_error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ToolCallError>() {
+            Ok(call_error) => return Self::InvalidArgs(call_error),
+            Err(error) => error,
+        };
+        if error.chain().count() > 1 {
+            return Self::Other(error);
+        }
+        match error.downcast::<std::io::Error>() {
+            Ok(io_error) => Self::Io(io_error),
+            Err(error) => Self::Other(error),
+        }
+    }
+}
+
 impl ToolExecutor {
     pub fn new() -> Self {
         Self
     }
     
-    pub async fn execute_tool(_tool_name: &str, _args: &[&str]) -> Result<String> {
-        // TODO: Implement high-performance tool execution
-        todo!("Implement in T021")
+    /// Runs `tool_name` to completion and captures both output streams. A
+    /// non-zero exit is reported in the output, not as an error; a tool that
+    /// cannot be started fails with `ToolError::NotFound` or
+    /// `ToolError::SpawnFailed`.
+    pub async fn execute_tool(tool_name: &str, args: &[&str]) -> Result<ToolOutput, ToolError> {
+        Self::execute_tool_with_stdin(tool_name, args, None).await
+    }
+
+    /// Runs `tool_name` and returns only its stdout, failing with
+    /// `ToolError::NonZeroExit` on a non-zero exit.
+    pub async fn execute_tool_simple(tool_name: &str, args: &[&str]) -> Result<String, ToolError> {
+        Self::execute_tool_cancellable(tool_name, args, None).await
+    }
+
+    /// Runs `tool_name` and returns its stdout. If `cancel` fires first the tool is
+    /// killed and `ToolError::Cancelled` carries the output captured so far.
+    ///
+    /// Argument values are not recorded on the span since they may carry secrets.
+    #[tracing::instrument(
+        name = "execute_tool",
+        level = "debug",
+        skip(args, cancel),
+        fields(tool = %tool_name, args_len = args.len(), exit_code = Empty, duration_ms = Empty)
+    )]
+    pub async fn execute_tool_cancellable(
+        tool_name: &str,
+        args: &[&str],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<String, ToolError> {
+        safe_mode::check(|| format!("running '{}'", tool_name)).map_err(|e| ToolError::Other(e.into()))?;
+        let started = Instant::now();
+        let mut child = Command::new(tool_name)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ToolError::spawn(tool_name, e))?;
+        let mut stdout = child.stdout.take().context("tool stdout was not captured")?;
+        let mut output = Vec::new();
+        let mut chunk = [0u8; 8192];
+
+        loop {
+            tokio::select! {
+                read = stdout.read(&mut chunk) => {
+                    let n = read?;
+                    if n == 0 {
+                        break;
+                    }
+                    output.extend_from_slice(&chunk[..n]);
+                }
+                _ = cancelled(cancel) => {
+                    child.kill().await?;
+                    return Err(ToolError::Cancelled { elapsed: started.elapsed(), partial_stdout: String::from_utf8_lossy(&output).into_owned() });
+                }
+            }
+        }
+
+        let status = tokio::select! {
+            status = child.wait() => status?,
+            _ = cancelled(cancel) => {
+                child.kill().await?;
+                return Err(ToolError::Cancelled { elapsed: started.elapsed(), partial_stdout: String::from_utf8_lossy(&output).into_owned() });
+            }
+        };
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let span = tracing::Span::current();
+        span.record("duration_ms", duration_ms);
+        if let Some(code) = status.code() {
+            span.record("exit_code", code);
+        }
+        tracing::debug!(
+            tool = %tool_name,
+            exit_code = status.code(),
+            duration_ms,
+            stdout_bytes = output.len(),
+            "tool finished"
+        );
+
+        if !status.success() {
+            let code = status.code().unwrap_or(-1);
+            let output = ToolOutput::new(output, Vec::new(), code, started.elapsed());
+            return Err(ToolError::NonZeroExit { code, output: Box::new(output) });
+        }
+        if std::str::from_utf8(&output).is_err() {
+            tracing::warn!(tool = %tool_name, "tool stdout is not valid UTF-8; invalid bytes were replaced");
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+impl ToolExecutor {
+    /// Runs `tool_name` to completion, optionally feeding `stdin`, and captures
+    /// both output streams. A non-zero exit is reported in the output, not as an error.
+    pub async fn execute_tool_with_stdin(tool_name: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<ToolOutput, ToolError> {
+        Self::execute_tool_with_options(tool_name, args, stdin, &ExecOptions::default()).await
+    }
+
+    /// Like `execute_tool_with_stdin`, subject to `options`. On timeout the
+    /// tool's whole process tree is killed (its process group on Unix,
+    /// `taskkill /T` on Windows) and `ToolError::Timeout` carries the stdout
+    /// captured until then; cancellation through `options.cancel` ends the
+    /// same way with `ToolError::Cancelled`. With `options.retry`, failed attempts are rerun
+    /// after a backoff and the output records how many attempts ran. With
+    /// `options.dry_run`, nothing runs and stdout describes the command.
+    /// With `options.log`, the run is recorded once, after any retries.
+    #[tracing::instrument(
+        name = "execute_tool",
+        level = "debug",
+        skip(args, stdin, options),
+        fields(tool = %tool_name, args_len = args.len(), exit_code = Empty, duration_ms = Empty, status = Empty)
+    )]
+    pub async fn execute_tool_with_options(
+        tool_name: &str,
+        args: &[&str],
+        stdin: Option<&[u8]>,
+        options: &ExecOptions,
+    ) -> Result<ToolOutput, ToolError> {
+        if options.dry_run {
+            return Ok(dry_run_output(describe_command(tool_name, args, stdin, options)?));
+        }
+        let result = options.recorded(tool_name, args, run_with_retry(tool_name, args, stdin, options)).await;
+        tracing::Span::current().record("status", run_status(&result));
+        Ok(result?)
     }
 }
 
+/// `ok`, `failed` for a non-zero exit, or `error` if the tool did not finish.
+fn run_status<E>(result: &Result<ToolOutput, E>) -> &'static str {
+    match result {
+        Ok(output) if output.success() => "ok",
+        Ok(_) => "failed",
+        Err(_) => "error",
+    }
+}
+
+async fn run_with_retry(tool_name: &str, args: &[&str], stdin: Option<&[u8]>, options: &ExecOptions) -> Result<ToolOutput> {
+    let Some(retry) = &options.retry else {
+        return run_once(tool_name, args, stdin, options).await;
+    };
+    let mut attempt = 1;
+    loop {
+        let result = run_once(tool_name, args, stdin, options).await;
+        if attempt >= retry.max_attempts || !retry.should_retry(&result) {
+            return result.map(|output| ToolOutput { attempts: attempt, ..output });
+        }
+        let backoff = retry.backoff(attempt);
+        tracing::warn!(
+            tool = %tool_name,
+            attempt,
+            max_attempts = retry.max_attempts,
+            backoff_ms = backoff.as_millis() as u64,
+            "tool attempt failed; retrying"
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = cancelled(options.cancel.as_ref()) => return result.map(|output| ToolOutput { attempts: attempt, ..output }),
+        }
+        attempt += 1;
+    }
+}
+
+async fn run_once(tool_name: &str, args: &[&str], stdin: Option<&[u8]>, options: &ExecOptions) -> Result<ToolOutput> {
+    if let Some(limiter) = &options.rate_limit {
+        limiter.acquire(tool_name).await?;
+    }
+    let started = Instant::now();
+    if options.is_cancelled() {
+        return Err(ToolError::Cancelled { elapsed: Duration::ZERO, partial_stdout: String::new() }.into());
+    }
+    let (stdio, input) = options.open_stdin(stdin).await?;
+    let mut child = spawn(tool_name, args, stdio, options)?;
+    if let (Some(input), Some(pipe)) = (input, child.stdin.take()) {
+        stdin::feed(pipe, input);
+    }
+
+    let mut stdout_pipe = child.stdout.take().context("tool stdout was not captured")?;
+    let mut stderr_pipe = child.stderr.take().context("tool stderr was not captured")?;
+    let (mut stdout, mut stderr) = options.captures();
+    let run = async {
+        let (out, err) = tokio::join!(
+            capture::read_capped(&mut stdout_pipe, &mut stdout),
+            capture::read_capped(&mut stderr_pipe, &mut stderr),
+        );
+        out?;
+        err?;
+        child.wait().await
+    };
+    let status = match options.supervise(run).await {
+        Ok(status) => status?,
+        Err(why) => return Err(options.interrupt(&mut child, why, tool_name, started, stdout.kept()).await.into()),
+    };
+    options.check_limits(&status, stderr.kept())?;
+    let duration = started.elapsed();
+    let exit_code = status.code().unwrap_or(-1);
+    let span = tracing::Span::current();
+    span.record("exit_code", exit_code);
+    span.record("duration_ms", duration.as_millis() as u64);
+    tracing::debug!(tool = %tool_name, exit_code, duration_ms = duration.as_millis() as u64, "tool finished");
+
+    let output = ToolOutput::captured(stdout, stderr, exit_code, duration);
+    output.warn_if_not_utf8(tool_name);
+    Ok(output)
+}
+
+impl ToolExecutor {
+    /// Runs a tool call as emitted by a model. Invalid arguments fail with
+    /// `ToolError::InvalidArgs` before anything runs; the `ToolCallError`'s
+    /// `to_json` turns it into feedback for the model.
+    pub async fn execute_json(registry: &ToolRegistry, name: &str, args: serde_json::Value) -> Result<ToolOutput, ToolError> {
+        Self::execute_registered(registry, name, &args, &ExecOptions::default()).await
+    }
+
+    /// Runs the tool registered as `name`, after validating `arguments`
+    /// against its parameters. `options` applies to command tools; built-ins
+    /// are recorded in `options.log` with their JSON arguments as argv. A
+    /// cacheable tool's output comes from `options.cache` when it holds the
+    /// same call, and nothing runs or is recorded.
+    pub async fn execute_registered(
+        registry: &ToolRegistry,
+        name: &str,
+        arguments: &serde_json::Value,
+        options: &ExecOptions,
+    ) -> Result<ToolOutput, ToolError> {
+        let spec = registry.resolve(name)?;
+        let Some(cache) = options.cache.as_ref().filter(|_| spec.cacheable && !options.dry_run) else {
+            return Ok(run_registered(spec, arguments, options).await?);
+        };
+        let key = cache.key(spec, arguments, options).await?;
+        if let Some(output) = cache.get(&key).await {
+            tracing::debug!(tool = %spec.name, "tool cache hit");
+            return Ok(output);
+        }
+        let output = run_registered(spec, arguments, options).await?;
+        if output.success() {
+            if let Err(e) = cache.put(&key, &output).await {
+                tracing::warn!(tool = %spec.name, "failed to cache tool output: {:#}", e);
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Runs a registered tool; see `ToolExecutor::execute_registered`. If
+/// `options.confirm` holds it, the gate is shown its dry run first. In
+/// `safe_mode` only read-only tools run.
+async fn run_registered(spec: &ToolSpec, arguments: &serde_json::Value, options: &ExecOptions) -> Result<ToolOutput> {
+    let read_only;
+    let options = if spec.read_only {
+        read_only = ExecOptions { read_only: true, ..options.clone() };
+        &read_only
+    } else {
+        if !options.dry_run {
+            safe_mode::check(|| format!("running tool '{}'", spec.name))?;
+        }
+        options
+    };
+    let prioritized;
+    let options = if options.priority.is_none() && spec.priority != Priority::Normal {
+        prioritized = options.clone().with_priority(spec.priority);
+        &prioritized
+    } else {
+        options
+    };
+    let gate = options.confirm.as_ref().filter(|gate| !options.dry_run && gate.requires(&spec.name, spec.danger));
+    if let Some(gate) = gate {
+        let preview = run_handler(spec, arguments, &options.clone().with_dry_run(true)).await?;
+        let request = ConfirmationRequest {
+            tool: spec.name.clone(),
+            danger: spec.danger,
+            description: preview.stdout_lossy().into_owned(),
+        };
+        gate.confirm(request).await?;
+    }
+    run_handler(spec, arguments, options).await
+}
+
+async fn run_handler(spec: &ToolSpec, arguments: &serde_json::Value, options: &ExecOptions) -> Result<ToolOutput> {
+    match &spec.handler {
+        ToolHandler::Command(command) => {
+            let args = spec.validate_in(arguments, options.cwd.as_deref())?;
+            let argv = spec.render_args(&args)?;
+            let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+            match &spec.rate_limit {
+                Some(limiter) => {
+                    let options = options.clone().with_rate_limit(Arc::clone(limiter));
+                    Ok(ToolExecutor::execute_tool_with_options(command, &argv, None, &options).await?)
+                }
+                None => Ok(ToolExecutor::execute_tool_with_options(command, &argv, None, options).await?),
+            }
+        }
+        ToolHandler::Shell(kind) => {
+            let args = spec.validate_in(arguments, options.cwd.as_deref())?;
+            let script = args.str("script").unwrap_or_default();
+            let positional = args.get("args").map(string_values).unwrap_or_default();
+            match &spec.rate_limit {
+                Some(limiter) => {
+                    let options = options.clone().with_rate_limit(Arc::clone(limiter));
+                    Ok(ToolExecutor::execute_shell(*kind, script, &positional, &options).await?)
+                }
+                None => Ok(ToolExecutor::execute_shell(*kind, script, &positional, options).await?),
+            }
+        }
+        ToolHandler::Builtin(handler) => {
+            let argv = arguments.to_string();
+            options.recorded(&spec.name, &[&argv], call_builtin(spec, handler, arguments, options)).await
+        }
+    }
+}
+
+async fn call_builtin(spec: &ToolSpec, handler: &BuiltinHandler, arguments: &serde_json::Value, options: &ExecOptions) -> Result<ToolOutput> {
+    let args = spec.validate(arguments)?;
+    // Parameters marked as paths are always checked; tools that mark
+    // none have every string argument vetted like a command's.
+    if let Some(policy) = &options.policy {
+        if spec.declares_paths() {
+            let paths: Vec<&str> = spec
+                .parameters
+                .iter()
+                .filter(|param| param.path)
+                .filter_map(|param| args.get(&param.name))
+                .flat_map(string_values)
+                .collect();
+            policy.check_paths(&spec.name, &paths)?;
+        } else {
+            let values: Vec<&str> = args.values().flat_map(string_values).collect();
+            policy.check(&spec.name, &values)?;
+        }
+    }
+    if options.dry_run {
+        let mut text = format!("would call built-in '{}': {}\n", spec.name, spec.describe(&args));
+        write_policy(&mut text, options);
+        return Ok(dry_run_output(text));
+    }
+    if let Some(limiter) = spec.rate_limit.as_ref().or(options.rate_limit.as_ref()) {
+        limiter.acquire(&spec.name).await?;
+    }
+    let started = Instant::now();
+    tokio::select! {
+        output = handler(args) => output,
+        _ = cancelled(options.cancel.as_ref()) => {
+            Err(ToolError::Cancelled { elapsed: started.elapsed(), partial_stdout: String::new() }.into())
+        }
+    }
+}
+
+impl ToolExecutor {
+    /// Runs `script` with `kind`, passing `args` as positional parameters
+    /// rather than splicing them into the script; see the `shell` module.
+    /// Refused unless `options.policy` sets `allow_shell`. Otherwise behaves
+    /// like `execute_tool_with_options`.
+    pub async fn execute_shell(kind: ShellKind, script: &str, args: &[&str], options: &ExecOptions) -> Result<ToolOutput, ToolError> {
+        let (argv, options) = match kind.invocation(script, args, options) {
+            Ok(invocation) => invocation,
+            Err(e) => return Ok(options.recorded(kind.program(), args, async { Err(e.into()) }).await?),
+        };
+        let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+        Self::execute_tool_with_options(kind.program(), &argv, None, &options).await
+    }
+}
+
+/// The strings in `value`, or in it if it is an array.
+fn string_values(value: &serde_json::Value) -> Vec<&str> {
+    let items = value.as_array().map_or_else(|| vec![value], |items| items.iter().collect());
+    items.into_iter().filter_map(serde_json::Value::as_str).collect()
+}
+
+/// Checks `options.policy` and starts the tool with its output piped.
+pub(super) fn spawn(tool_name: &str, args: &[&str], stdin: Stdio, options: &ExecOptions) -> Result<Child> {
+    let mut command = prepare(tool_name, args, options)?;
+    command.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped());
+    // Its own process group lets a timeout or cancellation take down
+    // grandchildren too, and keeps the terminal's Ctrl-C from reaching it.
+    #[cfg(unix)]
+    if options.timeout.is_some() || options.cancel.is_some() {
+        command.process_group(0);
+    }
+    Ok(command.spawn().map_err(|e| ToolError::spawn_at(tool_name, options.priority.unwrap_or_default(), e))?)
+}
+
+/// Checks `options.policy` and sets the tool up as `options` say, leaving
+/// its stdio to the caller.
+pub(super) fn prepare(tool_name: &str, args: &[&str], options: &ExecOptions) -> Result<Command> {
+    if !options.read_only {
+        safe_mode::check(|| format!("running '{}'", tool_name))?;
+    }
+    options.check_cwd()?;
+    if let Some(policy) = &options.policy {
+        policy.check_in(tool_name, options.checked_args(args), options.cwd.as_deref())?;
+    }
+    let mut command = match &options.search_path {
+        Some(search_path) => {
+            let executable = search_path.find(tool_name).ok_or_else(|| ToolError::NotFound { tool: tool_name.to_string() })?;
+            Command::new(executable)
+        }
+        None => Command::new(tool_name),
+    };
+    if options.clear_env {
+        command.env_clear().envs(EnvironmentManager::preserved_env_vars());
+    }
+    if let Some(search_path) = &options.search_path {
+        command.env("PATH", search_path.to_env()?);
+    }
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(cwd);
+    }
+    if let Some(resource_limits) = &options.limits {
+        limits::apply(&mut command, resource_limits)?;
+    }
+    if let Some(config) = &options.sandbox {
+        let workdir = options.cwd.clone().or_else(|| std::env::current_dir().ok());
+        sandbox::apply(&mut command, config, workdir)?;
+    }
+    options.priority.unwrap_or_default().apply(&mut command, false);
+    command.args(args).envs(&options.env).kill_on_drop(true);
+    Ok(command)
+}
+
+/// Asks `child` and everything it spawned to exit (`SIGTERM` to its
+/// process group), killing them if `child` has not exited after `grace`.
+/// Elsewhere this is `kill_tree`.
+pub(super) async fn terminate_tree(child: &mut Child, grace: Duration) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: plain syscall; the group was created for this child at spawn.
+        unsafe { libc::killpg(pid as libc::pid_t, libc::SIGTERM) };
+        if tokio::time::timeout(grace, child.wait()).await.is_ok() {
+            return;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = grace;
+    kill_tree(child).await;
+}
+
+/// Kills `child` and, where the platform allows, everything it spawned.
+/// Timeouts and `ProcessHandle::kill_tree` both end processes this way.
+pub(super) async fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: plain syscall; the group was created for this child at spawn.
+        unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
+    }
+    #[cfg(windows)]
+    if let Some(pid) = child.id() {
+        let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &pid.to_string()]).output().await;
+    }
+    let _ = child.kill().await;
+}
+
 impl Default for ToolExecutor {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
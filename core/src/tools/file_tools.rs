@@ -0,0 +1,255 @@
+// Built-in file tools, run in-process over file_processor
+use std::fmt::Write as _;
+use std::time::{Instant, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{json, Value};
+
+use super::confirm::DangerLevel;
+use super::executor::ToolOutput;
+use super::registry::{ParamType, ToolArgs, ToolParameter, ToolRegistry, ToolSpec};
+use crate::file_processor::{DirWalker, FileReader, FileWriter};
+use crate::system::PathUtils;
+
+/// Bytes `read_file` returns when no `length` is given.
+const READ_FILE_DEFAULT_LENGTH: u64 = 64 * 1024;
+/// Most bytes a file tool reads or writes in one call; `search_files` skips larger files.
+pub const MAX_FILE_TOOL_BYTES: u64 = 1024 * 1024;
+/// Most entries `list_directory` returns.
+const MAX_LIST_ENTRIES: usize = 1000;
+/// Matches `search_files` returns when no `max_results` is given, and its ceiling.
+const DEFAULT_SEARCH_RESULTS: u64 = 100;
+const MAX_SEARCH_RESULTS: u64 = 1000;
+
+pub(super) fn register(registry: &mut ToolRegistry) {
+    registry.register(
+        ToolSpec::builtin("read_file", "Read part of a file as text", read_file)
+            .cacheable()
+            .read_only()
+            .describe_with(|args| {
+                format!(
+                    "read up to {} bytes of {} starting at byte {}",
+                    args.get("length").and_then(Value::as_u64).unwrap_or(READ_FILE_DEFAULT_LENGTH),
+                    args.str("path").unwrap_or_default(),
+                    args.get("offset").and_then(Value::as_u64).unwrap_or(0),
+                )
+            })
+            .param(ToolParameter::new("path", ParamType::String, "File to read").path())
+            .param(ToolParameter::new("offset", ParamType::Integer, "Byte offset to start at (default 0)").optional())
+            .param(
+                ToolParameter::new("length", ParamType::Integer, "Maximum bytes to return (default 65536, at most 1048576)")
+                    .optional(),
+            ),
+    );
+    registry.register(
+        ToolSpec::builtin("write_file", "Create or overwrite a file with the given text", write_file)
+            .danger(DangerLevel::Destructive)
+            .describe_with(|args| describe_write("write", args))
+            .param(ToolParameter::new("path", ParamType::String, "File to write").path())
+            .param(ToolParameter::new("content", ParamType::String, "Text to write (at most 1048576 bytes)")),
+    );
+    registry.register(
+        ToolSpec::builtin("append_file", "Append text to a file, creating it if needed", append_file)
+            .danger(DangerLevel::Caution)
+            .describe_with(|args| describe_write("append", args))
+            .param(ToolParameter::new("path", ParamType::String, "File to append to").path())
+            .param(ToolParameter::new("content", ParamType::String, "Text to append as-is (at most 1048576 bytes)")),
+    );
+    registry.register(
+        ToolSpec::builtin("list_directory", "List a directory's entries, directories ending in '/'", list_directory)
+            .cacheable()
+            .read_only()
+            .describe_with(|args| format!("list the entries of {}", args.str("path").unwrap_or(".")))
+            .param(ToolParameter::new("path", ParamType::String, "Directory to list (default '.')").optional().path()),
+    );
+    registry.register(
+        ToolSpec::builtin("search_files", "Search text files under a directory for a regular expression", search_files)
+            .cacheable()
+            .read_only()
+            .describe_with(|args| {
+                format!(
+                    "search files under {} for /{}/",
+                    args.str("path").unwrap_or("."),
+                    args.str("pattern").unwrap_or_default()
+                )
+            })
+            .param(ToolParameter::new("pattern", ParamType::String, "Regular expression matched against each line"))
+            .param(ToolParameter::new("path", ParamType::String, "File or directory to search (default '.')").optional().path())
+            .param(
+                ToolParameter::new("max_results", ParamType::Integer, "Stop after this many matches (default 100, at most 1000)")
+                    .optional(),
+            ),
+    );
+    registry.register(
+        ToolSpec::builtin("file_info", "Report a path's type, size and modification time as JSON", file_info)
+            .cacheable()
+            .read_only()
+            .describe_with(|args| format!("report metadata for {}", args.str("path").unwrap_or_default()))
+            .param(ToolParameter::new("path", ParamType::String, "File or directory to inspect").path()),
+    );
+}
+
+fn describe_write(verb: &str, args: &ToolArgs) -> String {
+    let content = args.str("content").unwrap_or_default();
+    format!("{} {} bytes to {}", verb, content.len(), args.str("path").unwrap_or_default())
+}
+
+fn text_output(text: String, started: Instant) -> ToolOutput {
+    ToolOutput::new(text, Vec::new(), 0, started.elapsed())
+}
+
+/// The `content` argument, refused if over `MAX_FILE_TOOL_BYTES`.
+fn content(args: &ToolArgs) -> Result<&str> {
+    let content = args.str("content").unwrap_or_default();
+    if content.len() as u64 > MAX_FILE_TOOL_BYTES {
+        bail!("content is {} bytes; the limit is {}", content.len(), MAX_FILE_TOOL_BYTES);
+    }
+    Ok(content)
+}
+
+async fn read_file(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let path = args.str("path").unwrap_or_default();
+    let offset = args.get("offset").and_then(Value::as_u64).unwrap_or(0);
+    let length = args.get("length").and_then(Value::as_u64).unwrap_or(READ_FILE_DEFAULT_LENGTH);
+    let bytes = FileReader::read_range(path, offset, usize::try_from(length.min(MAX_FILE_TOOL_BYTES))?).await?;
+    Ok(ToolOutput::new(bytes, Vec::new(), 0, started.elapsed()))
+}
+
+async fn write_file(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let path = args.str("path").unwrap_or_default();
+    let content = content(&args)?;
+    FileWriter::write_file(path, content).await?;
+    Ok(text_output(format!("wrote {} bytes to {}\n", content.len(), path), started))
+}
+
+async fn append_file(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let path = args.str("path").unwrap_or_default();
+    let content = content(&args)?;
+    FileWriter::append(path, content.as_bytes()).await?;
+    Ok(text_output(format!("appended {} bytes to {}\n", content.len(), path), started))
+}
+
+async fn list_directory(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let path = args.str("path").unwrap_or(".");
+    let mut dir = tokio::fs::read_dir(path).await.with_context(|| format!("Failed to read directory: {}", path))?;
+    let mut entries = Vec::new();
+    while let Some(entry) = dir.next_entry().await? {
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type().await?.is_dir() {
+            name.push('/');
+        }
+        entries.push(name);
+    }
+    entries.sort();
+    let mut text = String::new();
+    for name in entries.iter().take(MAX_LIST_ENTRIES) {
+        let _ = writeln!(text, "{}", name);
+    }
+    if entries.len() > MAX_LIST_ENTRIES {
+        let _ = writeln!(text, "... {} more entries not shown", entries.len() - MAX_LIST_ENTRIES);
+    }
+    Ok(text_output(text, started))
+}
+
+/// Lines as `path:line:text`. Binary, non-UTF-8 and oversized files are skipped.
+async fn search_files(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let pattern = args.str("pattern").unwrap_or_default();
+    let regex = Regex::new(pattern).with_context(|| format!("invalid pattern '{}'", pattern))?;
+    let path = args.str("path").unwrap_or(".");
+    let limit = args.get("max_results").and_then(Value::as_u64).unwrap_or(DEFAULT_SEARCH_RESULTS).min(MAX_SEARCH_RESULTS);
+    let files = if tokio::fs::metadata(path).await.with_context(|| format!("Failed to read {}", path))?.is_dir() {
+        DirWalker::new(path).walk().await?.files
+    } else {
+        vec![path.into()]
+    };
+
+    let mut text = String::new();
+    let mut found = 0;
+    for file in files {
+        if tokio::fs::metadata(&file).await.map_or(true, |m| m.len() > MAX_FILE_TOOL_BYTES) {
+            continue;
+        }
+        let Ok(contents) = String::from_utf8(tokio::fs::read(&file).await?) else {
+            continue;
+        };
+        for (number, line) in contents.lines().enumerate().filter(|(_, line)| regex.is_match(line)) {
+            if found == limit {
+                let _ = writeln!(text, "... stopped after {} matches", limit);
+                return Ok(text_output(text, started));
+            }
+            let _ = writeln!(text, "{}:{}:{}", file.display(), number + 1, line);
+            found += 1;
+        }
+    }
+    Ok(text_output(text, started))
+}
+
+async fn file_info(args: ToolArgs) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let path = args.str("path").unwrap_or_default();
+    let link = tokio::fs::symlink_metadata(path).await.with_context(|| format!("Failed to read {}", path))?;
+    let metadata = if link.is_symlink() { tokio::fs::metadata(path).await.unwrap_or(link.clone()) } else { link.clone() };
+    let kind = if metadata.is_dir() {
+        "directory"
+    } else if metadata.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs());
+    let info = json!({
+        "path": PathUtils::resolve_path(path)?,
+        "type": kind,
+        "symlink": link.is_symlink(),
+        "size": metadata.len(),
+        "modified": modified,
+        "readonly": metadata.permissions().readonly(),
+    });
+    Ok(text_output(format!("{}\n", serde_json::to_string_pretty(&info)?), started))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tools::{ExecOptions, ExecPolicy, ToolError, ToolExecutor, ToolRegistry};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_file_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let registry = ToolRegistry::builtin();
+        let options = ExecOptions::new().with_policy(ExecPolicy::confined_to(&root));
+        let call = |name: &'static str, args: Value| {
+            let (registry, options) = (&registry, &options);
+            async move { ToolExecutor::execute_registered(registry, name, &args, options).await }
+        };
+        let file = root.join("notes/a.txt").display().to_string();
+        std::fs::create_dir(root.join("notes")).unwrap();
+
+        // Content is never mistaken for a path, only `path` is confined.
+        call("write_file", json!({ "path": file, "content": "one /etc/passwd\n" })).await.unwrap();
+        call("append_file", json!({ "path": file, "content": "two\n" })).await.unwrap();
+        let read = call("read_file", json!({ "path": file })).await.unwrap();
+        assert_eq!(read.stdout_lossy(), "one /etc/passwd\ntwo\n");
+
+        let listing = call("list_directory", json!({ "path": root })).await.unwrap();
+        assert_eq!(listing.stdout_lossy(), "notes/\n");
+
+        let hits = call("search_files", json!({ "pattern": "^t", "path": root })).await.unwrap();
+        assert_eq!(hits.stdout_lossy(), format!("{}:2:two\n", file));
+        assert!(call("search_files", json!({ "pattern": "(" })).await.is_err());
+
+        let info: Value = serde_json::from_str(&call("file_info", json!({ "path": file })).await.unwrap().stdout_lossy()).unwrap();
+        assert_eq!((info["type"].as_str(), info["size"].as_u64()), (Some("file"), Some(20)));
+
+        let escape = call("write_file", json!({ "path": "/tmp/escape.txt", "content": "x" })).await.unwrap_err();
+        assert!(matches!(escape, ToolError::PolicyViolation { .. }));
+        let big = "x".repeat(super::MAX_FILE_TOOL_BYTES as usize + 1);
+        assert!(call("write_file", json!({ "path": file, "content": big })).await.is_err());
+    }
+}
@@ -0,0 +1,344 @@
+// Execution history: an audit log of every tool run, kept as JSON lines
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::executor::{ExecOptions, ToolError, ToolOutput};
+use crate::file_processor::FileWriter;
+use crate::system::{EnvironmentManager, PathUtils, REDACTED_VALUE};
+
+/// Name of the log file in `PathUtils::app_data_dir`.
+pub const EXECUTION_LOG_FILE: &str = "tool-history.jsonl";
+/// Bytes of stdout and of stderr kept per entry.
+pub const LOG_OUTPUT_LIMIT: usize = 4096;
+/// Secret values shorter than this are not scrubbed; masking every `1` or
+/// `on` would make the log unreadable without hiding anything.
+const MIN_SECRET_LEN: usize = 4;
+
+/// One tool run as it appears in the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    /// When the run started, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub tool: String,
+    /// Arguments as passed; a built-in's are its JSON arguments.
+    pub argv: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// Variables set for the run; those matching the sensitive patterns are masked.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// `None` if the tool did not run to completion; see `error`.
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether stdout or stderr was cut to `LOG_OUTPUT_LIMIT`.
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl ExecutionRecord {
+    /// A record of `tool` starting now with `options`.
+    pub fn start(tool: &str, argv: &[&str], options: &ExecOptions) -> Self {
+        Self {
+            timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+            tool: tool.to_string(),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+            cwd: options.cwd.clone().or_else(|| std::env::current_dir().ok()),
+            env: options.env.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            exit_code: None,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+            error: None,
+            task_id: options.task_id.clone(),
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Fills in how the run ended.
+    pub fn finish(self, result: &Result<ToolOutput>) -> Self {
+        match result {
+            Ok(output) => self.completed(output),
+            Err(error) => self.failed(error),
+        }
+    }
+
+    pub fn completed(mut self, output: &ToolOutput) -> Self {
+        self.exit_code = Some(output.exit_code);
+        self.duration_ms = output.duration.as_millis() as u64;
+        self.stdout = output.stdout_lossy().into_owned();
+        self.stderr = output.stderr_lossy().into_owned();
+        self
+    }
+
+    pub fn failed(mut self, error: &anyhow::Error) -> Self {
+        self.duration_ms = self.started.map_or(0, |started| started.elapsed().as_millis() as u64);
+        if let Some(ToolError::Timeout { partial_stdout, .. }) = error.downcast_ref::<ToolError>() {
+            self.stdout = partial_stdout.clone();
+        }
+        self.error = Some(format!("{:#}", error));
+        self
+    }
+
+    /// Whether the run exited non-zero or did not complete.
+    pub fn failed_run(&self) -> bool {
+        self.exit_code != Some(0)
+    }
+}
+
+/// Which entries `ExecutionLog::query` returns.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionFilter {
+    pub tool: Option<String>,
+    pub task_id: Option<String>,
+    /// Only runs that exited non-zero or did not complete.
+    pub failed_only: bool,
+    /// Keep only this many of the most recent matches.
+    pub limit: Option<usize>,
+}
+
+impl ExecutionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tool(mut self, tool: impl Into<String>) -> Self {
+        self.tool = Some(tool.into());
+        self
+    }
+
+    pub fn with_task_id(mut self, task_id: impl Into<String>) -> Self {
+        self.task_id = Some(task_id.into());
+        self
+    }
+
+    pub fn with_failed_only(mut self, failed_only: bool) -> Self {
+        self.failed_only = failed_only;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, record: &ExecutionRecord) -> bool {
+        self.tool.as_ref().is_none_or(|tool| &record.tool == tool)
+            && self.task_id.as_ref().is_none_or(|id| record.task_id.as_ref() == Some(id))
+            && (!self.failed_only || record.failed_run())
+    }
+}
+
+/// An append-only log of tool runs, one JSON `ExecutionRecord` per line.
+/// Secrets are scrubbed and output truncated before a record is written.
+/// Set it on `ExecOptions::log` to have `ToolExecutor` record every run.
+#[derive(Clone)]
+pub struct ExecutionLog {
+    path: PathBuf,
+    /// Values replaced with `REDACTED_VALUE` wherever they appear.
+    secrets: Vec<String>,
+    /// Keys of `ExecutionRecord::env` whose values are masked.
+    sensitive: Vec<String>,
+}
+
+impl ExecutionLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), secrets: Vec::new(), sensitive: Vec::new() }
+    }
+
+    /// The log at `EXECUTION_LOG_FILE` in the app data directory.
+    pub fn default_location() -> Result<Self> {
+        Ok(Self::new(PathUtils::app_data_dir()?.join(EXECUTION_LOG_FILE)))
+    }
+
+    /// Also scrubs `secrets` from everything written.
+    pub fn with_secrets<I, S>(mut self, secrets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.secrets.extend(secrets.into_iter().map(Into::into).filter(|s| s.len() >= MIN_SECRET_LEN));
+        // Longest first, so a secret containing another is masked whole.
+        self.secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        self.secrets.dedup();
+        self
+    }
+
+    /// Scrubs the values of this process's variables whose names match one
+    /// of `patterns` (e.g. `SENSITIVE_ENV_PATTERNS`), and masks matching
+    /// variables set for a run.
+    pub fn with_secret_env(mut self, patterns: &[&str]) -> Result<Self> {
+        let values = EnvironmentManager::sensitive_values(patterns)?;
+        self.sensitive.extend(patterns.iter().map(|p| p.to_string()));
+        Ok(self.with_secrets(values))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Scrubs, truncates and appends `record`, creating the log if needed.
+    pub async fn append(&self, mut record: ExecutionRecord) -> Result<()> {
+        self.redact(&mut record)?;
+        let (stdout, cut_out) = truncate(record.stdout);
+        let (stderr, cut_err) = truncate(record.stderr);
+        record.stdout = stdout;
+        record.stderr = stderr;
+        record.truncated |= cut_out || cut_err;
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        FileWriter::append_line(&self.path, &serde_json::to_string(&record)?).await
+    }
+
+    /// `append`, logging a warning instead of failing; a run is never
+    /// failed because its record could not be written.
+    pub async fn record(&self, record: ExecutionRecord) {
+        if let Err(e) = self.append(record).await {
+            tracing::warn!(log = %self.path.display(), error = %format!("{:#}", e), "could not record tool run");
+        }
+    }
+
+    /// The entries matching `filter`, oldest first. A missing log is empty;
+    /// lines that do not parse are skipped.
+    pub async fn query(&self, filter: &ExecutionFilter) -> Result<Vec<ExecutionRecord>> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.path.display())),
+        };
+        let mut records: Vec<ExecutionRecord> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    tracing::warn!(log = %self.path.display(), error = %e, "skipping unreadable history entry");
+                    None
+                }
+            })
+            .filter(|record| filter.matches(record))
+            .collect();
+        if let Some(limit) = filter.limit {
+            records.drain(..records.len().saturating_sub(limit));
+        }
+        Ok(records)
+    }
+
+    fn redact(&self, record: &mut ExecutionRecord) -> Result<()> {
+        if !self.sensitive.is_empty() {
+            let patterns: Vec<&str> = self.sensitive.iter().map(String::as_str).collect();
+            let mut env = std::mem::take(&mut record.env).into_iter().collect();
+            EnvironmentManager::redact_sensitive(&mut env, &patterns)?;
+            record.env = env.into_iter().collect();
+        }
+        let scrub = |text: &mut String| {
+            for secret in &self.secrets {
+                if text.contains(secret.as_str()) {
+                    *text = text.replace(secret.as_str(), REDACTED_VALUE);
+                }
+            }
+        };
+        record.argv.iter_mut().for_each(scrub);
+        record.env.values_mut().for_each(scrub);
+        [&mut record.stdout, &mut record.stderr].into_iter().for_each(scrub);
+        record.error.iter_mut().for_each(scrub);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ExecutionLog {
+    // Never print the secrets themselves.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExecutionLog")
+            .field("path", &self.path)
+            .field("secrets", &self.secrets.len())
+            .field("sensitive", &self.sensitive)
+            .finish()
+    }
+}
+
+/// `text` cut to `LOG_OUTPUT_LIMIT` bytes at a character boundary, and
+/// whether anything was cut.
+fn truncate(mut text: String) -> (String, bool) {
+    if text.len() <= LOG_OUTPUT_LIMIT {
+        return (text, false);
+    }
+    let mut end = LOG_OUTPUT_LIMIT;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::tools::ToolExecutor;
+
+    #[tokio::test]
+    async fn test_records_and_redacts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("HISTORYTEST_API_TOKEN", "hunter22");
+        let log = ExecutionLog::new(dir.path().join("nested/log.jsonl"))
+            .with_secret_env(&["HISTORYTEST_*_TOKEN"])
+            .unwrap()
+            .with_secrets(["s3cr3t", "x"]);
+        assert!(!format!("{:?}", log).contains("hunter22"));
+        let options = ExecOptions::new()
+            .with_log(Arc::new(log.clone()))
+            .with_task_id("task-1")
+            .with_env("HISTORYTEST_API_TOKEN", "per-run");
+
+        #[cfg(unix)]
+        {
+            ToolExecutor::execute_tool_with_options("echo", &["hunter22", "s3cr3t", "x"], None, &options).await.unwrap();
+            let long = "y".repeat(LOG_OUTPUT_LIMIT + 10);
+            ToolExecutor::execute_tool_with_options("echo", &[&long], None, &options.clone().with_task_id("task-2"))
+                .await
+                .unwrap();
+        }
+        assert!(ToolExecutor::execute_tool_with_options("no-such-tool-xyz", &[], None, &options).await.is_err());
+
+        let all = log.query(&ExecutionFilter::new()).await.unwrap();
+        let failed = all.last().unwrap();
+        assert_eq!((failed.tool.as_str(), failed.exit_code), ("no-such-tool-xyz", None));
+        assert!(failed.error.is_some() && failed.failed_run());
+        assert_eq!(failed.env["HISTORYTEST_API_TOKEN"], REDACTED_VALUE);
+
+        #[cfg(unix)]
+        {
+            assert_eq!(all.len(), 3);
+            let first = &all[0];
+            assert_eq!((first.tool.as_str(), first.exit_code, first.task_id.as_deref()), ("echo", Some(0), Some("task-1")));
+            assert_eq!(first.argv, ["***", "***", "x"]);
+            assert_eq!(first.stdout, "*** *** x\n");
+            assert!(all[1].truncated && all[1].stdout.len() == LOG_OUTPUT_LIMIT);
+
+            let task = log.query(&ExecutionFilter::new().with_task_id("task-2")).await.unwrap();
+            assert_eq!(task.len(), 1);
+            let recent = log.query(&ExecutionFilter::new().with_tool("echo").with_limit(1)).await.unwrap();
+            assert_eq!(recent, task);
+            assert_eq!(log.query(&ExecutionFilter::new().with_failed_only(true)).await.unwrap().len(), 1);
+        }
+
+        // Dry runs run nothing and are not recorded.
+        let before = log.query(&ExecutionFilter::new()).await.unwrap().len();
+        ToolExecutor::execute_tool_with_options("echo", &["hi"], None, &options.clone().with_dry_run(true)).await.unwrap();
+        assert_eq!(log.query(&ExecutionFilter::new()).await.unwrap().len(), before);
+        assert!(ExecutionLog::new(dir.path().join("missing")).query(&ExecutionFilter::new()).await.unwrap().is_empty());
+    }
+}
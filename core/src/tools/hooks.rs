@@ -0,0 +1,102 @@
+// Callbacks around tool runs, for auditing, extra checks and cleanup
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::Result;
+
+use super::executor::{ExecOptions, ToolError, ToolOutput};
+
+/// A tool run as hooks see it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolTask {
+    /// The program, or the registered name of a built-in tool.
+    pub tool: String,
+    /// Its arguments; for a built-in, its JSON arguments as one string.
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// `ExecOptions::task_id`.
+    pub task_id: Option<String>,
+}
+
+impl ToolTask {
+    pub(super) fn new(tool: &str, args: &[&str], options: &ExecOptions) -> Self {
+        Self {
+            tool: tool.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            cwd: options.cwd.clone(),
+            task_id: options.task_id.clone(),
+        }
+    }
+}
+
+/// Refuses a run by returning an error.
+pub type BeforeHook = Arc<dyn Fn(&ToolTask) -> Result<()> + Send + Sync>;
+
+pub type AfterHook = Arc<dyn Fn(&ToolTask, &Result<ToolOutput>) + Send + Sync>;
+
+/// Callbacks run around every tool run made with the `ExecOptions` holding
+/// them, batches through `ToolExecutor::execute_many` included: once per
+/// call, around any retries, and not for dry runs or cached output. Hooks
+/// run in the order added, on the task running the tool, so they should
+/// be quick.
+#[derive(Clone, Default)]
+pub struct ToolHooks {
+    before: Vec<BeforeHook>,
+    after: Vec<AfterHook>,
+}
+
+impl fmt::Debug for ToolHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolHooks").field("before", &self.before.len()).field("after", &self.after.len()).finish()
+    }
+}
+
+impl ToolHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `hook` before each run. An error from it stops the run, and
+    /// the hooks after it, with `ToolError::PolicyViolation`.
+    pub fn before_execute<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ToolTask) -> Result<()> + Send + Sync + 'static,
+    {
+        self.before.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with how each run ended, one stopped by a before hook
+    /// included.
+    pub fn after_execute<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ToolTask, &Result<ToolOutput>) + Send + Sync + 'static,
+    {
+        self.after.push(Arc::new(hook));
+        self
+    }
+
+    pub(super) fn before(&self, task: &ToolTask) -> Result<(), ToolError> {
+        for hook in &self.before {
+            hook(task).map_err(|e| ToolError::PolicyViolation { command: task.tool.clone(), reason: format!("{:#}", e) })?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn after(&self, task: &ToolTask, result: &Result<ToolOutput>) {
+        for hook in &self.after {
+            hook(task, result);
+        }
+    }
+
+    /// `after` for a run that failed before the tool started, handing `e`
+    /// back.
+    pub(super) fn after_failed(&self, task: &ToolTask, e: anyhow::Error) -> anyhow::Error {
+        let result: Result<ToolOutput> = Err(e);
+        self.after(task, &result);
+        match result {
+            Err(e) => e,
+            Ok(_) => unreachable!("the run failed"),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+// The `http_request` built-in tool
+//
+// Only http and https URLs are fetched. Every hostname is resolved before
+// connecting and the connection is pinned to the checked addresses, so a
+// name cannot resolve to a link-local (cloud metadata) address after the
+// check. Redirects are followed here rather than by the client, so each hop
+// goes through the same checks. Through a proxy the proxy resolves the name
+// instead, so the check only covers what this host resolves it to.
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, LOCATION, PROXY_AUTHORIZATION};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Method, NoProxy, Proxy, StatusCode, Url};
+use serde_json::{json, Value};
+
+use super::executor::ToolOutput;
+use super::registry::{ParamType, ToolArgs, ToolParameter, ToolSpec};
+use crate::system::EnvironmentManager;
+
+/// Used when a call gives no `timeout`.
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// The longest `timeout` a call may ask for.
+pub const MAX_HTTP_TIMEOUT: Duration = Duration::from_secs(300);
+/// Response bytes returned before the body is cut off.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct HttpToolConfig {
+    /// The body in the result is truncated after this many bytes.
+    pub max_response_bytes: usize,
+    /// Allow link-local addresses, which include metadata endpoints such as
+    /// 169.254.169.254. Off by default.
+    pub allow_link_local: bool,
+}
+
+impl Default for HttpToolConfig {
+    fn default() -> Self {
+        Self { max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES, allow_link_local: false }
+    }
+}
+
+impl HttpToolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    pub fn with_allow_link_local(mut self, allow_link_local: bool) -> Self {
+        self.allow_link_local = allow_link_local;
+        self
+    }
+}
+
+/// The `http_request` tool. Its stdout is a JSON object with `url` (after
+/// redirects), `status`, `headers`, `body`, `base64` (set when the body is
+/// not UTF-8 and was encoded) and `truncated`; it exits 0 for statuses
+/// below 400 and 1 otherwise. Proxies come from
+/// `EnvironmentManager::proxy_settings`. The policy treats it as a network
+/// client.
+pub fn http_request_tool(config: HttpToolConfig) -> ToolSpec {
+    ToolSpec::builtin("http_request", "Make an HTTP or HTTPS request; returns status, headers and body as JSON", move |args| {
+        request(args, config.clone())
+    })
+    .without_paths()
+    .describe_with(|args| {
+        format!("{} {}", args.str("method").unwrap_or("GET").to_ascii_uppercase(), args.str("url").unwrap_or_default())
+    })
+    .param(ToolParameter::new("url", ParamType::String, "http:// or https:// URL"))
+    .param(ToolParameter::new("method", ParamType::String, "HTTP method (default GET)").optional())
+    .param(ToolParameter::new("headers", ParamType::Array, "Request headers as 'Name: value'").optional())
+    .param(ToolParameter::new("body", ParamType::String, "Request body").optional())
+    .param(ToolParameter::new("timeout", ParamType::Number, "Seconds before giving up (default 30, at most 300)").optional())
+}
+
+async fn request(args: ToolArgs, config: HttpToolConfig) -> Result<ToolOutput> {
+    let started = Instant::now();
+    let mut url = Url::parse(args.str("url").unwrap_or_default()).context("invalid url")?;
+    let mut method = Method::from_bytes(args.str("method").unwrap_or("GET").to_ascii_uppercase().as_bytes())
+        .context("invalid HTTP method")?;
+    let mut headers = parse_headers(&args)?;
+    let mut body = args.str("body").map(str::to_string);
+    let timeout = match args.get("timeout").and_then(Value::as_f64) {
+        Some(secs) if secs.is_finite() && secs > 0.0 => Duration::from_secs_f64(secs).min(MAX_HTTP_TIMEOUT),
+        Some(_) => bail!("timeout must be a positive number of seconds"),
+        None => DEFAULT_HTTP_TIMEOUT,
+    };
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut redirects = 0;
+    let response = loop {
+        let client = client_for(&url, &config).await?;
+        let mut request = client.request(method.clone(), url.clone()).headers(headers.clone());
+        if let Some(body) = &body {
+            request = request.body(body.clone());
+        }
+        let response = tokio::time::timeout_at(deadline, request.send())
+            .await
+            .map_err(|_| anyhow::anyhow!("request timed out after {:?}", timeout))?
+            .with_context(|| format!("request to {} failed", url))?;
+        let Some(location) = response.headers().get(LOCATION).filter(|_| response.status().is_redirection()) else {
+            break response;
+        };
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            bail!("stopped after {} redirects", MAX_REDIRECTS);
+        }
+        let location = location.to_str().context("redirect location is not valid text")?;
+        let next = url.join(location).with_context(|| format!("invalid redirect location '{}'", location))?;
+        // As reqwest does: credentials only go back to the origin they were given for.
+        if (next.scheme(), next.host_str(), next.port_or_known_default()) != (url.scheme(), url.host_str(), url.port_or_known_default()) {
+            for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+                headers.remove(name);
+            }
+        }
+        url = next;
+        // As browsers do: 303, and 301/302 after a POST, continue as a GET without a body.
+        let status = response.status();
+        if status == StatusCode::SEE_OTHER
+            || (method == Method::POST && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND))
+        {
+            method = Method::GET;
+            body = None;
+        }
+    };
+
+    let status = response.status();
+    let mut response_headers = BTreeMap::<String, String>::new();
+    for (name, value) in response.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        response_headers
+            .entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    let final_url = response.url().to_string();
+    let (bytes, truncated) = tokio::time::timeout_at(deadline, read_capped(response, config.max_response_bytes))
+        .await
+        .map_err(|_| anyhow::anyhow!("request timed out after {:?}", timeout))??;
+    let (body, base64) = encode_body(bytes, truncated);
+
+    let result = json!({
+        "url": final_url,
+        "status": status.as_u16(),
+        "headers": response_headers,
+        "body": body,
+        "base64": base64,
+        "truncated": truncated,
+    });
+    let exit_code = if status.as_u16() < 400 { 0 } else { 1 };
+    Ok(ToolOutput::new(format!("{}\n", serde_json::to_string_pretty(&result)?), Vec::new(), exit_code, started.elapsed()))
+}
+
+fn parse_headers(args: &ToolArgs) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for header in args.get("headers").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+        let (name, value) = header.split_once(':').with_context(|| format!("header '{}' is not 'Name: value'", header))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).with_context(|| format!("invalid header name in '{}'", header))?;
+        let value = HeaderValue::from_str(value.trim()).with_context(|| format!("invalid header value in '{}'", header))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// A client that may only reach `url`'s host at addresses that pass
+/// `check_address`, and that uses the environment's proxies. A proxy
+/// resolves the host itself, so requests sent through one are not pinned
+/// to the checked addresses.
+async fn client_for(url: &Url, config: &HttpToolConfig) -> Result<Client> {
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("only http and https URLs are allowed, not '{}'", url.scheme());
+    }
+    let host = url.host_str().context("url has no host")?;
+    let port = url.port_or_known_default().context("url has no port")?;
+    let mut builder = Client::builder().redirect(Policy::none()).no_proxy();
+
+    let literal = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>();
+    let addrs: Vec<SocketAddr> = match literal {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port)).await.with_context(|| format!("cannot resolve {}", host))?.collect(),
+    };
+    for addr in &addrs {
+        check_address(host, addr.ip(), config)?;
+    }
+    if literal.is_err() {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+
+    let proxies = EnvironmentManager::proxy_settings();
+    let no_proxy = || proxies.no_proxy.as_deref().and_then(NoProxy::from_string);
+    if let Some(proxy) = &proxies.http {
+        builder = builder.proxy(Proxy::http(proxy).context("invalid http proxy")?.no_proxy(no_proxy()));
+    }
+    if let Some(proxy) = &proxies.https {
+        builder = builder.proxy(Proxy::https(proxy).context("invalid https proxy")?.no_proxy(no_proxy()));
+    }
+    Ok(builder.build()?)
+}
+
+fn check_address(host: &str, ip: IpAddr, config: &HttpToolConfig) -> Result<()> {
+    if !config.allow_link_local && is_link_local(ip) {
+        bail!("refusing to connect to {}: {} is a link-local or metadata address", host, ip);
+    }
+    Ok(())
+}
+
+/// Link-local addresses, including IPv4-mapped ones, and the well-known
+/// metadata addresses outside that range.
+fn is_link_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_link_local() || v4.octets() == [100, 100, 100, 200],
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_link_local(IpAddr::V4(v4)),
+            // fe80::/10, and fd00:ec2::254 for EC2 over IPv6.
+            None => (v6.segments()[0] & 0xffc0) == 0xfe80 || v6.segments() == [0xfd00, 0xec2, 0, 0, 0, 0, 0, 0x254],
+        },
+    }
+}
+
+/// Up to `limit` bytes of the body, and whether there was more.
+async fn read_capped(mut response: reqwest::Response, limit: usize) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = limit - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+/// The body as text, or base64 when it is not UTF-8. A truncated body that
+/// only ends partway through a character is still text.
+fn encode_body(mut bytes: Vec<u8>, truncated: bool) -> (String, bool) {
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        if truncated && e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, false),
+        Err(e) => (STANDARD.encode(e.into_bytes()), true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ExecOptions, ExecPolicy, ToolError, ToolExecutor, ToolRegistry};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers each connection by request path; enough HTTP/1.1 for the tests.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buffer = [0u8; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        let n = socket.read(&mut buffer).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buffer[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&request).into_owned();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let echo = request.lines().find_map(|l| l.strip_prefix("x-echo: ")).unwrap_or("").to_string();
+                    let auth = request.lines().find_map(|l| l.strip_prefix("authorization: ")).unwrap_or("none").to_string();
+                    let (status, extra, body): (&str, String, Vec<u8>) = match path.as_str() {
+                        "/text" => ("200 OK", format!("X-Echo: {}\r\n", echo), b"hello".to_vec()),
+                        "/binary" => ("200 OK", String::new(), vec![0xff, 0x00, 0xfe]),
+                        "/redirect" => ("302 Found", "Location: /text\r\n".to_string(), Vec::new()),
+                        "/metadata" => ("302 Found", "Location: http://169.254.169.254/\r\n".to_string(), Vec::new()),
+                        "/auth" => ("200 OK", String::new(), auth.into_bytes()),
+                        to if to.starts_with("/to/") => {
+                            ("302 Found", format!("Location: http://127.0.0.1:{}/auth\r\n", &to[4..]), Vec::new())
+                        }
+                        _ => ("404 Not Found", String::new(), b"missing".to_vec()),
+                    };
+                    let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n", status, body.len(), extra);
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let _ = socket.write_all(&body).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_http_request_tool() {
+        let base = serve().await;
+        let mut registry = ToolRegistry::new();
+        registry.register(http_request_tool(HttpToolConfig::new().with_max_response_bytes(4)));
+        let options = ExecOptions::new().with_policy(ExecPolicy::allow_all());
+        let call = |args: Value| {
+            let (registry, options) = (&registry, &options);
+            async move {
+                let output = ToolExecutor::execute_registered(registry, "http_request", &args, options).await?;
+                anyhow::Ok((output.exit_code, serde_json::from_str::<Value>(&output.stdout_lossy())?))
+            }
+        };
+
+        let (code, text) = call(json!({ "url": format!("{}/redirect", base), "headers": ["X-Echo: hi"] })).await.unwrap();
+        assert_eq!(code, 0);
+        assert_eq!((text["status"].as_u64(), text["body"].as_str()), (Some(200), Some("hell")));
+        assert_eq!((text["truncated"].as_bool(), text["base64"].as_bool()), (Some(true), Some(false)));
+        assert_eq!(text["headers"]["x-echo"], "hi");
+        assert!(text["url"].as_str().unwrap().ends_with("/text"));
+
+        // Credentials follow a redirect to the same origin but not to another port.
+        let other = serve().await;
+        let port = |base: &str| base.rsplit(':').next().unwrap().to_string();
+        let auth = json!(["Authorization: Bearer secret"]);
+        let (_, same) = call(json!({ "url": format!("{}/to/{}", base, port(&base)), "headers": auth })).await.unwrap();
+        assert_eq!(same["body"], "Bear");
+        let (_, away) = call(json!({ "url": format!("{}/to/{}", base, port(&other)), "headers": auth })).await.unwrap();
+        assert_eq!(away["body"], "none");
+
+        let (_, binary) = call(json!({ "url": format!("{}/binary", base) })).await.unwrap();
+        assert_eq!((binary["body"].as_str(), binary["base64"].as_bool()), (Some("/wD+"), Some(true)));
+        let (code, missing) = call(json!({ "url": format!("{}/nope", base), "method": "post", "body": "x" })).await.unwrap();
+        assert_eq!((code, missing["status"].as_u64()), (1, Some(404)));
+
+        for url in ["file:///etc/passwd".to_string(), "http://169.254.169.254/".to_string(), format!("{}/metadata", base)] {
+            assert!(call(json!({ "url": url })).await.is_err(), "{}", url);
+        }
+        let offline = ExecOptions::new().with_policy(ExecPolicy { network: false, ..ExecPolicy::allow_all() });
+        let err = ToolExecutor::execute_registered(&registry, "http_request", &json!({ "url": base }), &offline).await.unwrap_err();
+        assert!(matches!(err, ToolError::PolicyViolation { .. }));
+    }
+}
@@ -0,0 +1,209 @@
+// Resource limits for spawned tools (setrlimit on Unix)
+use std::fmt;
+use std::process::ExitStatus;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::executor::ToolError;
+
+/// Caps on what a tool may consume, applied with `setrlimit` in the child
+/// before it execs, so they bind the tool and everything it starts. On
+/// other platforms requesting limits fails with `ToolError::Unsupported`.
+///
+/// A tool over its CPU limit is stopped by `SIGXCPU`. The other limits make
+/// allocations, `open` or `fork` fail inside the tool; they are reported as
+/// `ToolError::ResourceLimit` when the tool then exits non-zero with the
+/// matching error text (`MemoryError`, "Too many open files", ...) on stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Address space (`RLIMIT_AS`); not enforced on macOS.
+    pub max_memory_bytes: Option<u64>,
+    pub max_cpu_seconds: Option<u64>,
+    pub max_open_files: Option<u64>,
+    /// `RLIMIT_NPROC`, which counts every process of the user, not just the
+    /// tool's, and does not bind root.
+    pub max_processes: Option<u64>,
+}
+
+/// Which limit a tool ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Memory,
+    Cpu,
+    OpenFiles,
+    Processes,
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Memory => "memory",
+            Self::Cpu => "CPU time",
+            Self::OpenFiles => "open files",
+            Self::Processes => "process",
+        })
+    }
+}
+
+/// What runtimes print when an allocation, `open` or `fork` is refused.
+const MEMORY_ERRORS: &[&str] = &["MemoryError", "Cannot allocate memory", "out of memory", "memory allocation of"];
+const OPEN_FILES_ERRORS: &[&str] = &["Too many open files"];
+const PROCESSES_ERRORS: &[&str] = &["Resource temporarily unavailable"];
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_memory_bytes(mut self, bytes: u64) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_max_cpu_seconds(mut self, seconds: u64) -> Self {
+        self.max_cpu_seconds = Some(seconds);
+        self
+    }
+
+    pub fn with_max_open_files(mut self, files: u64) -> Self {
+        self.max_open_files = Some(files);
+        self
+    }
+
+    pub fn with_max_processes(mut self, processes: u64) -> Self {
+        self.max_processes = Some(processes);
+        self
+    }
+
+    /// The limits as `name=value` pairs, for dry runs.
+    pub fn describe(&self) -> Vec<String> {
+        [
+            ("memory", self.max_memory_bytes),
+            ("cpu_seconds", self.max_cpu_seconds),
+            ("open_files", self.max_open_files),
+            ("processes", self.max_processes),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{}={}", name, value?)))
+        .collect()
+    }
+
+    /// The limit a finished tool ran into, judged from how it exited and
+    /// what it wrote to stderr. Only limits that are set are considered.
+    pub fn exceeded(&self, status: &ExitStatus, stderr: &[u8]) -> Option<ResourceKind> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if self.max_cpu_seconds.is_some() && status.signal() == Some(libc::SIGXCPU) {
+                return Some(ResourceKind::Cpu);
+            }
+        }
+        if status.success() {
+            return None;
+        }
+        let stderr = String::from_utf8_lossy(stderr);
+        [
+            (self.max_memory_bytes, ResourceKind::Memory, MEMORY_ERRORS),
+            (self.max_open_files, ResourceKind::OpenFiles, OPEN_FILES_ERRORS),
+            (self.max_processes, ResourceKind::Processes, PROCESSES_ERRORS),
+        ]
+        .into_iter()
+        .find(|(limit, _, errors)| limit.is_some() && errors.iter().any(|e| stderr.contains(e)))
+        .map(|(_, kind, _)| kind)
+    }
+}
+
+/// Arranges for `command` to take on `limits` before it execs. A limit
+/// above the current hard limit is lowered to it, as an unprivileged
+/// process cannot raise it.
+#[cfg(unix)]
+pub(super) fn apply(command: &mut Command, limits: &ResourceLimits) -> Result<(), ToolError> {
+    // Resolved here, since the hook may only make async-signal-safe calls.
+    let mut resolved = Vec::new();
+    for (resource, value) in [
+        (libc::RLIMIT_AS, limits.max_memory_bytes),
+        (libc::RLIMIT_CPU, limits.max_cpu_seconds),
+        (libc::RLIMIT_NOFILE, limits.max_open_files),
+        (libc::RLIMIT_NPROC, limits.max_processes),
+    ] {
+        let Some(value) = value else { continue };
+        let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        // SAFETY: getrlimit writes only to `current`.
+        if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+            return Err(ToolError::Unsupported(format!("resource limits: {}", std::io::Error::last_os_error())));
+        }
+        let value = (value as libc::rlim_t).min(current.rlim_max);
+        // A hard CPU limit one second above the soft one lets SIGXCPU
+        // arrive before SIGKILL, so the cause can be told apart.
+        let hard = if resource == libc::RLIMIT_CPU { value.saturating_add(1).min(current.rlim_max) } else { value };
+        resolved.push((resource, libc::rlimit { rlim_cur: value, rlim_max: hard }));
+    }
+    // SAFETY: the hook only calls setrlimit on data prepared above; it does
+    // not allocate or take locks, as required between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, limit) in &resolved {
+                if libc::setrlimit(*resource, limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Job objects could cap memory and process counts on Windows; until then
+/// limits are refused rather than silently ignored.
+#[cfg(not(unix))]
+pub(super) fn apply(_command: &mut Command, _limits: &ResourceLimits) -> Result<(), ToolError> {
+    Err(ToolError::Unsupported("resource limits on this platform".to_string()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::tools::{ExecOptions, ToolExecutor};
+
+    fn limit_hit(err: ToolError) -> Option<ResourceKind> {
+        match err {
+            ToolError::ResourceLimit { kind } => Some(kind),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit() {
+        if crate::system::PathUtils::find_executable("python3").is_none() {
+            return;
+        }
+        let options = ExecOptions::new()
+            .with_timeout(Duration::from_secs(30))
+            .with_limits(ResourceLimits::new().with_max_memory_bytes(512 << 20));
+        let hungry = "b = bytearray(2 << 30)";
+        let err = ToolExecutor::execute_tool_with_options("python3", &["-c", hungry], None, &options).await.unwrap_err();
+        assert_eq!(limit_hit(err), Some(ResourceKind::Memory));
+
+        // A tool within its limits, or failing for another reason, is unaffected.
+        let fine = ToolExecutor::execute_tool_with_options("python3", &["-c", "print(1)"], None, &options).await.unwrap();
+        assert_eq!(fine.stdout_lossy(), "1\n");
+        let other = ToolExecutor::execute_tool_with_options("python3", &["-c", "raise SystemExit(3)"], None, &options);
+        assert_eq!(other.await.unwrap().exit_code, 3);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_and_open_file_limits() {
+        let options = ExecOptions::new()
+            .with_timeout(Duration::from_secs(30))
+            .with_limits(ResourceLimits::new().with_max_cpu_seconds(1).with_max_open_files(16));
+        let err = ToolExecutor::execute_tool_with_options("sh", &["-c", "while :; do :; done"], None, &options).await.unwrap_err();
+        assert_eq!(limit_hit(err), Some(ResourceKind::Cpu));
+
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", "ulimit -n"], None, &options).await.unwrap();
+        assert_eq!(output.stdout_lossy().trim(), "16");
+        assert_eq!(ResourceLimits::new().with_max_processes(8).describe(), ["processes=8"]);
+    }
+}
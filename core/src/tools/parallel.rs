@@ -0,0 +1,210 @@
+// Running several independent tools at once
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::Semaphore;
+
+use super::executor::{ExecOptions, ToolError, ToolExecutor, ToolOutput};
+
+/// One tool run in a batch, with its own timeout, environment and policy.
+#[derive(Debug, Clone)]
+pub struct ToolInvocation {
+    pub tool: String,
+    pub args: Vec<String>,
+    pub stdin: Option<Vec<u8>>,
+    pub options: ExecOptions,
+    /// Names the run in an `ExecutionReport`; defaults to the command line.
+    pub label: Option<String>,
+}
+
+impl ToolInvocation {
+    pub fn new<S: Into<String>>(tool: impl Into<String>, args: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            tool: tool.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            stdin: None,
+            options: ExecOptions::default(),
+            label: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// `label`, or the tool and its arguments.
+    pub fn describe(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => std::iter::once(&self.tool).chain(&self.args).cloned().collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    pub fn with_stdin(mut self, stdin: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(stdin.into());
+        self
+    }
+
+    pub fn with_options(mut self, options: ExecOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    async fn run(&self) -> Result<ToolOutput, ToolError> {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        ToolExecutor::execute_tool_with_options(&self.tool, &args, self.stdin.as_deref(), &self.options).await
+    }
+}
+
+impl ToolExecutor {
+    /// Runs every invocation with at most `max_concurrency` in flight and
+    /// returns their results in input order. A failing tool does not stop
+    /// the others. Give the invocations clones of one `ExecOptions::cancel`
+    /// token to stop the whole batch: running tools are terminated and
+    /// those not yet started fail with `ToolError::Cancelled` without running.
+    pub async fn execute_parallel(invocations: Vec<ToolInvocation>, max_concurrency: usize) -> Vec<Result<ToolOutput, ToolError>> {
+        run_batch(&invocations, max_concurrency, false).await
+    }
+
+    /// Like `execute_parallel`, but summarises the batch: how many runs
+    /// succeeded, exited non-zero or failed to run, and which ones.
+    pub async fn execute_many(invocations: Vec<ToolInvocation>, max_concurrency: usize) -> ExecutionReport {
+        let started = Instant::now();
+        let results = run_batch(&invocations, max_concurrency, false).await;
+        let runs = invocations
+            .into_iter()
+            .zip(results)
+            .enumerate()
+            .map(|(index, (invocation, result))| BatchRun { index, label: invocation.describe(), result })
+            .collect();
+        let report = ExecutionReport { runs, duration: started.elapsed() };
+        tracing::debug!(
+            succeeded = report.succeeded(),
+            failed = report.failed(),
+            errored = report.errored(),
+            duration_ms = report.duration.as_millis() as u64,
+            "tool batch finished"
+        );
+        report
+    }
+
+    /// Like `execute_parallel`, but the first error or non-zero exit kills
+    /// the tools still running and skips those not started; their results
+    /// are `ToolError::Cancelled`.
+    pub async fn execute_parallel_fail_fast(
+        invocations: Vec<ToolInvocation>,
+        max_concurrency: usize,
+    ) -> Vec<Result<ToolOutput, ToolError>> {
+        run_batch(&invocations, max_concurrency, true).await
+    }
+}
+
+async fn run_batch(invocations: &[ToolInvocation], max_concurrency: usize, fail_fast: bool) -> Vec<Result<ToolOutput, ToolError>> {
+    let permits = Arc::new(Semaphore::new(max_concurrency.max(1)));
+    let mut results: Vec<Option<Result<ToolOutput, ToolError>>> = invocations.iter().map(|_| None).collect();
+    let mut running: FuturesUnordered<_> = invocations
+        .iter()
+        .enumerate()
+        .map(|(index, invocation)| {
+            let permits = Arc::clone(&permits);
+            async move {
+                let _permit = permits.acquire().await.expect("semaphore is never closed");
+                (index, invocation.run().await)
+            }
+        })
+        .collect();
+
+    while let Some((index, result)) = running.next().await {
+        let failed = !matches!(&result, Ok(output) if output.success());
+        results[index] = Some(result);
+        if fail_fast && failed {
+            // Dropping the futures kills their tools (`kill_on_drop`).
+            break;
+        }
+    }
+    drop(running);
+
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or(Err(ToolError::Cancelled { elapsed: Duration::ZERO, partial_stdout: String::new() })))
+        .collect()
+}
+
+/// One run of an `execute_many` batch.
+#[derive(Debug)]
+pub struct BatchRun {
+    /// Position in the batch as submitted.
+    pub index: usize,
+    /// `ToolInvocation::describe` of the run.
+    pub label: String,
+    pub result: Result<ToolOutput, ToolError>,
+}
+
+impl BatchRun {
+    pub fn success(&self) -> bool {
+        matches!(&self.result, Ok(output) if output.success())
+    }
+}
+
+/// What `execute_many` ran and how each run ended, in input order.
+#[derive(Debug)]
+pub struct ExecutionReport {
+    pub runs: Vec<BatchRun>,
+    /// Wall-clock time for the whole batch.
+    pub duration: Duration,
+}
+
+impl ExecutionReport {
+    /// Runs that exited with code 0.
+    pub fn succeeded(&self) -> usize {
+        self.runs.iter().filter(|run| run.success()).count()
+    }
+
+    /// Runs that finished with a non-zero exit code.
+    pub fn failed(&self) -> usize {
+        self.runs.iter().filter(|run| matches!(&run.result, Ok(output) if !output.success())).count()
+    }
+
+    /// Runs that did not finish: not found, timed out, refused and so on.
+    pub fn errored(&self) -> usize {
+        self.runs.iter().filter(|run| run.result.is_err()).count()
+    }
+
+    pub fn all_succeeded(&self) -> bool {
+        self.runs.iter().all(BatchRun::success)
+    }
+
+    /// The runs that failed or errored, in input order.
+    pub fn failures(&self) -> impl Iterator<Item = &BatchRun> {
+        self.runs.iter().filter(|run| !run.success())
+    }
+
+    /// The results in input order, as `execute_parallel` returns them.
+    pub fn into_results(self) -> Vec<Result<ToolOutput, ToolError>> {
+        self.runs.into_iter().map(|run| run.result).collect()
+    }
+}
+
+/// A summary line, then one line per run that did not succeed.
+impl fmt::Display for ExecutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} run(s) in {:?}: {} succeeded, {} failed, {} errored",
+            self.runs.len(),
+            self.duration,
+            self.succeeded(),
+            self.failed(),
+            self.errored()
+        )?;
+        for run in self.failures() {
+            match &run.result {
+                Ok(output) => write!(f, "\n  #{} {}: exit code {}", run.index, run.label, output.exit_code)?,
+                Err(e) => write!(f, "\n  #{} {}: {}", run.index, run.label, e)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+// Execution policy: which commands tools may run and which paths they may touch
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use super::executor::ToolError;
+use crate::system::PathUtils;
+
+/// Clients denied when `network` is off. This is a deny list, not a sandbox:
+/// it stops the obvious tools, not a program that opens sockets itself.
+pub const NETWORK_COMMANDS: &[&str] = &[
+    "curl", "wget", "nc", "ncat", "netcat", "ssh", "scp", "sftp", "rsync", "ftp", "telnet", "socat", "http_request",
+];
+
+/// Guardrails checked before a tool is spawned.
+///
+/// Arguments that look like paths (containing a separator, or starting with
+/// `.` or `~`, including the value of `--flag=value`) must resolve inside one
+/// of `allowed_roots`; relative ones are taken against the current
+/// directory. The check is deliberately conservative, so a regex such as
+/// `^/api` is also read as a path. An empty `allowed_roots` leaves paths
+/// unrestricted.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecPolicy {
+    /// Command basenames that may run; `None` allows any not denied.
+    pub allowed_commands: Option<Vec<String>>,
+    /// Command basenames that never run, checked before `allowed_commands`.
+    pub denied_commands: Vec<String>,
+    pub allowed_roots: Vec<PathBuf>,
+    /// When false, the commands in `NETWORK_COMMANDS` are denied.
+    pub network: bool,
+    /// Lets the `shell` tool run scripts, which no argument check can vet.
+    pub allow_shell: bool,
+}
+
+impl ExecPolicy {
+    /// A policy that permits everything except the `shell` tool.
+    pub fn allow_all() -> Self {
+        Self {
+            allowed_commands: None,
+            denied_commands: Vec::new(),
+            allowed_roots: Vec::new(),
+            network: true,
+            allow_shell: false,
+        }
+    }
+
+    /// Any non-network command, with paths confined to `root`.
+    pub fn confined_to(root: impl Into<PathBuf>) -> Self {
+        Self {
+            allowed_roots: vec![root.into()],
+            network: false,
+            ..Self::allow_all()
+        }
+    }
+
+    /// Checks that `command` may run with `args`.
+    pub fn check(&self, command: &str, args: &[&str]) -> Result<(), ToolError> {
+        self.check_in(command, args, None)
+    }
+
+    /// Checks `command` and that each of `paths` stays inside the allowed
+    /// roots, whether or not it looks like a path.
+    pub fn check_paths(&self, command: &str, paths: &[&str]) -> Result<(), ToolError> {
+        self.check_command(command)
+            .and_then(|_| paths.iter().try_for_each(|path| self.check_path(&expand_home(path), path)))
+            .map_err(|reason| ToolError::PolicyViolation { command: command.to_string(), reason })
+    }
+
+    /// Like `check` for a tool started in `cwd`: relative path arguments are
+    /// resolved against it, and it must itself lie inside the allowed roots.
+    pub fn check_in(&self, command: &str, args: &[&str], cwd: Option<&Path>) -> Result<(), ToolError> {
+        self.check_command(command)
+            .and_then(|_| cwd.map_or(Ok(()), |cwd| self.check_path(cwd, &cwd.display().to_string())))
+            .and_then(|_| args.iter().try_for_each(|arg| self.check_arg(arg, cwd)))
+            .map_err(|reason| ToolError::PolicyViolation { command: command.to_string(), reason })
+    }
+
+    /// The rules `check` enforces, in words.
+    pub fn rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        if let Some(allowed) = &self.allowed_commands {
+            rules.push(format!("command is one of allowed_commands ({})", allowed.join(", ")));
+        }
+        if !self.denied_commands.is_empty() {
+            rules.push(format!("command is not denied ({})", self.denied_commands.join(", ")));
+        }
+        if !self.network {
+            rules.push("command does not need network access".to_string());
+        }
+        if !self.allowed_roots.is_empty() {
+            let roots: Vec<_> = self.allowed_roots.iter().map(|root| root.display().to_string()).collect();
+            rules.push(format!("path arguments stay inside {}", roots.join(", ")));
+        }
+        if self.allow_shell {
+            rules.push("shell scripts are allowed".to_string());
+        }
+        rules
+    }
+
+    fn check_command(&self, command: &str) -> Result<(), String> {
+        let name = basename(command);
+        if self.denied_commands.iter().any(|denied| denied == name) {
+            return Err(format!("command '{}' is denied", name));
+        }
+        if !self.network && NETWORK_COMMANDS.contains(&name) {
+            return Err(format!("command '{}' needs network access, which is disabled", name));
+        }
+        match &self.allowed_commands {
+            Some(allowed) if !allowed.iter().any(|a| a == name) => {
+                Err(format!("command '{}' is not in allowed_commands", name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_arg(&self, arg: &str, cwd: Option<&Path>) -> Result<(), String> {
+        let value = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with('-') => value,
+            _ => arg,
+        };
+        if !looks_like_path(value) {
+            return Ok(());
+        }
+        let path = expand_home(value);
+        match cwd {
+            Some(cwd) => self.check_path(&cwd.join(path), value),
+            None => self.check_path(&path, value),
+        }
+    }
+
+    /// Checks that `path` (shown to the user as `value`) is inside a root.
+    fn check_path(&self, path: &Path, value: &str) -> Result<(), String> {
+        if self.allowed_roots.is_empty() {
+            return Ok(());
+        }
+        let resolved = PathUtils::resolve_path(path)
+            .map_err(|e| format!("cannot resolve path '{}': {}", value, e))?;
+        let inside = self
+            .allowed_roots
+            .iter()
+            .any(|root| PathUtils::resolve_path(root).is_ok_and(|root| resolved.starts_with(root)));
+        if inside {
+            Ok(())
+        } else {
+            Err(format!("path '{}' resolves to {}, outside the allowed roots", value, resolved.display()))
+        }
+    }
+}
+
+impl Default for ExecPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+fn basename(command: &str) -> &str {
+    let name = Path::new(command).file_name().and_then(|n| n.to_str()).unwrap_or(command);
+    name.strip_suffix(".exe").unwrap_or(name)
+}
+
+fn looks_like_path(arg: &str) -> bool {
+    arg.contains('/') || arg.contains(std::path::MAIN_SEPARATOR) || arg.starts_with('.') || arg.starts_with('~')
+}
+
+fn expand_home(arg: &str) -> PathBuf {
+    match (arg.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(arg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(result: Result<(), ToolError>) -> String {
+        match result {
+            Err(ToolError::PolicyViolation { reason, .. }) => reason,
+            other => panic!("expected a policy violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_commands() {
+        let policy = ExecPolicy {
+            allowed_commands: Some(vec!["ls".into(), "rm".into(), "curl".into()]),
+            denied_commands: vec!["rm".into()],
+            ..ExecPolicy::allow_all()
+        };
+        assert!(policy.check("/bin/ls", &["-la"]).is_ok());
+        assert_eq!(reason(policy.check("rm", &[])), "command 'rm' is denied");
+        assert_eq!(reason(policy.check("cat", &[])), "command 'cat' is not in allowed_commands");
+        assert!(policy.check("curl", &[]).is_ok());
+        let offline = ExecPolicy { network: false, ..policy };
+        assert!(reason(offline.check("/usr/bin/curl", &[])).contains("network access"));
+    }
+
+    #[test]
+    fn test_paths_stay_inside_roots() {
+        let cwd = std::env::current_dir().unwrap();
+        let policy = ExecPolicy::confined_to(&cwd);
+        assert!(policy.check("cat", &["src/lib.rs", "./Cargo.toml", "-n", "pattern"]).is_ok());
+        assert!(policy.check("cat", &["src/../Cargo.toml"]).is_ok());
+
+        assert!(reason(policy.check("cat", &["../secrets.txt"])).contains("outside the allowed roots"));
+        assert!(policy.check("cat", &["src/../../../etc/passwd"]).is_err());
+        assert!(policy.check("cat", &["/etc/passwd"]).is_err());
+        assert!(policy.check("grep", &["--file=/etc/passwd"]).is_err());
+        assert!(policy.check("cat", &["~/.ssh/id_rsa"]).is_err());
+        assert!(ExecPolicy::allow_all().check("cat", &["/etc/passwd"]).is_ok());
+    }
+}
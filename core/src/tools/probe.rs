@@ -0,0 +1,120 @@
+// Finding out which external tools are installed, and at which version
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+
+use super::executor::{ExecOptions, ToolExecutor};
+use super::registry::{ToolHandler, ToolSpec};
+use crate::system::PathUtils;
+
+/// How long a version invocation may take before the version is given up on.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Matches the first dotted version number, e.g. `2.43.0` or `3.12`.
+pub const DEFAULT_VERSION_PATTERN: &str = r"\d+(?:\.\d+)+";
+
+/// How to ask a tool's executable for its version: run it with `args` and
+/// take the first match of `pattern` (its first capture group, if it has
+/// one) in stdout, or else stderr.
+#[derive(Debug, Clone)]
+pub struct VersionProbe {
+    pub args: Vec<String>,
+    pub pattern: Regex,
+    pub timeout: Duration,
+}
+
+impl VersionProbe {
+    pub fn new<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            pattern: Regex::new(DEFAULT_VERSION_PATTERN).expect("valid default version pattern"),
+            timeout: PROBE_TIMEOUT,
+        }
+    }
+
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        self.pattern = Regex::new(pattern).with_context(|| format!("invalid version pattern '{}'", pattern))?;
+        Ok(self)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The version in a tool's output, if the pattern finds one.
+    pub fn extract(&self, output: &str) -> Option<String> {
+        let found = self.pattern.captures(output)?;
+        found.get(1).or_else(|| found.get(0)).map(|m| m.as_str().to_string())
+    }
+}
+
+/// `--version`, which most command-line tools understand.
+impl Default for VersionProbe {
+    fn default() -> Self {
+        Self::new(["--version"])
+    }
+}
+
+/// Whether a registered tool can run here. Built-ins are always available
+/// and have no executable or version of their own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolProbe {
+    pub name: String,
+    pub available: bool,
+    pub resolved_path: Option<PathBuf>,
+    pub version: Option<String>,
+}
+
+/// Versions found so far, by executable and probe arguments.
+type VersionCache = Mutex<HashMap<(PathBuf, Vec<String>), Option<String>>>;
+
+fn versions() -> &'static VersionCache {
+    static VERSIONS: OnceLock<VersionCache> = OnceLock::new();
+    VERSIONS.get_or_init(Default::default)
+}
+
+/// Probes `spec`. Never fails: a missing executable is reported as
+/// unavailable, and one that will not say its version has `version: None`.
+pub(super) async fn probe(spec: &ToolSpec) -> ToolProbe {
+    let program = match &spec.handler {
+        ToolHandler::Command(command) => command.as_str(),
+        ToolHandler::Shell(kind) => kind.program(),
+        ToolHandler::Builtin(_) => {
+            return ToolProbe { name: spec.name.clone(), available: true, resolved_path: None, version: None };
+        }
+    };
+    let Some(path) = PathUtils::find_executable(program) else {
+        return ToolProbe { name: spec.name.clone(), available: false, resolved_path: None, version: None };
+    };
+    let version = probe_version(&path, &spec.version_probe).await;
+    ToolProbe { name: spec.name.clone(), available: true, resolved_path: Some(path), version }
+}
+
+/// Runs `probe` against the executable at `path` once per process.
+async fn probe_version(path: &Path, probe: &VersionProbe) -> Option<String> {
+    let key = (path.to_path_buf(), probe.args.clone());
+    if let Some(version) = versions().lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return version.clone();
+    }
+    let args: Vec<&str> = probe.args.iter().map(String::as_str).collect();
+    // Asking for a version changes nothing, so it is allowed in safe mode.
+    let options = ExecOptions { read_only: true, ..ExecOptions::new().with_timeout(probe.timeout) };
+    let version = match ToolExecutor::execute_tool_with_options(&path.to_string_lossy(), &args, None, &options).await {
+        Ok(output) => probe.extract(&output.stdout_lossy()).or_else(|| probe.extract(&output.stderr_lossy())),
+        Err(e) => {
+            tracing::debug!(tool = %path.display(), "version probe failed: {}", e);
+            None
+        }
+    };
+    versions().lock().unwrap_or_else(|e| e.into_inner()).insert(key, version.clone());
+    version
+}
@@ -1,21 +1,765 @@
 // Process manager implementation
-use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, bail, Context, Result};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::field::Empty;
 
-pub struct ProcessManager;
+use crate::cancel::{cancelled, CancellationToken};
+use crate::error::CoreError;
+use crate::safe_mode;
+use crate::system::{EnvironmentManager, SENSITIVE_ENV_PATTERNS};
 
-impl ProcessManager {
+mod detached;
+mod exit;
+mod named;
+mod output;
+mod priority;
+mod pty;
+mod supervise;
+mod tree;
+#[cfg(unix)]
+mod unix;
+mod usage;
+#[cfg(windows)]
+mod windows;
+
+pub use detached::DetachedProcess;
+pub use exit::{ExitInfo, EXIT_TAIL_BYTES};
+pub use named::{BackgroundSpec, ProcessStatus, DEFAULT_STOP_GRACE, PROCESS_STATE_DIR};
+pub use output::{OutputLine, OutputSource, FOLLOW_POLL_INTERVAL};
+pub use priority::{Priority, PriorityError};
+pub use usage::ProcessUsage;
+pub use supervise::{ProcessEvent, RestartPolicy, Supervision, MAX_RESTART_BACKOFF, RESTART_BACKOFF};
+use exit::{ExitReport, ExitWaiters, SharedTails};
+use supervise::{Rebuild, RunState, Supervisor, EVENT_CAPACITY};
+pub(crate) use pty::{read_chunks, Pty};
+use tree::{ProcessTree, Tree};
+use usage::CpuMark;
+
+/// Where one of a spawned process's standard streams goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdioMode {
+    /// Shared with this process.
+    #[default]
+    Inherit,
+    /// Discarded, or empty for stdin.
+    Null,
+    /// A pipe, taken from the `ProcessHandle` with `take_stdin` and friends.
+    Piped,
+}
+
+impl StdioMode {
+    fn to_stdio(self) -> Stdio {
+        match self {
+            Self::Inherit => Stdio::inherit(),
+            Self::Null => Stdio::null(),
+            Self::Piped => Stdio::piped(),
+        }
+    }
+}
+
+/// How a process's stdin, stdout and stderr are set up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StdioConfig {
+    pub stdin: StdioMode,
+    pub stdout: StdioMode,
+    pub stderr: StdioMode,
+}
+
+impl StdioConfig {
+    pub fn piped() -> Self {
+        Self { stdin: StdioMode::Piped, stdout: StdioMode::Piped, stderr: StdioMode::Piped }
+    }
+
+    pub fn null() -> Self {
+        Self { stdin: StdioMode::Null, stdout: StdioMode::Null, stderr: StdioMode::Null }
+    }
+}
+
+/// Which of this process's variables a spawned process gets. Variables
+/// whose key matches one of `SENSITIVE_ENV_PATTERNS` (API keys, tokens and
+/// the like) are never inherited unless named in `InheritAllowlist`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EnvMode {
+    /// Everything but the sensitive variables.
+    #[default]
+    InheritAll,
+    /// Only the variables named here that are set, sensitive or not.
+    InheritAllowlist(Vec<String>),
+    /// Only the `PRESERVED_ENV_VARS`, plus `extra`.
+    Clean { extra: HashMap<String, String> },
+}
+
+impl EnvMode {
+    fn apply(&self, command: &mut Command) {
+        match self {
+            Self::InheritAll => {
+                let sensitive = EnvironmentManager::sensitive_keys(SENSITIVE_ENV_PATTERNS).expect("sensitive patterns are valid globs");
+                for key in sensitive {
+                    command.env_remove(key);
+                }
+            }
+            Self::InheritAllowlist(allowed) => {
+                command.env_clear();
+                for key in allowed {
+                    if let Some(value) = std::env::var_os(key) {
+                        command.env(key, value);
+                    }
+                }
+            }
+            Self::Clean { extra } => {
+                command.env_clear().envs(EnvironmentManager::preserved_env_vars()).envs(extra);
+            }
+        }
+    }
+}
+
+/// How `ProcessManager::spawn_process_with` starts a process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpawnOptions {
+    pub stdio: StdioConfig,
+    /// Start the process in a process group of its own, so that
+    /// `ProcessHandle::kill_tree` takes down everything it spawned on Unix
+    /// (Windows does so through a job object either way) and
+    /// `ProcessHandle::shutdown` reaches it all. It then also no longer
+    /// receives the terminal's Ctrl-C.
+    pub new_process_group: bool,
+    /// Start the process again when it exits; see `RestartPolicy`.
+    pub restart: RestartPolicy,
+    pub env_mode: EnvMode,
+    /// Run the process on a pseudo-terminal of its own instead of `stdio`,
+    /// for programs that behave differently when not on one, such as
+    /// REPLs. Drive it through `ProcessHandle::write_input` and
+    /// `ProcessHandle::pty_output`. It also leads a session of its own,
+    /// which `ProcessHandle::kill_tree` takes down. Unix only for now.
+    pub pty: bool,
+    /// Scheduling priority; see `Priority`. If the system refuses it, the
+    /// spawn fails with a `PriorityError`.
+    pub priority: Priority,
+}
+
+impl SpawnOptions {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    pub fn with_stdio(mut self, stdio: StdioConfig) -> Self {
+        self.stdio = stdio;
+        self
+    }
+
+    pub fn with_new_process_group(mut self, enabled: bool) -> Self {
+        self.new_process_group = enabled;
+        self
+    }
+
+    pub fn with_restart(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn with_env_mode(mut self, env_mode: EnvMode) -> Self {
+        self.env_mode = env_mode;
+        self
+    }
+
+    pub fn with_pty(mut self, pty: bool) -> Self {
+        self.pty = pty;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// How a process ended, or why waiting for it failed.
+type ExitResult = std::result::Result<ExitStatus, String>;
+
+/// What a `ProcessHandle` asks of the task that owns the child.
+#[derive(Debug, Clone, Copy)]
+enum Stop {
+    /// Ask the process (and its group) to exit.
+    Terminate,
+    Kill,
+    KillTree,
+}
+
+/// A process started by a `ProcessManager`. Clones refer to the same
+/// process. A background task owns the child, so the process keeps running
+/// when handles are dropped; `kill` it to stop it early. Under a
+/// `RestartPolicy` the handle follows the process through its restarts, and
+/// it only counts as exited once it is not restarted again.
+#[derive(Debug, Clone)]
+pub struct ProcessHandle {
+    id: u64,
+    run: Arc<Mutex<RunState>>,
+    command: String,
+    options: SpawnOptions,
+    exit: watch::Receiver<Option<ExitResult>>,
+    stop: mpsc::UnboundedSender<Stop>,
+    pipes: Arc<Mutex<Pipes>>,
+    pty: Option<Arc<Pty>>,
+    cpu_mark: Arc<Mutex<Option<CpuMark>>>,
+    tails: SharedTails,
+    exit_waiters: Arc<Mutex<ExitWaiters>>,
+}
+
+#[derive(Debug, Default)]
+struct Pipes {
+    stdin: Option<ChildStdin>,
+    stdout: Option<ChildStdout>,
+    stderr: Option<ChildStderr>,
+}
+
+impl ProcessHandle {
+    /// Identifies the process within its `ProcessManager`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The pid of the current run.
+    pub fn pid(&self) -> u32 {
+        self.run_state().pid
+    }
+
+    /// How often the process has been restarted.
+    pub fn restarts(&self) -> u32 {
+        self.run_state().restarts
+    }
+
+    pub fn supervision(&self) -> Supervision {
+        self.run_state().supervision
+    }
+
+    fn run_state(&self) -> RunState {
+        *self.run.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn stdio(&self) -> StdioConfig {
+        self.options.stdio
+    }
+
+    pub fn options(&self) -> &SpawnOptions {
+        &self.options
+    }
+
+    /// Waits for the process to exit.
+    pub async fn wait(&self) -> Result<ExitStatus> {
+        let mut exit = self.exit.clone();
+        let result = exit
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| anyhow!("lost track of '{}' (pid {})", self.command, self.pid()))?;
+        exit_status(&self.command, result.as_ref().expect("waited for an exit"))
+    }
+
+    /// Waits up to `timeout` for the process to exit, and returns `None` if
+    /// it is still running then; it is left running, and can be waited for
+    /// again. Its supervising task reaps it either way.
+    pub async fn wait_timeout(&self, timeout: Duration) -> Result<Option<ExitStatus>> {
+        match tokio::time::timeout(timeout, self.wait()).await {
+            Ok(status) => status.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The exit status if the process has exited, without waiting.
+    pub fn try_wait(&self) -> Result<Option<ExitStatus>> {
+        match self.exit.borrow().as_ref() {
+            Some(result) => exit_status(&self.command, result).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Kills the process and waits for it to go. Killing one that has
+    /// already exited does nothing.
+    pub async fn kill(&self) -> Result<()> {
+        self.stop(Stop::Kill).await
+    }
+
+    /// Kills the process and everything it spawned, then waits for the
+    /// process to go: on Unix every process in its group, which needs
+    /// `SpawnOptions::new_process_group` (without it only the process itself
+    /// is killed); on Windows every process in the job object it was put in
+    /// when it started.
+    pub async fn kill_tree(&self) -> Result<()> {
+        self.stop(Stop::KillTree).await
+    }
+
+    /// Asks the process to exit, and gives it `grace` to do so before
+    /// `kill_tree`: `SIGTERM` on Unix, to its whole group with
+    /// `SpawnOptions::new_process_group`; on Windows Ctrl-Break to its
+    /// console process group with `new_process_group`, or otherwise
+    /// `taskkill` without `/F`. Returns `false` if it had to be killed.
+    pub async fn shutdown(&self, grace: Duration) -> Result<bool> {
+        if self.try_wait()?.is_some() {
+            return Ok(true);
+        }
+        // The owning task is gone once the process has exited.
+        let _ = self.stop.send(Stop::Terminate);
+        match tokio::time::timeout(grace, self.wait()).await {
+            Ok(status) => status.map(|_| true),
+            Err(_) => {
+                tracing::debug!(command = %self.command, pid = self.pid(), "process ignored SIGTERM; killing it");
+                self.kill_tree().await.map(|_| false)
+            }
+        }
+    }
+
+    async fn stop(&self, stop: Stop) -> Result<()> {
+        let _ = self.stop.send(stop);
+        self.wait().await.map(|_| ())
+    }
+
+    /// The write end of a piped stdin; `None` if not piped or already taken.
+    pub fn take_stdin(&self) -> Option<ChildStdin> {
+        self.pipes.lock().unwrap_or_else(|e| e.into_inner()).stdin.take()
+    }
+
+    pub fn take_stdout(&self) -> Option<ChildStdout> {
+        self.pipes.lock().unwrap_or_else(|e| e.into_inner()).stdout.take()
+    }
+
+    pub fn take_stderr(&self) -> Option<ChildStderr> {
+        self.pipes.lock().unwrap_or_else(|e| e.into_inner()).stderr.take()
+    }
+}
+
+fn exit_status(command: &str, result: &ExitResult) -> Result<ExitStatus> {
+    result.clone().map_err(|e| anyhow!("waiting for '{}' failed: {}", command, e))
+}
+
+fn command_line(command: &str, args: &[&str], options: &SpawnOptions) -> Command {
+    let stdio = options.stdio;
+    #[cfg(windows)]
+    let mut command_line = Command::new(windows::program(command));
+    #[cfg(not(windows))]
+    let mut command_line = Command::new(command);
+    command_line
+        .args(args)
+        .stdin(stdio.stdin.to_stdio())
+        .stdout(stdio.stdout.to_stdio())
+        .stderr(stdio.stderr.to_stdio())
+        .kill_on_drop(true);
+    options.env_mode.apply(&mut command_line);
+    // A terminal's session is a group of its own already.
+    let grouped = options.new_process_group && !options.pty;
+    if grouped {
+        Tree::new_group(&mut command_line);
+    }
+    options.priority.apply(&mut command_line, grouped);
+    command_line
+}
+
+async fn read_all<R: AsyncRead + Unpin>(reader: Option<R>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Some(mut reader) = reader {
+        // A read error ends the output early; the stage's status tells more.
+        let _ = reader.read_to_end(&mut bytes).await;
+    }
+    bytes
+}
+
+/// One stage of a `ProcessManager::pipeline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl CommandSpec {
+    pub fn new<I, S>(program: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { program: program.into(), args: args.into_iter().map(Into::into).collect() }
+    }
+}
+
+/// A running `ProcessManager::pipeline`.
+#[derive(Debug)]
+pub struct PipelineHandle {
+    stages: Vec<ProcessHandle>,
+    stdout: Option<ChildStdout>,
+    stderr: Vec<JoinHandle<Vec<u8>>>,
+}
+
+impl PipelineHandle {
+    /// The stages' processes, in pipeline order.
+    pub fn stages(&self) -> &[ProcessHandle] {
+        &self.stages
     }
-    
-    pub async fn spawn_process(_command: &str, _args: &[&str]) -> Result<()> {
-        // TODO: Implement process spawning and management
-        todo!("Implement in T022")
+
+    /// Kills every stage.
+    pub async fn kill(&self) -> Result<()> {
+        for stage in &self.stages {
+            stage.kill().await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the last stage's output and waits for every stage to exit.
+    pub async fn wait(self) -> Result<PipelineOutput> {
+        let stdout = read_all(self.stdout).await;
+        let mut stages = Vec::with_capacity(self.stages.len());
+        for (handle, stderr) in self.stages.iter().zip(self.stderr) {
+            let status = handle.wait().await?;
+            let stderr = stderr.await.context("pipeline stderr reader failed")?;
+            stages.push(StageResult { program: handle.command().to_string(), exit_code: status.code(), stderr });
+        }
+        Ok(PipelineOutput { stdout, stages })
+    }
+}
+
+/// How one pipeline stage ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageResult {
+    pub program: String,
+    /// `None` if a signal ended the stage, as `SIGPIPE` does to a stage
+    /// whose reader exited first.
+    pub exit_code: Option<i32>,
+    pub stderr: Vec<u8>,
+}
+
+/// What a pipeline produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineOutput {
+    /// The last stage's stdout.
+    pub stdout: Vec<u8>,
+    /// Every stage's outcome, in pipeline order.
+    pub stages: Vec<StageResult>,
+}
+
+impl PipelineOutput {
+    pub fn stdout_lossy(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    pub fn exit_codes(&self) -> Vec<Option<i32>> {
+        self.stages.iter().map(|stage| stage.exit_code).collect()
+    }
+
+    /// Whether every stage exited with 0, like a shell's `pipefail`.
+    pub fn success(&self) -> bool {
+        self.stages.iter().all(|stage| stage.exit_code == Some(0))
+    }
+}
+
+/// Asks `child`, and the rest of `tree` if it has one, to exit. Only
+/// called before the child has been waited for, so its pid is still its own.
+async fn terminate(child: &Child, tree: Option<&Tree>) {
+    match tree {
+        Some(tree) => tree.terminate().await,
+        None => {
+            if let Some(pid) = child.id() {
+                terminate_pid(pid, false).await;
+            }
+        }
+    }
+}
+
+/// Kills `child`, and the rest of `tree` if it has one.
+async fn kill_tree(child: &mut Child, tree: Option<&Tree>) -> std::io::Result<()> {
+    match tree {
+        Some(tree) => {
+            tree.kill().await;
+            // Normally gone with its tree already.
+            let _ = child.kill().await;
+            Ok(())
+        }
+        None => child.kill().await,
     }
 }
 
+/// `terminate` by pid; for `tree` on Unix, `pid` must lead its group.
+async fn terminate_pid(pid: u32, tree: bool) {
+    #[cfg(unix)]
+    {
+        // SAFETY: plain syscalls that only send a signal; callers make sure
+        // `pid` still names the process they mean.
+        unsafe {
+            if tree {
+                libc::killpg(pid as libc::pid_t, libc::SIGTERM);
+            } else {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+    #[cfg(windows)]
+    {
+        let mut taskkill = Command::new("taskkill");
+        if tree {
+            taskkill.arg("/T");
+        }
+        let _ = taskkill.args(["/PID", &pid.to_string()]).output().await;
+    }
+}
+
+type Registry = Arc<Mutex<HashMap<u64, ProcessHandle>>>;
+
+/// Starts processes and keeps track of the ones still running, by id. In
+/// `safe_mode` nothing is started and spawning fails with
+/// `CoreError::SafeMode`.
+#[derive(Debug, Clone)]
+pub struct ProcessManager {
+    processes: Registry,
+    next_id: Arc<AtomicU64>,
+    named: Arc<named::NamedProcesses>,
+    /// Where named processes' pid files go; see `with_state_dir`.
+    state_dir: Option<PathBuf>,
+    events: broadcast::Sender<ProcessEvent>,
+}
+
 impl Default for ProcessManager {
     fn default() -> Self {
-        Self::new()
+        Self {
+            processes: Registry::default(),
+            next_id: Arc::default(),
+            named: Arc::default(),
+            state_dir: None,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl ProcessManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events for every process this manager starts from now on. A
+    /// receiver that falls more than a few hundred events behind misses
+    /// the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
+        self.events.subscribe()
+    }
+
+    /// Starts `command` with inherited stdio and returns without waiting.
+    pub async fn spawn_process(&self, command: &str, args: &[&str]) -> Result<ProcessHandle> {
+        self.spawn_process_with(command, args, SpawnOptions::default()).await
+    }
+
+    /// Starts `command` as `options` say and registers it until it exits.
+    pub async fn spawn_process_with(&self, command: &str, args: &[&str], options: SpawnOptions) -> Result<ProcessHandle> {
+        let (program, args_owned) = (command.to_string(), args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+        let pty = if options.pty { Some(Arc::new(Pty::open()?)) } else { None };
+        let (command_options, command_pty) = (options.clone(), pty.clone());
+        let rebuild: Rebuild = Box::new(move || {
+            let args: Vec<&str> = args_owned.iter().map(String::as_str).collect();
+            let mut command = command_line(&program, &args, &command_options);
+            if let Some(pty) = &command_pty {
+                pty.attach(&mut command)?;
+            }
+            Ok(command)
+        });
+        let mut command_line = command_line(command, args, &options);
+        if let Some(pty) = &pty {
+            pty.attach(&mut command_line)?;
+        }
+        self.spawn(command, command_line, options, Some(rebuild), pty, ExitReport::default())
+    }
+
+    /// Runs `stages` connected like a shell pipeline: each stage's stdout is
+    /// the next one's stdin, passed as the pipe itself so no data goes
+    /// through this process. The first stage reads nothing; the last
+    /// stage's stdout and every stage's stderr are collected by
+    /// `PipelineHandle::wait`. Every stage is registered like any other
+    /// process. If a stage fails to start, those already started are killed.
+    pub async fn pipeline(&self, stages: Vec<CommandSpec>) -> Result<PipelineHandle> {
+        if stages.is_empty() {
+            bail!("a pipeline needs at least one stage");
+        }
+        safe_mode::check(|| format!("spawning pipeline '{}'", stages[0].program))?;
+        let mut handles: Vec<ProcessHandle> = Vec::with_capacity(stages.len());
+        let mut stderr = Vec::with_capacity(stages.len());
+        let mut previous: Option<ChildStdout> = None;
+        for (i, stage) in stages.iter().enumerate() {
+            let options = SpawnOptions::new().with_stdio(StdioConfig { stdin: StdioMode::Null, ..StdioConfig::piped() });
+            let args: Vec<&str> = stage.args.iter().map(String::as_str).collect();
+            let mut command = command_line(&stage.program, &args, &options);
+            if let Some(upstream) = previous.take() {
+                let upstream: Stdio = upstream.try_into().context("Failed to connect pipeline stages")?;
+                command.stdin(upstream);
+            }
+            // `command` and with it this process's copy of the upstream pipe
+            // are dropped here, so a stage sees end of input once the stage
+            // before it exits.
+            let handle = match self.spawn(&stage.program, command, options, None, None, ExitReport::default()) {
+                Ok(handle) => handle,
+                Err(e) => {
+                    for started in &handles {
+                        let _ = started.kill().await;
+                    }
+                    return Err(e.context(format!("pipeline stage {} failed to start", i + 1)));
+                }
+            };
+            stderr.push(tokio::spawn(read_all(handle.take_stderr())));
+            previous = handle.take_stdout();
+            handles.push(handle);
+        }
+        Ok(PipelineHandle { stages: handles, stdout: previous, stderr })
+    }
+
+    /// Registers and supervises the process `command` starts, reporting
+    /// its exit as `report` says.
+    fn spawn(
+        &self,
+        name: &str,
+        mut command: Command,
+        options: SpawnOptions,
+        rebuild: Option<Rebuild>,
+        pty: Option<Arc<Pty>>,
+        report: ExitReport,
+    ) -> Result<ProcessHandle> {
+        safe_mode::check(|| format!("spawning '{}'", name))?;
+        let started = Instant::now();
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(source) if options.priority.refused(&source) => {
+                return Err(PriorityError { program: name.to_string(), priority: options.priority, source }.into());
+            }
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to spawn '{}'", name))),
+        };
+        let pid = child.id().context("spawned process has no pid")?;
+        let pipes = Pipes { stdin: child.stdin.take(), stdout: child.stdout.take(), stderr: child.stderr.take() };
+        let (exited, exit) = watch::channel(None);
+        let (stop, stops) = mpsc::unbounded_channel();
+        let (policy, grouped) = (options.restart, options.new_process_group || options.pty);
+        let tree = Tree::attach(&child, grouped);
+        let handle = ProcessHandle {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            run: Arc::new(Mutex::new(RunState { pid, restarts: 0, supervision: Supervision::Running })),
+            command: name.to_string(),
+            options,
+            exit,
+            stop,
+            pipes: Arc::new(Mutex::new(pipes)),
+            pty,
+            cpu_mark: Arc::default(),
+            tails: SharedTails::default(),
+            exit_waiters: Arc::default(),
+        };
+        self.lock().insert(handle.id, handle.clone());
+        let _ = self.events.send(ProcessEvent::Started { id: handle.id, pid, command: name.to_string() });
+
+        let supervisor = Supervisor {
+            id: handle.id,
+            command: name.to_string(),
+            policy,
+            grouped,
+            tree,
+            rebuild,
+            run: Arc::clone(&handle.run),
+            pipes: Arc::clone(&handle.pipes),
+            events: self.events.clone(),
+        };
+        let (id, processes, events) = (handle.id, Arc::clone(&self.processes), self.events.clone());
+        let (stdio, tails, waiters) = (handle.options.stdio, Arc::clone(&handle.tails), Arc::clone(&handle.exit_waiters));
+        tokio::spawn(async move {
+            let status = supervisor.run(child, stops).await;
+            let info = report.info(status.as_ref().ok().copied(), started.elapsed(), stdio, &tails).await;
+            exit::notify(&waiters, info.clone());
+            if let Some(name) = report.name {
+                let _ = events.send(ProcessEvent::NamedExited { name, info });
+            }
+            // Unregister first, so a waiter woken below no longer finds it.
+            processes.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+            let _ = exited.send(Some(status.map_err(|e| e.to_string())));
+        });
+        Ok(handle)
+    }
+
+    /// `ProcessHandle::shutdown` for every running process at once, each
+    /// with its own `grace`. Returns the handles with their outcomes. Named
+    /// processes are left running; `stop` them by name.
+    pub async fn shutdown_all(&self, grace: Duration) -> Vec<(ProcessHandle, Result<bool>)> {
+        let named = self.named.ids();
+        let handles: Vec<_> = self.list().into_iter().filter(|handle| !named.contains(&handle.id)).collect();
+        let outcomes = futures::future::join_all(handles.iter().map(|handle| handle.shutdown(grace))).await;
+        handles.into_iter().zip(outcomes).collect()
+    }
+
+    /// `ProcessHandle::wait_timeout` for every running process at once,
+    /// named ones included, all within the same `timeout`. Returns the
+    /// handles with their outcomes, oldest first; kill those still running
+    /// to finish shutting down.
+    pub async fn wait_all(&self, timeout: Duration) -> Vec<(ProcessHandle, Result<Option<ExitStatus>>)> {
+        let handles = self.list();
+        let outcomes = futures::future::join_all(handles.iter().map(|handle| handle.wait_timeout(timeout))).await;
+        handles.into_iter().zip(outcomes).collect()
+    }
+
+    /// A running process by `ProcessHandle::id`.
+    pub fn get(&self, id: u64) -> Option<ProcessHandle> {
+        self.lock().get(&id).cloned()
     }
-}
\ No newline at end of file
+
+    /// Every process still running, oldest first.
+    pub fn list(&self) -> Vec<ProcessHandle> {
+        let mut handles: Vec<_> = self.lock().values().cloned().collect();
+        handles.sort_by_key(ProcessHandle::id);
+        handles
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, ProcessHandle>> {
+        self.processes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Spawns `command` and waits for it to exit successfully. If `cancel` fires
+    /// first the process is killed and `CoreError::Cancelled` is returned.
+    #[tracing::instrument(
+        name = "spawn_process",
+        level = "debug",
+        skip(self, args, cancel),
+        fields(command = %command, args_len = args.len(), pid = Empty, exit_code = Empty, duration_ms = Empty)
+    )]
+    pub async fn spawn_process_cancellable(
+        &self,
+        command: &str,
+        args: &[&str],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        let started = Instant::now();
+        let handle = self.spawn_process(command, args).await?;
+        tracing::Span::current().record("pid", handle.pid());
+
+        let status = tokio::select! {
+            status = handle.wait() => status?,
+            _ = cancelled(cancel) => {
+                handle.kill().await?;
+                return Err(CoreError::Cancelled {
+                    operation: format!("process '{}'", command),
+                    partial: Vec::new(),
+                }
+                .into());
+            }
+        };
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let span = tracing::Span::current();
+        span.record("duration_ms", duration_ms);
+        if let Some(code) = status.code() {
+            span.record("exit_code", code);
+        }
+        tracing::debug!(command = %command, exit_code = status.code(), duration_ms, "process exited");
+
+        if !status.success() {
+            bail!("'{}' exited with {}", command, status);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+// Daemons that outlive the process starting them
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use anyhow::{bail, Context, Result};
+
+use super::named::{
+    check_name, kill_pid, log_files_in, log_output, now_ms, stop_pid, write_record, Named, NamedRecord,
+};
+use super::{command_line, BackgroundSpec, ProcessManager, RestartPolicy, StdioMode, DEFAULT_STOP_GRACE, PROCESS_STATE_DIR};
+use crate::safe_mode;
+use crate::system::PathUtils;
+
+/// A process started with `ProcessManager::spawn_detached`, found again
+/// through its pid file by `ProcessManager::adopt`. Before anything is
+/// sent to it, its pid is checked to still run the executable it started
+/// with, so a process that took over the pid is left alone.
+#[derive(Debug, Clone)]
+pub struct DetachedProcess {
+    pid_file: PathBuf,
+    record: NamedRecord,
+}
+
+impl DetachedProcess {
+    pub fn pid(&self) -> u32 {
+        self.record.pid
+    }
+
+    pub fn pid_file(&self) -> &Path {
+        &self.pid_file
+    }
+
+    /// The program followed by its arguments.
+    pub fn command(&self) -> String {
+        self.record.command()
+    }
+
+    /// The executable it started with, where the platform tells.
+    pub fn exe(&self) -> Option<&Path> {
+        self.record.exe.as_deref()
+    }
+
+    /// Where its stdout and stderr go, next to the pid file.
+    pub fn log_files(&self) -> Option<(PathBuf, PathBuf)> {
+        let name = self.pid_file.file_stem()?.to_str()?;
+        Some(log_files_in(self.pid_file.parent()?, name))
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.record.is_alive().await
+    }
+
+    /// Asks the process and whatever it started to exit, kills them after
+    /// `grace`, and removes the pid file. Returns `false` if they had to
+    /// be killed; one already gone counts as a graceful exit.
+    pub async fn stop(&self, grace: Duration) -> Result<bool> {
+        let graceful = !self.is_running().await || stop_pid(self.record.pid, grace).await;
+        self.forget().await?;
+        Ok(graceful)
+    }
+
+    /// Kills the process and whatever it started right away, and removes
+    /// the pid file.
+    pub async fn kill(&self) -> Result<()> {
+        if self.is_running().await {
+            kill_pid(self.record.pid, true).await;
+        }
+        self.forget().await
+    }
+
+    async fn forget(&self) -> Result<()> {
+        match tokio::fs::remove_file(&self.pid_file).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {}", self.pid_file.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ProcessManager {
+    /// Starts `spec` as a daemon that keeps running once this process
+    /// exits, and returns its pid. On Unix it is forked twice into a
+    /// session of its own, with no controlling terminal; on Windows it has
+    /// no console. Either way it leads a process group of its own. Stdin is
+    /// null, and stdout and stderr set to `StdioMode::Null` go to log files.
+    /// The log files and a pid file named after `name` go to the state
+    /// directory, or else to that of `ProcessManager::persistent`. Since it
+    /// has no parent to report to, a `RestartPolicy` does not apply and its
+    /// exit status is lost; `adopt` its pid file to manage it, here or in a
+    /// later run. Fails like `start_named` if `name` is running already,
+    /// and a named process by the same name is the same process.
+    pub async fn spawn_detached(&self, name: &str, spec: BackgroundSpec) -> Result<u32> {
+        check_name(name)?;
+        if spec.options.pty || spec.options.stdio.stdout == StdioMode::Piped || spec.options.stdio.stderr == StdioMode::Piped {
+            bail!("a detached process cannot keep a terminal or pipes to this one");
+        }
+        if !matches!(spec.options.restart, RestartPolicy::Never) {
+            bail!("a detached process cannot be restarted");
+        }
+        safe_mode::check(|| format!("starting '{}'", name))?;
+        let _busy = self.named.busy.lock().await;
+        let dir = match &self.state_dir {
+            Some(dir) => dir.clone(),
+            None => PathUtils::app_data_dir()?.join(PROCESS_STATE_DIR),
+        };
+        let pid_file = dir.join(format!("{}.json", name));
+        if let Ok(current) = adopt_file(&pid_file).await {
+            if !spec.replace {
+                bail!("a process named '{}' is already running (pid {})", name, current.pid());
+            }
+            current.stop(DEFAULT_STOP_GRACE).await?;
+        }
+        tokio::fs::create_dir_all(&dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        // No new group: it gets a whole session instead.
+        let options = spec.options.clone().with_new_process_group(false);
+        let args: Vec<&str> = spec.command.args.iter().map(String::as_str).collect();
+        let mut command = command_line(&spec.command.program, &args, &options);
+        command.stdin(Stdio::null()).kill_on_drop(false);
+        log_output(&mut command, &options, &log_files_in(&dir, name), false)?;
+        let pid = detach(&mut command, &spec.command.program).await?;
+
+        let record = NamedRecord {
+            pid,
+            program: spec.command.program,
+            args: spec.command.args,
+            started_ms: now_ms(),
+            exe: process_exe(pid),
+        };
+        write_record(&pid_file, &record).await?;
+        if self.state_dir.as_ref() == Some(&dir) {
+            self.named.lock().insert(name.to_string(), Named { record, handle: None });
+        }
+        Ok(pid)
+    }
+
+    /// The detached process recorded in `pid_file`, if it still runs the
+    /// executable it was started with. Fails otherwise, leaving the file
+    /// for the caller to remove. A program that replaces itself with
+    /// another, as a wrapper script ending in `exec` does, is not
+    /// recognised either.
+    pub async fn adopt(&self, pid_file: impl AsRef<Path>) -> Result<DetachedProcess> {
+        adopt_file(pid_file.as_ref()).await
+    }
+}
+
+async fn adopt_file(pid_file: &Path) -> Result<DetachedProcess> {
+    let text = tokio::fs::read_to_string(pid_file).await.with_context(|| format!("Failed to read {}", pid_file.display()))?;
+    let record: NamedRecord =
+        serde_json::from_str(&text).with_context(|| format!("Invalid pid file {}", pid_file.display()))?;
+    let process = DetachedProcess { pid_file: pid_file.to_path_buf(), record };
+    if !process.is_running().await {
+        bail!("'{}' (pid {}) is no longer running", process.command(), process.pid());
+    }
+    Ok(process)
+}
+
+/// Spawns `command` as a grandchild in a session of its own: the child
+/// starts the session, forks, reports the pid of its own child through a
+/// pipe and exits, leaving that grandchild to init. The grandchild can
+/// never take a controlling terminal, as it does not lead its session,
+/// and it leads a process group of its own.
+#[cfg(unix)]
+async fn detach(command: &mut tokio::process::Command, program: &str) -> Result<u32> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+
+    let (mut reader, writer) = std::io::pipe().context("Failed to create a pipe")?;
+    let fd = writer.as_raw_fd();
+    // SAFETY: only async-signal-safe calls between fork and exec. The
+    // pipe is closed on exec, so the grandchild does not keep it.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            match libc::fork() {
+                -1 => Err(std::io::Error::last_os_error()),
+                // A group of its own, for stopping what it starts with it.
+                0 if libc::setpgid(0, 0) == -1 => Err(std::io::Error::last_os_error()),
+                0 => Ok(()),
+                pid => {
+                    let bytes = pid.to_ne_bytes();
+                    libc::write(fd, bytes.as_ptr().cast(), bytes.len());
+                    libc::_exit(0)
+                }
+            }
+        });
+    }
+    // Returns once the grandchild has run the program, or failed to.
+    let mut child = command.spawn().with_context(|| format!("Failed to spawn '{}'", program))?;
+    drop(writer);
+    child.wait().await.with_context(|| format!("Failed to detach '{}'", program))?;
+    let mut bytes = [0; std::mem::size_of::<libc::pid_t>()];
+    reader.read_exact(&mut bytes).with_context(|| format!("Failed to detach '{}'", program))?;
+    Ok(libc::pid_t::from_ne_bytes(bytes) as u32)
+}
+
+#[cfg(windows)]
+async fn detach(command: &mut tokio::process::Command, program: &str) -> Result<u32> {
+    use windows_sys::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    // Windows refuses DETACHED_PROCESS together with CREATE_NEW_CONSOLE, and
+    // a daemon has no use for a console window, so it gets a group instead.
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    let child = command.spawn().with_context(|| format!("Failed to spawn '{}'", program))?;
+    child.id().context("spawned process has no pid")
+}
+
+/// The executable `pid` runs, where the platform tells.
+#[cfg(target_os = "linux")]
+pub(super) fn process_exe(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+}
+
+#[cfg(target_os = "macos")]
+pub(super) fn process_exe(pid: u32) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: the buffer is writable for the size passed.
+    let len = unsafe { libc::proc_pidpath(pid as libc::c_int, buffer.as_mut_ptr().cast(), buffer.len() as u32) };
+    (len > 0).then(|| PathBuf::from(std::ffi::OsStr::from_bytes(&buffer[..len as usize])))
+}
+
+#[cfg(windows)]
+pub(super) fn process_exe(pid: u32) -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle returned is checked and then owned here.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return None;
+    }
+    // SAFETY: `process` is open and nothing else owns it.
+    let process = unsafe { OwnedHandle::from_raw_handle(process) };
+    let mut buffer = vec![0u16; 32768];
+    let mut len = buffer.len() as u32;
+    // SAFETY: the handle is open and the buffer holds `len` characters.
+    let found = unsafe {
+        QueryFullProcessImageNameW(process.as_raw_handle() as HANDLE, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len)
+    };
+    (found != 0).then(|| PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len as usize])))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(super) fn process_exe(_pid: u32) -> Option<PathBuf> {
+    None
+}
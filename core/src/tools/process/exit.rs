@@ -0,0 +1,189 @@
+// Telling callers when a process has exited, and what it last wrote
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::oneshot;
+
+use super::{OutputSource, ProcessHandle, StdioConfig, StdioMode};
+
+/// How much of the end of each of stdout and stderr an `ExitInfo` keeps.
+pub const EXIT_TAIL_BYTES: usize = 8 * 1024;
+
+/// How a process ended, with the last of what it wrote for telling why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitInfo {
+    /// `None` if waiting for it failed.
+    pub status: Option<ExitStatus>,
+    /// From when it was first started, restarts included.
+    pub duration: Duration,
+    /// Up to `EXIT_TAIL_BYTES` of the end of its stdout; see
+    /// `ProcessHandle::on_exit` for when there is any.
+    pub stdout_tail: Vec<u8>,
+    pub stderr_tail: Vec<u8>,
+}
+
+impl ExitInfo {
+    /// `None` if a signal ended it or waiting for it failed.
+    pub fn code(&self) -> Option<i32> {
+        self.status.and_then(|status| status.code())
+    }
+
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|status| status.success())
+    }
+
+    pub fn tail_lossy(&self, source: OutputSource) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(match source {
+            OutputSource::Stdout => &self.stdout_tail,
+            OutputSource::Stderr => &self.stderr_tail,
+        })
+    }
+}
+
+/// Longest an exit waits for the line streams to read what the process
+/// wrote last.
+const TAIL_SETTLE: Duration = Duration::from_millis(200);
+
+/// The last `EXIT_TAIL_BYTES` of each stream read through a handle's line
+/// streams.
+#[derive(Debug, Default)]
+pub(super) struct OutputTails {
+    stdout: VecDeque<u8>,
+    stderr: VecDeque<u8>,
+    /// Streams still being read.
+    readers: usize,
+}
+
+/// Counts a stream as read until dropped.
+pub(super) struct TailReader(SharedTails);
+
+impl TailReader {
+    pub(super) fn start(tails: &SharedTails) -> Self {
+        lock(tails).readers += 1;
+        Self(Arc::clone(tails))
+    }
+
+    pub(super) fn push(&self, source: OutputSource, line: &str) {
+        lock(&self.0).push(source, line);
+    }
+}
+
+impl Drop for TailReader {
+    fn drop(&mut self) {
+        lock(&self.0).readers -= 1;
+    }
+}
+
+fn lock(tails: &SharedTails) -> std::sync::MutexGuard<'_, OutputTails> {
+    tails.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl OutputTails {
+    fn push(&mut self, source: OutputSource, line: &str) {
+        let tail = match source {
+            OutputSource::Stdout => &mut self.stdout,
+            OutputSource::Stderr => &mut self.stderr,
+        };
+        tail.extend(line.as_bytes());
+        tail.push_back(b'\n');
+        let excess = tail.len().saturating_sub(EXIT_TAIL_BYTES);
+        tail.drain(..excess);
+    }
+}
+
+pub(super) type SharedTails = Arc<Mutex<OutputTails>>;
+
+/// Callers waiting for `ExitInfo`, and the info once there is some.
+#[derive(Debug, Default)]
+pub(super) struct ExitWaiters {
+    info: Option<ExitInfo>,
+    waiting: Vec<oneshot::Sender<ExitInfo>>,
+}
+
+/// How the exit of a process is put together: the log files a named
+/// process writes to stand in for the tails of its streams.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ExitReport {
+    pub(super) name: Option<String>,
+    pub(super) logs: Option<(PathBuf, PathBuf)>,
+}
+
+impl ExitReport {
+    pub(super) fn named(name: &str, logs: Option<(PathBuf, PathBuf)>) -> Self {
+        Self { name: Some(name.to_string()), logs }
+    }
+
+    /// The `ExitInfo` of a process that ran for `duration`.
+    pub(super) async fn info(&self, status: Option<ExitStatus>, duration: Duration, stdio: StdioConfig, tails: &SharedTails) -> ExitInfo {
+        // The pipes only end once the readers get to it, which a child
+        // left holding them may put off for good.
+        let settle = tokio::time::Instant::now() + TAIL_SETTLE;
+        while lock(tails).readers > 0 && tokio::time::Instant::now() < settle {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let (stdout_tail, stderr_tail) = {
+            let tails = lock(tails);
+            (Vec::from(tails.stdout.clone()), Vec::from(tails.stderr.clone()))
+        };
+        let mut info = ExitInfo { status, duration, stdout_tail, stderr_tail };
+        if let Some((stdout, stderr)) = &self.logs {
+            // Only streams set to `Null` go to the log files.
+            if stdio.stdout == StdioMode::Null {
+                info.stdout_tail = file_tail(stdout).await;
+            }
+            if stdio.stderr == StdioMode::Null {
+                info.stderr_tail = file_tail(stderr).await;
+            }
+        }
+        info
+    }
+}
+
+/// The last `EXIT_TAIL_BYTES` of the file at `path`; nothing if it cannot
+/// be read.
+async fn file_tail(path: &Path) -> Vec<u8> {
+    let mut tail = Vec::new();
+    if let Ok(mut file) = tokio::fs::File::open(path).await {
+        let start = file.metadata().await.map(|meta| meta.len().saturating_sub(EXIT_TAIL_BYTES as u64)).unwrap_or(0);
+        if file.seek(SeekFrom::Start(start)).await.is_ok() {
+            let _ = file.take(EXIT_TAIL_BYTES as u64).read_to_end(&mut tail).await;
+        }
+    }
+    tail
+}
+
+/// Hands `info` to everyone waiting, and to whoever asks from now on.
+pub(super) fn notify(waiters: &Mutex<ExitWaiters>, info: ExitInfo) {
+    let mut waiters = waiters.lock().unwrap_or_else(|e| e.into_inner());
+    for waiter in waiters.waiting.drain(..) {
+        // A dropped receiver no longer cares.
+        let _ = waiter.send(info.clone());
+    }
+    waiters.info = Some(info);
+}
+
+impl ProcessHandle {
+    /// Receives `ExitInfo` once the process has exited for good, or right
+    /// away if it has already; no need to poll `try_wait`. It is sent
+    /// before `wait` returns. The tails hold what was read through
+    /// `stdout_lines`, `stderr_lines` or `events` for as long as those
+    /// streams were kept, and for a named process the end of its log
+    /// files; output read through a pipe taken with `take_stdout` or
+    /// `take_stderr`, or not piped at all, is not seen. The sender is
+    /// dropped without sending only if the process is lost track of.
+    pub fn on_exit(&self) -> oneshot::Receiver<ExitInfo> {
+        let (tx, rx) = oneshot::channel();
+        let mut waiters = self.exit_waiters.lock().unwrap_or_else(|e| e.into_inner());
+        match &waiters.info {
+            Some(info) => {
+                let _ = tx.send(info.clone());
+            }
+            None => waiters.waiting.push(tx),
+        }
+        rx
+    }
+}
@@ -0,0 +1,498 @@
+// Long-running processes kept under a name
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::detached::process_exe;
+use super::exit::ExitReport;
+use super::output::{follow_files, StillRunning};
+use super::{
+    command_line, terminate_pid, CommandSpec, OutputLine, ProcessHandle, ProcessManager, ProcessUsage, Rebuild, SpawnOptions, StdioConfig,
+    StdioMode,
+};
+use crate::file_processor::FileWriter;
+use crate::safe_mode;
+use crate::system::PathUtils;
+
+/// Directory under `PathUtils::app_data_dir` where `ProcessManager::persistent`
+/// keeps the pids of named processes.
+pub const PROCESS_STATE_DIR: &str = "processes";
+
+/// What `ProcessManager::stop` gives a process to exit by default.
+pub const DEFAULT_STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// How often a process found through its pid file is checked while it is
+/// being stopped.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A process `ProcessManager::start_named` keeps running under a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackgroundSpec {
+    pub command: CommandSpec,
+    /// Defaults to null stdio in a process group of its own, so stopping
+    /// the process also stops whatever it started.
+    pub options: SpawnOptions,
+    /// Stop a running process of the same name first instead of failing.
+    pub replace: bool,
+}
+
+impl BackgroundSpec {
+    pub fn new(command: CommandSpec) -> Self {
+        let options = SpawnOptions::new().with_stdio(StdioConfig::null()).with_new_process_group(true);
+        Self { command, options, replace: false }
+    }
+
+    pub fn with_options(mut self, options: SpawnOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+/// A named process, as `ProcessManager::status` reports it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessStatus {
+    pub name: String,
+    pub pid: u32,
+    /// The program followed by its arguments.
+    pub command: String,
+    pub running: bool,
+    /// Time since it started; zero once it has exited.
+    pub uptime: Duration,
+    /// Its exit code, once it has exited, if this manager saw it exit and
+    /// no signal ended it.
+    pub last_exit: Option<i32>,
+    /// How often its `RestartPolicy` has restarted it, if this manager
+    /// started it.
+    pub restarts: u32,
+    /// What it uses, while it runs and where that can be read. CPU use is
+    /// averaged as `ProcessHandle::usage` does for a process this manager
+    /// started, and over its whole run for one found through its pid file.
+    pub usage: Option<ProcessUsage>,
+}
+
+/// What a pid file holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct NamedRecord {
+    pub(super) pid: u32,
+    pub(super) program: String,
+    pub(super) args: Vec<String>,
+    /// Milliseconds since the Unix epoch.
+    pub(super) started_ms: u64,
+    /// The executable the process ran when it started, if known, so a
+    /// process that later got the same pid is not mistaken for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exe: Option<PathBuf>,
+}
+
+impl NamedRecord {
+    /// Whether the process is still running what it started with.
+    pub(super) async fn is_alive(&self) -> bool {
+        pid_alive(self.pid).await && self.exe.as_ref().is_none_or(|exe| process_exe(self.pid).as_ref() == Some(exe))
+    }
+
+    pub(super) fn command(&self) -> String {
+        std::iter::once(&self.program).chain(&self.args).cloned().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// A named process and, if this manager started it, its handle.
+#[derive(Debug, Clone)]
+pub(super) struct Named {
+    pub(super) record: NamedRecord,
+    pub(super) handle: Option<ProcessHandle>,
+}
+
+impl Named {
+    async fn status(&self, name: &str) -> ProcessStatus {
+        let (running, last_exit) = match &self.handle {
+            Some(handle) => match handle.try_wait() {
+                Ok(None) => (true, None),
+                Ok(Some(status)) => (false, status.code()),
+                Err(_) => (false, None),
+            },
+            None => (self.record.is_alive().await, None),
+        };
+        let started = UNIX_EPOCH + Duration::from_millis(self.record.started_ms);
+        let uptime = if running { SystemTime::now().duration_since(started).unwrap_or_default() } else { Duration::ZERO };
+        let command = self.record.command();
+        // A restart changes the pid; the record keeps the first one.
+        let (pid, restarts) = match &self.handle {
+            Some(handle) => (handle.pid(), handle.restarts()),
+            None => (self.record.pid, 0),
+        };
+        let usage = match &self.handle {
+            _ if !running => None,
+            Some(handle) => handle.usage().ok(),
+            None => ProcessUsage::of_pid(pid).ok(),
+        };
+        ProcessStatus { name: name.to_string(), pid, command, running, uptime, last_exit, restarts, usage }
+    }
+
+    async fn is_running(&self) -> bool {
+        match &self.handle {
+            Some(handle) => matches!(handle.try_wait(), Ok(None)),
+            None => self.record.is_alive().await,
+        }
+    }
+}
+
+/// The named processes a manager knows of.
+#[derive(Debug, Default)]
+pub(super) struct NamedProcesses {
+    entries: Mutex<HashMap<String, Named>>,
+    /// Held through `start_named` and `stop`, so two calls for one name
+    /// cannot interleave.
+    pub(super) busy: tokio::sync::Mutex<()>,
+}
+
+impl NamedProcesses {
+    pub(super) fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Named>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Ids of the processes started under a name.
+    pub(super) fn ids(&self) -> Vec<u64> {
+        self.lock().values().filter_map(|named| named.handle.as_ref().map(ProcessHandle::id)).collect()
+    }
+}
+
+impl ProcessManager {
+    /// A manager keeping pid files in `PROCESS_STATE_DIR` under
+    /// `PathUtils::app_data_dir`.
+    pub fn persistent() -> Result<Self> {
+        Ok(Self::new().with_state_dir(PathUtils::app_data_dir()?.join(PROCESS_STATE_DIR)))
+    }
+
+    /// Keeps a pid file per named process in `dir`, so that another manager,
+    /// such as one in a later run of the CLI, can find the process again.
+    /// Pids are not checked for reuse once their process has gone.
+    pub fn with_state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(dir.into());
+        self
+    }
+
+    /// Starts `spec` under `name`, which may hold letters, digits, `-`, `_`
+    /// and `.`. Fails if a process of that name is running, unless
+    /// `spec.replace` says to stop it first. The process is not killed when
+    /// the manager or this process goes away; `stop` it by name. With a
+    /// state directory, stdout and stderr set to `StdioMode::Null` go to
+    /// log files there instead, read back by `logs`.
+    pub async fn start_named(&self, name: &str, spec: BackgroundSpec) -> Result<()> {
+        check_name(name)?;
+        // Its terminal would close with this process, hanging it up.
+        if spec.options.pty {
+            bail!("named processes cannot run on a pseudo-terminal");
+        }
+        // Checked before its log files are created.
+        safe_mode::check(|| format!("starting '{}'", name))?;
+        let _busy = self.named.busy.lock().await;
+        if let Some(current) = self.find_named(name).await? {
+            if current.is_running().await {
+                if !spec.replace {
+                    bail!("a process named '{}' is already running (pid {})", name, current.record.pid);
+                }
+                self.stop_named(name, current, DEFAULT_STOP_GRACE).await?;
+            }
+        }
+
+        let logs = self.log_files(name);
+        if let Some(dir) = &self.state_dir {
+            tokio::fs::create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let args: Vec<&str> = spec.command.args.iter().map(String::as_str).collect();
+        let mut command = command_line(&spec.command.program, &args, &spec.options);
+        command.kill_on_drop(false);
+        if let Some(logs) = &logs {
+            log_output(&mut command, &spec.options, logs, false)?;
+        }
+        let (program, args_owned, options) = (spec.command.program.clone(), spec.command.args.clone(), spec.options.clone());
+        let rebuild: Rebuild = Box::new(move || {
+            let args: Vec<&str> = args_owned.iter().map(String::as_str).collect();
+            let mut command = command_line(&program, &args, &options);
+            command.kill_on_drop(false);
+            if let Some(logs) = &logs {
+                // A restart without its log still beats no restart.
+                if let Err(e) = log_output(&mut command, &options, logs, true) {
+                    tracing::warn!(program = %program, "could not reopen process log: {:#}", e);
+                }
+            }
+            Ok(command)
+        });
+        let report = ExitReport::named(name, self.log_files(name));
+        let handle = self.spawn(&spec.command.program, command, spec.options, Some(rebuild), None, report)?;
+        let record = NamedRecord {
+            pid: handle.pid(),
+            program: spec.command.program,
+            args: spec.command.args,
+            started_ms: now_ms(),
+            exe: None,
+        };
+        if let Some(path) = self.pid_file(name) {
+            // The process is running either way; only finding it later suffers.
+            if let Err(e) = write_record(&path, &record).await {
+                tracing::warn!(name, "could not record named process: {:#}", e);
+            }
+        }
+        self.named.lock().insert(name.to_string(), Named { record, handle: Some(handle) });
+        Ok(())
+    }
+
+    /// How the process named `name` is doing. One that has exited is
+    /// reported until it is `stop`ped.
+    pub async fn status(&self, name: &str) -> Result<ProcessStatus> {
+        match self.find_named(name).await? {
+            Some(named) => Ok(named.status(name).await),
+            None => bail!("no process named '{}'", name),
+        }
+    }
+
+    /// Stops the process named `name` as `ProcessHandle::shutdown` does and
+    /// forgets it. Returns `false` if it had to be killed.
+    pub async fn stop(&self, name: &str, grace: Duration) -> Result<bool> {
+        let _busy = self.named.busy.lock().await;
+        match self.find_named(name).await? {
+            Some(named) => self.stop_named(name, named, grace).await,
+            None => bail!("no process named '{}'", name),
+        }
+    }
+
+    /// Every named process, including those only known from the state
+    /// directory, sorted by name.
+    pub async fn list_named(&self) -> Result<Vec<ProcessStatus>> {
+        let mut names: Vec<String> = self.named.lock().keys().cloned().collect();
+        if let Some(dir) = &self.state_dir {
+            names.extend(recorded_names(dir).await?);
+        }
+        names.sort();
+        names.dedup();
+        let mut statuses = Vec::with_capacity(names.len());
+        for name in names {
+            if let Some(named) = self.find_named(&name).await? {
+                statuses.push(named.status(&name).await);
+            }
+        }
+        Ok(statuses)
+    }
+
+    /// The output of the process named `name`: its log files if it has
+    /// them, or else the pipes of a process this manager started with
+    /// piped output, read as `ProcessHandle::events` reads them. With
+    /// `follow`, log files are read until the process exits rather than to
+    /// their current end. The logs of a stopped process stay readable until
+    /// it is started again.
+    pub async fn logs(&self, name: &str, follow: bool) -> Result<BoxStream<'static, Result<OutputLine>>> {
+        check_name(name)?;
+        if let Some((stdout, stderr)) = self.log_files(name).filter(|(stdout, stderr)| stdout.exists() || stderr.exists()) {
+            let running = follow.then(|| {
+                let (manager, name) = (self.clone(), name.to_string());
+                Arc::new(move || {
+                    let (manager, name) = (manager.clone(), name.clone());
+                    async move { matches!(manager.find_named(&name).await, Ok(Some(named)) if named.is_running().await) }.boxed()
+                }) as StillRunning
+            });
+            return follow_files(&stdout, &stderr, running).await;
+        }
+        match self.find_named(name).await? {
+            Some(Named { handle: Some(handle), .. }) => Ok(handle.events().boxed()),
+            Some(_) => bail!("the output of '{}' was not kept", name),
+            None => bail!("no process named '{}'", name),
+        }
+    }
+
+    async fn stop_named(&self, name: &str, named: Named, grace: Duration) -> Result<bool> {
+        let graceful = match &named.handle {
+            Some(handle) => handle.shutdown(grace).await?,
+            // Its pid may have gone to another process since.
+            None if !named.record.is_alive().await => true,
+            None => stop_pid(named.record.pid, grace).await,
+        };
+        self.named.lock().remove(name);
+        if let Some(path) = self.pid_file(name) {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(graceful)
+    }
+
+    /// The process known as `name`, from memory or else its pid file.
+    async fn find_named(&self, name: &str) -> Result<Option<Named>> {
+        if let Some(named) = self.named.lock().get(name) {
+            return Ok(Some(named.clone()));
+        }
+        let Some(path) = self.pid_file(name) else { return Ok(None) };
+        let text = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let record = serde_json::from_str(&text).with_context(|| format!("Invalid pid file {}", path.display()))?;
+        let named = Named { record, handle: None };
+        self.named.lock().entry(name.to_string()).or_insert_with(|| named.clone());
+        Ok(Some(named))
+    }
+
+    pub(super) fn pid_file(&self, name: &str) -> Option<PathBuf> {
+        self.state_dir.as_ref().map(|dir| dir.join(format!("{}.json", name)))
+    }
+
+    /// Where the stdout and stderr of `name` are logged.
+    fn log_files(&self, name: &str) -> Option<(PathBuf, PathBuf)> {
+        Some(log_files_in(self.state_dir.as_ref()?, name))
+    }
+}
+
+pub(super) fn log_files_in(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+    (dir.join(format!("{}.stdout.log", name)), dir.join(format!("{}.stderr.log", name)))
+}
+
+pub(super) fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Sends whichever of stdout and stderr `options` discards to its file in
+/// `logs`, starting the file over unless `append`.
+pub(super) fn log_output(command: &mut Command, options: &SpawnOptions, logs: &(PathBuf, PathBuf), append: bool) -> Result<()> {
+    let open = |path: &Path| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(append)
+            .write(true)
+            .truncate(!append)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))
+    };
+    if options.stdio.stdout == StdioMode::Null {
+        command.stdout(open(&logs.0)?);
+    }
+    if options.stdio.stderr == StdioMode::Null {
+        command.stderr(open(&logs.1)?);
+    }
+    Ok(())
+}
+
+pub(super) fn check_name(name: &str) -> Result<()> {
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if name.is_empty() || !valid || name.starts_with('.') {
+        bail!("invalid process name '{}': use letters, digits, '-', '_' and '.'", name);
+    }
+    Ok(())
+}
+
+pub(super) async fn write_record(path: &Path, record: &NamedRecord) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    FileWriter::write_file(path, &serde_json::to_string(record)?).await
+}
+
+/// Names with a pid file in `dir`.
+async fn recorded_names(dir: &Path) -> Result<Vec<String>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        if let Some(name) = file_name.to_str().and_then(|file| file.strip_suffix(".json")) {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Stops a process this manager did not start: asks it to exit, polls for
+/// `grace`, then kills it. Returns `false` if it had to be killed.
+pub(super) async fn stop_pid(pid: u32, grace: Duration) -> bool {
+    if !pid_alive(pid).await {
+        return true;
+    }
+    let tree = leads_group(pid);
+    terminate_pid(pid, tree).await;
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+        if !pid_alive(pid).await {
+            return true;
+        }
+    }
+    kill_pid(pid, tree).await;
+    false
+}
+
+#[cfg(unix)]
+async fn pid_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists.
+    let found = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    // EPERM: it exists but belongs to someone else.
+    (found || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)) && !is_zombie(pid)
+}
+
+/// Whether `pid` has exited but not been waited for, as happens to
+/// orphans where nothing reaps them, such as in some containers.
+#[cfg(target_os = "linux")]
+fn is_zombie(pid: u32) -> bool {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+    stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z'))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_zombie(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(windows)]
+async fn pid_alive(pid: u32) -> bool {
+    let filter = format!("PID eq {}", pid);
+    match tokio::process::Command::new("tasklist").args(["/FI", &filter, "/NH"]).output().await {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).split_whitespace().any(|word| word == pid.to_string()),
+        Err(_) => false,
+    }
+}
+
+/// Whether `pid` leads its own process group, as processes started with
+/// `SpawnOptions::new_process_group` do.
+#[cfg(unix)]
+fn leads_group(pid: u32) -> bool {
+    // SAFETY: plain syscall reading another process's group id.
+    unsafe { libc::getpgid(pid as libc::pid_t) == pid as libc::pid_t }
+}
+
+#[cfg(windows)]
+fn leads_group(_pid: u32) -> bool {
+    true
+}
+
+pub(super) async fn kill_pid(pid: u32, tree: bool) {
+    #[cfg(unix)]
+    // SAFETY: plain syscalls that only send a signal to the process found above.
+    unsafe {
+        if tree {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        } else {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    #[cfg(windows)]
+    {
+        let pid = pid.to_string();
+        let args = if tree { vec!["/T", "/F", "/PID", &pid] } else { vec!["/F", "/PID", &pid] };
+        let _ = tokio::process::Command::new("taskkill").args(args).output().await;
+    }
+}
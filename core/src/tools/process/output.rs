@@ -0,0 +1,218 @@
+// Reading a process's output line by line while it runs
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use super::exit::TailReader;
+use super::ProcessHandle;
+
+/// How often a followed log file is checked for more output.
+pub const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Which pipe a line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSource {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for OutputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        })
+    }
+}
+
+/// One line of a process's output, lossily decoded and without its line
+/// terminator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputLine {
+    pub source: OutputSource,
+    pub line: String,
+    /// When the line was read, which is close to when it was written for
+    /// a pipe but not for a log file read later.
+    pub time: SystemTime,
+}
+
+/// Whether the process writing a followed file is still running.
+pub(super) type StillRunning = Arc<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>;
+
+impl ProcessHandle {
+    /// The lines of the current run's piped stdout as they are written,
+    /// ending when the process closes it. Lines are read by a task of their
+    /// own and held until consumed, so a full pipe never blocks the process.
+    /// Takes stdout like `take_stdout`; the stream yields an error if it is
+    /// not piped or was taken before.
+    pub fn stdout_lines(&self) -> impl Stream<Item = Result<String>> + Send + 'static {
+        let pipe = self.take_stdout().ok_or_else(|| self.not_piped("stdout"));
+        self.lines_of(pipe, OutputSource::Stdout)
+    }
+
+    /// `stdout_lines` for stderr.
+    pub fn stderr_lines(&self) -> impl Stream<Item = Result<String>> + Send + 'static {
+        let pipe = self.take_stderr().ok_or_else(|| self.not_piped("stderr"));
+        self.lines_of(pipe, OutputSource::Stderr)
+    }
+
+    /// Lines from stdout and stderr together, in the order they were read,
+    /// each tagged with its pipe and time. Both pipes are read
+    /// independently, so neither can block the other. Takes whichever of
+    /// them is piped; yields an error if neither is.
+    pub fn events(&self) -> impl Stream<Item = Result<OutputLine>> + Send + 'static {
+        let (stdout, stderr) = (self.take_stdout(), self.take_stderr());
+        if stdout.is_none() && stderr.is_none() {
+            return stream::once(std::future::ready(Err(self.not_piped("stdout or stderr")))).boxed();
+        }
+        let (tx, lines) = mpsc::unbounded_channel();
+        if let Some(stdout) = stdout {
+            let tail = TailReader::start(&self.tails);
+            tokio::spawn(tag_lines(stdout, OutputSource::Stdout, None, Some(tail), tx.clone()));
+        }
+        if let Some(stderr) = stderr {
+            let tail = TailReader::start(&self.tails);
+            tokio::spawn(tag_lines(stderr, OutputSource::Stderr, None, Some(tail), tx));
+        }
+        receiver_stream(lines).boxed()
+    }
+
+    fn lines_of<R>(&self, pipe: Result<R>, source: OutputSource) -> BoxStream<'static, Result<String>>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let pipe = match pipe {
+            Ok(pipe) => pipe,
+            Err(e) => return stream::once(std::future::ready(Err(e))).boxed(),
+        };
+        let (tx, lines) = mpsc::unbounded_channel();
+        let (command, tail) = (self.command.clone(), TailReader::start(&self.tails));
+        tokio::spawn(async move {
+            let read = read_lines(pipe, None, |line| {
+                tail.push(source, &line);
+                tx.send(Ok(line)).is_ok()
+            })
+            .await;
+            if let Err(e) = read {
+                let _ = tx.send(Err(anyhow::Error::new(e).context(format!("Failed to read the output of '{}'", command))));
+            }
+        });
+        receiver_stream(lines).boxed()
+    }
+
+    fn not_piped(&self, pipe: &str) -> anyhow::Error {
+        anyhow!("{} of '{}' is not piped or was already taken", pipe, self.command)
+    }
+}
+
+/// The lines of the log files `stdout` and `stderr`, tagged as
+/// `ProcessHandle::events` tags them. A missing file is skipped, but not
+/// both. With `follow`, waits for more at the end of each until the
+/// process is no longer running.
+pub(super) async fn follow_files(
+    stdout: &Path,
+    stderr: &Path,
+    follow: Option<StillRunning>,
+) -> Result<BoxStream<'static, Result<OutputLine>>> {
+    let (tx, lines) = mpsc::unbounded_channel();
+    let mut opened = 0;
+    for (path, source) in [(stdout, OutputSource::Stdout), (stderr, OutputSource::Stderr)] {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        tokio::spawn(tag_lines(file, source, follow.clone(), None, tx.clone()));
+        opened += 1;
+    }
+    if opened == 0 {
+        return Err(anyhow!("neither {} nor {} exists", stdout.display(), stderr.display()));
+    }
+    Ok(receiver_stream(lines).boxed())
+}
+
+/// Sends each line of `reader` to `tx` as an `OutputLine` from `source`,
+/// keeping its `tail` too if given.
+async fn tag_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    source: OutputSource,
+    follow: Option<StillRunning>,
+    tail: Option<TailReader>,
+    tx: mpsc::UnboundedSender<Result<OutputLine>>,
+) {
+    // Nobody left to read it means there is no point waiting for more.
+    let follow = follow.map(|running| {
+        let closed = tx.clone();
+        Arc::new(move || if closed.is_closed() { Box::pin(std::future::ready(false)) } else { running() }) as StillRunning
+    });
+    let send = |line: String| {
+        if let Some(tail) = &tail {
+            tail.push(source, &line);
+        }
+        tx.send(Ok(OutputLine { source, line, time: SystemTime::now() })).is_ok()
+    };
+    if let Err(e) = read_lines(reader, follow, send).await {
+        let _ = tx.send(Err(anyhow::Error::new(e).context(format!("Failed to read {}", source))));
+    }
+}
+
+/// Calls `send` with each line of `reader` until it ends or `send` returns
+/// `false`. With `follow`, the end of `reader` is waited out until the
+/// process stops running, and whatever it wrote before is read then.
+async fn read_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    follow: Option<StillRunning>,
+    mut send: impl FnMut(String) -> bool,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        reader.read_until(b'\n', &mut line).await?;
+        if line.ends_with(b"\n") {
+            if !send(decode(&line)) {
+                return Ok(());
+            }
+            line.clear();
+            continue;
+        }
+        // At the end for now; a partial line waits for the rest.
+        match &follow {
+            Some(running) if running().await => tokio::time::sleep(FOLLOW_POLL_INTERVAL).await,
+            Some(_) => {
+                reader.read_to_end(&mut line).await?;
+                for rest in line.split_inclusive(|&byte| byte == b'\n') {
+                    if !send(decode(rest)) {
+                        break;
+                    }
+                }
+                return Ok(());
+            }
+            None => {
+                if !line.is_empty() {
+                    send(decode(&line));
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// `line` as text without its `\n` or `\r\n`.
+fn decode(line: &[u8]) -> String {
+    let text = String::from_utf8_lossy(line);
+    let text = text.strip_suffix('\n').map(|t| t.strip_suffix('\r').unwrap_or(t)).unwrap_or(&text);
+    text.to_string()
+}
+
+pub(super) fn receiver_stream<T: Send + 'static>(receiver: mpsc::UnboundedReceiver<T>) -> impl Stream<Item = T> + Send + 'static {
+    stream::unfold(receiver, |mut receiver| async move { receiver.recv().await.map(|item| (item, receiver)) })
+}
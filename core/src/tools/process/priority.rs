@@ -0,0 +1,108 @@
+// Scheduling priority of spawned processes (nice values on Unix, priority classes on Windows)
+use std::fmt;
+use std::io;
+use thiserror::Error;
+use tokio::process::Command;
+
+/// How much CPU time a spawned process gets when others want it too, so
+/// heavy background work does not slow down what the user is waiting on.
+/// Whatever the process starts inherits it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// As this process runs; nothing is changed.
+    #[default]
+    Normal,
+    /// Nice 10 on Unix, `BELOW_NORMAL_PRIORITY_CLASS` on Windows.
+    BelowNormal,
+    /// Runs only when the CPU is otherwise idle: nice 19 on Unix,
+    /// `IDLE_PRIORITY_CLASS` on Windows.
+    Idle,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Normal => "normal",
+            Self::BelowNormal => "below normal",
+            Self::Idle => "idle",
+        })
+    }
+}
+
+/// The system would not start a process at the priority asked for, as
+/// when this process already runs at a lower one and raising it takes
+/// privileges.
+#[derive(Debug, Error)]
+#[error("cannot run '{program}' at {priority} priority: {source}")]
+pub struct PriorityError {
+    pub program: String,
+    pub priority: Priority,
+    #[source]
+    pub source: io::Error,
+}
+
+impl Priority {
+    /// The nice value asked for; `None` leaves the inherited one.
+    #[cfg(unix)]
+    fn nice(self) -> Option<libc::c_int> {
+        match self {
+            Self::Normal => None,
+            Self::BelowNormal => Some(10),
+            Self::Idle => Some(19),
+        }
+    }
+
+    /// Arranges for `command` to start at this priority. `grouped` says
+    /// whether it was given a process group of its own, whose creation
+    /// flag Windows takes together with the priority class.
+    pub(crate) fn apply(self, command: &mut Command, grouped: bool) {
+        #[cfg(unix)]
+        {
+            let _ = grouped;
+            let Some(nice) = self.nice() else { return };
+            // SAFETY: the hook makes one syscall on a value computed above;
+            // it does not allocate or take locks, as required between fork
+            // and exec.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::System::Threading::{BELOW_NORMAL_PRIORITY_CLASS, CREATE_NEW_PROCESS_GROUP, IDLE_PRIORITY_CLASS};
+            let class = match self {
+                Self::Normal => return,
+                Self::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+                Self::Idle => IDLE_PRIORITY_CLASS,
+            };
+            // The flags are set as a whole, so the group's is kept here.
+            command.creation_flags(if grouped { class | CREATE_NEW_PROCESS_GROUP } else { class });
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = (command, grouped);
+    }
+
+    /// Whether a process failing to start with `error` was the system
+    /// refusing this priority rather than the program failing to run: a
+    /// permission error when starting it meant raising the priority this
+    /// process runs at. Lowering it is always allowed.
+    pub(crate) fn refused(self, error: &io::Error) -> bool {
+        #[cfg(unix)]
+        {
+            let Some(nice) = self.nice() else { return false };
+            // SAFETY: plain syscall about this process.
+            let current = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            error.kind() == io::ErrorKind::PermissionDenied && current > nice
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = error;
+            false
+        }
+    }
+}
@@ -0,0 +1,173 @@
+// Running a process on a pseudo-terminal
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use futures::stream;
+use futures::{Stream, StreamExt};
+use portable_pty::{MasterPty, PtySize};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use super::output::receiver_stream;
+use super::ProcessHandle;
+
+/// Bytes read from the terminal at a time.
+const PTY_READ_CHUNK: usize = 4096;
+
+/// The controlling end of the terminal a process was started on with
+/// `SpawnOptions::pty`, or a tool with `ToolExecutor::execute_tool_pty`.
+/// Restarts reopen the same terminal.
+pub(crate) struct Pty {
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    reader: Mutex<Option<Box<dyn Read + Send>>>,
+    #[cfg(unix)]
+    tty: std::path::PathBuf,
+}
+
+impl fmt::Debug for Pty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Pty");
+        #[cfg(unix)]
+        debug.field("tty", &self.tty);
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl Pty {
+    /// A new terminal of the default 24 by 80 size.
+    pub(super) fn open() -> Result<Self> {
+        Self::open_sized(PtySize::default())
+    }
+
+    pub(crate) fn open_sized(size: PtySize) -> Result<Self> {
+        let pair = portable_pty::native_pty_system().openpty(size).context("Failed to open a pseudo-terminal")?;
+        let master = pair.master;
+        #[cfg(unix)]
+        let tty = master.tty_name().context("the pseudo-terminal has no device to open")?;
+        let writer = master.take_writer()?;
+        let reader = master.try_clone_reader()?;
+        Ok(Self {
+            master: Mutex::new(master),
+            writer: Arc::new(Mutex::new(writer)),
+            reader: Mutex::new(Some(reader)),
+            #[cfg(unix)]
+            tty,
+        })
+    }
+
+    /// Sets `command` up to run on this terminal: all three standard
+    /// streams go to it, and the process leads a new session whose
+    /// controlling terminal it is, as under a terminal emulator. That
+    /// session is also the process group `ProcessHandle::kill_tree` reaches.
+    #[cfg(unix)]
+    pub(crate) fn attach(&self, command: &mut Command) -> Result<()> {
+        let open = || {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.tty)
+                .with_context(|| format!("Failed to open {}", self.tty.display()))
+        };
+        command.stdin(open()?).stdout(open()?).stderr(open()?);
+        // SAFETY: only async-signal-safe calls between fork and exec.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// The reading end of the terminal; `None` once taken.
+    pub(crate) fn take_reader(&self) -> Option<Box<dyn Read + Send>> {
+        self.reader.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Windows runs programs on a pseudo-console only when it starts them
+    /// itself, which does not fit how processes are supervised here.
+    #[cfg(windows)]
+    pub(crate) fn attach(&self, _command: &mut Command) -> Result<()> {
+        Err(anyhow!("processes cannot be started on a pseudo-terminal on Windows yet"))
+    }
+}
+
+impl ProcessHandle {
+    /// Types `input` into the terminal of a process started with
+    /// `SpawnOptions::pty`, as if at a keyboard: end a line with `\n` to
+    /// send it, and `\x03` is Ctrl-C.
+    pub async fn write_input(&self, input: &str) -> Result<()> {
+        let writer = Arc::clone(&self.pty()?.writer);
+        let input = input.as_bytes().to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer.write_all(&input)?;
+            writer.flush()
+        })
+        .await?
+        .with_context(|| format!("Failed to write to the terminal of '{}'", self.command))
+    }
+
+    /// Everything written to the terminal of a process started with
+    /// `SpawnOptions::pty`, stdout and stderr alike, in chunks as it is
+    /// read. Escape sequences are passed through as the program wrote them.
+    /// The stream ends once no process has the terminal open, so at the
+    /// first exit even under a `RestartPolicy`. Like `take_stdout`, it can
+    /// only be taken once; afterwards the stream yields an error.
+    pub fn pty_output(&self) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let reader = self.pty().and_then(|pty| {
+            pty.take_reader().ok_or_else(|| anyhow!("the terminal output of '{}' was already taken", self.command))
+        });
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => return stream::once(std::future::ready(Err(e))).boxed(),
+        };
+        receiver_stream(read_chunks(reader)).boxed()
+    }
+
+    /// Changes the size of the process's terminal, which tells the program
+    /// it changed (`SIGWINCH` on Unix).
+    pub fn resize(&self, rows: u16, cols: u16) -> Result<()> {
+        let pty = self.pty()?;
+        let master = pty.master.lock().unwrap_or_else(|e| e.into_inner());
+        master.resize(PtySize { rows, cols, ..PtySize::default() })
+    }
+
+    fn pty(&self) -> Result<&Pty> {
+        self.pty.as_deref().ok_or_else(|| anyhow!("'{}' was not started on a pseudo-terminal", self.command))
+    }
+}
+
+/// What `reader` reads, in chunks as they come, until no process has the
+/// terminal open.
+pub(crate) fn read_chunks(mut reader: Box<dyn Read + Send>) -> mpsc::UnboundedReceiver<Result<Bytes>> {
+    let (tx, chunks) = mpsc::unbounded_channel();
+    // Reads block, so they get a thread of their own.
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; PTY_READ_CHUNK];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(read) => {
+                    if tx.send(Ok(Bytes::copy_from_slice(&buffer[..read]))).is_err() {
+                        return;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                // Linux reports the last process closing the terminal as EIO.
+                #[cfg(unix)]
+                Err(e) if e.raw_os_error() == Some(libc::EIO) => return,
+                Err(e) => {
+                    let _ = tx.send(Err(anyhow::Error::new(e).context("Failed to read from the terminal")));
+                    return;
+                }
+            }
+        }
+    });
+    chunks
+}
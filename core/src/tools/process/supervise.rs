@@ -0,0 +1,214 @@
+// Restarting processes that exit, and reporting what happens to them
+use std::collections::VecDeque;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, mpsc};
+
+use super::exit::ExitInfo;
+use super::tree::{ProcessTree, Tree};
+use super::{kill_tree, terminate, Pipes, Stop};
+
+/// Delay before the first restart; it doubles with every further one.
+pub const RESTART_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest delay between restarts. A run lasting longer than this starts
+/// the backoff over.
+pub const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Events kept for subscribers that fall behind.
+pub(super) const EVENT_CAPACITY: usize = 256;
+
+/// Whether a process is started again after it exits. Stopping it through
+/// its handle never restarts it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    /// Restart after a non-zero exit or a signal, giving up once
+    /// `max_restarts` restarts have happened within `window`.
+    OnFailure { max_restarts: u32, window: Duration },
+    /// Restart after every exit, however often.
+    Always,
+}
+
+/// Where a process is in its `RestartPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supervision {
+    Running,
+    /// It exited and will be started again after a backoff.
+    Restarting,
+    /// It exited for good: its policy does not restart it, or it was stopped.
+    Exited,
+    /// It kept failing until its policy's limit was reached, or a restart
+    /// could not be spawned.
+    GaveUp,
+}
+
+/// What happened to a process, as sent to subscribers of
+/// `ProcessManager::subscribe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessEvent {
+    Started { id: u64, pid: u32, command: String },
+    /// `code` is `None` if a signal ended it or waiting for it failed.
+    Exited { id: u64, pid: u32, command: String, code: Option<i32> },
+    Restarting { id: u64, command: String, restarts: u32, delay: Duration },
+    GaveUp { id: u64, command: String, restarts: u32 },
+    /// A named process exited for good, after any restarts; see
+    /// `ProcessHandle::on_exit` for what `info` holds.
+    NamedExited { name: String, info: ExitInfo },
+}
+
+/// Builds the command again for a restart.
+pub(super) type Rebuild = Box<dyn Fn() -> anyhow::Result<Command> + Send + Sync>;
+
+/// The current run of a process, shared with its handles.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RunState {
+    pub(super) pid: u32,
+    pub(super) restarts: u32,
+    pub(super) supervision: Supervision,
+}
+
+/// Owns a process's child through its restarts.
+pub(super) struct Supervisor {
+    pub(super) id: u64,
+    pub(super) command: String,
+    pub(super) policy: RestartPolicy,
+    /// Whether the child is started in a process group of its own.
+    pub(super) grouped: bool,
+    /// The current run's tree, which `kill_tree` takes down.
+    pub(super) tree: Option<Tree>,
+    /// `None` for processes that cannot be restarted, such as pipeline stages.
+    pub(super) rebuild: Option<Rebuild>,
+    pub(super) run: Arc<Mutex<RunState>>,
+    pub(super) pipes: Arc<Mutex<Pipes>>,
+    pub(super) events: broadcast::Sender<ProcessEvent>,
+}
+
+impl Supervisor {
+    /// Waits for `child`, restarting it as the policy says, until it exits
+    /// for good or is stopped. Returns how the last run ended.
+    pub(super) async fn run(mut self, mut child: Child, mut stops: mpsc::UnboundedReceiver<Stop>) -> std::io::Result<ExitStatus> {
+        let mut recent: VecDeque<Instant> = VecDeque::new();
+        let mut backoff = RESTART_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let (status, stopped) = self.wait(&mut child, &mut stops).await;
+            let pid = self.state().pid;
+            let code = status.as_ref().ok().and_then(ExitStatus::code);
+            self.emit(ProcessEvent::Exited { id: self.id, pid, command: self.command.clone(), code });
+            let status = match status {
+                Ok(status) if !stopped => status,
+                other => return self.finish(Supervision::Exited, other),
+            };
+            match self.restart_allowed(status, &mut recent) {
+                Some(true) => {}
+                Some(false) => return self.finish(Supervision::GaveUp, Ok(status)),
+                None => return self.finish(Supervision::Exited, Ok(status)),
+            }
+
+            if started.elapsed() > MAX_RESTART_BACKOFF {
+                backoff = RESTART_BACKOFF;
+            }
+            let delay = backoff;
+            backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+            let restarts = self.update(|run| {
+                run.supervision = Supervision::Restarting;
+                run.restarts
+            }) + 1;
+            self.emit(ProcessEvent::Restarting { id: self.id, command: self.command.clone(), restarts, delay });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                Some(_) = stops.recv() => return self.finish(Supervision::Exited, Ok(status)),
+            }
+
+            let rebuild = self.rebuild.as_ref().expect("only rebuildable processes restart");
+            child = match rebuild().and_then(|mut command| Ok(command.spawn()?)) {
+                Ok(child) => child,
+                Err(e) => {
+                    tracing::warn!(command = %self.command, "could not restart process: {:#}", e);
+                    return self.finish(Supervision::GaveUp, Ok(status));
+                }
+            };
+            recent.push_back(Instant::now());
+            self.tree = Tree::attach(&child, self.grouped);
+            let pid = child.id().unwrap_or_default();
+            *self.pipes.lock().unwrap_or_else(|e| e.into_inner()) =
+                Pipes { stdin: child.stdin.take(), stdout: child.stdout.take(), stderr: child.stderr.take() };
+            self.update(|run| {
+                *run = RunState { pid, restarts, supervision: Supervision::Running };
+            });
+            tracing::debug!(command = %self.command, pid, restarts, "process restarted");
+            self.emit(ProcessEvent::Started { id: self.id, pid, command: self.command.clone() });
+        }
+    }
+
+    /// Waits for `child` to exit, carrying out stop requests meanwhile.
+    /// Also returns whether one was made.
+    async fn wait(&self, child: &mut Child, stops: &mut mpsc::UnboundedReceiver<Stop>) -> (std::io::Result<ExitStatus>, bool) {
+        let mut stopped = false;
+        loop {
+            tokio::select! {
+                status = child.wait() => return (status, stopped),
+                Some(stop) = stops.recv() => {
+                    stopped = true;
+                    match stop {
+                        Stop::Terminate => terminate(child, self.tree.as_ref()).await,
+                        Stop::KillTree => {
+                            let killed = kill_tree(child, self.tree.as_ref()).await;
+                            return (killed.and(child.wait().await), true);
+                        }
+                        Stop::Kill => match child.kill().await {
+                            Ok(()) => return (child.wait().await, true),
+                            Err(e) => return (Err(e), true),
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the policy restarts a process that exited with `status`:
+    /// `None` if it does not apply, `Some(false)` if its limit is reached.
+    fn restart_allowed(&self, status: ExitStatus, recent: &mut VecDeque<Instant>) -> Option<bool> {
+        self.rebuild.as_ref()?;
+        match self.policy {
+            RestartPolicy::Never => None,
+            RestartPolicy::Always => Some(true),
+            RestartPolicy::OnFailure { .. } if status.success() => None,
+            RestartPolicy::OnFailure { max_restarts, window } => {
+                while recent.front().is_some_and(|restart| restart.elapsed() > window) {
+                    recent.pop_front();
+                }
+                Some(recent.len() < max_restarts as usize)
+            }
+        }
+    }
+
+    fn finish(&self, supervision: Supervision, status: std::io::Result<ExitStatus>) -> std::io::Result<ExitStatus> {
+        let restarts = self.update(|run| {
+            run.supervision = supervision;
+            run.restarts
+        });
+        if supervision == Supervision::GaveUp {
+            tracing::warn!(command = %self.command, restarts, "gave up restarting process");
+            self.emit(ProcessEvent::GaveUp { id: self.id, command: self.command.clone(), restarts });
+        }
+        status
+    }
+
+    fn state(&self) -> RunState {
+        *self.run.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut RunState) -> T) -> T {
+        change(&mut self.run.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn emit(&self, event: ProcessEvent) {
+        // Nobody listening is fine.
+        let _ = self.events.send(event);
+    }
+}
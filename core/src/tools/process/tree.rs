@@ -0,0 +1,28 @@
+// Keeping a process together with everything it starts
+use tokio::process::{Child, Command};
+
+#[cfg(unix)]
+pub(super) type Tree = super::unix::ProcessGroup;
+#[cfg(windows)]
+pub(super) type Tree = super::windows::JobTree;
+
+/// What each platform provides for stopping a process together with the
+/// processes it starts: a process group on Unix, a job object on Windows.
+/// `ProcessHandle` only goes through this, so it behaves the same on both.
+pub(super) trait ProcessTree: Sized + Send + Sync + 'static {
+    /// Sets `command` up to start its process in a group of its own, apart
+    /// from this process's terminal or console and the signals sent there.
+    fn new_group(command: &mut Command);
+
+    /// Starts tracking the tree of `child`, which was just spawned; `grouped`
+    /// says whether its command went through `new_group`. `None` if the
+    /// platform cannot tell its tree apart, and only the process itself can
+    /// then be stopped.
+    fn attach(child: &Child, grouped: bool) -> Option<Self>;
+
+    /// Asks every process in the tree to exit.
+    async fn terminate(&self);
+
+    /// Kills every process in the tree.
+    async fn kill(&self);
+}
@@ -0,0 +1,39 @@
+// Process trees on Unix: process groups and signals
+use tokio::process::{Child, Command};
+
+use super::tree::ProcessTree;
+
+/// The process group a process started with `SpawnOptions::new_process_group`
+/// leads. Whatever it starts joins the group unless it makes one of its own.
+#[derive(Debug)]
+pub(super) struct ProcessGroup {
+    pgid: libc::pid_t,
+}
+
+impl ProcessTree for ProcessGroup {
+    fn new_group(command: &mut Command) {
+        command.process_group(0);
+    }
+
+    fn attach(child: &Child, grouped: bool) -> Option<Self> {
+        // Signalling a group other than the child's own would hit this process.
+        let pid = child.id().filter(|_| grouped)?;
+        Some(Self { pgid: pid as libc::pid_t })
+    }
+
+    async fn terminate(&self) {
+        self.signal(libc::SIGTERM);
+    }
+
+    async fn kill(&self) {
+        self.signal(libc::SIGKILL);
+    }
+}
+
+impl ProcessGroup {
+    fn signal(&self, signal: libc::c_int) {
+        // SAFETY: plain syscall; the group was created for the child at spawn
+        // and is only signalled before the child has been waited for.
+        unsafe { libc::killpg(self.pgid, signal) };
+    }
+}
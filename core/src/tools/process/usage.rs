@@ -0,0 +1,249 @@
+// Resource usage of running processes
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use super::{ProcessHandle, ProcessManager};
+
+/// What a running process uses, as `ProcessHandle::usage` samples it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProcessUsage {
+    /// CPU time as a share of wall time, where 100 is one core kept busy,
+    /// so a process using several cores can go past 100.
+    pub cpu_percent: f64,
+    /// Resident memory.
+    pub rss_bytes: u64,
+    pub num_threads: u32,
+    /// Time since the process started.
+    pub uptime: Duration,
+}
+
+impl ProcessUsage {
+    /// Samples the process with `pid`. Its CPU use is averaged over its
+    /// whole life.
+    pub fn of_pid(pid: u32) -> Result<Self> {
+        let sample = read(pid).with_context(|| format!("Failed to read the resource usage of pid {}", pid))?;
+        Ok(sample.usage(share(sample.cpu, sample.uptime)))
+    }
+}
+
+/// CPU time of a process at the last time it was sampled, so the next
+/// sample can report use since then.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct CpuMark {
+    pid: u32,
+    cpu: Duration,
+    at: Instant,
+}
+
+/// One reading of what the operating system reports for a process.
+struct Sample {
+    /// User and system CPU time used so far.
+    cpu: Duration,
+    rss_bytes: u64,
+    num_threads: u32,
+    uptime: Duration,
+}
+
+impl Sample {
+    fn usage(&self, cpu_percent: f64) -> ProcessUsage {
+        ProcessUsage { cpu_percent, rss_bytes: self.rss_bytes, num_threads: self.num_threads, uptime: self.uptime }
+    }
+}
+
+fn share(cpu: Duration, wall: Duration) -> f64 {
+    if wall.is_zero() {
+        return 0.0;
+    }
+    cpu.as_secs_f64() / wall.as_secs_f64() * 100.0
+}
+
+impl ProcessHandle {
+    /// What the process uses now. CPU use is averaged since the previous
+    /// call on this handle or a clone of it, or over the whole run for the
+    /// first call, so calling this at an interval gives the use in each
+    /// interval. Fails once the process has exited.
+    pub fn usage(&self) -> Result<ProcessUsage> {
+        if !matches!(self.try_wait(), Ok(None)) {
+            bail!("'{}' is not running", self.command);
+        }
+        let pid = self.pid();
+        let sample = read(pid).with_context(|| format!("Failed to read the resource usage of '{}'", self.command))?;
+        let now = Instant::now();
+        let mut mark = lock(&self.cpu_mark);
+        let cpu_percent = match *mark {
+            // A restart is a new process whose CPU time starts over.
+            Some(last) if last.pid == pid && now > last.at => share(sample.cpu.saturating_sub(last.cpu), now - last.at),
+            _ => share(sample.cpu, sample.uptime),
+        };
+        *mark = Some(CpuMark { pid, cpu: sample.cpu, at: now });
+        Ok(sample.usage(cpu_percent))
+    }
+}
+
+impl ProcessManager {
+    /// `ProcessHandle::usage` of every process still running, oldest first.
+    pub fn usage_all(&self) -> Vec<(ProcessHandle, Result<ProcessUsage>)> {
+        self.list()
+            .into_iter()
+            .map(|handle| {
+                let usage = handle.usage();
+                (handle, usage)
+            })
+            .collect()
+    }
+}
+
+fn lock(mark: &Mutex<Option<CpuMark>>) -> std::sync::MutexGuard<'_, Option<CpuMark>> {
+    mark.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn read(pid: u32) -> io::Result<Sample> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    // The command name comes second, in parentheses, and may hold spaces
+    // and parentheses itself; the fields after it are numbered from 3.
+    let (_, rest) = stat.rsplit_once(')').ok_or_else(|| invalid("malformed /proc stat"))?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |n: usize| -> io::Result<u64> {
+        fields.get(n - 3).and_then(|field| field.parse().ok()).ok_or_else(|| invalid("malformed /proc stat"))
+    };
+    // SAFETY: sysconf only reads configuration values.
+    let (ticks, page_size) = unsafe { (libc::sysconf(libc::_SC_CLK_TCK), libc::sysconf(libc::_SC_PAGESIZE)) };
+    let ticks = ticks.max(1) as f64;
+    let since_boot: f64 = std::fs::read_to_string("/proc/uptime")?
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse().ok())
+        .ok_or_else(|| invalid("malformed /proc/uptime"))?;
+    let started = field(22)? as f64 / ticks;
+    Ok(Sample {
+        cpu: Duration::from_secs_f64((field(14)? + field(15)?) as f64 / ticks),
+        rss_bytes: field(24)? * page_size.max(0) as u64,
+        num_threads: field(20)? as u32,
+        uptime: Duration::from_secs_f64((since_boot - started).max(0.0)),
+    })
+}
+
+#[cfg(target_os = "macos")]
+#[allow(deprecated)]
+fn read(pid: u32) -> io::Result<Sample> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Fills `info` with the `flavor` of information about `pid`.
+    fn pid_info<T>(pid: u32, flavor: libc::c_int, info: &mut T) -> io::Result<()> {
+        let size = std::mem::size_of::<T>() as libc::c_int;
+        // SAFETY: `info` is a writable buffer of `size` bytes of the type
+        // `flavor` fills in.
+        let written = unsafe { libc::proc_pidinfo(pid as libc::c_int, flavor, 0, info as *mut T as *mut libc::c_void, size) };
+        match written {
+            n if n == size => Ok(()),
+            n if n <= 0 => Err(io::Error::last_os_error()),
+            _ => Err(invalid("short proc_pidinfo result")),
+        }
+    }
+
+    // SAFETY: both are plain C structs for which all zeroes is valid.
+    let (mut task, mut bsd): (libc::proc_taskinfo, libc::proc_bsdinfo) = unsafe { (std::mem::zeroed(), std::mem::zeroed()) };
+    pid_info(pid, libc::PROC_PIDTASKINFO, &mut task)?;
+    pid_info(pid, libc::PROC_PIDTBSDINFO, &mut bsd)?;
+    // CPU times are in Mach absolute time units, which are not nanoseconds
+    // on Apple silicon.
+    let mut timebase = libc::mach_timebase_info { numer: 1, denom: 1 };
+    // SAFETY: fills in the struct passed.
+    unsafe { libc::mach_timebase_info(&mut timebase) };
+    let units = task.pti_total_user as u128 + task.pti_total_system as u128;
+    let nanos = units * timebase.numer as u128 / timebase.denom.max(1) as u128;
+    let started = UNIX_EPOCH + Duration::new(bsd.pbi_start_tvsec, bsd.pbi_start_tvusec as u32 * 1000);
+    Ok(Sample {
+        cpu: Duration::from_nanos(nanos as u64),
+        rss_bytes: task.pti_resident_size,
+        num_threads: task.pti_threadnum.max(0) as u32,
+        uptime: SystemTime::now().duration_since(started).unwrap_or_default(),
+    })
+}
+
+#[cfg(windows)]
+fn read(pid: u32) -> io::Result<Sample> {
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+    use windows_sys::Win32::Foundation::{FILETIME, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+    use windows_sys::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows_sys::Win32::System::SystemInformation::GetSystemTimeAsFileTime;
+    use windows_sys::Win32::System::Threading::{GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    /// A `FILETIME` as a count of 100 nanosecond intervals.
+    fn intervals(time: FILETIME) -> u64 {
+        (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64
+    }
+
+    /// The threads of `pid`, which only a snapshot of all processes tells.
+    fn thread_count(pid: u32) -> io::Result<u32> {
+        // SAFETY: the handle returned is checked and then owned here.
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `snapshot` is open and nothing else owns it.
+        let snapshot = unsafe { OwnedHandle::from_raw_handle(snapshot) };
+        // SAFETY: all zeroes is a valid entry; `dwSize` is set as required.
+        let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+        let raw = snapshot.as_raw_handle() as HANDLE;
+        // SAFETY: the snapshot is open and `entry` is writable.
+        let mut more = unsafe { Process32FirstW(raw, &mut entry) } != 0;
+        while more {
+            if entry.th32ProcessID == pid {
+                return Ok(entry.cntThreads);
+            }
+            // SAFETY: as above.
+            more = unsafe { Process32NextW(raw, &mut entry) } != 0;
+        }
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    // SAFETY: the handle returned is checked and then owned here.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `process` is open and nothing else owns it.
+    let process = unsafe { OwnedHandle::from_raw_handle(process) };
+    let raw = process.as_raw_handle() as HANDLE;
+
+    // SAFETY: all zeroes is valid for the counters; `cb` is set as required.
+    let mut memory: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    memory.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    // SAFETY: the handle is open and `memory` is `cb` writable bytes.
+    if unsafe { GetProcessMemoryInfo(raw, &mut memory, memory.cb) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let zero = FILETIME { dwLowDateTime: 0, dwHighDateTime: 0 };
+    let (mut created, mut exited, mut kernel, mut user, mut now) = (zero, zero, zero, zero, zero);
+    // SAFETY: the handle is open and every out pointer is writable.
+    if unsafe { GetProcessTimes(raw, &mut created, &mut exited, &mut kernel, &mut user) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: fills in the struct passed.
+    unsafe { GetSystemTimeAsFileTime(&mut now) };
+    Ok(Sample {
+        cpu: Duration::from_nanos((intervals(kernel) + intervals(user)) * 100),
+        rss_bytes: memory.WorkingSetSize as u64,
+        num_threads: thread_count(pid)?,
+        uptime: Duration::from_nanos(intervals(now).saturating_sub(intervals(created)) * 100),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn read(_pid: u32) -> io::Result<Sample> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "resource usage is not available on this platform"))
+}
@@ -0,0 +1,89 @@
+// Process trees on Windows: job objects and console control events
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::path::PathBuf;
+use tokio::process::{Child, Command};
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject};
+use windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP;
+
+use super::tree::ProcessTree;
+use crate::system::PathUtils;
+
+/// Exit code of the processes `JobTree::kill` ends, as `taskkill /F` uses.
+const KILLED_EXIT_CODE: u32 = 1;
+
+/// A job object holding a process and, from then on, whatever it starts.
+/// Processes it started before it was added to the job are not in it. The
+/// job does not kill its processes when closed, so processes started to
+/// outlive this one still do.
+#[derive(Debug)]
+pub(super) struct JobTree {
+    job: OwnedHandle,
+    pid: u32,
+    /// Whether the process leads a console process group of its own, which
+    /// is what a graceful stop can reach.
+    grouped: bool,
+}
+
+impl ProcessTree for JobTree {
+    fn new_group(command: &mut Command) {
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    fn attach(child: &Child, grouped: bool) -> Option<Self> {
+        let (pid, process) = (child.id()?, child.raw_handle()?);
+        // SAFETY: creates an unnamed job with default security; the handle
+        // returned is owned here and checked before use.
+        let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if job.is_null() {
+            tracing::warn!(pid, "could not create a job object: {}", std::io::Error::last_os_error());
+            return None;
+        }
+        // SAFETY: `job` was just created and nothing else owns it.
+        let job = unsafe { OwnedHandle::from_raw_handle(job) };
+        // SAFETY: both handles are open: the job's above, the process's for
+        // as long as `child` has not been waited for.
+        if unsafe { AssignProcessToJobObject(job.as_raw_handle() as HANDLE, process as HANDLE) } == 0 {
+            tracing::warn!(pid, "could not add process to a job object: {}", std::io::Error::last_os_error());
+            return None;
+        }
+        Some(Self { job, pid, grouped })
+    }
+
+    async fn terminate(&self) {
+        // Ctrl-Break is the one console event a new process group receives.
+        // SAFETY: plain call; the group id is the pid of the process leading it.
+        if self.grouped && unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, self.pid) } != 0 {
+            return;
+        }
+        // No console to share: ask its windows to close instead.
+        let _ = Command::new("taskkill").args(["/T", "/PID", &self.pid.to_string()]).output().await;
+    }
+
+    async fn kill(&self) {
+        // SAFETY: the job handle is open for as long as `self` lives.
+        if unsafe { TerminateJobObject(self.job.as_raw_handle() as HANDLE, KILLED_EXIT_CODE) } == 0 {
+            tracing::warn!(pid = self.pid, "could not terminate job object: {}", std::io::Error::last_os_error());
+            let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &self.pid.to_string()]).output().await;
+        }
+    }
+}
+
+/// What to start for `program`. `CreateProcess` runs `.bat` and `.cmd`
+/// scripts through `cmd.exe`, which splits and expands arguments by rules
+/// of its own, and the standard library only quotes arguments for those
+/// rules when the program it is given ends in `.bat` or `.cmd`. So a name
+/// such as `npm` is resolved through `PATHEXT` first, and a script it
+/// names is started by its full path; other executables keep the usual
+/// quoting, and are left to `CreateProcess` to find.
+pub(super) fn program(program: &str) -> PathBuf {
+    match PathUtils::find_executable(program) {
+        Some(path) if is_batch_script(&path) => path,
+        _ => PathBuf::from(program),
+    }
+}
+
+fn is_batch_script(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bat") || ext.eq_ignore_ascii_case("cmd"))
+}
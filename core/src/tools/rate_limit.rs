@@ -0,0 +1,111 @@
+// Token-bucket rate limiting for tool runs
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::executor::ToolError;
+
+/// What happens to a run that finds the bucket empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitMode {
+    /// Wait for the next token.
+    #[default]
+    Block,
+    /// Fail with `ToolError::RateLimited`.
+    Error,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Allows `max_calls` runs per `per`, with bursts up to `max_calls`. Share
+/// one limiter (behind an `Arc`) between every run it should count.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    tokens_per_sec: f64,
+    mode: RateLimitMode,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(max_calls: u32, per: Duration) -> Self {
+        let capacity = f64::from(max_calls.max(1));
+        Self {
+            capacity,
+            tokens_per_sec: capacity / per.as_secs_f64().max(f64::EPSILON),
+            mode: RateLimitMode::default(),
+            bucket: Mutex::new(Bucket { tokens: capacity, refilled: Instant::now() }),
+        }
+    }
+
+    pub fn per_second(max_calls: u32) -> Self {
+        Self::new(max_calls, Duration::from_secs(1))
+    }
+
+    pub fn with_mode(mut self, mode: RateLimitMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> RateLimitMode {
+        self.mode
+    }
+
+    /// Takes a token if one is available, otherwise returns how long until
+    /// the next one is.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * self.tokens_per_sec;
+        bucket.tokens = (bucket.tokens + refill).min(self.capacity);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.tokens_per_sec))
+        }
+    }
+
+    /// Takes a token for a run of `tool_name`, waiting or failing per the mode.
+    pub async fn acquire(&self, tool_name: &str) -> Result<(), ToolError> {
+        loop {
+            match self.try_acquire() {
+                Ok(()) => return Ok(()),
+                Err(retry_after) if self.mode == RateLimitMode::Error => {
+                    return Err(ToolError::RateLimited { tool: tool_name.to_string(), retry_after });
+                }
+                // Concurrent waiters all wake and race; the losers wait again.
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(100));
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait <= Duration::from_millis(50), "{:?}", wait);
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_error_mode() {
+        let limiter = RateLimiter::per_second(1).with_mode(RateLimitMode::Error);
+        limiter.acquire("curl").await.unwrap();
+        let err = limiter.acquire("curl").await.unwrap_err();
+        assert!(matches!(err, ToolError::RateLimited { ref tool, .. } if tool == "curl"));
+    }
+}
@@ -0,0 +1,909 @@
+// Registry of tools that can be described to and invoked by a model
+//
+// `ToolRegistry::manifest` produces the following document (version 1); new
+// fields may be added but existing ones keep their meaning:
+//
+// {
+//   "manifest_version": 1,
+//   "tools": [
+//     {
+//       "name": "grep",
+//       "description": "...",
+//       "inputSchema": {
+//         "type": "object",
+//         "properties": { "pattern": { "type": "string", "description": "..." } },
+//         "required": ["pattern"],
+//         "additionalProperties": false
+//       }
+//     }
+//   ]
+// }
+//
+// `inputSchema` is JSON Schema, as in MCP `tools/list`. Tools are sorted by
+// name and properties appear in declaration order. `to_openai_tools` wraps
+// the same schemas in the OpenAI function-calling `tools` array.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use super::confirm::DangerLevel;
+use super::executor::{ExecOptions, ToolError, ToolExecutor, ToolOutput};
+use super::file_tools;
+use super::probe::{self, ToolProbe, VersionProbe};
+use super::process::Priority;
+use super::rate_limit::RateLimiter;
+use super::shell::ShellKind;
+use super::template::{CommandTemplate, TemplateError};
+use crate::system::PathUtils;
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// A list of strings, passed as one argument each.
+    Array,
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::String => "a string",
+            Self::Integer => "an integer",
+            Self::Number => "a number",
+            Self::Boolean => "a boolean",
+            Self::Array => "an array of strings",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ToolCallError {
+    #[error("unknown tool '{name}'{}", did_you_mean(suggestions))]
+    UnknownTool { name: String, suggestions: Vec<String> },
+    #[error("tool arguments must be a JSON object")]
+    NotAnObject,
+    #[error("missing required argument '{0}'")]
+    MissingArgument(String),
+    #[error("unexpected argument '{0}'")]
+    UnexpectedArgument(String),
+    #[error("argument '{name}' must be {expected}, got {found}")]
+    WrongType {
+        name: String,
+        expected: ParamType,
+        found: &'static str,
+    },
+    #[error("argument '{name}' must be one of {}, got '{value}'", quoted(allowed))]
+    NotOneOf { name: String, value: String, allowed: Vec<String> },
+    #[error("argument '{name}' is not a usable path: {reason}")]
+    InvalidPath { name: String, reason: String },
+    /// A templated tool's value would have started an argument with `-`.
+    #[error("argument '{name}' may not start with '-', got '{value}'")]
+    OptionLike { name: String, value: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolParameter {
+    pub name: String,
+    pub param_type: ParamType,
+    pub description: String,
+    pub required: bool,
+    /// Passed as `flag value` (or just `flag` for a true boolean) instead of positionally.
+    pub flag: Option<String>,
+    /// A filesystem path, always checked against the execution policy and
+    /// made absolute with `PathUtils::resolve_path` before the tool sees it.
+    pub path: bool,
+    /// The only values a string (or each item of an array) may take; empty
+    /// allows any.
+    pub choices: Vec<String>,
+}
+
+impl ToolParameter {
+    pub fn new(name: impl Into<String>, param_type: ParamType, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            param_type,
+            description: description.into(),
+            required: true,
+            flag: None,
+            path: false,
+            choices: Vec::new(),
+        }
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    pub fn flag(mut self, flag: impl Into<String>) -> Self {
+        self.flag = Some(flag.into());
+        self
+    }
+
+    pub fn path(mut self) -> Self {
+        self.path = true;
+        self
+    }
+
+    /// Restricts the value to `choices`, listed as an `enum` in the schema.
+    pub fn one_of<I, S>(mut self, choices: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.choices = choices.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl ToolCallError {
+    /// The error as a JSON object to hand back to a model so it can correct
+    /// its call: a stable `error` code, a readable `message` and the
+    /// offending `argument` or `suggestions` where there are any.
+    pub fn to_json(&self) -> Value {
+        let (code, extra) = match self {
+            Self::UnknownTool { suggestions, .. } => ("unknown_tool", json!({ "suggestions": suggestions })),
+            Self::NotAnObject => ("not_an_object", json!({})),
+            Self::MissingArgument(name) => ("missing_argument", json!({ "argument": name })),
+            Self::UnexpectedArgument(name) => ("unexpected_argument", json!({ "argument": name })),
+            Self::WrongType { name, expected, .. } => {
+                ("wrong_type", json!({ "argument": name, "expected": expected }))
+            }
+            Self::NotOneOf { name, allowed, .. } => ("not_one_of", json!({ "argument": name, "allowed": allowed })),
+            Self::InvalidPath { name, .. } => ("invalid_path", json!({ "argument": name })),
+            Self::OptionLike { name, .. } => ("option_like", json!({ "argument": name })),
+        };
+        let mut error = json!({ "error": code, "message": self.to_string() });
+        if let (Value::Object(error), Value::Object(extra)) = (&mut error, extra) {
+            error.extend(extra);
+        }
+        error
+    }
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        _ => format!("; did you mean {}?", quoted(suggestions)),
+    }
+}
+
+fn quoted(values: &[String]) -> String {
+    values.iter().map(|s| format!("'{}'", s)).collect::<Vec<_>>().join(", ")
+}
+
+/// Validated arguments for a tool call, keyed by parameter name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolArgs(Map<String, Value>);
+
+impl ToolArgs {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name).filter(|value| !value.is_null())
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(Value::as_str)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.0.values()
+    }
+
+    pub fn into_inner(self) -> Map<String, Value> {
+        self.0
+    }
+}
+
+/// A tool implemented in-process.
+pub type BuiltinHandler = Arc<dyn Fn(ToolArgs) -> BoxFuture<'static, Result<ToolOutput>> + Send + Sync>;
+
+/// What runs when a tool is called.
+#[derive(Clone)]
+pub enum ToolHandler {
+    /// An external program, given the arguments rendered by `ToolSpec::build_args`.
+    Command(String),
+    /// A script run by a shell; see `ToolSpec::shell`.
+    Shell(ShellKind),
+    Builtin(BuiltinHandler),
+}
+
+impl fmt::Debug for ToolHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command(command) => f.debug_tuple("Command").field(command).finish(),
+            Self::Shell(kind) => f.debug_tuple("Shell").field(kind).finish(),
+            Self::Builtin(_) => f.write_str("Builtin(..)"),
+        }
+    }
+}
+
+/// Explains in words what a built-in would do with the given arguments.
+pub type Describer = Arc<dyn Fn(&ToolArgs) -> String + Send + Sync>;
+
+/// A tool: either an external command or a built-in closure. Command
+/// arguments are rendered as the fixed `args`, then flagged parameters, then
+/// `--` and the positional parameters, so a value starting with `-` is never
+/// read as an option.
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub handler: ToolHandler,
+    /// Leading arguments for command tools; unused by built-ins.
+    pub args: Vec<String>,
+    pub parameters: Vec<ToolParameter>,
+    /// Shared by every run of this tool, including clones of the spec.
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// The same call with unchanged inputs gives the same output and has
+    /// no side effects, so `ToolCache` may reuse it.
+    pub cacheable: bool,
+    /// The tool only reads, so it may still run in `safe_mode`.
+    pub read_only: bool,
+    /// Runs at or above an `ExecOptions::confirm` gate's threshold wait
+    /// for its approval.
+    pub danger: DangerLevel,
+    /// What a command tool runs at unless `ExecOptions::priority` says
+    /// otherwise; bulk work such as searching a tree is best `Idle`.
+    /// Built-in tools run in this process, so it does not apply to them.
+    pub priority: Priority,
+    /// How `ToolRegistry::probe_all` asks the executable for its version.
+    pub version_probe: VersionProbe,
+    /// Lays out a command tool's arguments instead of the rules above; see
+    /// `ToolSpec::templated`.
+    pub template: Option<CommandTemplate>,
+    describer: Option<DescriberFn>,
+    without_paths: bool,
+}
+
+#[derive(Clone)]
+struct DescriberFn(Describer);
+
+impl fmt::Debug for DescriberFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Describer(..)")
+    }
+}
+
+impl ToolSpec {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, command: impl Into<String>) -> Self {
+        Self::with_handler(name, description, ToolHandler::Command(command.into()))
+    }
+
+    /// A tool run in-process by `handler`, which receives the validated arguments.
+    pub fn builtin<F, Fut>(name: impl Into<String>, description: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(ToolArgs) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<ToolOutput>> + Send + 'static,
+    {
+        let handler: BuiltinHandler = Arc::new(move |args| Box::pin(handler(args)));
+        Self::with_handler(name, description, ToolHandler::Builtin(handler))
+    }
+
+    /// A command tool whose arguments are laid out by `template`, e.g.
+    /// `grep -n {pattern} {file}`; see `CommandTemplate` for the syntax. Each
+    /// placeholder declares a string parameter, which `param` can replace to
+    /// describe it better or give it another type.
+    pub fn templated(name: impl Into<String>, description: impl Into<String>, template: &str) -> Result<Self, TemplateError> {
+        let template = CommandTemplate::parse(template)?;
+        let mut spec = Self::new(name, description, template.program());
+        spec.parameters = template.parameters();
+        spec.template = Some(template);
+        Ok(spec)
+    }
+
+    /// The `shell` tool: runs `{ "script": ..., "args": [...] }` with
+    /// `kind`, each argument a positional parameter. It only runs under a
+    /// policy with `allow_shell`.
+    pub fn shell(kind: ShellKind) -> Self {
+        let description = match kind {
+            ShellKind::Bash => "Run a bash script; arguments are available as \"$1\", \"$2\", ...",
+            ShellKind::PowerShell => "Run a PowerShell script; arguments are available as $args",
+            ShellKind::Cmd => "Run a one-line cmd command; arguments are available as !SHELL_ARG1!, !SHELL_ARG2!, ...",
+        };
+        Self::with_handler("shell", description, ToolHandler::Shell(kind))
+            .danger(DangerLevel::Destructive)
+            .version_probe(kind.version_probe())
+            .param(ToolParameter::new("script", ParamType::String, "Script to run"))
+            .param(ToolParameter::new("args", ParamType::Array, "Arguments passed to the script, never spliced into it").optional())
+    }
+
+    fn with_handler(name: impl Into<String>, description: impl Into<String>, handler: ToolHandler) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            handler,
+            args: Vec::new(),
+            parameters: Vec::new(),
+            rate_limit: None,
+            cacheable: false,
+            read_only: false,
+            danger: DangerLevel::Safe,
+            priority: Priority::Normal,
+            version_probe: VersionProbe::default(),
+            template: None,
+            describer: None,
+            without_paths: false,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Sets the dry-run explanation for a built-in.
+    pub fn describe_with<F>(mut self, describer: F) -> Self
+    where
+        F: Fn(&ToolArgs) -> String + Send + Sync + 'static,
+    {
+        self.describer = Some(DescriberFn(Arc::new(describer)));
+        self
+    }
+
+    /// What a dry run reports this tool would do with `args`.
+    pub fn describe(&self, args: &ToolArgs) -> String {
+        match &self.describer {
+            Some(DescriberFn(describe)) => describe(args),
+            None => format!("{} with arguments {}", self.description, Value::Object(args.0.clone())),
+        }
+    }
+
+    /// Marks a built-in none of whose arguments are paths, so the policy
+    /// checks its name without vetting every string argument as a path.
+    pub fn without_paths(mut self) -> Self {
+        self.without_paths = true;
+        self
+    }
+
+    /// Whether only the parameters marked `.path()` hold paths.
+    pub fn declares_paths(&self) -> bool {
+        self.without_paths || self.parameters.iter().any(|param| param.path)
+    }
+
+    /// Marks the tool safe to cache; see `cacheable`. Its path parameters
+    /// must be marked `.path()` for edits to those files to be noticed.
+    pub fn cacheable(mut self) -> Self {
+        self.cacheable = true;
+        self
+    }
+
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn danger(mut self, danger: DangerLevel) -> Self {
+        self.danger = danger;
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn version_probe(mut self, probe: VersionProbe) -> Self {
+        self.version_probe = probe;
+        self
+    }
+
+    pub fn rate_limited(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limit = Some(Arc::new(limiter));
+        self
+    }
+
+    /// Adds a parameter, replacing one of the same name, such as one
+    /// declared by a template.
+    pub fn param(mut self, parameter: ToolParameter) -> Self {
+        match self.parameters.iter_mut().find(|param| param.name == parameter.name) {
+            Some(existing) => *existing = parameter,
+            None => self.parameters.push(parameter),
+        }
+        self
+    }
+
+    /// JSON Schema for the tool's arguments object.
+    pub fn input_schema(&self) -> Value {
+        let mut properties = Map::new();
+        for param in &self.parameters {
+            let mut schema = json!({ "type": param.param_type, "description": param.description });
+            if param.param_type == ParamType::Array {
+                schema["items"] = json!({ "type": "string" });
+            }
+            if !param.choices.is_empty() {
+                let target = if param.param_type == ParamType::Array { &mut schema["items"] } else { &mut schema };
+                target["enum"] = json!(param.choices);
+            }
+            properties.insert(param.name.clone(), schema);
+        }
+        let required: Vec<_> = self.parameters.iter().filter(|p| p.required).map(|p| p.name.as_str()).collect();
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    /// Checks `arguments` against the parameters; see `validate_in`.
+    pub fn validate(&self, arguments: &Value) -> Result<ToolArgs, ToolCallError> {
+        self.validate_in(arguments, None)
+    }
+
+    /// Checks `arguments` against the parameters and normalises them: values
+    /// a model commonly sends as strings (`"5"`, `"true"`) are converted to
+    /// the declared type, a lone string is accepted for an array, and path
+    /// parameters are resolved against `cwd` (or the current directory).
+    pub fn validate_in(&self, arguments: &Value, cwd: Option<&Path>) -> Result<ToolArgs, ToolCallError> {
+        let mut arguments = match arguments {
+            Value::Object(map) => map.clone(),
+            Value::Null => Map::new(),
+            _ => return Err(ToolCallError::NotAnObject),
+        };
+        if let Some(unknown) = arguments.keys().find(|key| !self.parameters.iter().any(|p| &p.name == *key)) {
+            return Err(ToolCallError::UnexpectedArgument(unknown.clone()));
+        }
+        for param in &self.parameters {
+            match arguments.get(&param.name) {
+                None | Some(Value::Null) if param.required => {
+                    return Err(ToolCallError::MissingArgument(param.name.clone()))
+                }
+                None | Some(Value::Null) => {}
+                Some(value) => {
+                    let value = normalize(param, value, cwd)?;
+                    arguments.insert(param.name.clone(), value);
+                }
+            }
+        }
+        Ok(ToolArgs(arguments))
+    }
+
+    /// Checks `arguments` against the parameters and renders the command line.
+    pub fn build_args(&self, arguments: &Value) -> Result<Vec<String>, ToolCallError> {
+        self.render_args(&self.validate(arguments)?)
+    }
+
+    /// The command line for arguments already checked by `validate_in`.
+    pub fn render_args(&self, arguments: &ToolArgs) -> Result<Vec<String>, ToolCallError> {
+        if let Some(template) = &self.template {
+            let mut argv = self.args.clone();
+            argv.extend(template.render(&self.parameters, arguments)?);
+            return Ok(argv);
+        }
+        let mut flagged = Vec::new();
+        let mut positional = Vec::new();
+        for param in &self.parameters {
+            let Some(value) = arguments.get(&param.name) else { continue };
+            let values = render(param, value)?;
+            match &param.flag {
+                Some(flag) if param.param_type == ParamType::Boolean => {
+                    if value.as_bool() == Some(true) {
+                        flagged.push(flag.clone());
+                    }
+                }
+                Some(flag) => values.into_iter().for_each(|v| flagged.extend([flag.clone(), v])),
+                None => positional.extend(values),
+            }
+        }
+
+        let mut argv = self.args.clone();
+        argv.extend(flagged);
+        if !positional.is_empty() {
+            argv.push("--".to_string());
+            argv.extend(positional);
+        }
+        Ok(argv)
+    }
+}
+
+/// `value` converted to `param`'s type where that is unambiguous, checked
+/// against its choices, with paths resolved against `cwd`.
+fn normalize(param: &ToolParameter, value: &Value, cwd: Option<&Path>) -> Result<Value, ToolCallError> {
+    let value = match (param.param_type, value) {
+        (ParamType::Integer, Value::String(s)) => match s.trim().parse::<i64>() {
+            Ok(n) => json!(n),
+            Err(_) => s.trim().parse::<u64>().map_or_else(|_| value.clone(), |n| json!(n)),
+        },
+        (ParamType::Integer, Value::Number(n)) if n.as_f64().is_some_and(|f| f.fract() == 0.0 && f.abs() < 2f64.powi(53)) => {
+            n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)).map_or_else(|| value.clone(), |n| json!(n))
+        }
+        (ParamType::Number, Value::String(s)) => {
+            s.trim().parse::<f64>().ok().filter(|f| f.is_finite()).map_or_else(|| value.clone(), |f| json!(f))
+        }
+        (ParamType::Boolean, Value::String(s)) if s.eq_ignore_ascii_case("true") => Value::Bool(true),
+        (ParamType::Boolean, Value::String(s)) if s.eq_ignore_ascii_case("false") => Value::Bool(false),
+        (ParamType::Array, Value::String(_)) => Value::Array(vec![value.clone()]),
+        _ => value.clone(),
+    };
+    let mut values = render(param, &value)?;
+    if !param.choices.is_empty() {
+        if let Some(bad) = values.iter().find(|v| !param.choices.contains(v)) {
+            return Err(ToolCallError::NotOneOf { name: param.name.clone(), value: bad.clone(), allowed: param.choices.clone() });
+        }
+    }
+    if !param.path {
+        return Ok(value);
+    }
+    for path in &mut values {
+        let joined = cwd.map_or_else(|| Path::new(path.as_str()).to_path_buf(), |cwd| cwd.join(&*path));
+        let resolved = PathUtils::resolve_path(joined)
+            .map_err(|e| ToolCallError::InvalidPath { name: param.name.clone(), reason: format!("{:#}", e) })?;
+        *path = resolved.to_string_lossy().into_owned();
+    }
+    Ok(match value {
+        Value::Array(_) => json!(values),
+        _ => json!(values.remove(0)),
+    })
+}
+
+pub(super) fn render(param: &ToolParameter, value: &Value) -> Result<Vec<String>, ToolCallError> {
+    let ok = match (param.param_type, value) {
+        (ParamType::String, Value::String(s)) => Some(vec![s.clone()]),
+        (ParamType::Integer, Value::Number(n)) if n.is_i64() || n.is_u64() => Some(vec![n.to_string()]),
+        (ParamType::Number, Value::Number(n)) => Some(vec![n.to_string()]),
+        (ParamType::Boolean, Value::Bool(b)) => Some(vec![b.to_string()]),
+        (ParamType::Array, Value::Array(items)) => items.iter().map(|i| i.as_str().map(str::to_string)).collect(),
+        _ => None,
+    };
+    ok.ok_or_else(|| ToolCallError::WrongType {
+        name: param.name.clone(),
+        expected: param.param_type,
+        found: json_type(value),
+    })
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a number",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Tools available to the agent, keyed by name.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, ToolSpec>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the read-only command-line tools the agent ships with
+    /// and the in-process file tools, plus `shell`, which runs only under a
+    /// policy that allows it, and `http_request` with the `http` feature.
+    /// `grep` and `wc`, which may read whole trees, run at `Priority::Idle`.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            ToolSpec::new("grep", "Search files for lines matching a regular expression", "grep")
+                .arg("-n")
+                .cacheable()
+                .read_only()
+                .priority(Priority::Idle)
+                .param(ToolParameter::new("ignore_case", ParamType::Boolean, "Match case-insensitively").optional().flag("-i"))
+                .param(ToolParameter::new("recursive", ParamType::Boolean, "Search directories recursively").optional().flag("-r"))
+                .param(ToolParameter::new("pattern", ParamType::String, "Regular expression to search for"))
+                .param(ToolParameter::new("paths", ParamType::Array, "Files or directories to search").path()),
+        );
+        registry.register(
+            ToolSpec::new("ls", "List directory contents", "ls")
+                .cacheable()
+                .read_only()
+                .param(ToolParameter::new("all", ParamType::Boolean, "Include hidden entries").optional().flag("-a"))
+                .param(ToolParameter::new("path", ParamType::String, "Directory to list").optional().path()),
+        );
+        registry.register(
+            ToolSpec::new("head", "Print the first lines of a file", "head")
+                .cacheable()
+                .read_only()
+                .param(ToolParameter::new("lines", ParamType::Integer, "Number of lines to print").optional().flag("-n"))
+                .param(ToolParameter::new("path", ParamType::String, "File to read").path()),
+        );
+        registry.register(
+            ToolSpec::new("wc", "Count lines, words and bytes in files", "wc")
+                .cacheable()
+                .read_only()
+                .priority(Priority::Idle)
+                .param(ToolParameter::new("paths", ParamType::Array, "Files to count").path()),
+        );
+        file_tools::register(&mut registry);
+        registry.register(ToolSpec::shell(ShellKind::default()));
+        #[cfg(feature = "http")]
+        registry.register(super::http::http_request_tool(super::http::HttpToolConfig::default()));
+        registry
+    }
+
+    /// Adds a tool, returning the one it replaced, if any.
+    pub fn register(&mut self, spec: ToolSpec) -> Option<ToolSpec> {
+        self.tools.insert(spec.name.clone(), spec)
+    }
+
+    /// Removes a tool, returning it if it was registered.
+    pub fn unregister(&mut self, name: &str) -> Option<ToolSpec> {
+        self.tools.remove(name)
+    }
+
+    /// Caps how often the tool registered as `name` runs, e.g.
+    /// `RateLimiter::per_second(5)`.
+    pub fn set_rate_limit(&mut self, name: &str, limiter: RateLimiter) -> Result<(), ToolCallError> {
+        self.resolve(name)?;
+        if let Some(spec) = self.tools.get_mut(name) {
+            spec.rate_limit = Some(Arc::new(limiter));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ToolSpec> {
+        self.tools.get(name)
+    }
+
+    /// Like `get`, but an unknown name is an error listing similar tool names.
+    pub fn resolve(&self, name: &str) -> Result<&ToolSpec, ToolCallError> {
+        self.get(name).ok_or_else(|| ToolCallError::UnknownTool {
+            name: name.to_string(),
+            suggestions: self.near_matches(name),
+        })
+    }
+
+    /// Registered names within a few edits of `name`, closest first.
+    fn near_matches(&self, name: &str) -> Vec<String> {
+        let limit = name.chars().count() / 3 + 1;
+        let mut matches: Vec<(usize, &String)> = self
+            .tools
+            .keys()
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, candidate)| *distance <= limit || candidate.starts_with(name) || name.starts_with(candidate.as_str()))
+            .collect();
+        matches.sort();
+        matches.into_iter().take(3).map(|(_, candidate)| candidate.clone()).collect()
+    }
+
+    /// Checks every tool concurrently: whether its executable is on `PATH`
+    /// and, if so, which version it reports. Versions are cached for the
+    /// life of the process. Sorted by name, like `list`.
+    pub async fn probe_all(&self) -> Vec<ToolProbe> {
+        futures::future::join_all(self.tools.values().map(probe::probe)).await
+    }
+
+    /// Registered tools, sorted by name.
+    pub fn list(&self) -> Vec<&ToolSpec> {
+        self.tools.values().collect()
+    }
+
+    /// The manifest document described at the top of this module.
+    pub fn manifest(&self) -> Value {
+        let tools: Vec<_> = self
+            .tools
+            .values()
+            .map(|spec| json!({ "name": spec.name, "description": spec.description, "inputSchema": spec.input_schema() }))
+            .collect();
+        json!({ "manifest_version": MANIFEST_VERSION, "tools": tools })
+    }
+
+    /// Every tool in the OpenAI function-calling `tools` format, sorted by name.
+    pub fn to_openai_tools(&self) -> Value {
+        self.tools
+            .values()
+            .map(|spec| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": spec.name,
+                        "description": spec.description,
+                        "parameters": spec.input_schema(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Validates `arguments` and runs the tool. Validation failures are
+    /// returned as `ToolError::InvalidArgs` before anything is executed.
+    pub async fn call(&self, name: &str, arguments: &Value) -> Result<ToolOutput, ToolError> {
+        ToolExecutor::execute_registered(self, name, arguments, &ExecOptions::default()).await
+    }
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_is_stable() {
+        let manifest = ToolRegistry::builtin().manifest();
+        assert_eq!(manifest["manifest_version"], 1);
+        let names: Vec<_> = manifest["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .filter(|name| *name != "http_request")
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "append_file", "file_info", "grep", "head", "list_directory", "ls", "read_file", "search_files", "shell", "wc",
+                "write_file",
+            ]
+        );
+        assert_eq!(
+            manifest["tools"][3]["inputSchema"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "lines": { "type": "integer", "description": "Number of lines to print" },
+                    "path": { "type": "string", "description": "File to read" },
+                },
+                "required": ["path"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_args_validates_types() {
+        let registry = ToolRegistry::builtin();
+        let grep = registry.get("grep").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let at = |name: &str| root.join(name).display().to_string();
+        let args = grep.validate_in(&json!({"pattern": "-v", "paths": ["a", "b"], "ignore_case": true, "recursive": false}), Some(&root));
+        assert_eq!(grep.render_args(&args.unwrap()).unwrap(), vec!["-n".into(), "-i".into(), "--".into(), "-v".into(), at("a"), at("b")]);
+
+        // Strings a model sends for other types are converted, paths resolved.
+        let head = registry.get("head").unwrap();
+        let args = head.validate_in(&json!({"path": "./x/../f", "lines": "5"}), Some(&root)).unwrap();
+        assert_eq!(args.get("lines"), Some(&json!(5)));
+        assert_eq!(head.render_args(&args).unwrap(), vec!["-n".into(), "5".into(), "--".into(), at("f")]);
+        assert_eq!(head.validate(&json!({"path": "f", "lines": 5.0})).unwrap().get("lines"), Some(&json!(5)));
+        assert_eq!(
+            head.build_args(&json!({"path": "f", "lines": "five"})),
+            Err(ToolCallError::WrongType { name: "lines".into(), expected: ParamType::Integer, found: "a string" })
+        );
+        assert!(head.build_args(&json!({"path": "f", "lines": 5.5})).is_err());
+        let wc = registry.get("wc").unwrap();
+        assert_eq!(wc.validate_in(&json!({"paths": "a"}), Some(&root)).unwrap().get("paths"), Some(&json!([at("a")])));
+        assert_eq!(head.build_args(&json!({"lines": 5})), Err(ToolCallError::MissingArgument("path".into())));
+        assert_eq!(head.build_args(&json!({"path": "f", "x": 1})), Err(ToolCallError::UnexpectedArgument("x".into())));
+        assert_eq!(head.build_args(&json!(["f"])), Err(ToolCallError::NotAnObject));
+        assert!(wc.build_args(&json!({"paths": ["a", 1]})).is_err());
+
+        let sort = ToolSpec::new("sort", "Sort lines", "sort")
+            .param(ToolParameter::new("order", ParamType::String, "Sort order").one_of(["asc", "desc"]))
+            .param(ToolParameter::new("numeric", ParamType::Boolean, "Compare numerically").optional().flag("-n"));
+        assert_eq!(sort.input_schema()["properties"]["order"]["enum"], json!(["asc", "desc"]));
+        assert_eq!(sort.build_args(&json!({"order": "desc", "numeric": "TRUE"})).unwrap(), vec!["-n", "--", "desc"]);
+        let err = sort.build_args(&json!({"order": "up"})).unwrap_err();
+        assert_eq!(err.to_string(), "argument 'order' must be one of 'asc', 'desc', got 'up'");
+        assert_eq!(err.to_json()["allowed"], json!(["asc", "desc"]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_call_rejects_before_executing() {
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            ToolSpec::new("say", "Echo a word", "echo").param(ToolParameter::new("word", ParamType::String, "Word")),
+        );
+        let output = registry.call("say", &json!({"word": "hi"})).await.unwrap();
+        assert_eq!(output.stdout_lossy(), "-- hi\n");
+
+        let err = registry.call("say", &json!({"word": 1})).await.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidArgs(ToolCallError::WrongType { .. })));
+        let err = registry.call("nope", &json!({})).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ToolError::InvalidArgs(ToolCallError::UnknownTool { ref name, ref suggestions }) if name == "nope" && suggestions.is_empty()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_builtin_tools_and_lookup() {
+        let mut registry = ToolRegistry::builtin();
+        registry.register(
+            ToolSpec::builtin("upper", "Upper-case a word", |args: ToolArgs| async move {
+                Ok(ToolOutput::new(
+                    args.str("word").unwrap_or_default().to_uppercase(),
+                    Vec::new(),
+                    0,
+                    std::time::Duration::ZERO,
+                ))
+            })
+            .param(ToolParameter::new("word", ParamType::String, "Word")),
+        );
+        assert_eq!(registry.call("upper", &json!({"word": "hi"})).await.unwrap().stdout_lossy(), "HI");
+        assert!(registry.call("upper", &json!({})).await.is_err());
+
+        let err = registry.resolve("gerp").unwrap_err();
+        assert_eq!(err, ToolCallError::UnknownTool { name: "gerp".into(), suggestions: vec!["grep".into()] });
+        assert_eq!(err.to_string(), "unknown tool 'gerp'; did you mean 'grep'?");
+        assert_eq!(registry.resolve("he").unwrap_err().to_string(), "unknown tool 'he'; did you mean 'head'?");
+
+        assert!(registry.unregister("upper").is_some());
+        assert!(registry.unregister("upper").is_none());
+        assert_eq!(registry.list().len(), 11 + usize::from(cfg!(feature = "http")));
+    }
+
+    #[test]
+    fn test_openai_tools_format() {
+        let tools = ToolRegistry::builtin().to_openai_tools();
+        let read_file = tools.as_array().unwrap().iter().find(|t| t["function"]["name"] == "read_file").unwrap();
+        assert_eq!(read_file["type"], "function");
+        assert_eq!(read_file["function"]["parameters"]["required"], json!(["path"]));
+        assert_eq!(read_file["function"]["parameters"]["properties"]["offset"]["type"], "integer");
+    }
+
+    #[tokio::test]
+    async fn test_execute_json_validates_before_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        let path = path.to_str().unwrap();
+        let registry = ToolRegistry::builtin();
+
+        let output = ToolExecutor::execute_json(&registry, "read_file", json!({"path": path, "offset": 3, "length": 4}))
+            .await
+            .unwrap();
+        assert_eq!(output.stdout_lossy(), "3456");
+
+        let feedback = |args: Value| {
+            let registry = registry.clone();
+            async move {
+                match ToolExecutor::execute_json(&registry, "read_file", args).await.unwrap_err() {
+                    ToolError::InvalidArgs(err) => err.to_json(),
+                    other => panic!("expected a validation error, got {:?}", other),
+                }
+            }
+        };
+        assert_eq!(
+            feedback(json!({"offset": 1})).await,
+            json!({"error": "missing_argument", "message": "missing required argument 'path'", "argument": "path"})
+        );
+        assert_eq!(feedback(json!({"path": path, "mode": "r"})).await["error"], "unexpected_argument");
+        assert_eq!(
+            feedback(json!({"path": path, "length": "all"})).await,
+            json!({
+                "error": "wrong_type",
+                "message": "argument 'length' must be an integer, got a string",
+                "argument": "length",
+                "expected": "integer",
+            })
+        );
+        assert_eq!(feedback(json!("notes.txt")).await["error"], "not_an_object");
+    }
+}
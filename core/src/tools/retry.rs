@@ -0,0 +1,110 @@
+// Retrying flaky tools
+use std::time::Duration;
+use anyhow::Result;
+use regex::Regex;
+
+use super::executor::{ToolError, ToolOutput};
+
+/// Which failed attempts are worth running again. Spawn failures and policy
+/// violations are never retried.
+#[derive(Debug, Clone)]
+pub enum RetryOn {
+    /// Any non-zero exit, and timeouts.
+    NonZeroExit,
+    /// Only these exit codes.
+    ExitCodes(Vec<i32>),
+    /// A non-zero exit whose stderr matches the pattern.
+    StderrMatches(Regex),
+}
+
+/// How often and how patiently `ExecOptions::retry` reruns a tool. The
+/// timeout, if any, applies to each attempt separately.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    /// Factor applied to the backoff after each retry.
+    pub multiplier: f64,
+    pub retry_on: RetryOn,
+}
+
+impl RetryPolicy {
+    /// Retries any failure with a 100ms backoff that doubles each time.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            multiplier: 2.0,
+            retry_on: RetryOn::NonZeroExit,
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, multiplier: f64) -> Self {
+        self.initial_backoff = initial;
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_retry_on(mut self, retry_on: RetryOn) -> Self {
+        self.retry_on = retry_on;
+        self
+    }
+
+    /// Backoff to wait after the given failed attempt (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(0.0).powi(attempt.saturating_sub(1) as i32);
+        self.initial_backoff.mul_f64(factor)
+    }
+
+    pub fn should_retry(&self, result: &Result<ToolOutput>) -> bool {
+        match result {
+            Ok(output) if output.success() => false,
+            Ok(output) => match &self.retry_on {
+                RetryOn::NonZeroExit => true,
+                RetryOn::ExitCodes(codes) => codes.contains(&output.exit_code),
+                RetryOn::StderrMatches(pattern) => pattern.is_match(&output.stderr_lossy()),
+            },
+            Err(e) => {
+                matches!(self.retry_on, RetryOn::NonZeroExit)
+                    && matches!(e.downcast_ref::<ToolError>(), Some(ToolError::Timeout { .. }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_retry() {
+        let failed = |code, stderr: &str| -> Result<ToolOutput> { Ok(ToolOutput::new("", stderr, code, Duration::ZERO)) };
+        let timeout = || -> Result<ToolOutput> {
+            Err(ToolError::Timeout { elapsed: Duration::ZERO, partial_stdout: String::new() }.into())
+        };
+
+        let any = RetryPolicy::new(3);
+        assert!(!any.should_retry(&failed(0, "")));
+        assert!(any.should_retry(&failed(1, "")));
+        assert!(any.should_retry(&timeout()));
+        assert!(!any.should_retry(&Err(anyhow::anyhow!("spawn failed"))));
+
+        let codes = RetryPolicy::new(3).with_retry_on(RetryOn::ExitCodes(vec![75]));
+        assert!(codes.should_retry(&failed(75, "")));
+        assert!(!codes.should_retry(&failed(1, "")));
+        assert!(!codes.should_retry(&timeout()));
+
+        let stderr = RetryPolicy::new(3).with_retry_on(RetryOn::StderrMatches(Regex::new("(?i)connection reset").unwrap()));
+        assert!(stderr.should_retry(&failed(1, "error: Connection reset by peer")));
+        assert!(!stderr.should_retry(&failed(1, "error: not found")));
+    }
+
+    #[test]
+    fn test_backoff_grows() {
+        let policy = RetryPolicy::new(4).with_backoff(Duration::from_millis(10), 3.0);
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(2), Duration::from_millis(30));
+        assert_eq!(policy.backoff(3), Duration::from_millis(90));
+    }
+}
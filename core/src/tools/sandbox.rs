@@ -0,0 +1,310 @@
+// OS-level isolation for untrusted tools (Linux namespaces)
+use std::path::PathBuf;
+use tokio::process::Command;
+
+use super::executor::ToolError;
+
+/// Host directories visible (read-only) in a default sandbox, where present.
+pub const DEFAULT_SANDBOX_READ_ONLY: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib64", "/lib32"];
+
+/// Where a sandboxed tool runs. On Linux with the `sandbox` feature the tool
+/// gets new user, mount, pid and UTS namespaces and is chrooted into a fresh
+/// tmpfs holding only the listed host directories, `/dev/{null,zero,urandom}`,
+/// a private `/proc` and an empty `/tmp`. Without `network` it also gets an
+/// empty network namespace. Elsewhere, requesting a sandbox fails with
+/// `ToolError::Unsupported`.
+#[derive(Debug, Clone)]
+pub struct SandboxConfig {
+    /// Host directories mounted read-only at the same path.
+    pub read_only: Vec<PathBuf>,
+    /// Host directories mounted read-write at the same path.
+    pub read_write: Vec<PathBuf>,
+    pub network: bool,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            read_only: DEFAULT_SANDBOX_READ_ONLY.iter().map(PathBuf::from).collect(),
+            read_write: Vec::new(),
+            network: false,
+        }
+    }
+}
+
+impl SandboxConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_read_only(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_only.push(path.into());
+        self
+    }
+
+    pub fn with_read_write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_write.push(path.into());
+        self
+    }
+
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+}
+
+/// Arranges for `command` to enter the sandbox before it execs. `workdir`
+/// is where the tool should start; it falls back to `/` if not mounted.
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+pub(super) fn apply(command: &mut Command, config: &SandboxConfig, workdir: Option<PathBuf>) -> Result<(), ToolError> {
+    let setup = linux::Setup::new(config, workdir).map_err(|e| ToolError::Unsupported(format!("sandbox setup: {}", e)))?;
+    // SAFETY: the hook only makes syscalls on data prepared above; it does
+    // not allocate or take locks, as required between fork and exec.
+    unsafe {
+        command.pre_exec(move || setup.enter());
+    }
+    Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", feature = "sandbox")))]
+pub(super) fn apply(_command: &mut Command, _config: &SandboxConfig, _workdir: Option<PathBuf>) -> Result<(), ToolError> {
+    let what = if cfg!(target_os = "linux") { "sandboxing without the `sandbox` feature" } else { "sandboxing on this platform" };
+    Err(ToolError::Unsupported(what.to_string()))
+}
+
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+mod linux {
+    use std::ffi::CString;
+    use std::io::{Error, Result};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr::null;
+
+    use super::SandboxConfig;
+
+    /// Host directory the sandbox tmpfs is mounted on. Each sandbox mounts
+    /// it in its own mount namespace, so concurrent sandboxes share it and
+    /// the host only ever sees an empty directory.
+    const ROOT_DIR_NAME: &str = "ai-agent-sandbox";
+    const DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/urandom"];
+
+    struct Bind {
+        source: CString,
+        target: CString,
+        /// Directories to create, outermost first, before mounting.
+        dirs: Vec<CString>,
+        read_only: bool,
+        is_file: bool,
+    }
+
+    pub(super) struct Setup {
+        flags: libc::c_int,
+        uid_map: CString,
+        gid_map: CString,
+        root: CString,
+        binds: Vec<Bind>,
+        proc_dir: CString,
+        tmp_dir: CString,
+        workdir: CString,
+    }
+
+    fn c_path(path: &Path) -> Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(Error::other)
+    }
+
+    impl Setup {
+        pub(super) fn new(config: &SandboxConfig, workdir: Option<PathBuf>) -> Result<Self> {
+            let root = std::env::temp_dir().join(ROOT_DIR_NAME);
+            std::fs::create_dir_all(&root)?;
+            let devices = DEVICES.iter().map(|d| (PathBuf::from(d), false, true));
+            let dirs = config
+                .read_only
+                .iter()
+                .map(|p| (p.clone(), true, false))
+                .chain(config.read_write.iter().map(|p| (p.clone(), false, false)));
+            let mut binds = Vec::new();
+            let mut mounted = Vec::new();
+            for (source, read_only, is_file) in dirs.chain(devices) {
+                if !source.is_absolute() || !source.exists() {
+                    continue;
+                }
+                let target = root.join(source.strip_prefix("/").unwrap_or(&source));
+                let mut dirs: Vec<_> = target.ancestors().skip(1).take_while(|a| *a != root).collect();
+                dirs.reverse();
+                if !is_file {
+                    dirs.push(&target);
+                }
+                binds.push(Bind {
+                    source: c_path(&source)?,
+                    target: c_path(&target)?,
+                    dirs: dirs.into_iter().map(c_path).collect::<Result<_>>()?,
+                    read_only,
+                    is_file,
+                });
+                mounted.push(source);
+            }
+            let workdir = workdir
+                .filter(|dir| mounted.iter().any(|m| dir.starts_with(m)))
+                .unwrap_or_else(|| PathBuf::from("/"));
+
+            let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWUTS;
+            if !config.network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            // SAFETY: getuid/getgid cannot fail.
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Ok(Self {
+                flags,
+                uid_map: CString::new(format!("0 {} 1", uid)).map_err(Error::other)?,
+                gid_map: CString::new(format!("0 {} 1", gid)).map_err(Error::other)?,
+                proc_dir: c_path(&root.join("proc"))?,
+                tmp_dir: c_path(&root.join("tmp"))?,
+                root: c_path(&root)?,
+                binds,
+                workdir: c_path(&workdir)?,
+            })
+        }
+
+        /// Runs in the forked child. Forks once more so the tool is pid 1 of
+        /// the new pid namespace; the intermediate process waits for it and
+        /// exits the same way.
+        pub(super) fn enter(&self) -> Result<()> {
+            // SAFETY: raw syscalls on NUL-terminated strings owned by `self`.
+            unsafe {
+                check(libc::unshare(self.flags))?;
+                write_file(c"/proc/self/setgroups", c"deny")?;
+                write_file(c"/proc/self/uid_map", &self.uid_map)?;
+                write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+                let pid = check(libc::fork())?;
+                if pid > 0 {
+                    wait_and_exit(pid);
+                }
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+
+                check(libc::mount(null(), c"/".as_ptr(), null(), libc::MS_REC | libc::MS_PRIVATE, null()))?;
+                check(libc::mount(c"tmpfs".as_ptr(), self.root.as_ptr(), c"tmpfs".as_ptr(), 0, c"mode=0755".as_ptr().cast()))?;
+                for bind in &self.binds {
+                    self.mount_bind(bind)?;
+                }
+                check(libc::mkdir(self.proc_dir.as_ptr(), 0o555))?;
+                check(libc::mount(c"proc".as_ptr(), self.proc_dir.as_ptr(), c"proc".as_ptr(), 0, null()))?;
+                check(libc::mkdir(self.tmp_dir.as_ptr(), 0o777))?;
+                check(libc::chmod(self.tmp_dir.as_ptr(), 0o1777))?;
+                check(libc::chroot(self.root.as_ptr()))?;
+                check(libc::chdir(self.workdir.as_ptr()))?;
+            }
+            Ok(())
+        }
+
+        unsafe fn mount_bind(&self, bind: &Bind) -> Result<()> {
+            for dir in &bind.dirs {
+                if libc::mkdir(dir.as_ptr(), 0o755) != 0 && *libc::__errno_location() != libc::EEXIST {
+                    return Err(Error::last_os_error());
+                }
+            }
+            if bind.is_file {
+                check(libc::close(check(libc::open(bind.target.as_ptr(), libc::O_CREAT | libc::O_WRONLY, 0o644))?))?;
+            }
+            let target = bind.target.as_ptr();
+            check(libc::mount(bind.source.as_ptr(), target, null(), libc::MS_BIND | libc::MS_REC, null()))?;
+            if bind.read_only {
+                // A remount inside a user namespace must keep the flags the
+                // host mount is locked with.
+                let mut stat: libc::statvfs = std::mem::zeroed();
+                check(libc::statvfs(bind.source.as_ptr(), &mut stat))?;
+                let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+                for (st, ms) in [
+                    (libc::ST_NOSUID, libc::MS_NOSUID),
+                    (libc::ST_NODEV, libc::MS_NODEV),
+                    (libc::ST_NOEXEC, libc::MS_NOEXEC),
+                    (libc::ST_NOATIME, libc::MS_NOATIME),
+                    (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+                    (libc::ST_RELATIME, libc::MS_RELATIME),
+                ] {
+                    if stat.f_flag & st != 0 {
+                        flags |= ms;
+                    }
+                }
+                check(libc::mount(null(), target, null(), flags, null()))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn check<T: Default + PartialOrd>(result: T) -> Result<T> {
+        if result < T::default() {
+            Err(Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    unsafe fn write_file(path: &std::ffi::CStr, contents: &std::ffi::CStr) -> Result<()> {
+        let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY))?;
+        let bytes = contents.to_bytes();
+        let written = libc::write(fd, bytes.as_ptr().cast(), bytes.len());
+        libc::close(fd);
+        if written != bytes.len() as isize {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Closes every descriptor (so the parent sees EOF and exec success as
+    /// soon as the tool does), waits for `pid` and mirrors its exit.
+    unsafe fn wait_and_exit(pid: libc::pid_t) -> ! {
+        if libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0) != 0 {
+            for fd in 0..1024 {
+                libc::close(fd);
+            }
+        }
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0 && *libc::__errno_location() == libc::EINTR {}
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ExecOptions, ToolExecutor};
+
+    #[cfg(all(target_os = "linux", feature = "sandbox"))]
+    #[tokio::test]
+    async fn test_sandbox_hides_host_files() {
+        let options = ExecOptions::new().with_sandbox(SandboxConfig::new());
+        let script = "cat /etc/hostname; echo status=$?; echo pid=$$; ls /";
+        let output = match ToolExecutor::execute_tool_with_options("sh", &["-c", script], None, &options).await {
+            Ok(output) => output,
+            Err(e) if format!("{:#}", e).contains("ermission") => {
+                eprintln!("skipping: user namespaces are unavailable ({:#})", e);
+                return;
+            }
+            Err(e) => panic!("{:#}", e),
+        };
+        let stdout = output.stdout_lossy();
+        assert!(std::path::Path::new("/etc/hostname").exists());
+        assert!(stdout.contains("status=1"), "{}", stdout);
+        assert!(stdout.contains("pid=1\n"), "{}", stdout);
+        assert!(!stdout.lines().any(|line| line == "etc"), "{}", stdout);
+        assert!(output.stderr_lossy().contains("/etc/hostname"));
+
+        // The tool keeps its exit status through the intermediate process.
+        let output = ToolExecutor::execute_tool_with_options("sh", &["-c", "exit 3"], None, &options).await.unwrap();
+        assert_eq!(output.exit_code, 3);
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "sandbox")))]
+    #[tokio::test]
+    async fn test_sandbox_unsupported() {
+        let options = ExecOptions::new().with_sandbox(SandboxConfig::new());
+        let err = ToolExecutor::execute_tool_with_options("echo", &[], None, &options).await.unwrap_err();
+        assert!(matches!(err, ToolError::Unsupported(_)));
+    }
+}
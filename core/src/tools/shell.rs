@@ -0,0 +1,96 @@
+// The `shell` tool: scripts run by a shell, arguments passed positionally
+//
+// The script and the arguments never share a command line. On Unix the
+// script is handed to bash through an environment variable and `eval`ed, and
+// each argument is a positional parameter (`"$1"`, `"$2"`, ...), so nothing
+// a caller passes is spliced into shell syntax. PowerShell sees the
+// arguments as `$args`; cmd can only read them as `!SHELL_ARG1!`, ... whose
+// delayed expansion happens after the line is parsed.
+use super::executor::{ExecOptions, ToolError};
+use super::probe::VersionProbe;
+
+/// Carries the script to the shell.
+pub const SHELL_SCRIPT_VAR: &str = "AI_AGENT_SHELL_SCRIPT";
+/// Prefix of the numbered variables carrying arguments to PowerShell and cmd.
+const SHELL_ARG_PREFIX: &str = "SHELL_ARG";
+
+const BASH_WRAPPER: &str = r#"eval "$AI_AGENT_SHELL_SCRIPT""#;
+const POWERSHELL_WRAPPER: &str = concat!(
+    r#"$a = @(); for ($i = 1; Test-Path "env:SHELL_ARG$i"; $i++) { $a += (Get-Item "env:SHELL_ARG$i").Value }; "#,
+    r#"& ([scriptblock]::Create($env:AI_AGENT_SHELL_SCRIPT)) @a"#,
+);
+
+/// Which shell runs `shell` tool scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Bash,
+    PowerShell,
+    /// Windows `cmd`; the script must be a single line.
+    Cmd,
+}
+
+impl Default for ShellKind {
+    /// Bash on Unix, PowerShell on Windows.
+    fn default() -> Self {
+        if cfg!(windows) {
+            Self::PowerShell
+        } else {
+            Self::Bash
+        }
+    }
+}
+
+impl ShellKind {
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::PowerShell => "powershell",
+            Self::Cmd => "cmd",
+        }
+    }
+
+    /// How to ask the shell for its version; cmd has no `--version`.
+    pub fn version_probe(self) -> VersionProbe {
+        match self {
+            Self::Bash => VersionProbe::default(),
+            Self::PowerShell => VersionProbe::new(["-NoProfile", "-Command", "$PSVersionTable.PSVersion.ToString()"]),
+            Self::Cmd => VersionProbe::new(["/c", "ver"]),
+        }
+    }
+
+    /// The program's arguments and the options to run `script` with `args`.
+    /// Fails with a policy violation unless `options.policy` sets
+    /// `allow_shell`; the program and `args` are then checked like any
+    /// command's.
+    pub(super) fn invocation(self, script: &str, args: &[&str], options: &ExecOptions) -> Result<(Vec<String>, ExecOptions), ToolError> {
+        match &options.policy {
+            Some(policy) if policy.allow_shell => policy.check_in(self.program(), args, options.cwd.as_deref())?,
+            _ => {
+                return Err(ToolError::PolicyViolation {
+                    command: self.program().to_string(),
+                    reason: "the shell tool is disabled; set allow_shell in the execution policy to enable it".to_string(),
+                })
+            }
+        }
+        let mut options = options.clone().with_env(SHELL_SCRIPT_VAR, script);
+        let argv = match self {
+            // `$0` is "shell", so the first argument is `$1`.
+            Self::Bash => ["-c", BASH_WRAPPER, "shell"].into_iter().chain(args.iter().copied()).map(String::from).collect(),
+            Self::PowerShell | Self::Cmd => {
+                for (i, arg) in args.iter().enumerate() {
+                    options = options.with_env(format!("{}{}", SHELL_ARG_PREFIX, i + 1), *arg);
+                }
+                match self {
+                    Self::PowerShell => vec!["-NoProfile".into(), "-NonInteractive".into(), "-Command".into(), POWERSHELL_WRAPPER.into()],
+                    _ => vec!["/D".into(), "/V:ON".into(), "/C".into(), format!("%{}%", SHELL_SCRIPT_VAR)],
+                }
+            }
+        };
+        // The wrapper arguments are ours, and cmd's switches look like paths.
+        options.trusted_args = match self {
+            Self::Bash => 3,
+            Self::PowerShell | Self::Cmd => argv.len(),
+        };
+        Ok((argv, options))
+    }
+}
@@ -0,0 +1,101 @@
+// What a tool reads on standard input
+use std::path::PathBuf;
+use std::process::Stdio;
+use anyhow::{Context, Result};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+use tokio::task::JoinHandle;
+
+/// Where a tool's standard input comes from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StdinSource {
+    /// Ours. On Unix a tool run with a timeout or cancellation is in its own
+    /// process group and stops if it reads from the terminal.
+    Inherit,
+    /// Nothing: the tool sees end of input at once.
+    #[default]
+    Null,
+    Text(String),
+    Bytes(Vec<u8>),
+    /// The file's contents, opened when the tool starts.
+    File(PathBuf),
+}
+
+impl StdinSource {
+    /// How the tool's stdin is set up at spawn, and the input to write to
+    /// it. Fails if a `File` source cannot be opened.
+    pub(super) async fn open(&self) -> Result<(Stdio, Option<StdinFeed>)> {
+        Ok(match self {
+            StdinSource::Inherit => (Stdio::inherit(), None),
+            StdinSource::Null => (Stdio::null(), None),
+            StdinSource::Text(text) => (Stdio::piped(), Some(StdinFeed::Bytes(text.clone().into_bytes()))),
+            StdinSource::Bytes(bytes) => (Stdio::piped(), Some(StdinFeed::Bytes(bytes.clone()))),
+            StdinSource::File(path) => {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .with_context(|| format!("Failed to open {} for stdin", path.display()))?;
+                (Stdio::piped(), Some(StdinFeed::File(file)))
+            }
+        })
+    }
+
+    /// For dry runs; `None` when the tool gets no input.
+    pub(super) fn describe(&self) -> Option<String> {
+        match self {
+            StdinSource::Inherit => Some("inherited".to_string()),
+            StdinSource::Null => None,
+            StdinSource::Text(text) => Some(format!("{} bytes of text", text.len())),
+            StdinSource::Bytes(bytes) => Some(format!("{} bytes", bytes.len())),
+            StdinSource::File(path) => Some(format!("from {}", path.display())),
+        }
+    }
+}
+
+/// Input ready to be written to a spawned tool by `feed`.
+pub(super) enum StdinFeed {
+    Bytes(Vec<u8>),
+    File(tokio::fs::File),
+    /// Each line is written followed by `\n`; an error ends the input early.
+    Lines(BoxStream<'static, Result<String>>),
+}
+
+/// Writes `input` to `pipe` from its own task, so the caller can read the
+/// tool's output meanwhile and neither side blocks on a full pipe. The pipe
+/// is closed when the input runs out; a tool that exits without reading
+/// all of it is not an error.
+pub(super) fn feed(mut pipe: ChildStdin, input: StdinFeed) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let written = match input {
+            StdinFeed::Bytes(bytes) => pipe.write_all(&bytes).await,
+            StdinFeed::File(mut file) => tokio::io::copy(&mut file, &mut pipe).await.map(drop),
+            StdinFeed::Lines(mut lines) => {
+                let mut written = Ok(());
+                while let Some(line) = lines.next().await {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "stdin input failed; closing the tool's stdin");
+                            break;
+                        }
+                    };
+                    written = async {
+                        pipe.write_all(line.as_bytes()).await?;
+                        pipe.write_all(b"\n").await
+                    }
+                    .await;
+                    if written.is_err() {
+                        break;
+                    }
+                }
+                written
+            }
+        };
+        if let Err(e) = written.and(pipe.shutdown().await) {
+            if e.kind() != std::io::ErrorKind::BrokenPipe {
+                tracing::debug!(error = %e, "writing tool stdin failed");
+            }
+        }
+    })
+}
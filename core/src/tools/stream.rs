@@ -0,0 +1,240 @@
+// Live tool output: stdout and stderr lines as they are produced
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdout};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::capture::{self, Capture};
+use super::dry_run::{describe_command, dry_run_output};
+use super::executor::{spawn, ExecOptions, ToolError, ToolExecutor, ToolOutput};
+use super::history::ExecutionRecord;
+use super::hooks::ToolTask;
+use super::shell::ShellKind;
+use super::stdin::{self, StdinFeed};
+
+/// Events buffered between the tool and a slow consumer before reading pauses.
+const EVENT_BUFFER: usize = 256;
+
+/// Something a running tool did. Output lines are lossily decoded and have
+/// their line terminator removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolEvent {
+    Stdout(String),
+    Stderr(String),
+    /// Always the last event of a run that was not killed.
+    Exited(i32),
+}
+
+/// A running tool's events, in the order its lines were read. Dropping the
+/// stream kills the tool.
+pub struct ToolStream {
+    events: mpsc::Receiver<ToolEvent>,
+    run: JoinHandle<Result<ToolOutput>>,
+}
+
+impl ToolStream {
+    /// Waits for the tool to finish, discarding events not yet consumed, and
+    /// returns everything it wrote as a `ToolOutput`.
+    pub async fn finish(mut self) -> Result<ToolOutput, ToolError> {
+        self.events.close();
+        while self.events.recv().await.is_some() {}
+        Ok((&mut self.run).await.context("tool task failed")??)
+    }
+}
+
+impl Stream for ToolStream {
+    type Item = ToolEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<ToolEvent>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Drop for ToolStream {
+    fn drop(&mut self) {
+        self.run.abort();
+    }
+}
+
+impl ToolExecutor {
+    /// Starts `tool_name` and streams its output line by line while it runs.
+    /// Policy violations and spawn failures are returned immediately;
+    /// timeouts and cancellation surface from `ToolStream::finish` as
+    /// `ToolError::Timeout` and `ToolError::Cancelled`.
+    /// With `options.log`, the run is recorded when it ends, and
+    /// `options.hooks` after hooks are called then.
+    pub async fn execute_streaming(tool_name: &str, args: &[&str], options: &ExecOptions) -> Result<ToolStream, ToolError> {
+        Ok(start_streaming(tool_name, args, options, None).await?)
+    }
+
+    /// `execute_streaming`, writing each line of `input` followed by `\n` to
+    /// the tool's stdin while its output is read, so a `FileReader::read_lines`
+    /// stream can be piped through a filter of any size. Stdin is closed when
+    /// `input` ends or yields an error; `options.stdin` is not used.
+    pub async fn execute_streaming_with_input<S>(
+        tool_name: &str,
+        args: &[&str],
+        input: S,
+        options: &ExecOptions,
+    ) -> Result<ToolStream, ToolError>
+    where
+        S: Stream<Item = Result<String>> + Send + 'static,
+    {
+        Ok(start_streaming(tool_name, args, options, Some(StdinFeed::Lines(input.boxed()))).await?)
+    }
+}
+
+/// Starts a streamed run fed `input`, or `options.stdin` when there is none.
+async fn start_streaming(tool_name: &str, args: &[&str], options: &ExecOptions, input: Option<StdinFeed>) -> Result<ToolStream> {
+    if options.dry_run {
+        return Ok(dry_run_stream(describe_command(tool_name, args, None, options)?));
+    }
+    let log = options.log.clone().map(|log| (log, ExecutionRecord::start(tool_name, args, options)));
+    let hooked = options.hooks.clone().map(|hooks| (hooks, ToolTask::new(tool_name, args, options)));
+    let spawned = async {
+        if let Some((hooks, task)) = &hooked {
+            hooks.before(task)?;
+        }
+        if let Some(limiter) = &options.rate_limit {
+            limiter.acquire(tool_name).await?;
+        }
+        if options.is_cancelled() {
+            return Err(ToolError::Cancelled { elapsed: Duration::ZERO, partial_stdout: String::new() }.into());
+        }
+        let (stdio, input) = match input {
+            Some(input) => (std::process::Stdio::piped(), Some(input)),
+            None => options.stdin.open().await?,
+        };
+        let mut child = spawn(tool_name, args, stdio, options)?;
+        if let (Some(input), Some(pipe)) = (input, child.stdin.take()) {
+            stdin::feed(pipe, input);
+        }
+        let stdout = child.stdout.take().context("tool stdout was not captured")?;
+        let stderr = child.stderr.take().context("tool stderr was not captured")?;
+        Ok((child, stdout, stderr))
+    };
+    let (child, stdout, stderr) = match spawned.await {
+        Ok(parts) => parts,
+        Err(e) => {
+            if let Some((log, record)) = log {
+                log.record(record.failed(&e)).await;
+            }
+            return Err(match &hooked {
+                Some((hooks, task)) => hooks.after_failed(task, e),
+                None => e,
+            });
+        }
+    };
+    let started = Instant::now();
+    let (tx, events) = mpsc::channel(EVENT_BUFFER);
+    let tool = tool_name.to_string();
+    let options = options.clone();
+
+    let run = tokio::spawn(async move {
+        let result = run_streamed(child, stdout, stderr, tx, &options, started, &tool).await;
+        if let Some((log, record)) = log {
+            log.record(record.finish(&result)).await;
+        }
+        if let Some((hooks, task)) = hooked {
+            hooks.after(&task, &result);
+        }
+        result
+    });
+    Ok(ToolStream { events, run })
+}
+
+/// Pumps a spawned tool's output into `tx` until it exits or times out.
+async fn run_streamed(
+    mut child: Child,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    tx: mpsc::Sender<ToolEvent>,
+    options: &ExecOptions,
+    started: Instant,
+    tool: &str,
+) -> Result<ToolOutput> {
+    let (mut out, mut err) = options.captures();
+    let pumped = async {
+        let (read_out, read_err) = tokio::join!(
+            pump(stdout, &mut out, &tx, ToolEvent::Stdout),
+            pump(stderr, &mut err, &tx, ToolEvent::Stderr),
+        );
+        read_out?;
+        read_err?;
+        child.wait().await
+    };
+    let status = match options.supervise(pumped).await {
+        Ok(status) => status?,
+        Err(why) => return Err(options.interrupt(&mut child, why, tool, started, out.kept()).await.into()),
+    };
+    options.check_limits(&status, err.kept())?;
+    let exit_code = status.code().unwrap_or(-1);
+    let _ = tx.send(ToolEvent::Exited(exit_code)).await;
+    let output = ToolOutput::captured(out, err, exit_code, started.elapsed());
+    output.warn_if_not_utf8(tool);
+    Ok(output)
+}
+
+impl ToolExecutor {
+    /// `execute_shell`, streaming the script's output as it runs.
+    pub async fn execute_shell_streaming(kind: ShellKind, script: &str, args: &[&str], options: &ExecOptions) -> Result<ToolStream, ToolError> {
+        let (argv, options) = match kind.invocation(script, args, options) {
+            Ok(invocation) => invocation,
+            Err(e) => {
+                let e = e.into();
+                if let Some(log) = &options.log {
+                    log.record(ExecutionRecord::start(kind.program(), args, options).failed(&e)).await;
+                }
+                return Err(e.into());
+            }
+        };
+        let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+        Self::execute_streaming(kind.program(), &argv, &options).await
+    }
+}
+
+/// A stream replaying a dry-run description as stdout lines.
+fn dry_run_stream(description: String) -> ToolStream {
+    let (tx, events) = mpsc::channel(EVENT_BUFFER);
+    let run = tokio::spawn(async move {
+        for line in description.lines() {
+            let _ = tx.send(ToolEvent::Stdout(line.to_string())).await;
+        }
+        let _ = tx.send(ToolEvent::Exited(0)).await;
+        Ok(dry_run_output(description))
+    });
+    ToolStream { events, run }
+}
+
+/// Forwards each line of `reader` as an event while keeping a copy in `all`.
+/// Keeps reading after the consumer goes away so the tool never blocks on a
+/// full pipe. Once `all` is full, the rest is drained without events; a
+/// line is cut where the limit falls.
+async fn pump<R: AsyncRead + Unpin>(
+    reader: R,
+    all: &mut Capture,
+    tx: &mpsc::Sender<ToolEvent>,
+    event: fn(String) -> ToolEvent,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if all.room() == 0 {
+            return capture::read_capped(reader, all).await;
+        }
+        let budget = u64::try_from(all.room()).unwrap_or(u64::MAX);
+        if (&mut reader).take(budget).read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+        all.push(&line);
+        let text = String::from_utf8_lossy(&line);
+        let text = text.strip_suffix('\n').map(|t| t.strip_suffix('\r').unwrap_or(t)).unwrap_or(&text);
+        let _ = tx.send(event(text.to_string())).await;
+    }
+}
@@ -0,0 +1,292 @@
+// Command lines built from templates such as `grep -n {pattern} {file}`
+use std::fmt;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::registry::{render, ParamType, ToolArgs, ToolCallError, ToolParameter};
+
+/// A template that could not be parsed.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("invalid command template '{template}': {reason}")]
+pub struct TemplateError {
+    pub template: String,
+    pub reason: String,
+}
+
+/// A command line with named placeholders, split into words on whitespace.
+/// Every word becomes exactly one argument, whatever its values contain,
+/// since the command runs without a shell; a word that is only a
+/// placeholder for an array becomes one argument per item.
+///
+/// - `{name}` is required;
+/// - `{name?}` is optional, and a word using it is left out when it has no value;
+/// - `{name=default}` uses `default` when no value is given;
+/// - `{{` and `}}` are literal braces.
+///
+/// A value starting a word may not start with `-`, so it cannot be taken
+/// for an option, unless the template has a `--` word before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTemplate {
+    source: String,
+    program: String,
+    words: Vec<Word>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Word(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// A `{...}` in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub name: String,
+    pub optional: bool,
+    pub default: Option<String>,
+}
+
+impl CommandTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let error = |reason: &str| TemplateError { template: template.to_string(), reason: reason.to_string() };
+        let mut words = template.split_whitespace().map(|word| parse_word(word).map_err(|reason| error(&reason)));
+        let program = match words.next() {
+            Some(Ok(Word(segments))) => match segments.as_slice() {
+                [Segment::Literal(program)] => program.clone(),
+                _ => return Err(error("the program must be written out, not a placeholder")),
+            },
+            Some(Err(e)) => return Err(e),
+            None => return Err(error("it is empty")),
+        };
+        let words = words.collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { source: template.to_string(), program, words })
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Every placeholder, in order of first use.
+    pub fn placeholders(&self) -> Vec<&Placeholder> {
+        let mut found: Vec<&Placeholder> = Vec::new();
+        for Word(segments) in &self.words {
+            for segment in segments {
+                if let Segment::Placeholder(placeholder) = segment {
+                    if !found.iter().any(|seen| seen.name == placeholder.name) {
+                        found.push(placeholder);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// The parameters the placeholders stand for: strings, required unless
+    /// optional or defaulted.
+    pub fn parameters(&self) -> Vec<ToolParameter> {
+        self.placeholders()
+            .into_iter()
+            .map(|placeholder| {
+                let description = match &placeholder.default {
+                    Some(default) => format!("Value for {{{}}} (default '{}')", placeholder.name, default),
+                    None => format!("Value for {{{}}}", placeholder.name),
+                };
+                let param = ToolParameter::new(&placeholder.name, ParamType::String, description);
+                if placeholder.optional || placeholder.default.is_some() {
+                    param.optional()
+                } else {
+                    param
+                }
+            })
+            .collect()
+    }
+
+    /// The arguments after the program for `arguments`, already validated
+    /// against `parameters`. Fails with `ToolCallError::MissingArgument` for
+    /// a required placeholder without a value.
+    pub fn render(&self, parameters: &[ToolParameter], arguments: &ToolArgs) -> Result<Vec<String>, ToolCallError> {
+        let mut argv = Vec::with_capacity(self.words.len());
+        let mut options_ended = false;
+        for Word(segments) in &self.words {
+            if let [Segment::Literal(literal)] = segments.as_slice() {
+                options_ended |= literal == "--";
+                argv.push(literal.clone());
+                continue;
+            }
+            let Some(values) = self.word_values(segments, parameters, arguments)? else { continue };
+            for value in values {
+                if let (false, Some(Segment::Placeholder(first))) = (options_ended, segments.first()) {
+                    if value.starts_with('-') {
+                        return Err(ToolCallError::OptionLike { name: first.name.clone(), value });
+                    }
+                }
+                argv.push(value);
+            }
+        }
+        Ok(argv)
+    }
+
+    /// The arguments one word renders to, or `None` to leave it out.
+    fn word_values(
+        &self,
+        segments: &[Segment],
+        parameters: &[ToolParameter],
+        arguments: &ToolArgs,
+    ) -> Result<Option<Vec<String>>, ToolCallError> {
+        let mut text = String::new();
+        for segment in segments {
+            let placeholder = match segment {
+                Segment::Literal(literal) => {
+                    text.push_str(literal);
+                    continue;
+                }
+                Segment::Placeholder(placeholder) => placeholder,
+            };
+            let mut values = match (arguments.get(&placeholder.name), &placeholder.default) {
+                (Some(value), _) => values(parameters, &placeholder.name, value)?,
+                (None, Some(default)) => vec![default.clone()],
+                (None, None) if placeholder.optional => return Ok(None),
+                (None, None) => return Err(ToolCallError::MissingArgument(placeholder.name.clone())),
+            };
+            // Only a placeholder alone in its word can stand for several arguments.
+            if segments.len() == 1 {
+                return Ok(Some(values));
+            }
+            if values.len() != 1 {
+                return Err(ToolCallError::WrongType {
+                    name: placeholder.name.clone(),
+                    expected: ParamType::String,
+                    found: "an array",
+                });
+            }
+            text.push_str(&values.remove(0));
+        }
+        Ok(Some(vec![text]))
+    }
+}
+
+/// `value` as strings, as its parameter renders it.
+fn values(parameters: &[ToolParameter], name: &str, value: &Value) -> Result<Vec<String>, ToolCallError> {
+    match parameters.iter().find(|param| param.name == name) {
+        Some(param) => render(param, value),
+        None => Ok(vec![value.as_str().map_or_else(|| value.to_string(), str::to_string)]),
+    }
+}
+
+impl fmt::Display for CommandTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn parse_word(word: &str) -> Result<Word, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err("unmatched '}'; write '}}' for a literal brace".to_string()),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => return Err(format!("placeholder '{{{}' is not closed", inner)),
+                    }
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Placeholder(parse_placeholder(&inner)?));
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(Word(segments))
+}
+
+fn parse_placeholder(inner: &str) -> Result<Placeholder, String> {
+    let (name, optional, default) = match inner.split_once('=') {
+        Some((name, default)) => (name, false, Some(default.to_string())),
+        None => match inner.strip_suffix('?') {
+            Some(name) => (name, true, None),
+            None => (inner, false, None),
+        },
+    };
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("'{{{}}}' is not a placeholder; names are letters, digits and '_'", inner));
+    }
+    Ok(Placeholder { name: name.to_string(), optional, default })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::registry::ToolSpec;
+    use serde_json::json;
+
+    fn render_with(spec: &ToolSpec, arguments: Value) -> Result<Vec<String>, ToolCallError> {
+        spec.build_args(&arguments)
+    }
+
+    #[test]
+    fn test_template_parsing() {
+        let template = CommandTemplate::parse("grep -n --max-count={max=10} {{x}} {pattern} {files?}").unwrap();
+        assert_eq!(template.program(), "grep");
+        let names: Vec<_> = template.placeholders().iter().map(|p| (p.name.as_str(), p.optional, p.default.clone())).collect();
+        assert_eq!(names, vec![("max", false, Some("10".into())), ("pattern", false, None), ("files", true, None)]);
+        let required: Vec<_> = template.parameters().iter().map(|p| (p.name.clone(), p.required)).collect();
+        assert_eq!(required, vec![("max".into(), false), ("pattern".into(), true), ("files".into(), false)]);
+
+        for bad in ["", "{program} x", "grep {pattern", "grep }", "grep {1st}", "grep {}"] {
+            assert!(CommandTemplate::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_templated_tool_renders_one_argument_per_word() {
+        let spec = ToolSpec::templated("find_text", "Search a file", "grep -n --max-count={max=10} {pattern} {file} {extra?}")
+            .unwrap()
+            .param(ToolParameter::new("file", ParamType::String, "File to search"));
+        assert_eq!(
+            render_with(&spec, json!({"pattern": "a b; rm -rf /", "file": "notes.txt"})).unwrap(),
+            vec!["-n", "--max-count=10", "a b; rm -rf /", "notes.txt"]
+        );
+        assert_eq!(
+            render_with(&spec, json!({"pattern": "x", "file": "f", "max": "3", "extra": "$(id)"})).unwrap(),
+            vec!["-n", "--max-count=3", "x", "f", "$(id)"]
+        );
+        assert_eq!(spec.parameters.iter().find(|p| p.name == "file").unwrap().description, "File to search");
+        assert_eq!(render_with(&spec, json!({"file": "f"})).unwrap_err(), ToolCallError::MissingArgument("pattern".into()));
+        assert!(matches!(
+            render_with(&spec, json!({"pattern": "--exec=sh", "file": "f"})).unwrap_err(),
+            ToolCallError::OptionLike { ref name, .. } if name == "pattern"
+        ));
+        // Inside a word, or after `--`, a leading dash is just text.
+        assert!(render_with(&spec, json!({"pattern": "p", "file": "f", "max": "-1"})).is_ok());
+        let spec = ToolSpec::templated("search", "Search", "grep -e {pattern} -- {files}")
+            .unwrap()
+            .param(ToolParameter::new("files", ParamType::Array, "Files"));
+        assert_eq!(
+            render_with(&spec, json!({"pattern": "x", "files": ["-a", "b c"]})).unwrap(),
+            vec!["-e", "x", "--", "-a", "b c"]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// Running tools on a pseudo-terminal, for those that act differently on one
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use portable_pty::PtySize;
+
+use super::dry_run::{describe_command, dry_run_output};
+use super::executor::{prepare, ExecOptions, ToolError, ToolExecutor, ToolOutput};
+use super::process::{read_chunks, Pty};
+
+/// How long a tool's terminal is still read once the tool has exited, for
+/// what it wrote last. Whatever it started that still has the terminal
+/// open is killed then.
+const PTY_DRAIN: Duration = Duration::from_millis(200);
+
+/// The size of the terminal a tool runs on, in character cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for TerminalSize {
+    fn default() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
+impl ToolExecutor {
+    /// Runs `tool_name` on a pseudo-terminal of the default size, so it
+    /// behaves as it does when run by hand; see
+    /// `execute_tool_pty_with_options`.
+    pub async fn execute_tool_pty(tool_name: &str, args: &[&str]) -> Result<ToolOutput, ToolError> {
+        Self::execute_tool_pty_with_options(tool_name, args, TerminalSize::default(), &ExecOptions::default()).await
+    }
+
+    /// Runs `tool_name` on a new pseudo-terminal of `size`, for tools that
+    /// only color their output, show progress or flush each line when on
+    /// one. Its stdin, stdout and stderr are all the terminal, so the
+    /// output's stdout is everything it wrote, as the terminal passed it
+    /// on: line ends as `\r\n` and escape sequences kept; stderr is empty.
+    /// Nothing is typed into it, and `options.stdin` and `options.retry`
+    /// do not apply; otherwise `options` apply as for
+    /// `execute_tool_with_options`. The tool leads a session of its own,
+    /// which a timeout or cancellation takes down. The terminal is closed
+    /// once the tool exits, after killing anything it left running on it.
+    /// Unix only for now; elsewhere this fails with
+    /// `ToolError::Unsupported`.
+    pub async fn execute_tool_pty_with_options(
+        tool_name: &str,
+        args: &[&str],
+        size: TerminalSize,
+        options: &ExecOptions,
+    ) -> Result<ToolOutput, ToolError> {
+        if cfg!(not(unix)) {
+            return Err(ToolError::Unsupported("running tools on a pseudo-terminal on this platform".to_string()));
+        }
+        if options.dry_run {
+            return Ok(dry_run_output(describe_command(tool_name, args, None, options)?));
+        }
+        Ok(options.recorded(tool_name, args, run_on_pty(tool_name, args, size, options)).await?)
+    }
+}
+
+async fn run_on_pty(tool_name: &str, args: &[&str], size: TerminalSize, options: &ExecOptions) -> Result<ToolOutput> {
+    if let Some(limiter) = &options.rate_limit {
+        limiter.acquire(tool_name).await?;
+    }
+    let started = Instant::now();
+    if options.is_cancelled() {
+        return Err(ToolError::Cancelled { elapsed: Duration::ZERO, partial_stdout: String::new() }.into());
+    }
+    let pty = Pty::open_sized(PtySize { rows: size.rows, cols: size.cols, ..PtySize::default() })?;
+    let mut command = prepare(tool_name, args, options)?;
+    pty.attach(&mut command)?;
+    let mut child = command.spawn().map_err(|e| ToolError::spawn_at(tool_name, options.priority.unwrap_or_default(), e))?;
+    // Our copies of the terminal's device close with `command`, so reads
+    // end once the tool and whatever it started are gone.
+    drop(command);
+    let session = child.id();
+    let mut chunks = read_chunks(pty.take_reader().expect("a new terminal has its reader"));
+
+    let (mut output, stderr) = options.captures();
+    let run = async {
+        loop {
+            tokio::select! {
+                Some(chunk) = chunks.recv() => output.push(&chunk?),
+                status = child.wait() => break Ok::<_, anyhow::Error>(status?),
+            }
+        }
+    };
+    let status = match options.supervise(run).await {
+        Ok(status) => status?,
+        Err(why) => return Err(options.interrupt(&mut child, why, tool_name, started, output.kept()).await.into()),
+    };
+    let drain = async {
+        while let Some(chunk) = chunks.recv().await {
+            output.push(&chunk?);
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    match tokio::time::timeout(PTY_DRAIN, drain).await {
+        Ok(drained) => drained?,
+        Err(_) => {
+            if let Some(session) = session {
+                kill_session(session);
+            }
+        }
+    }
+    drop(pty);
+
+    options.check_limits(&status, output.kept())?;
+    let duration = started.elapsed();
+    let exit_code = status.code().unwrap_or(-1);
+    tracing::debug!(tool = %tool_name, exit_code, duration_ms = duration.as_millis() as u64, "tool finished on a terminal");
+    let output = ToolOutput::captured(output, stderr, exit_code, duration);
+    output.warn_if_not_utf8(tool_name);
+    Ok(output)
+}
+
+/// Kills what is left of the session a tool led. Its process group has
+/// the tool's pid, which is not reused while any of the group remain.
+fn kill_session(session: u32) {
+    #[cfg(unix)]
+    // SAFETY: plain syscall; only the tool's leftovers have this group.
+    unsafe {
+        libc::killpg(session as libc::pid_t, libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = session;
+}
@@ -0,0 +1,39 @@
+// Safe mode is process-wide and cannot be turned off, so it is tested in a
+// binary of its own.
+use ai_agent_core::{safe_mode, CoreError, FileReader, FileWriter, ProcessManager, ToolError, ToolExecutor, ToolRegistry};
+use serde_json::json;
+
+fn is_safe_mode(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<CoreError>(), Some(CoreError::SafeMode { .. }))
+}
+
+#[tokio::test]
+async fn test_safe_mode_blocks_writes_spawns_and_unsafe_tools() {
+    let dir = tempfile::tempdir().unwrap();
+    let existing = dir.path().join("notes.txt");
+    std::fs::write(&existing, "keep me\n").unwrap();
+    safe_mode::enable();
+    assert!(safe_mode::is_enabled());
+
+    let created = dir.path().join("new.txt");
+    assert!(is_safe_mode(&FileWriter::write_file(&created, "x").await.unwrap_err()));
+    assert!(is_safe_mode(&FileWriter::append_line(&existing, "more").await.unwrap_err()));
+    assert!(is_safe_mode(&FileWriter::new().write(&created, b"x").await.unwrap_err()));
+    assert!(!created.exists());
+    assert_eq!(FileReader::read_file(&existing).await.unwrap(), "keep me\n");
+
+    let err = ProcessManager::new().spawn_process("true", &[]).await.unwrap_err();
+    assert!(is_safe_mode(&err), "{:#}", err);
+    let err = ToolExecutor::execute_tool("true", &[]).await.unwrap_err();
+    assert!(matches!(err, ToolError::Other(ref e) if is_safe_mode(e)), "{}", err);
+
+    let registry = ToolRegistry::builtin();
+    let path = existing.to_string_lossy();
+    let read = registry.call("read_file", &json!({"path": path})).await.unwrap();
+    assert_eq!(read.stdout_lossy(), "keep me\n");
+    let grep = registry.call("grep", &json!({"pattern": "keep", "paths": [path]})).await.unwrap();
+    assert_eq!(grep.stdout_lossy(), "1:keep me\n");
+    let err = registry.call("write_file", &json!({"path": path, "content": "gone"})).await.unwrap_err();
+    assert!(err.to_string().contains("not allowed in safe mode"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "keep me\n");
+}
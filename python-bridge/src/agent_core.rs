@@ -1,24 +1,418 @@
 // Agent core bridge implementation
+use pyo3::exceptions::{PyRuntimeError, PyStopIteration, PyTypeError, PyValueError};
+use std::time::{Duration, Instant};
+use ai_agent_core::{HeartbeatThread, TaskResult, DEFAULT_HEARTBEAT_INTERVAL};
 use pyo3::prelude::*;
+use tokio::sync::mpsc;
+
+use crate::async_bridge::AsyncBridge;
+use crate::stats::{self, TaskTimer};
+
+/// Chunks buffered between the backend and the consumer before production pauses.
+pub const DEFAULT_STREAM_BUFFER: usize = 16;
+
+/// Seconds between calls to a task's `heartbeat` callback.
+pub const DEFAULT_HEARTBEAT_SECONDS: f64 = DEFAULT_HEARTBEAT_INTERVAL.as_secs_f64();
 
 #[pyclass]
-pub struct AgentCore;
+pub struct AgentCore {
+    /// Python callable taking the task and returning an iterable of text chunks.
+    backend: Option<PyObject>,
+    /// Reported as the model in task results.
+    model: String,
+}
 
 #[pymethods]
 impl AgentCore {
     #[new]
-    pub fn new() -> Self {
-        Self
+    #[pyo3(signature = (backend=None, model=None))]
+    pub fn new(backend: Option<PyObject>, model: Option<String>) -> Self {
+        Self { backend, model: model.unwrap_or_else(|| "python".to_string()) }
+    }
+
+    /// Runs `task` on the backend and returns a `TaskResult` with its output
+    /// joined together.
+    /// `progress(fraction, message)` is called at the start, after each chunk
+    /// when the backend returns a sized collection, and at the end. An
+    /// exception raised by `progress` aborts the task and is re-raised.
+    /// `heartbeat(beat, elapsed_seconds)` is called every
+    /// `heartbeat_interval` seconds while the task runs; see `Heartbeat`.
+    #[pyo3(signature = (task, progress=None, heartbeat=None, heartbeat_interval=DEFAULT_HEARTBEAT_SECONDS))]
+    pub fn execute_task(
+        &self,
+        py: Python<'_>,
+        task: String,
+        progress: Option<PyObject>,
+        heartbeat: Option<PyObject>,
+        heartbeat_interval: f64,
+    ) -> PyResult<PyTaskResult> {
+        let started = Instant::now();
+        let mut timer = TaskTimer::start(true);
+        let backend = self.backend(py)?;
+        let progress = progress.map(|callback| ProgressCallback::new(py, callback)).transpose()?;
+        let heartbeat = heartbeat.map(|callback| Heartbeat::new(py, callback, heartbeat_interval)).transpose()?;
+        let report = |fraction: f64, message: &str| match &progress {
+            Some(progress) => progress.report(fraction, message),
+            None => Ok(()),
+        };
+
+        // Only backend and callback calls take the GIL; everything else runs without it.
+        let output = timer.allow_threads(py, || {
+            // Stopped when this closure returns, before the GIL is taken back.
+            let _heartbeat = heartbeat.map(Heartbeat::start);
+            report(0.0, "started")?;
+            let (iterator, total) = stats::with_gil(|py| -> PyResult<(PyObject, Option<usize>)> {
+                let chunks = backend.call1(py, (task.as_str(),))?;
+                let chunks = chunks.as_ref(py);
+                Ok((chunks.iter()?.to_object(py), chunks.len().ok()))
+            })?;
+
+            let mut output = String::new();
+            let mut done = 0;
+            while let Some(chunk) = next_chunk(&iterator)? {
+                output.push_str(&chunk);
+                done += 1;
+                if let Some(total) = total.filter(|&total| done < total) {
+                    report(done as f64 / total as f64, &format!("{}/{} chunks", done, total))?;
+                }
+            }
+            report(1.0, "completed")?;
+            Ok::<_, PyErr>(output)
+        })?;
+        let result = TaskResult::new(task, output, self.model.as_str()).with_duration(started.elapsed());
+        Ok(PyTaskResult(result))
+    }
+
+    /// Runs `task` on the backend and returns an iterator over its chunks as
+    /// they are produced. At most `buffer` chunks are held in between, so a
+    /// slow consumer pauses the backend instead of growing memory.
+    /// `heartbeat` is called as for `execute_task` until the backend is
+    /// done or the stream is closed.
+    #[pyo3(signature = (task, buffer=DEFAULT_STREAM_BUFFER, heartbeat=None, heartbeat_interval=DEFAULT_HEARTBEAT_SECONDS))]
+    pub fn execute_task_streaming(
+        &self,
+        py: Python<'_>,
+        task: String,
+        buffer: usize,
+        heartbeat: Option<PyObject>,
+        heartbeat_interval: f64,
+    ) -> PyResult<TokenStream> {
+        let backend = self.backend(py)?;
+        if buffer == 0 {
+            return Err(PyValueError::new_err("buffer must be at least 1"));
+        }
+        let heartbeat = heartbeat.map(|callback| Heartbeat::new(py, callback, heartbeat_interval)).transpose()?;
+
+        let (tx, rx) = mpsc::channel(buffer);
+        AsyncBridge::spawn_blocking(move || {
+            let _heartbeat = heartbeat.map(Heartbeat::start);
+            produce(backend, task, tx)
+        });
+        Ok(TokenStream { rx: Some(rx) })
     }
-    
-    pub fn execute_task(&self, _task: &str) -> PyResult<String> {
-        // TODO: Implement agent core bridge
-        todo!("Implement in T029")
+}
+
+impl AgentCore {
+    fn backend(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.backend
+            .as_ref()
+            .map(|backend| backend.clone_ref(py))
+            .ok_or_else(|| PyRuntimeError::new_err("AgentCore has no backend configured"))
     }
 }
 
 impl Default for AgentCore {
     fn default() -> Self {
-        Self::new()
+        Self::new(None, None)
+    }
+}
+
+/// The outcome of `AgentCore.execute_task`; `str()` gives the output text.
+#[pyclass(name = "TaskResult")]
+#[derive(Debug, Clone)]
+pub struct PyTaskResult(pub TaskResult);
+
+#[pymethods]
+impl PyTaskResult {
+    #[getter]
+    fn task(&self) -> &str {
+        &self.0.task
+    }
+
+    #[getter]
+    fn output(&self) -> &str {
+        &self.0.output
+    }
+
+    #[getter]
+    fn model(&self) -> &str {
+        &self.0.model
+    }
+
+    /// Wall-clock time in seconds.
+    #[getter]
+    fn duration(&self) -> f64 {
+        self.0.duration.as_secs_f64()
+    }
+
+    #[getter]
+    fn prompt_tokens(&self) -> Option<u64> {
+        self.0.prompt_tokens
+    }
+
+    #[getter]
+    fn completion_tokens(&self) -> Option<u64> {
+        self.0.completion_tokens
+    }
+
+    #[getter]
+    fn success(&self) -> bool {
+        self.0.success
+    }
+
+    pub fn text(&self) -> &str {
+        self.0.text()
+    }
+
+    pub fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __str__(&self) -> &str {
+        self.0.text()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("TaskResult(model={:?}, success={}, duration={:.3})", self.0.model, self.0.success, self.duration())
+    }
+}
+
+/// Pulls chunks from the backend one at a time, holding the GIL only while
+/// calling into Python. Stops as soon as the consumer goes away.
+fn produce(backend: PyObject, task: String, tx: mpsc::Sender<PyResult<String>>) {
+    let iterator = stats::with_gil(|py| -> PyResult<PyObject> {
+        let chunks = backend.call1(py, (task,))?;
+        Ok(chunks.as_ref(py).iter()?.to_object(py))
+    });
+    let iterator = match iterator {
+        Ok(iterator) => iterator,
+        Err(e) => {
+            let _ = tx.blocking_send(Err(e));
+            return;
+        }
+    };
+
+    loop {
+        let item = match next_chunk(&iterator) {
+            Ok(Some(chunk)) => Ok(chunk),
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        let failed = item.is_err();
+        // Blocks while the buffer is full; errors once the stream is dropped or closed.
+        if tx.blocking_send(item).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Advances a Python iterator, holding the GIL only for the call.
+fn next_chunk(iterator: &PyObject) -> PyResult<Option<String>> {
+    stats::with_gil(|py| {
+        let iterator: &pyo3::types::PyIterator = iterator.downcast(py)?;
+        match iterator.call_method0("__next__") {
+            Ok(chunk) => Ok(Some(chunk.str()?.to_string())),
+            Err(e) if e.is_instance_of::<PyStopIteration>(py) => Ok(None),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// A Python callable receiving `(fraction, message)` progress updates.
+pub struct ProgressCallback(PyObject);
+
+impl ProgressCallback {
+    pub fn new(py: Python<'_>, callback: PyObject) -> PyResult<Self> {
+        if !callback.as_ref(py).is_callable() {
+            return Err(PyTypeError::new_err("progress must be callable"));
+        }
+        Ok(Self(callback))
+    }
+
+    /// Calls back into Python; safe to call without holding the GIL.
+    pub fn report(&self, fraction: f64, message: &str) -> PyResult<()> {
+        stats::with_gil(|py| self.0.call1(py, (fraction.clamp(0.0, 1.0), message)).map(drop))
+    }
+}
+
+/// A Python callable receiving `(beat, elapsed_seconds)` while a task runs,
+/// from a thread of its own. Exceptions it raises are printed and do not
+/// affect the task.
+pub struct Heartbeat {
+    callback: PyObject,
+    interval: Duration,
+}
+
+impl Heartbeat {
+    pub fn new(py: Python<'_>, callback: PyObject, interval: f64) -> PyResult<Self> {
+        if !callback.as_ref(py).is_callable() {
+            return Err(PyTypeError::new_err("heartbeat must be callable"));
+        }
+        let interval = Duration::try_from_secs_f64(interval)
+            .ok()
+            .filter(|interval| !interval.is_zero())
+            .ok_or_else(|| PyValueError::new_err("heartbeat_interval must be a positive number of seconds"))?;
+        Ok(Self { callback, interval })
+    }
+
+    /// Starts calling back; dropping the result stops it. Must not be
+    /// dropped while holding the GIL, since it waits for a call in progress.
+    pub fn start(self) -> HeartbeatThread {
+        let Self { callback, interval } = self;
+        HeartbeatThread::start(interval, move |beat| {
+            stats::with_gil(|py| {
+                if let Err(e) = callback.call1(py, (beat.beat, beat.elapsed.as_secs_f64())) {
+                    e.print(py);
+                }
+            })
+        })
+    }
+}
+
+/// Iterator over streamed task output.
+#[pyclass]
+pub struct TokenStream {
+    rx: Option<mpsc::Receiver<PyResult<String>>>,
+}
+
+#[pymethods]
+impl TokenStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<String>> {
+        let Some(rx) = self.rx.as_mut() else {
+            return Ok(None);
+        };
+        // Release the GIL while waiting so the producer can call the backend.
+        match py.allow_threads(|| rx.blocking_recv()) {
+            Some(item) => item.map(Some),
+            None => {
+                self.rx = None;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Stops the backend early; further iteration ends immediately.
+    pub fn close(&mut self) {
+        self.rx = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    fn backend<'py>(py: Python<'py>, code: &str) -> (PyObject, &'py PyDict) {
+        let globals = PyDict::new(py);
+        py.run(code, Some(globals), None).unwrap();
+        (globals.get_item("backend").unwrap().unwrap().to_object(py), globals)
+    }
+
+    #[test]
+    fn test_streams_chunks_in_order() {
+        Python::with_gil(|py| {
+            let (backend, _) = backend(py, "def backend(task):\n    for word in task.split():\n        yield word + ' '\n");
+            let core = AgentCore::new(Some(backend), None);
+            let mut stream = core.execute_task_streaming(py, "a b c".into(), 2, None, DEFAULT_HEARTBEAT_SECONDS).unwrap();
+            let mut chunks = Vec::new();
+            while let Some(chunk) = stream.__next__(py).unwrap() {
+                chunks.push(chunk);
+            }
+            assert_eq!(chunks, vec!["a ", "b ", "c "]);
+        });
+    }
+
+    #[test]
+    fn test_slow_consumer_pauses_backend() {
+        Python::with_gil(|py| {
+            let code = "produced = []\ndef backend(task):\n    for i in range(100):\n        produced.append(i)\n        yield str(i)\n";
+            let (backend, globals) = backend(py, code);
+            let core = AgentCore::new(Some(backend), None);
+            let mut stream = core.execute_task_streaming(py, "t".into(), 2, None, DEFAULT_HEARTBEAT_SECONDS).unwrap();
+            assert_eq!(stream.__next__(py).unwrap().as_deref(), Some("0"));
+            py.allow_threads(|| std::thread::sleep(std::time::Duration::from_millis(200)));
+
+            // One taken, two buffered, and at most one more waiting to be sent.
+            let produced = globals.get_item("produced").unwrap().unwrap().len().unwrap();
+            assert!(produced <= 4, "backend ran ahead: {} chunks", produced);
+            stream.close();
+            assert!(stream.__next__(py).unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        Python::with_gil(|py| {
+            let (backend, globals) = backend(py, "updates = []\ndef backend(task):\n    return task.split()\ndef progress(p, m):\n    updates.append((p, m))\n");
+            let progress = globals.get_item("progress").unwrap().unwrap().to_object(py);
+            let core = AgentCore::new(Some(backend), Some("echo".into()));
+            let result = core.execute_task(py, "a b".into(), Some(progress), None, DEFAULT_HEARTBEAT_SECONDS).unwrap();
+            assert_eq!((result.text(), result.model(), result.success()), ("ab", "echo", true));
+            assert!(result.to_json().unwrap().contains(r#""task":"a b""#));
+            let updates: Vec<(f64, String)> = globals.get_item("updates").unwrap().unwrap().extract().unwrap();
+            assert_eq!(
+                updates,
+                vec![(0.0, "started".to_string()), (0.5, "1/2 chunks".to_string()), (1.0, "completed".to_string())]
+            );
+            assert!(core.execute_task(py, "a".into(), Some(1.to_object(py)), None, DEFAULT_HEARTBEAT_SECONDS).unwrap_err().is_instance_of::<PyTypeError>(py));
+        });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_progress_errors_fail_the_task() {
+        Python::with_gil(|py| {
+            let (backend, globals) = backend(py, "def backend(task):\n    yield task\ndef progress(p, m):\n    if p > 0.9:\n        raise KeyError('cancelled')\n");
+            let progress = globals.get_item("progress").unwrap().unwrap().to_object(py);
+            let err = AgentCore::new(Some(backend), None).execute_task(py, "t".into(), Some(progress), None, DEFAULT_HEARTBEAT_SECONDS).unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyKeyError>(py));
+        });
+    }
+
+    #[test]
+    fn test_heartbeat_while_the_task_runs() {
+        Python::with_gil(|py| {
+            let code = "import time\nbeats = []\ndef backend(task):\n    time.sleep(0.15)\n    return [task]\ndef heartbeat(beat, elapsed):\n    beats.append(beat)\n";
+            let (backend, globals) = backend(py, code);
+            let heartbeat = globals.get_item("heartbeat").unwrap().unwrap().to_object(py);
+            let core = AgentCore::new(Some(backend), None);
+            core.execute_task(py, "t".into(), None, Some(heartbeat.clone_ref(py)), 0.03).unwrap();
+            let beats: Vec<u64> = globals.get_item("beats").unwrap().unwrap().extract().unwrap();
+            assert!((2..=5).contains(&beats.len()), "{:?}", beats);
+            assert_eq!(beats[0], 1);
+            // Nothing more once the task is done.
+            py.allow_threads(|| std::thread::sleep(std::time::Duration::from_millis(100)));
+            assert_eq!(globals.get_item("beats").unwrap().unwrap().len().unwrap(), beats.len());
+
+            let err = core.execute_task(py, "t".into(), None, Some(heartbeat), 0.0).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn test_backend_errors_surface_in_python() {
+        Python::with_gil(|py| {
+            let (backend, _) = backend(py, "def backend(task):\n    yield 'ok'\n    raise ValueError('model crashed')\n");
+            let core = AgentCore::new(Some(backend), None);
+            let mut stream = core.execute_task_streaming(py, "t".into(), 4, None, DEFAULT_HEARTBEAT_SECONDS).unwrap();
+            assert_eq!(stream.__next__(py).unwrap().as_deref(), Some("ok"));
+            let err = stream.__next__(py).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert!(stream.__next__(py).unwrap().is_none());
+            assert!(AgentCore::default().execute_task_streaming(py, "t".into(), 4, None, DEFAULT_HEARTBEAT_SECONDS).is_err());
+        });
+    }
+}
@@ -1,4 +1,9 @@
 // Async bridge implementation
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::stats::TaskTimer;
 
 pub struct AsyncBridge;
 
@@ -6,7 +11,26 @@ impl AsyncBridge {
     pub fn new() -> Self {
         Self
     }
-    
+
+    /// The tokio runtime shared by everything the bridge runs on behalf of Python.
+    pub fn runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| Runtime::new().expect("failed to start tokio runtime"))
+    }
+
+    /// Runs `f` on the runtime's blocking threads, which start without the
+    /// GIL, timed as a task in `BridgeStats`.
+    pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        Self::runtime().spawn_blocking(move || {
+            let _timer = TaskTimer::start(false);
+            f()
+        })
+    }
+
     // TODO: Implement async bridge in T031
     // pub fn run_async_task(_py: Python, _task: &str) -> PyResult<&PyAny> {
     //     todo!("Implement in T031")
@@ -17,4 +41,4 @@ impl Default for AsyncBridge {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
@@ -2,27 +2,134 @@
 use pyo3::prelude::*;
 use serde::{Serialize, Deserialize};
 
+#[cfg(feature = "arrow")]
+use pyo3::exceptions::{PyImportError, PyValueError};
+#[cfg(feature = "arrow")]
+use pyo3::types::{PyBytes, PyDict};
+
 #[derive(Serialize, Deserialize)]
 #[pyclass]
 pub struct DataExchange {
     data: String,
+    /// Tabular payload as an Arrow IPC stream, when built from a DataFrame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arrow_ipc: Option<Vec<u8>>,
 }
 
 #[pymethods]
 impl DataExchange {
     #[new]
     pub fn new(data: String) -> Self {
-        Self { data }
+        Self { data, arrow_ipc: None }
     }
-    
+
     pub fn serialize(&self) -> PyResult<String> {
         // TODO: Implement data serialization
         Ok(self.data.clone())
     }
-    
+
     #[staticmethod]
     pub fn deserialize(data: String) -> PyResult<Self> {
         // TODO: Implement data deserialization
         Ok(Self::new(data))
     }
-}
\ No newline at end of file
+
+    /// Captures a pandas DataFrame as Arrow IPC. pyarrow's pandas metadata
+    /// keeps column names, the index and nullable extension dtypes, so
+    /// `to_dataframe` restores an equivalent frame.
+    #[cfg(feature = "arrow")]
+    #[staticmethod]
+    pub fn from_dataframe(df: &PyAny) -> PyResult<Self> {
+        let py = df.py();
+        let pa = import_pyarrow(py)?;
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("preserve_index", true)?;
+        let table = pa.getattr("Table")?.call_method("from_pandas", (df,), Some(kwargs))?;
+
+        let sink = pa.getattr("BufferOutputStream")?.call0()?;
+        let writer = pa.getattr("ipc")?.call_method1("new_stream", (sink, table.getattr("schema")?))?;
+        writer.call_method1("write_table", (table,))?;
+        writer.call_method0("close")?;
+        let bytes: Vec<u8> = sink.call_method0("getvalue")?.call_method0("to_pybytes")?.extract()?;
+        Ok(Self { data: String::new(), arrow_ipc: Some(bytes) })
+    }
+
+    /// Rebuilds the DataFrame captured by `from_dataframe`.
+    #[cfg(feature = "arrow")]
+    pub fn to_dataframe(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let bytes = self
+            .arrow_ipc
+            .as_deref()
+            .ok_or_else(|| PyValueError::new_err("DataExchange does not hold a DataFrame"))?;
+        let pa = import_pyarrow(py)?;
+        let reader = pa.getattr("ipc")?.call_method1("open_stream", (PyBytes::new(py, bytes),))?;
+        let table = reader.call_method0("read_all")?;
+        Ok(table.call_method0("to_pandas")?.to_object(py))
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl DataExchange {
+    /// Decodes the Arrow payload for use on the Rust side.
+    pub fn record_batches(&self) -> anyhow::Result<Vec<arrow_array::RecordBatch>> {
+        let bytes = self
+            .arrow_ipc
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("DataExchange does not hold a DataFrame"))?;
+        let reader = arrow_ipc::reader::StreamReader::try_new(std::io::Cursor::new(bytes), None)?;
+        Ok(reader.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn import_pyarrow(py: Python<'_>) -> PyResult<&PyModule> {
+    // pandas is imported first so a missing pandas is reported as such.
+    py.import("pandas")
+        .and_then(|_| py.import("pyarrow"))
+        .and_then(|pa| py.import("pyarrow.ipc").map(|_| pa))
+        .map_err(|e| {
+            PyImportError::new_err(format!(
+                "DataFrame exchange requires pandas and pyarrow (pip install pandas pyarrow): {}",
+                e
+            ))
+        })
+}
+
+#[cfg(all(test, feature = "arrow"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataframe_round_trip() {
+        Python::with_gil(|py| {
+            if py.import("pandas").is_err() || py.import("pyarrow").is_err() {
+                eprintln!("skipping: pandas/pyarrow not installed");
+                return;
+            }
+            let locals = PyDict::new(py);
+            py.run(
+                "import pandas as pd\n\
+                 df = pd.DataFrame({'name': ['a', None, 'c'], 'count': pd.array([1, None, 3], dtype='Int64')},\n\
+                                   index=pd.Index(['x', 'y', 'z'], name='key'))\n",
+                None,
+                Some(locals),
+            )
+            .unwrap();
+            let df = locals.get_item("df").unwrap().unwrap();
+
+            let exchange = DataExchange::from_dataframe(df).unwrap();
+            let batches = exchange.record_batches().unwrap();
+            assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+            let restored = exchange.to_dataframe(py).unwrap();
+            locals.set_item("restored", restored).unwrap();
+            let same: bool = py
+                .eval("df.equals(restored) and list(restored.dtypes.astype(str)) == ['object', 'Int64'] and restored.index.name == 'key'", None, Some(locals))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(same);
+            assert!(DataExchange::new("x".into()).to_dataframe(py).is_err());
+        });
+    }
+}
@@ -1,6 +1,24 @@
 // Error handling utilities
 use pyo3::prelude::*;
+use pyo3::create_exception;
 use pyo3::exceptions::PyRuntimeError;
+use ai_agent_core::ToolError as RustToolError;
+
+create_exception!(ai_agent_rust, ToolError, PyRuntimeError, "A tool run failed; the subclasses say why.");
+create_exception!(ai_agent_rust, ToolNotFoundError, ToolError);
+create_exception!(ai_agent_rust, ToolSpawnError, ToolError);
+create_exception!(ai_agent_rust, ToolExitError, ToolError);
+create_exception!(ai_agent_rust, ToolIOError, ToolError);
+create_exception!(ai_agent_rust, InvalidToolArgsError, ToolError);
+create_exception!(ai_agent_rust, ToolTimeoutError, ToolError);
+create_exception!(ai_agent_rust, PolicyViolationError, ToolError);
+create_exception!(ai_agent_rust, ToolRateLimitedError, ToolError);
+create_exception!(ai_agent_rust, WorkingDirectoryError, ToolError);
+create_exception!(ai_agent_rust, ToolCancelledError, ToolError);
+create_exception!(ai_agent_rust, ResourceLimitError, ToolError);
+create_exception!(ai_agent_rust, ConfirmationDeniedError, ToolError);
+create_exception!(ai_agent_rust, PriorityRefusedError, ToolError);
+create_exception!(ai_agent_rust, UnsupportedError, ToolError);
 
 pub struct ErrorHandler;
 
@@ -8,12 +26,59 @@ impl ErrorHandler {
     pub fn new() -> Self {
         Self
     }
-    
-    pub fn rust_error_to_python(_error: anyhow::Error) -> PyErr {
-        // TODO: Implement error conversion in T032
-        PyRuntimeError::new_err("Error conversion not implemented")
+
+    /// A `ToolError` becomes the exception class for its variant, with the
+    /// whole context chain as the message; anything else is a `RuntimeError`.
+    pub fn rust_error_to_python(error: anyhow::Error) -> PyErr {
+        let message = format!("{:#}", error);
+        match error.downcast_ref::<RustToolError>() {
+            Some(tool_error) => Self::tool_error_to_python(tool_error, message),
+            None => PyRuntimeError::new_err(message),
+        }
+    }
+
+    /// The exception raised for `error`: a distinct subclass of `ToolError`
+    /// per variant, or `ToolError` itself for `Other`.
+    pub fn tool_error_to_python(error: &RustToolError, message: String) -> PyErr {
+        match error {
+            RustToolError::NotFound { .. } => ToolNotFoundError::new_err(message),
+            RustToolError::SpawnFailed { .. } => ToolSpawnError::new_err(message),
+            RustToolError::NonZeroExit { .. } => ToolExitError::new_err(message),
+            RustToolError::Io(_) => ToolIOError::new_err(message),
+            RustToolError::InvalidArgs(_) => InvalidToolArgsError::new_err(message),
+            RustToolError::Timeout { .. } => ToolTimeoutError::new_err(message),
+            RustToolError::PolicyViolation { .. } => PolicyViolationError::new_err(message),
+            RustToolError::RateLimited { .. } => ToolRateLimitedError::new_err(message),
+            RustToolError::MissingWorkingDirectory { .. } => WorkingDirectoryError::new_err(message),
+            RustToolError::Cancelled { .. } => ToolCancelledError::new_err(message),
+            RustToolError::ResourceLimit { .. } => ResourceLimitError::new_err(message),
+            RustToolError::ConfirmationDenied { .. } => ConfirmationDeniedError::new_err(message),
+            RustToolError::PriorityRefused(_) => PriorityRefusedError::new_err(message),
+            RustToolError::Unsupported(_) => UnsupportedError::new_err(message),
+            RustToolError::Other(_) => ToolError::new_err(message),
+        }
+    }
+
+    /// Adds `ToolError` and its subclasses to `module`.
+    pub fn register_exceptions(py: Python<'_>, module: &PyModule) -> PyResult<()> {
+        module.add("ToolError", py.get_type::<ToolError>())?;
+        module.add("ToolNotFoundError", py.get_type::<ToolNotFoundError>())?;
+        module.add("ToolSpawnError", py.get_type::<ToolSpawnError>())?;
+        module.add("ToolExitError", py.get_type::<ToolExitError>())?;
+        module.add("ToolIOError", py.get_type::<ToolIOError>())?;
+        module.add("InvalidToolArgsError", py.get_type::<InvalidToolArgsError>())?;
+        module.add("ToolTimeoutError", py.get_type::<ToolTimeoutError>())?;
+        module.add("PolicyViolationError", py.get_type::<PolicyViolationError>())?;
+        module.add("ToolRateLimitedError", py.get_type::<ToolRateLimitedError>())?;
+        module.add("WorkingDirectoryError", py.get_type::<WorkingDirectoryError>())?;
+        module.add("ToolCancelledError", py.get_type::<ToolCancelledError>())?;
+        module.add("ResourceLimitError", py.get_type::<ResourceLimitError>())?;
+        module.add("ConfirmationDeniedError", py.get_type::<ConfirmationDeniedError>())?;
+        module.add("PriorityRefusedError", py.get_type::<PriorityRefusedError>())?;
+        module.add("UnsupportedError", py.get_type::<UnsupportedError>())?;
+        Ok(())
     }
-    
+
     pub fn python_error_to_rust(_error: PyErr) -> anyhow::Error {
         // TODO: Implement error conversion in T032
         anyhow::anyhow!("Error conversion not implemented")
@@ -24,4 +89,34 @@ impl Default for ErrorHandler {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use ai_agent_core::ToolCallError;
+
+    #[test]
+    fn test_tool_errors_map_to_distinct_exceptions() {
+        Python::with_gil(|py| {
+            let cases: Vec<(RustToolError, &PyAny)> = vec![
+                (RustToolError::NotFound { tool: "x".into() }, py.get_type::<ToolNotFoundError>()),
+                (RustToolError::Timeout { elapsed: Duration::ZERO, partial_stdout: String::new() }, py.get_type::<ToolTimeoutError>()),
+                (RustToolError::PolicyViolation { command: "rm".into(), reason: "denied".into() }, py.get_type::<PolicyViolationError>()),
+                (RustToolError::InvalidArgs(ToolCallError::NotAnObject), py.get_type::<InvalidToolArgsError>()),
+                (RustToolError::Io(std::io::ErrorKind::BrokenPipe.into()), py.get_type::<ToolIOError>()),
+                (RustToolError::Other(anyhow::anyhow!("odd")), py.get_type::<ToolError>()),
+            ];
+            for (error, class) in cases {
+                let raised = ErrorHandler::rust_error_to_python(anyhow::Error::from(error).context("running tool"));
+                let value = raised.value(py);
+                assert!(value.get_type().is(class), "{} is {}", value, value.get_type());
+                assert!(raised.is_instance_of::<ToolError>(py));
+                assert!(value.to_string().starts_with("running tool: "));
+            }
+            let plain = ErrorHandler::rust_error_to_python(anyhow::anyhow!("boom"));
+            assert!(plain.is_instance_of::<PyRuntimeError>(py) && !plain.is_instance_of::<ToolError>(py));
+        });
+    }
+}
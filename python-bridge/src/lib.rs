@@ -1,21 +1,36 @@
 // AI Agent Python Bridge
 // PyO3 bindings for seamless Rust-Python integration
 
+// pyo3 0.20 macros expand to impls the newer non_local_definitions lint flags
+#![allow(non_local_definitions)]
 use pyo3::prelude::*;
 
 pub mod agent_core;
 pub mod data_exchange;
 pub mod async_bridge;
 pub mod error_handling;
+pub mod stats;
+
+/// Imports `module` in the embedded interpreter, returning the Python
+/// version on success. Used by health checks to confirm the ML backend loads.
+pub fn check_import(module: &str) -> PyResult<String> {
+    Python::with_gil(|py| {
+        py.import(module)?;
+        Ok(py.version().split_whitespace().next().unwrap_or_default().to_string())
+    })
+}
 
 // Python module definition
 #[pymodule]
-fn ai_agent_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn ai_agent_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    error_handling::ErrorHandler::register_exceptions(py, m)?;
     
-    // Add submodules when implemented
-    // m.add_class::<agent_core::AgentCore>()?;
-    // m.add_class::<data_exchange::DataExchange>()?;
+    m.add_class::<agent_core::AgentCore>()?;
+    m.add_class::<agent_core::TokenStream>()?;
+    m.add_class::<agent_core::PyTaskResult>()?;
+    m.add_class::<data_exchange::DataExchange>()?;
+    m.add_class::<stats::BridgeStats>()?;
     
     Ok(())
 }
@@ -26,7 +41,15 @@ mod tests {
 
     #[test]
     fn test_python_bridge_loads() {
-        // Basic test to ensure library compiles
-        assert!(true);
+        // Basic test to ensure the bridge types construct
+        let _ = agent_core::AgentCore::default();
+        let _ = async_bridge::AsyncBridge::new();
+    }
+
+    #[test]
+    fn test_check_import() {
+        assert!(check_import("json").unwrap().starts_with('3'));
+        let err = check_import("no_such_module_xyz").unwrap_err();
+        assert!(err.to_string().contains("No module named"));
     }
 }
\ No newline at end of file
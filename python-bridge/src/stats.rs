@@ -0,0 +1,218 @@
+// Timing of how long the bridge holds the GIL
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use pyo3::marker::Ungil;
+use pyo3::prelude::*;
+
+/// Environment variable that turns `BridgeStats` collection on at startup
+/// when set to anything but `0` or nothing.
+pub const BRIDGE_STATS_VAR: &str = "AI_AGENT_BRIDGE_STATS";
+
+/// Running totals since the last reset. Collecting them costs two clock
+/// reads per GIL section and per task, and a few relaxed atomic adds; when
+/// off, a single atomic load.
+struct Counters {
+    enabled: AtomicBool,
+    tasks: AtomicU64,
+    task_nanos: AtomicU64,
+    held_nanos: AtomicU64,
+    wait_nanos: AtomicU64,
+    acquisitions: AtomicU64,
+}
+
+fn counters() -> &'static Counters {
+    static COUNTERS: OnceLock<Counters> = OnceLock::new();
+    COUNTERS.get_or_init(|| Counters {
+        enabled: AtomicBool::new(std::env::var(BRIDGE_STATS_VAR).is_ok_and(|value| !value.is_empty() && value != "0")),
+        tasks: AtomicU64::new(0),
+        task_nanos: AtomicU64::new(0),
+        held_nanos: AtomicU64::new(0),
+        wait_nanos: AtomicU64::new(0),
+        acquisitions: AtomicU64::new(0),
+    })
+}
+
+fn add(counter: &AtomicU64, elapsed: Duration) {
+    counter.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    counters().enabled.load(Ordering::Relaxed)
+}
+
+/// Like `Python::with_gil`, also counting the wait for the GIL and the
+/// time `f` holds it.
+pub fn with_gil<F, R>(f: F) -> R
+where
+    F: for<'py> FnOnce(Python<'py>) -> R,
+{
+    if !is_enabled() {
+        return Python::with_gil(f);
+    }
+    let asked = Instant::now();
+    Python::with_gil(|py| {
+        let acquired = Instant::now();
+        let result = f(py);
+        let counters = counters();
+        add(&counters.wait_nanos, acquired - asked);
+        add(&counters.held_nanos, acquired.elapsed());
+        counters.acquisitions.fetch_add(1, Ordering::Relaxed);
+        result
+    })
+}
+
+/// Times one call into the bridge, counted in `BridgeStats` when dropped.
+/// For a call made from Python, which holds the GIL throughout except
+/// where it is released through `allow_threads`, that time counts as held.
+pub struct TaskTimer {
+    started: Option<Instant>,
+    holds_gil: bool,
+    released: Duration,
+}
+
+impl TaskTimer {
+    /// `holds_gil` tells whether the caller holds the GIL, as a method
+    /// called from Python does, or runs on a thread of its own.
+    pub fn start(holds_gil: bool) -> Self {
+        Self { started: is_enabled().then(Instant::now), holds_gil, released: Duration::ZERO }
+    }
+
+    /// `Python::allow_threads`, not counting the time `f` takes as held.
+    pub fn allow_threads<T, F>(&mut self, py: Python<'_>, f: F) -> T
+    where
+        F: Ungil + FnOnce() -> T,
+        T: Ungil,
+    {
+        if self.started.is_none() {
+            return py.allow_threads(f);
+        }
+        let released = Instant::now();
+        let result = py.allow_threads(f);
+        self.released += released.elapsed();
+        result
+    }
+}
+
+impl Drop for TaskTimer {
+    fn drop(&mut self) {
+        let Some(started) = self.started else { return };
+        let counters = counters();
+        let wall = started.elapsed();
+        counters.tasks.fetch_add(1, Ordering::Relaxed);
+        add(&counters.task_nanos, wall);
+        if self.holds_gil {
+            add(&counters.held_nanos, wall.saturating_sub(self.released));
+        }
+    }
+}
+
+/// Totals of how long bridge calls took and how much of that the GIL was
+/// held, to find where releasing it would help. Collection is off unless
+/// `BridgeStats.enable()` is called or `AI_AGENT_BRIDGE_STATS` is set.
+/// `BridgeStats.current()` takes a snapshot.
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BridgeStats {
+    /// Calls to `execute_task` and streaming tasks that have finished.
+    #[pyo3(get)]
+    pub tasks: u64,
+    /// Wall time of those tasks, in seconds.
+    #[pyo3(get)]
+    pub task_seconds: f64,
+    /// Time the bridge held the GIL, in seconds, including its callbacks
+    /// into Python and the backend's own work.
+    #[pyo3(get)]
+    pub gil_held_seconds: f64,
+    /// Time spent waiting to take the GIL back, in seconds.
+    #[pyo3(get)]
+    pub gil_wait_seconds: f64,
+    /// How often the bridge took the GIL from a thread without it.
+    #[pyo3(get)]
+    pub gil_acquisitions: u64,
+}
+
+#[pymethods]
+impl BridgeStats {
+    /// The totals so far.
+    #[staticmethod]
+    pub fn current() -> Self {
+        let counters = counters();
+        let seconds = |counter: &AtomicU64| Duration::from_nanos(counter.load(Ordering::Relaxed)).as_secs_f64();
+        Self {
+            tasks: counters.tasks.load(Ordering::Relaxed),
+            task_seconds: seconds(&counters.task_nanos),
+            gil_held_seconds: seconds(&counters.held_nanos),
+            gil_wait_seconds: seconds(&counters.wait_nanos),
+            gil_acquisitions: counters.acquisitions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Turns collection on or off; the totals are kept either way.
+    #[staticmethod]
+    #[pyo3(signature = (enabled=true))]
+    pub fn enable(enabled: bool) {
+        counters().enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    #[staticmethod]
+    pub fn enabled() -> bool {
+        is_enabled()
+    }
+
+    /// Sets every total back to zero.
+    #[staticmethod]
+    pub fn reset() {
+        let counters = counters();
+        for counter in [&counters.tasks, &counters.task_nanos, &counters.held_nanos, &counters.wait_nanos, &counters.acquisitions] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// The share of task time the GIL was held, from 0 to 1.
+    #[getter]
+    pub fn gil_held_fraction(&self) -> f64 {
+        if self.task_seconds == 0.0 {
+            return 0.0;
+        }
+        (self.gil_held_seconds / self.task_seconds).min(1.0)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BridgeStats(tasks={}, task_seconds={:.6}, gil_held_seconds={:.6}, gil_wait_seconds={:.6}, gil_acquisitions={})",
+            self.tasks, self.task_seconds, self.gil_held_seconds, self.gil_wait_seconds, self.gil_acquisitions
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent_core::{AgentCore, DEFAULT_HEARTBEAT_SECONDS};
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_bridge_stats_time_gil_sections() {
+        Python::with_gil(|py| {
+            let globals = PyDict::new(py);
+            py.run("def backend(task):\n    for _ in range(3):\n        sum(range(200000))\n        yield 'x'\n", Some(globals), None).unwrap();
+            let core = AgentCore::new(Some(globals.get_item("backend").unwrap().unwrap().to_object(py)), None);
+
+            BridgeStats::enable(true);
+            BridgeStats::reset();
+            core.execute_task(py, "t".into(), None, None, DEFAULT_HEARTBEAT_SECONDS).unwrap();
+            let stats = BridgeStats::current();
+            // Other tests may run tasks at the same time.
+            assert!(stats.tasks >= 1 && stats.task_seconds > 0.0, "{:?}", stats);
+            // The call itself, then one `__next__` per chunk and the last.
+            assert!(stats.gil_acquisitions >= 5, "{:?}", stats);
+            assert!(stats.gil_held_seconds > 0.0 && stats.gil_held_fraction() <= 1.0, "{:?}", stats);
+            assert!(stats.__repr__().starts_with("BridgeStats(tasks="));
+
+            BridgeStats::enable(false);
+            assert!(!BridgeStats::enabled());
+            BridgeStats::reset();
+        });
+    }
+}